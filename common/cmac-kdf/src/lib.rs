@@ -0,0 +1,462 @@
+//! AES-128 CMAC and the KDF in counter mode that uses it as PRF
+//!
+//! References: NIST SP 800-38B (CMAC) and NIST SP 800-108 (KDF in counter mode)
+//!
+//! This crate implements the CMAC construction and the KDF framing; the block cipher is provided
+//! by the user through the `BlockCipher` trait
+
+#![deny(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+use core::ptr;
+
+/// Size of an AES block, in bytes
+pub const BLOCK_SIZE: usize = 16;
+
+/// Maximum length of a key derived with `derive`, in bytes
+///
+/// The length (in bits) is encoded as a 32-bit integer in the PRF input
+pub const MAX_KEY_LEN: usize = (0xffff_ffff_u32 / 8) as usize;
+
+/// An AES-128 implementation with a fixed key
+pub trait BlockCipher {
+    /// Error reported by the cipher
+    type Error;
+
+    /// Encrypts a single `block` in place
+    fn encrypt_block(&self, block: &mut [u8; BLOCK_SIZE]) -> Result<(), Self::Error>;
+}
+
+/// CMAC subkeys `K1` and `K2` (SP 800-38B, section 6.1)
+///
+/// The subkeys depend only on the key so they can be computed once and used for several MACs
+pub struct Subkeys {
+    k1: [u8; BLOCK_SIZE],
+    k2: [u8; BLOCK_SIZE],
+}
+
+impl Subkeys {
+    /// Computes the subkeys of the key used by `cipher`
+    pub fn new<C>(cipher: &C) -> Result<Self, C::Error>
+    where
+        C: BlockCipher,
+    {
+        fn double(block: u128) -> u128 {
+            const R: u128 = 0x87;
+
+            (block << 1) ^ (R & (block >> 127).wrapping_neg())
+        }
+
+        let mut l = [0; BLOCK_SIZE];
+        let res = cipher.encrypt_block(&mut l);
+        let k1 = double(u128::from_be_bytes(l));
+        let k2 = double(k1);
+        zeroize(&mut l);
+        res?;
+
+        Ok(Subkeys {
+            k1: k1.to_be_bytes(),
+            k2: k2.to_be_bytes(),
+        })
+    }
+}
+
+impl Drop for Subkeys {
+    fn drop(&mut self) {
+        zeroize(&mut self.k1);
+        zeroize(&mut self.k2);
+    }
+}
+
+/// AES-128 CMAC computation (SP 800-38B, section 6.2)
+pub struct Cmac<'a, C>
+where
+    C: BlockCipher,
+{
+    cipher: &'a C,
+    subkeys: &'a Subkeys,
+    state: [u8; BLOCK_SIZE],
+    buffer: [u8; BLOCK_SIZE],
+    len: usize,
+}
+
+impl<'a, C> Cmac<'a, C>
+where
+    C: BlockCipher,
+{
+    /// Starts a new MAC computation; `subkeys` must have been computed with the same `cipher`
+    pub fn new(cipher: &'a C, subkeys: &'a Subkeys) -> Self {
+        Cmac {
+            cipher,
+            subkeys,
+            state: [0; BLOCK_SIZE],
+            buffer: [0; BLOCK_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Feeds `data` into the MAC
+    pub fn update(&mut self, data: &[u8]) -> Result<(), C::Error> {
+        for byte in data {
+            // the last block gets special treatment so a full block is only processed once we
+            // know more data follows
+            if self.len == BLOCK_SIZE {
+                self.process()?;
+            }
+
+            self.buffer[self.len] = *byte;
+            self.len += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the MAC of all the data fed so far
+    pub fn finalize(mut self) -> Result<[u8; BLOCK_SIZE], C::Error> {
+        let subkey = if self.len == BLOCK_SIZE {
+            &self.subkeys.k1
+        } else {
+            // pad with `10*`
+            self.buffer[self.len] = 0x80;
+            for byte in &mut self.buffer[self.len + 1..] {
+                *byte = 0;
+            }
+            &self.subkeys.k2
+        };
+
+        for (byte, key) in self.buffer.iter_mut().zip(subkey) {
+            *byte ^= key;
+        }
+
+        self.process()?;
+        Ok(self.state)
+    }
+
+    // CBC-MAC step
+    fn process(&mut self) -> Result<(), C::Error> {
+        for (state, byte) in self.state.iter_mut().zip(&self.buffer) {
+            *state ^= byte;
+        }
+        self.len = 0;
+
+        self.cipher.encrypt_block(&mut self.state)
+    }
+}
+
+impl<C> Drop for Cmac<'_, C>
+where
+    C: BlockCipher,
+{
+    fn drop(&mut self) {
+        // don't leave intermediate values behind
+        zeroize(&mut self.state);
+        zeroize(&mut self.buffer);
+    }
+}
+
+/// Computes the AES-128 CMAC of `data`
+pub fn cmac<C>(cipher: &C, data: &[u8]) -> Result<[u8; BLOCK_SIZE], C::Error>
+where
+    C: BlockCipher,
+{
+    let subkeys = Subkeys::new(cipher)?;
+    let mut cmac = Cmac::new(cipher, &subkeys);
+    cmac.update(data)?;
+    cmac.finalize()
+}
+
+/// Fills `key` with key material derived, using the KDF in counter mode, from the key used by
+/// `cipher`, the given `label` and `context`
+///
+/// The counter is a 32-bit integer and the fixed input data is `Label || 0x00 || Context ||
+/// [L]_2`, where `[L]_2` is the length of `key` in bits, as a 32-bit integer (SP 800-108, section
+/// 5.1). The length of `key` is part of the PRF input so e.g. a 16-byte key is *not* a prefix of a
+/// 32-byte key derived from the same label and context
+///
+/// # Panics
+///
+/// This function panics if `key` is longer than `MAX_KEY_LEN` bytes
+pub fn derive<C>(cipher: &C, label: &[u8], context: &[u8], key: &mut [u8]) -> Result<(), C::Error>
+where
+    C: BlockCipher,
+{
+    assert!(key.len() <= MAX_KEY_LEN, "requested key is too long");
+
+    let bits = (key.len() as u32 * 8).to_be_bytes();
+    derive_counter_mode(cipher, 4, &[label, &[0], context, &bits], key)
+}
+
+/// Fills `key` with key material derived, using the KDF in counter mode, from the key used by
+/// `cipher` and the `fixed_input` data
+///
+/// `K(i) := PRF(KI, [i]_r || FixedInputData)` (SP 800-108, section 5.1), where the counter `[i]_r`
+/// is `counter_len` bytes long and the fixed input data is the concatenation of the slices in
+/// `fixed_input`. This is the building block of `derive`; prefer that function
+///
+/// # Panics
+///
+/// This function panics if `counter_len` is not in the range `1..=4` or if `key` needs more blocks
+/// than a `counter_len`-byte counter can count
+pub fn derive_counter_mode<C>(
+    cipher: &C,
+    counter_len: usize,
+    fixed_input: &[&[u8]],
+    key: &mut [u8],
+) -> Result<(), C::Error>
+where
+    C: BlockCipher,
+{
+    assert!((1..=4).contains(&counter_len), "invalid counter length");
+    let partial = key.len() % BLOCK_SIZE;
+    let blocks = key.len() / BLOCK_SIZE + usize::from(partial > 0);
+    assert!(
+        (blocks as u64) < 1 << (8 * counter_len),
+        "requested key is too long"
+    );
+
+    let subkeys = Subkeys::new(cipher)?;
+    for (i, chunk) in key.chunks_mut(BLOCK_SIZE).enumerate() {
+        let counter = (i as u32 + 1).to_be_bytes();
+        let mut cmac = Cmac::new(cipher, &subkeys);
+        cmac.update(&counter[4 - counter_len..])?;
+        for data in fixed_input {
+            cmac.update(data)?;
+        }
+
+        let mut block = cmac.finalize()?;
+        chunk.copy_from_slice(&block[..chunk.len()]);
+        zeroize(&mut block);
+    }
+
+    Ok(())
+}
+
+fn zeroize(block: &mut [u8; BLOCK_SIZE]) {
+    unsafe { ptr::write_volatile(block, [0; BLOCK_SIZE]) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cmac, derive, derive_counter_mode, BlockCipher, Subkeys, BLOCK_SIZE};
+
+    // Straightforward (and slow) software AES-128 (FIPS 197)
+    struct Aes128 {
+        round_keys: [[u8; 16]; 11],
+    }
+
+    impl Aes128 {
+        fn new(key: &[u8]) -> Self {
+            let sbox = sbox();
+            let mut rcon = 1;
+
+            let mut round_keys = [[0; 16]; 11];
+            round_keys[0].copy_from_slice(key);
+            for i in 1..11 {
+                let prev = round_keys[i - 1];
+                let mut word = [prev[13], prev[14], prev[15], prev[12]];
+                for byte in word.iter_mut() {
+                    *byte = sbox[usize::from(*byte)];
+                }
+                word[0] ^= rcon;
+                rcon = xtime(rcon);
+
+                let mut round_key = [0; 16];
+                for j in 0..16 {
+                    let w = if j < 4 { word[j] } else { round_key[j - 4] };
+                    round_key[j] = prev[j] ^ w;
+                }
+                round_keys[i] = round_key;
+            }
+
+            Aes128 { round_keys }
+        }
+    }
+
+    impl BlockCipher for Aes128 {
+        type Error = ();
+
+        fn encrypt_block(&self, block: &mut [u8; BLOCK_SIZE]) -> Result<(), ()> {
+            let sbox = sbox();
+
+            xor(block, &self.round_keys[0]);
+            for round in 1..11 {
+                // SubBytes and ShiftRows
+                let state = *block;
+                for col in 0..4 {
+                    for row in 0..4 {
+                        block[4 * col + row] =
+                            sbox[usize::from(state[4 * ((col + row) % 4) + row])];
+                    }
+                }
+
+                // MixColumns
+                if round != 10 {
+                    for col in block.chunks_mut(4) {
+                        let [a, b, c, d] = [col[0], col[1], col[2], col[3]];
+                        let all = a ^ b ^ c ^ d;
+                        col[0] ^= all ^ xtime(a ^ b);
+                        col[1] ^= all ^ xtime(b ^ c);
+                        col[2] ^= all ^ xtime(c ^ d);
+                        col[3] ^= all ^ xtime(d ^ a);
+                    }
+                }
+
+                xor(block, &self.round_keys[round]);
+            }
+
+            Ok(())
+        }
+    }
+
+    fn xor(block: &mut [u8; 16], key: &[u8; 16]) {
+        for (byte, key) in block.iter_mut().zip(key.iter()) {
+            *byte ^= key;
+        }
+    }
+
+    // multiplication by `x` in GF(2^8)
+    fn xtime(x: u8) -> u8 {
+        (x << 1) ^ if x & 0x80 != 0 { 0x1b } else { 0 }
+    }
+
+    fn sbox() -> [u8; 256] {
+        let mut sbox = [0; 256];
+        for x in 0..=255u8 {
+            // multiplicative inverse, x^254
+            let mut inv = 1u8;
+            for _ in 0..254 {
+                inv = mul(inv, x);
+            }
+
+            let mut y = inv;
+            for _ in 0..4 {
+                inv = inv.rotate_left(1);
+                y ^= inv;
+            }
+            sbox[usize::from(x)] = y ^ 0x63;
+        }
+        sbox
+    }
+
+    fn mul(mut a: u8, mut b: u8) -> u8 {
+        let mut p = 0;
+        while b != 0 {
+            if b & 1 != 0 {
+                p ^= a;
+            }
+            a = xtime(a);
+            b >>= 1;
+        }
+        p
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // RFC 4493, section 4 (the examples of SP 800-38B)
+    const KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+    const MESSAGE: &str = "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+                           30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710";
+
+    #[test]
+    fn subkeys() {
+        let cipher = Aes128::new(&hex(KEY));
+        let subkeys = Subkeys::new(&cipher).unwrap();
+        assert_eq!(
+            &subkeys.k1[..],
+            &hex("fbeed618357133667c85e08f7236a8de")[..]
+        );
+        assert_eq!(
+            &subkeys.k2[..],
+            &hex("f7ddac306ae266ccf90bc11ee46d513b")[..]
+        );
+    }
+
+    #[test]
+    fn rfc4493() {
+        let cipher = Aes128::new(&hex(KEY));
+        let message = hex(MESSAGE);
+
+        for (len, mac) in &[
+            // empty message; padded
+            (0, "bb1d6929e95937287fa37d129b756746"),
+            // one full block
+            (16, "070a16b46b4d4144f79bdd9dd04a287c"),
+            // last block is partial; padded
+            (40, "dfa66747de9ae63030ca32611497c827"),
+            (64, "51f0bebf7e3b9d92fc49741779363cfe"),
+        ] {
+            assert_eq!(
+                &cmac(&cipher, &message[..*len]).unwrap()[..],
+                &hex(mac)[..],
+                "{}-byte message",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn sp800_108_counter_mode() {
+        // NIST CAVP KBKDF test vectors: PRF=CMAC_AES128, CTRLOCATION=BEFORE_FIXED, RLEN=8_BITS,
+        // COUNT=0
+        let cipher = Aes128::new(&hex("dff1e50ac0b69dc40f1051d46c2b069c"));
+        let fixed_input = hex(
+            "c16e6e02c5a3dcc8d78b9ac1306877761310455b4e41469951d9e6c2245a064b33fd8c3b01203a78\
+             24485bf0a64060c4648b707d2607935699316ea5",
+        );
+
+        let mut key = [0; 16];
+        derive_counter_mode(&cipher, 1, &[&fixed_input], &mut key).unwrap();
+        assert_eq!(&key[..], &hex("8be8f0869b3c0ba97b71863d1b9f7813")[..]);
+
+        // splitting the fixed input data doesn't change the output
+        let (head, tail) = fixed_input.split_at(7);
+        let mut key2 = [0; 16];
+        derive_counter_mode(&cipher, 1, &[head, &[], tail], &mut key2).unwrap();
+        assert_eq!(key, key2);
+    }
+
+    #[test]
+    fn sp800_108_label_context() {
+        // 32-bit counter and `Label || 0x00 || Context || [L]_32` fixed input data; the expected
+        // values were computed with an independent implementation (the `KBKDFCMAC` of Python's
+        // `cryptography` package)
+        let cipher = Aes128::new(&hex(KEY));
+
+        let mut key = [0; 32];
+        derive(&cipher, b"storage", b"device-0123", &mut key).unwrap();
+        assert_eq!(
+            &key[..],
+            &hex("f2685b34573b086ce4c015a8a47ada383f654b94d9fa254f5644cbd1cd143f7f")[..]
+        );
+
+        // `[L]_2` is part of the input so this is not a prefix of the previous key
+        let mut key = [0; 20];
+        derive(&cipher, b"storage", b"device-0123", &mut key).unwrap();
+        assert_eq!(
+            &key[..],
+            &hex("df621f066700f8de1052521c17d0572df2cdd508")[..]
+        );
+
+        let mut key = [0; 16];
+        derive(&cipher, b"", b"", &mut key).unwrap();
+        assert_eq!(&key[..], &hex("4a10102ba6c210565f7e327e0befcae6")[..]);
+
+        // same as spelling out the fixed input data
+        let mut key2 = [0; 16];
+        derive_counter_mode(&cipher, 4, &[&[0, 0, 0, 0, 128]], &mut key2).unwrap();
+        assert_eq!(key, key2);
+    }
+
+    #[test]
+    #[should_panic(expected = "requested key is too long")]
+    fn counter_overflow() {
+        let cipher = Aes128::new(&hex(KEY));
+        let mut key = [0; 256 * BLOCK_SIZE];
+        let _ = derive_counter_mode(&cipher, 1, &[], &mut key);
+    }
+}
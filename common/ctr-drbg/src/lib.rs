@@ -0,0 +1,534 @@
+//! CTR_DRBG instantiated with AES-128 and the block cipher derivation function
+//!
+//! Reference: NIST SP 800-90A Rev. 1, section 10.2.1
+//!
+//! This crate implements the state machine of the DRBG; the block cipher is provided by the user
+//! through the `BlockCipher` trait
+
+#![deny(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+use core::{convert::TryFrom, ptr};
+
+/// Size of an AES block, in bytes
+pub const BLOCK_SIZE: usize = 16;
+
+/// Size of an AES-128 key, in bytes
+pub const KEY_SIZE: usize = 16;
+
+/// Size of the seed (`seedlen`), in bytes
+pub const SEED_SIZE: usize = KEY_SIZE + BLOCK_SIZE;
+
+/// Maximum number of bytes that can be requested in a single `generate` call
+pub const MAX_REQUEST_SIZE: usize = 1 << 16;
+
+/// Maximum number of `generate` calls between two reseeds
+pub const MAX_RESEED_INTERVAL: u64 = 1 << 48;
+
+// key used by the derivation function; SP 800-90A, section 10.3.2, step 8
+const DF_KEY: [u8; KEY_SIZE] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+/// An AES-128 implementation
+pub trait BlockCipher {
+    /// Error reported by the cipher
+    type Error;
+
+    /// Changes the key used by the cipher
+    fn set_key(&mut self, key: &[u8; KEY_SIZE]) -> Result<(), Self::Error>;
+
+    /// Encrypts a single `block` in place
+    fn encrypt_block(&mut self, block: &mut [u8; BLOCK_SIZE]) -> Result<(), Self::Error>;
+}
+
+/// CTR_DRBG error
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error<E> {
+    /// `MAX_RESEED_INTERVAL` requests were served since the last reseed; `reseed` must be called
+    /// before more output can be generated
+    ReseedRequired,
+
+    /// The block cipher reported an error
+    Cipher(E),
+}
+
+/// CTR_DRBG state
+///
+/// If the cipher reports an error the state is undefined; the DRBG must be instantiated again
+pub struct CtrDrbg<C>
+where
+    C: BlockCipher,
+{
+    cipher: C,
+    key: [u8; KEY_SIZE],
+    v: [u8; BLOCK_SIZE],
+    reseed_counter: u64,
+}
+
+impl<C> CtrDrbg<C>
+where
+    C: BlockCipher,
+{
+    /// Instantiates the DRBG (SP 800-90A, section 10.2.1.3.2)
+    ///
+    /// For a security strength of 128 bits `entropy_input` must contain at least 128 bits of
+    /// entropy and `nonce` must contain at least 64 bits of entropy (or be a value that's not
+    /// expected to repeat)
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the inputs add up to more than `u32::MAX` bytes
+    pub fn instantiate(
+        cipher: C,
+        entropy_input: &[u8],
+        nonce: &[u8],
+        personalization_string: &[u8],
+    ) -> Result<Self, C::Error> {
+        let mut drbg = CtrDrbg {
+            cipher,
+            key: [0; KEY_SIZE],
+            v: [0; BLOCK_SIZE],
+            reseed_counter: 1,
+        };
+
+        let mut seed_material = [0; SEED_SIZE];
+        let res = drbg
+            .derive(
+                &[entropy_input, nonce, personalization_string],
+                &mut seed_material,
+            )
+            .and_then(|_| drbg.update(&seed_material));
+        zeroize(&mut seed_material);
+        res?;
+
+        Ok(drbg)
+    }
+
+    /// Reseeds the DRBG (SP 800-90A, section 10.2.1.4.2)
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the inputs add up to more than `u32::MAX` bytes
+    pub fn reseed(
+        &mut self,
+        entropy_input: &[u8],
+        additional_input: &[u8],
+    ) -> Result<(), C::Error> {
+        let mut seed_material = [0; SEED_SIZE];
+        let res = self
+            .derive(&[entropy_input, additional_input], &mut seed_material)
+            .and_then(|_| self.update(&seed_material));
+        zeroize(&mut seed_material);
+        res?;
+
+        self.reseed_counter = 1;
+        Ok(())
+    }
+
+    /// Fills `output` with pseudorandom bytes (SP 800-90A, section 10.2.1.5.2)
+    ///
+    /// `additional_input` can be empty
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `output` is longer than `MAX_REQUEST_SIZE` bytes or if
+    /// `additional_input` is longer than `u32::MAX` bytes
+    pub fn generate(
+        &mut self,
+        output: &mut [u8],
+        additional_input: &[u8],
+    ) -> Result<(), Error<C::Error>> {
+        assert!(output.len() <= MAX_REQUEST_SIZE, "request is too large");
+
+        if self.reseed_counter > MAX_RESEED_INTERVAL {
+            return Err(Error::ReseedRequired);
+        }
+
+        let mut additional = [0; SEED_SIZE];
+        let res = self.fill(output, additional_input, &mut additional);
+        zeroize(&mut additional);
+        res.map_err(Error::Cipher)?;
+
+        self.reseed_counter += 1;
+        Ok(())
+    }
+
+    /// Returns the number of `generate` calls since the last reseed, plus one
+    pub fn reseed_counter(&self) -> u64 {
+        self.reseed_counter
+    }
+
+    fn fill(
+        &mut self,
+        output: &mut [u8],
+        additional_input: &[u8],
+        additional: &mut [u8; SEED_SIZE],
+    ) -> Result<(), C::Error> {
+        if !additional_input.is_empty() {
+            self.derive(&[additional_input], additional)?;
+            self.update(additional)?;
+        }
+
+        for chunk in output.chunks_mut(BLOCK_SIZE) {
+            increment(&mut self.v);
+            let mut block = self.v;
+            self.cipher.encrypt_block(&mut block)?;
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+
+        self.update(additional)
+    }
+
+    // CTR_DRBG_Update -- SP 800-90A, section 10.2.1.2
+    fn update(&mut self, provided_data: &[u8; SEED_SIZE]) -> Result<(), C::Error> {
+        let mut temp = [0; SEED_SIZE];
+        for chunk in temp.chunks_mut(BLOCK_SIZE) {
+            increment(&mut self.v);
+            let mut block = self.v;
+            self.cipher.encrypt_block(&mut block)?;
+            chunk.copy_from_slice(&block);
+        }
+
+        for (byte, data) in temp.iter_mut().zip(provided_data.iter()) {
+            *byte ^= data;
+        }
+
+        self.key.copy_from_slice(&temp[..KEY_SIZE]);
+        self.v.copy_from_slice(&temp[KEY_SIZE..]);
+        zeroize(&mut temp);
+
+        self.cipher.set_key(&self.key)
+    }
+
+    // Block_Cipher_df -- SP 800-90A, section 10.3.2
+    //
+    // The input string is the concatenation of `inputs`. The derivation function uses its own
+    // keys; the DRBG key is restored afterwards
+    fn derive(&mut self, inputs: &[&[u8]], output: &mut [u8; SEED_SIZE]) -> Result<(), C::Error> {
+        let len = inputs.iter().map(|input| input.len()).sum::<usize>();
+        let len = u32::try_from(len).expect("input is too long");
+
+        self.cipher.set_key(&DF_KEY)?;
+
+        // S = L || N || input_string || 0x80, padded with zeros
+        let mut temp = [0; SEED_SIZE];
+        for (i, chunk) in temp.chunks_mut(BLOCK_SIZE).enumerate() {
+            let mut iv = [0; BLOCK_SIZE];
+            iv[..4].copy_from_slice(&(i as u32).to_be_bytes());
+
+            let mut bcc = Bcc::new(&mut self.cipher);
+            bcc.update(&iv)?;
+            bcc.update(&len.to_be_bytes())?;
+            bcc.update(&(SEED_SIZE as u32).to_be_bytes())?;
+            for input in inputs {
+                bcc.update(input)?;
+            }
+            bcc.update(&[0x80])?;
+            chunk.copy_from_slice(&bcc.finalize()?);
+        }
+
+        let mut key = [0; KEY_SIZE];
+        key.copy_from_slice(&temp[..KEY_SIZE]);
+        let mut x = [0; BLOCK_SIZE];
+        x.copy_from_slice(&temp[KEY_SIZE..]);
+        zeroize(&mut temp);
+
+        let res = self.cipher.set_key(&key);
+        zeroize(&mut key);
+        res?;
+
+        for chunk in output.chunks_mut(BLOCK_SIZE) {
+            self.cipher.encrypt_block(&mut x)?;
+            chunk.copy_from_slice(&x);
+        }
+        zeroize(&mut x);
+
+        self.cipher.set_key(&self.key)
+    }
+}
+
+impl<C> Drop for CtrDrbg<C>
+where
+    C: BlockCipher,
+{
+    fn drop(&mut self) {
+        // don't leave the internal state behind
+        zeroize(&mut self.key);
+        zeroize(&mut self.v);
+    }
+}
+
+// The BCC function (SP 800-90A, section 10.3.3) computed over a stream of bytes; the last block
+// is padded with zeros
+struct Bcc<'c, C> {
+    cipher: &'c mut C,
+    chaining_value: [u8; BLOCK_SIZE],
+    pos: usize,
+}
+
+impl<'c, C> Bcc<'c, C>
+where
+    C: BlockCipher,
+{
+    fn new(cipher: &'c mut C) -> Self {
+        Bcc {
+            cipher,
+            chaining_value: [0; BLOCK_SIZE],
+            pos: 0,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) -> Result<(), C::Error> {
+        for byte in data {
+            self.chaining_value[self.pos] ^= byte;
+            self.pos += 1;
+
+            if self.pos == BLOCK_SIZE {
+                self.cipher.encrypt_block(&mut self.chaining_value)?;
+                self.pos = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finalize(mut self) -> Result<[u8; BLOCK_SIZE], C::Error> {
+        if self.pos != 0 {
+            self.cipher.encrypt_block(&mut self.chaining_value)?;
+        }
+
+        let output = self.chaining_value;
+        zeroize(&mut self.chaining_value);
+        Ok(output)
+    }
+}
+
+// V = (V + 1) mod 2^128
+fn increment(v: &mut [u8; BLOCK_SIZE]) {
+    *v = u128::from_be_bytes(*v).wrapping_add(1).to_be_bytes();
+}
+
+fn zeroize<T>(x: &mut T)
+where
+    T: Default,
+{
+    unsafe { ptr::write_volatile(x, T::default()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockCipher, CtrDrbg, Error, BLOCK_SIZE, KEY_SIZE};
+
+    // Straightforward (and slow) software AES-128 (FIPS 197)
+    struct Aes128 {
+        round_keys: [[u8; 16]; 11],
+    }
+
+    impl Aes128 {
+        fn new() -> Self {
+            Aes128 {
+                round_keys: [[0; 16]; 11],
+            }
+        }
+    }
+
+    impl BlockCipher for Aes128 {
+        type Error = ();
+
+        fn set_key(&mut self, key: &[u8; KEY_SIZE]) -> Result<(), ()> {
+            let sbox = sbox();
+            let mut rcon = 1;
+
+            self.round_keys[0] = *key;
+            for i in 1..11 {
+                let prev = self.round_keys[i - 1];
+                let mut word = [prev[13], prev[14], prev[15], prev[12]];
+                for byte in word.iter_mut() {
+                    *byte = sbox[usize::from(*byte)];
+                }
+                word[0] ^= rcon;
+                rcon = xtime(rcon);
+
+                let mut round_key = [0; 16];
+                for j in 0..16 {
+                    let w = if j < 4 { word[j] } else { round_key[j - 4] };
+                    round_key[j] = prev[j] ^ w;
+                }
+                self.round_keys[i] = round_key;
+            }
+
+            Ok(())
+        }
+
+        fn encrypt_block(&mut self, block: &mut [u8; BLOCK_SIZE]) -> Result<(), ()> {
+            let sbox = sbox();
+
+            xor(block, &self.round_keys[0]);
+            for round in 1..11 {
+                // SubBytes and ShiftRows
+                let state = *block;
+                for col in 0..4 {
+                    for row in 0..4 {
+                        block[4 * col + row] =
+                            sbox[usize::from(state[4 * ((col + row) % 4) + row])];
+                    }
+                }
+
+                // MixColumns
+                if round != 10 {
+                    for col in block.chunks_mut(4) {
+                        let [a, b, c, d] = [col[0], col[1], col[2], col[3]];
+                        let all = a ^ b ^ c ^ d;
+                        col[0] ^= all ^ xtime(a ^ b);
+                        col[1] ^= all ^ xtime(b ^ c);
+                        col[2] ^= all ^ xtime(c ^ d);
+                        col[3] ^= all ^ xtime(d ^ a);
+                    }
+                }
+
+                xor(block, &self.round_keys[round]);
+            }
+
+            Ok(())
+        }
+    }
+
+    fn xor(block: &mut [u8; 16], key: &[u8; 16]) {
+        for (byte, key) in block.iter_mut().zip(key.iter()) {
+            *byte ^= key;
+        }
+    }
+
+    // multiplication by `x` in GF(2^8)
+    fn xtime(x: u8) -> u8 {
+        (x << 1) ^ if x & 0x80 != 0 { 0x1b } else { 0 }
+    }
+
+    fn sbox() -> [u8; 256] {
+        let mut sbox = [0; 256];
+        for x in 0..=255u8 {
+            // multiplicative inverse, x^254
+            let mut inv = 1u8;
+            for _ in 0..254 {
+                inv = mul(inv, x);
+            }
+
+            let mut y = inv;
+            for _ in 0..4 {
+                inv = inv.rotate_left(1);
+                y ^= inv;
+            }
+            sbox[usize::from(x)] = y ^ 0x63;
+        }
+        sbox
+    }
+
+    fn mul(mut x: u8, mut y: u8) -> u8 {
+        let mut z = 0;
+        while y != 0 {
+            if y & 1 != 0 {
+                z ^= x;
+            }
+            x = xtime(x);
+            y >>= 1;
+        }
+        z
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn aes() {
+        // FIPS 197, Appendix C.1
+        let mut aes = Aes128::new();
+        let mut key = [0; 16];
+        key.copy_from_slice(&hex("000102030405060708090a0b0c0d0e0f"));
+        aes.set_key(&key).unwrap();
+
+        let mut block = [0; 16];
+        block.copy_from_slice(&hex("00112233445566778899aabbccddeeff"));
+        aes.encrypt_block(&mut block).unwrap();
+
+        assert_eq!(block.to_vec(), hex("69c4e0d86a7b0430d8cdb78070b4c55a"));
+    }
+
+    #[test]
+    fn cavp() {
+        // CAVP CTR_DRBG test vectors (drbgvectors_no_reseed), [AES-128 use df],
+        // [PredictionResistance = False], COUNT = 0
+        let mut drbg = CtrDrbg::instantiate(
+            Aes128::new(),
+            &hex("890eb067acf7382eff80b0c73bc872c6"),
+            &hex("aad471ef3ef1d203"),
+            &[],
+        )
+        .unwrap();
+
+        let mut output = [0; 64];
+        drbg.generate(&mut output, &[]).unwrap();
+        drbg.generate(&mut output, &[]).unwrap();
+
+        assert_eq!(
+            output.to_vec(),
+            hex(
+                "a5514ed7095f64f3d0d3a5760394ab42062f373a25072a6ea6bcfd8489e94af6\
+                 cf18659fea22ed1ca0a9e33f718b115ee536b12809c31b72b08ddd8be1910fa3"
+            )
+        );
+        assert_eq!(drbg.reseed_counter(), 3);
+    }
+
+    // The expected values were computed with a Python implementation of SP 800-90A that also
+    // reproduces the CAVP vector used in the `cavp` test
+    #[test]
+    fn reseed_and_additional_input() {
+        let entropy = (0..32).collect::<Vec<u8>>();
+        let nonce = (100..116).collect::<Vec<u8>>();
+        let mut drbg =
+            CtrDrbg::instantiate(Aes128::new(), &entropy, &nonce, b"personalization").unwrap();
+
+        let mut output = [0; 32];
+        drbg.generate(&mut output, b"extra").unwrap();
+        assert_eq!(
+            output.to_vec(),
+            hex("027eee59e7dd802152d4a66f3a57cf87183d2dcb98af20625bd6f810f6876156")
+        );
+
+        let entropy = (200..232).collect::<Vec<u8>>();
+        drbg.reseed(&entropy, b"reseed").unwrap();
+        assert_eq!(drbg.reseed_counter(), 1);
+
+        let mut output = [0; 64];
+        drbg.generate(&mut output, &[]).unwrap();
+        assert_eq!(
+            output.to_vec(),
+            hex(
+                "d60cf26d86ea17403011bcc2b6935a174a9f4886f9d11d93b5d446c9209b1ff7\
+                 6a7d00a89efd104cd5879c7f20eda5964d84086563b7e2a2d1cacb85b1599a8c"
+            )
+        );
+
+        // not a multiple of the block size
+        let mut output = [0; 20];
+        drbg.generate(&mut output, b"more").unwrap();
+        assert_eq!(
+            output.to_vec(),
+            hex("af764c947f3d35c94ac86daacf56af4e931e613b")
+        );
+    }
+
+    #[test]
+    fn reseed_required() {
+        let mut drbg = CtrDrbg::instantiate(Aes128::new(), &[0; 32], &[0; 16], &[]).unwrap();
+        drbg.reseed_counter = super::MAX_RESEED_INTERVAL + 1;
+
+        assert_eq!(drbg.generate(&mut [0; 16], &[]), Err(Error::ReseedRequired));
+
+        drbg.reseed(&[1; 32], &[]).unwrap();
+        drbg.generate(&mut [0; 16], &[]).unwrap();
+    }
+}
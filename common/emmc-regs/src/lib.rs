@@ -0,0 +1,616 @@
+//! Decoders for the eMMC device registers
+//!
+//! Reference: JEDEC JESD84-B51, sections 7.2 "CID register" and 7.4 "Extended CSD register"
+//!
+//! The registers are read from the device by the eMMC driver; this crate only interprets their
+//! contents
+
+#![deny(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+use core::{fmt, str, time::Duration};
+
+/// Size of the EXT_CSD register, in bytes
+pub const EXT_CSD_SIZE: usize = 512;
+
+/// Size of the CID register, in bytes
+pub const CID_SIZE: usize = 16;
+
+// EXT_CSD fields (byte offsets); the multi-byte fields are little endian
+const CACHE_CTRL: usize = 33;
+const GP_SIZE_MULT: usize = 143;
+const PARTITION_SETTING_COMPLETED: usize = 155;
+const WR_REL_PARAM: usize = 166;
+const RPMB_SIZE_MULT: usize = 168;
+const USER_WP: usize = 171;
+const ERASE_GROUP_DEF: usize = 175;
+const PARTITION_CONFIG: usize = 179;
+const EXT_CSD_REV: usize = 192;
+const DEVICE_TYPE: usize = 196;
+const SEC_COUNT: usize = 212;
+const HC_WP_GRP_SIZE: usize = 221;
+const REL_WR_SEC_C: usize = 222;
+const ERASE_TIMEOUT_MULT: usize = 223;
+const HC_ERASE_GRP_SIZE: usize = 224;
+const BOOT_SIZE_MULT: usize = 226;
+const SEC_TRIM_MULT: usize = 229;
+const SEC_FEATURE_SUPPORT: usize = 231;
+const TRIM_MULT: usize = 232;
+const CACHE_SIZE: usize = 249;
+const PRE_EOL_INFO: usize = 267;
+const DEVICE_LIFE_TIME_EST_TYP_A: usize = 268;
+const DEVICE_LIFE_TIME_EST_TYP_B: usize = 269;
+
+// WR_REL_PARAM bits
+const EN_REL_WR: u8 = 1 << 2;
+
+// SEC_FEATURE_SUPPORT bits
+const SECURE_ER_EN: u8 = 1;
+const SEC_GB_CL_EN: u8 = 1 << 4;
+const SEC_SANITIZE: u8 = 1 << 6;
+
+/// Unit of the partition sizes: 128 KB
+const SIZE_UNIT: u32 = 128 * 1024;
+
+/// Unit of the high capacity erase group size: 512 KB
+const ERASE_GROUP_UNIT: u32 = 512 * 1024;
+
+/// Unit of the erase and trim timeouts: 300 ms
+const TIMEOUT_UNIT_MS: u64 = 300;
+
+/// EXT_CSD_REV of version 4.3 of the specification
+const REV_V4_3: u8 = 3;
+
+/// EXT_CSD_REV of version 4.5 of the specification
+const REV_V4_5: u8 = 6;
+
+/// Extended Card Specific Data register
+#[derive(Clone)]
+pub struct ExtCsd {
+    bytes: [u8; EXT_CSD_SIZE],
+}
+
+impl ExtCsd {
+    /// Interprets the contents of the register, as sent by the device
+    pub fn from_bytes(bytes: &[u8; EXT_CSD_SIZE]) -> Self {
+        ExtCsd { bytes: *bytes }
+    }
+
+    /// Returns the raw contents of the register
+    pub fn as_bytes(&self) -> &[u8; EXT_CSD_SIZE] {
+        &self.bytes
+    }
+
+    /// Revision of the register (EXT_CSD_REV); e.g. 8 for version 5.1 of the specification
+    pub fn rev(&self) -> u8 {
+        self.bytes[EXT_CSD_REV]
+    }
+
+    /// Capacity of the user data area, in 512-byte sectors (SEC_COUNT)
+    pub fn sectors(&self) -> u32 {
+        self.u32_at(SEC_COUNT)
+    }
+
+    /// Bus modes supported by the device (DEVICE_TYPE)
+    pub fn device_type(&self) -> DeviceType {
+        DeviceType(self.bytes[DEVICE_TYPE])
+    }
+
+    /// Size of the volatile cache, in bytes; zero if the device has no cache
+    pub fn cache_size(&self) -> u64 {
+        // the size is given in KiB
+        u64::from(self.u32_at(CACHE_SIZE)) * 1024
+    }
+
+    /// Whether the volatile cache is enabled (CACHE_CTRL)
+    pub fn cache_enabled(&self) -> bool {
+        self.bytes[CACHE_CTRL] & 1 != 0
+    }
+
+    /// Whether reliable writes are atomic per 512-byte sector, regardless of the size of the
+    /// write (EN_REL_WR)
+    ///
+    /// Otherwise only reliable writes of a single sector or of `reliable_write_sectors` sectors,
+    /// aligned to that size, are atomic
+    pub fn enhanced_reliable_write(&self) -> bool {
+        self.bytes[WR_REL_PARAM] & EN_REL_WR != 0
+    }
+
+    /// Number of sectors that are written atomically by a (legacy) reliable write (REL_WR_SEC_C)
+    pub fn reliable_write_sectors(&self) -> u8 {
+        self.bytes[REL_WR_SEC_C]
+    }
+
+    /// Value of the USER_WP field: write protection settings of the user data area
+    pub fn user_wp(&self) -> u8 {
+        self.bytes[USER_WP]
+    }
+
+    /// Whether the device supports the high capacity erase and write protect group sizes
+    pub fn supports_high_capacity_groups(&self) -> bool {
+        self.rev() >= REV_V4_3
+    }
+
+    /// Whether the high capacity erase and write protect group sizes are in use (ERASE_GROUP_DEF)
+    pub fn high_capacity_groups(&self) -> bool {
+        self.bytes[ERASE_GROUP_DEF] & 1 != 0
+    }
+
+    /// Estimated wear of the SLC memory (or of the first memory type, on devices that use a
+    /// single type of memory)
+    pub fn life_time_estimate_a(&self) -> LifeTime {
+        LifeTime::from(self.bytes[DEVICE_LIFE_TIME_EST_TYP_A])
+    }
+
+    /// Estimated wear of the MLC memory (or of the second memory type)
+    pub fn life_time_estimate_b(&self) -> LifeTime {
+        LifeTime::from(self.bytes[DEVICE_LIFE_TIME_EST_TYP_B])
+    }
+
+    /// Consumption of the reserved blocks, used to replace the blocks that wear out
+    pub fn pre_eol_info(&self) -> PreEol {
+        PreEol::from(self.bytes[PRE_EOL_INFO])
+    }
+
+    /// Value of the PARTITION_CONFIG field: boot configuration and selected partition
+    pub fn partition_config(&self) -> u8 {
+        self.bytes[PARTITION_CONFIG]
+    }
+
+    /// Whether the configuration of the general purpose partitions has been completed; the
+    /// partitions can't be used until then
+    pub fn partition_setting_completed(&self) -> bool {
+        self.bytes[PARTITION_SETTING_COMPLETED] & 1 != 0
+    }
+
+    /// Size of each of the two boot partitions, in bytes
+    pub fn boot_size(&self) -> u32 {
+        u32::from(self.bytes[BOOT_SIZE_MULT]) * SIZE_UNIT
+    }
+
+    /// Size of the RPMB partition, in bytes
+    pub fn rpmb_size(&self) -> u32 {
+        u32::from(self.bytes[RPMB_SIZE_MULT]) * SIZE_UNIT
+    }
+
+    /// Size of the general purpose partition `index` (0-3), in bytes
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `index` is greater than 3
+    pub fn gp_size(&self, index: usize) -> u64 {
+        assert!(index < 4, "there are only 4 general purpose partitions");
+
+        let mult = &self.bytes[GP_SIZE_MULT + 3 * index..][..3];
+        let mult = u32::from_le_bytes([mult[0], mult[1], mult[2], 0]);
+        u64::from(mult) * self.wp_group_size()
+    }
+
+    /// Size of the high capacity erase unit, in bytes
+    pub fn erase_group_size(&self) -> u32 {
+        u32::from(self.bytes[HC_ERASE_GRP_SIZE]) * ERASE_GROUP_UNIT
+    }
+
+    /// Size of the high capacity write protect group, in bytes
+    pub fn wp_group_size(&self) -> u64 {
+        // given in multiples of the erase unit
+        u64::from(self.bytes[HC_WP_GRP_SIZE]) * u64::from(self.erase_group_size())
+    }
+
+    /// Whether the device supports the discard operation
+    pub fn supports_discard(&self) -> bool {
+        self.rev() >= REV_V4_5
+    }
+
+    /// Whether the device supports the trim and secure trim operations (SEC_GB_CL_EN)
+    pub fn supports_trim(&self) -> bool {
+        self.bytes[SEC_FEATURE_SUPPORT] & SEC_GB_CL_EN != 0
+    }
+
+    /// Whether the device supports the secure erase and secure trim operations (SECURE_ER_EN)
+    pub fn supports_secure_erase(&self) -> bool {
+        self.bytes[SEC_FEATURE_SUPPORT] & SECURE_ER_EN != 0
+    }
+
+    /// Whether the device supports the sanitize operation (SEC_SANITIZE)
+    pub fn supports_sanitize(&self) -> bool {
+        self.bytes[SEC_FEATURE_SUPPORT] & SEC_SANITIZE != 0
+    }
+
+    /// Maximum time a trim or discard operation takes per erase group
+    pub fn trim_timeout(&self) -> Duration {
+        timeout(u32::from(self.bytes[TRIM_MULT]))
+    }
+
+    /// Maximum time each step of a secure trim operation takes per erase group
+    pub fn secure_trim_timeout(&self) -> Duration {
+        timeout(u32::from(self.bytes[ERASE_TIMEOUT_MULT]) * u32::from(self.bytes[SEC_TRIM_MULT]))
+    }
+
+    fn u32_at(&self, offset: usize) -> u32 {
+        let b = &self.bytes[offset..][..4];
+        u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+    }
+}
+
+fn timeout(mult: u32) -> Duration {
+    Duration::from_millis(TIMEOUT_UNIT_MS * u64::from(mult))
+}
+
+/// Bus modes supported by the device
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeviceType(u8);
+
+impl DeviceType {
+    /// Raw value of the DEVICE_TYPE field
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// High speed SDR mode at up to 26 MHz
+    pub fn hs26(self) -> bool {
+        self.0 & 1 != 0
+    }
+
+    /// High speed SDR mode at up to 52 MHz
+    pub fn hs52(self) -> bool {
+        self.0 & (1 << 1) != 0
+    }
+
+    /// High speed DDR mode at up to 52 MHz, with 1.8V or 3V I/O
+    pub fn ddr52(self) -> bool {
+        self.0 & (1 << 2) != 0
+    }
+
+    /// High speed DDR mode at up to 52 MHz, with 1.2V I/O
+    pub fn ddr52_1v2(self) -> bool {
+        self.0 & (1 << 3) != 0
+    }
+
+    /// HS200 SDR mode at up to 200 MHz, with 1.8V I/O
+    pub fn hs200(self) -> bool {
+        self.0 & (1 << 4) != 0
+    }
+
+    /// HS200 SDR mode at up to 200 MHz, with 1.2V I/O
+    pub fn hs200_1v2(self) -> bool {
+        self.0 & (1 << 5) != 0
+    }
+
+    /// HS400 DDR mode at up to 200 MHz, with 1.8V I/O
+    pub fn hs400(self) -> bool {
+        self.0 & (1 << 6) != 0
+    }
+
+    /// HS400 DDR mode at up to 200 MHz, with 1.2V I/O
+    pub fn hs400_1v2(self) -> bool {
+        self.0 & (1 << 7) != 0
+    }
+}
+
+/// Estimated wear of the device, in terms of its rated erase cycles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LifeTime {
+    /// The device doesn't provide an estimate
+    Undefined,
+
+    /// Up to the given percentage (10, 20, .., 100) of the life time has been used; e.g.
+    /// `Used(30)` means between 20% and 30% has been used
+    Used(u8),
+
+    /// The device has exceeded its rated life time
+    Exceeded,
+}
+
+impl From<u8> for LifeTime {
+    fn from(value: u8) -> Self {
+        match value {
+            0x01..=0x0A => LifeTime::Used(value * 10),
+            0x0B => LifeTime::Exceeded,
+            // 0x00 = not defined; the other values are reserved
+            _ => LifeTime::Undefined,
+        }
+    }
+}
+
+/// Consumption of the reserved blocks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PreEol {
+    /// The device doesn't report this information
+    Undefined,
+
+    /// Normal
+    Normal,
+
+    /// 80% of the reserved blocks have been consumed
+    Warning,
+
+    /// 90% of the reserved blocks have been consumed
+    Urgent,
+}
+
+impl From<u8> for PreEol {
+    fn from(value: u8) -> Self {
+        match value {
+            0x01 => PreEol::Normal,
+            0x02 => PreEol::Warning,
+            0x03 => PreEol::Urgent,
+            // 0x00 = not defined; the other values are reserved
+            _ => PreEol::Undefined,
+        }
+    }
+}
+
+/// Card Identification register
+#[derive(Clone, Copy, PartialEq)]
+pub struct Cid {
+    bytes: [u8; CID_SIZE],
+}
+
+impl Cid {
+    /// Interprets the contents of the register; `bytes[0]` holds the most significant bits,
+    /// CID[127:120]
+    pub fn from_bytes(bytes: &[u8; CID_SIZE]) -> Self {
+        Cid { bytes: *bytes }
+    }
+
+    /// Returns the raw contents of the register
+    pub fn as_bytes(&self) -> &[u8; CID_SIZE] {
+        &self.bytes
+    }
+
+    /// Manufacturer ID (MID), assigned by JEDEC
+    pub fn manufacturer_id(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    /// OEM / application ID (OID)
+    pub fn oem_id(&self) -> u8 {
+        self.bytes[2]
+    }
+
+    /// Product name (PNM), usually ASCII padded with spaces or NUL characters
+    pub fn product_name(&self) -> [u8; 6] {
+        let mut pnm = [0; 6];
+        pnm.copy_from_slice(&self.bytes[3..9]);
+        pnm
+    }
+
+    /// Product name as a string, with the padding removed; `None` if it's not valid UTF-8
+    pub fn product_name_str(&self) -> Option<&str> {
+        str::from_utf8(&self.bytes[3..9])
+            .ok()
+            .map(|name| name.trim_end_matches(&[' ', '\0'][..]))
+    }
+
+    /// Product revision (PRV) as a (major, minor) pair
+    pub fn product_revision(&self) -> (u8, u8) {
+        let prv = self.bytes[9];
+        (prv >> 4, prv & 0xf)
+    }
+
+    /// Product serial number (PSN)
+    pub fn serial_number(&self) -> u32 {
+        let b = &self.bytes[10..14];
+        u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+    }
+
+    /// Manufacturing date (MDT) as a (year, month) pair
+    ///
+    /// The year is given in a 16-year window whose start depends on the EXT_CSD revision of the
+    /// device, `ext_csd_rev`; see `ExtCsd::rev`
+    pub fn manufacturing_date(&self, ext_csd_rev: u8) -> (u16, u8) {
+        let mdt = self.bytes[14];
+        let month = mdt >> 4;
+        let mut year = 1997 + u16::from(mdt & 0xf);
+
+        // on devices newer than version 4.41 of the specification the window spans 2010-2025;
+        // see section 7.2.9 of JESD84-B51
+        if ext_csd_rev > 4 && year < 2010 {
+            year += 16;
+        }
+
+        (year, month)
+    }
+}
+
+impl fmt::Debug for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (major, minor) = self.product_revision();
+        f.debug_struct("Cid")
+            .field("manufacturer_id", &self.manufacturer_id())
+            .field("oem_id", &self.oem_id())
+            .field("product_name", &self.product_name_str())
+            .field("product_revision", &format_args!("{}.{}", major, minor))
+            .field(
+                "serial_number",
+                &format_args!("{:#010x}", self.serial_number()),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{Cid, ExtCsd, LifeTime, PreEol, CID_SIZE, EXT_CSD_SIZE};
+
+    // EXT_CSD of an eMMC 5.1 device, 16 bytes per row; the rows not listed are all zeros
+    const EXT_CSD: &[(usize, &str)] = &[
+        (160, "07000000000000002000000000000001"),
+        (176, "00000048000000020001000000000000"),
+        (192, "08000000570000000000000000000000"),
+        (208, "0000000000a0d5010000000000100002"),
+        (224, "01002000001100550500000000000000"),
+        (240, "00000000000000000000040000000000"),
+        (256, "00000000000000000000000102010000"),
+    ];
+
+    // CID of the same device, as shown in `/sys/block/mmcblk*/device/cid` (CRC included)
+    const CID: &str = "450100444734303136109c2e4f0178c5";
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn captured_ext_csd() -> ExtCsd {
+        let mut bytes = [0; EXT_CSD_SIZE];
+        for (offset, row) in EXT_CSD {
+            bytes[*offset..][..16].copy_from_slice(&hex(row));
+        }
+        ExtCsd::from_bytes(&bytes)
+    }
+
+    fn captured_cid() -> Cid {
+        let mut bytes = [0; CID_SIZE];
+        bytes.copy_from_slice(&hex(CID));
+        Cid::from_bytes(&bytes)
+    }
+
+    #[test]
+    fn ext_csd_health() {
+        let ext_csd = captured_ext_csd();
+
+        assert_eq!(ext_csd.rev(), 8);
+        assert_eq!(ext_csd.life_time_estimate_a(), LifeTime::Used(20));
+        assert_eq!(ext_csd.life_time_estimate_b(), LifeTime::Used(10));
+        assert_eq!(ext_csd.pre_eol_info(), PreEol::Normal);
+        assert_eq!(ext_csd.cache_size(), 1024 * 1024);
+    }
+
+    #[test]
+    fn ext_csd_write_settings() {
+        let ext_csd = captured_ext_csd();
+
+        assert!(!ext_csd.cache_enabled());
+        assert!(!ext_csd.enhanced_reliable_write());
+        assert_eq!(ext_csd.reliable_write_sectors(), 0);
+        assert_eq!(ext_csd.user_wp(), 0);
+        assert!(ext_csd.supports_high_capacity_groups());
+        assert!(ext_csd.high_capacity_groups());
+
+        let mut bytes = *ext_csd.as_bytes();
+        bytes[33] = 1; // CACHE_CTRL
+        bytes[166] = 0x15; // WR_REL_PARAM
+        bytes[171] = 0x08; // USER_WP
+        bytes[175] = 0; // ERASE_GROUP_DEF
+        bytes[222] = 1; // REL_WR_SEC_C
+        let ext_csd = ExtCsd::from_bytes(&bytes);
+
+        assert!(ext_csd.cache_enabled());
+        assert!(ext_csd.enhanced_reliable_write());
+        assert_eq!(ext_csd.reliable_write_sectors(), 1);
+        assert_eq!(ext_csd.user_wp(), 0x08);
+        assert!(!ext_csd.high_capacity_groups());
+    }
+
+    #[test]
+    fn ext_csd_bus_modes() {
+        let device_type = captured_ext_csd().device_type();
+
+        assert_eq!(device_type.bits(), 0x57);
+        assert!(device_type.hs26());
+        assert!(device_type.hs52());
+        assert!(device_type.ddr52());
+        assert!(!device_type.ddr52_1v2());
+        assert!(device_type.hs200());
+        assert!(!device_type.hs200_1v2());
+        assert!(device_type.hs400());
+        assert!(!device_type.hs400_1v2());
+    }
+
+    #[test]
+    fn ext_csd_partitions() {
+        let ext_csd = captured_ext_csd();
+
+        // 14.7 GiB
+        assert_eq!(ext_csd.sectors(), 30_777_344);
+        assert_eq!(ext_csd.boot_size(), 4 * 1024 * 1024);
+        assert_eq!(ext_csd.rpmb_size(), 4 * 1024 * 1024);
+        assert_eq!(ext_csd.partition_config(), 0x48);
+        assert!(!ext_csd.partition_setting_completed());
+        assert_eq!(ext_csd.erase_group_size(), 512 * 1024);
+        assert_eq!(ext_csd.wp_group_size(), 8 * 1024 * 1024);
+        for i in 0..4 {
+            assert_eq!(ext_csd.gp_size(i), 0);
+        }
+    }
+
+    #[test]
+    fn ext_csd_gp_size() {
+        let mut bytes = *captured_ext_csd().as_bytes();
+        // GP_SIZE_MULT_2 = 0x000102, little endian
+        bytes[146..149].copy_from_slice(&[0x02, 0x01, 0x00]);
+        let ext_csd = ExtCsd::from_bytes(&bytes);
+
+        assert_eq!(ext_csd.gp_size(0), 0);
+        assert_eq!(ext_csd.gp_size(1), 0x102 * 8 * 1024 * 1024);
+    }
+
+    #[test]
+    fn ext_csd_erase() {
+        let ext_csd = captured_ext_csd();
+
+        assert!(ext_csd.supports_discard());
+        assert!(ext_csd.supports_trim());
+        assert!(ext_csd.supports_secure_erase());
+        assert!(ext_csd.supports_sanitize());
+        assert_eq!(ext_csd.trim_timeout(), Duration::from_millis(5 * 300));
+        assert_eq!(
+            ext_csd.secure_trim_timeout(),
+            Duration::from_millis(2 * 17 * 300)
+        );
+    }
+
+    #[test]
+    fn life_time() {
+        assert_eq!(LifeTime::from(0x00), LifeTime::Undefined);
+        assert_eq!(LifeTime::from(0x01), LifeTime::Used(10));
+        assert_eq!(LifeTime::from(0x0A), LifeTime::Used(100));
+        assert_eq!(LifeTime::from(0x0B), LifeTime::Exceeded);
+        assert_eq!(LifeTime::from(0x0C), LifeTime::Undefined);
+
+        assert_eq!(PreEol::from(0x00), PreEol::Undefined);
+        assert_eq!(PreEol::from(0x02), PreEol::Warning);
+        assert_eq!(PreEol::from(0x03), PreEol::Urgent);
+        assert_eq!(PreEol::from(0x04), PreEol::Undefined);
+    }
+
+    #[test]
+    fn cid() {
+        let cid = captured_cid();
+
+        assert_eq!(cid.manufacturer_id(), 0x45);
+        assert_eq!(cid.oem_id(), 0x00);
+        assert_eq!(&cid.product_name(), b"DG4016");
+        assert_eq!(cid.product_name_str(), Some("DG4016"));
+        assert_eq!(cid.product_revision(), (1, 0));
+        assert_eq!(cid.serial_number(), 0x9c2e_4f01);
+        assert_eq!(
+            format!("{:?}", cid),
+            "Cid { manufacturer_id: 69, oem_id: 0, product_name: Some(\"DG4016\"), \
+             product_revision: 1.0, serial_number: 0x9c2e4f01 }"
+        );
+    }
+
+    #[test]
+    fn manufacturing_date() {
+        let cid = captured_cid();
+
+        // July, year 8 of the window
+        assert_eq!(cid.manufacturing_date(8), (2021, 7));
+        assert_eq!(cid.manufacturing_date(4), (2005, 7));
+
+        let mut bytes = *cid.as_bytes();
+        bytes[3..9].copy_from_slice(b"ABC\0\0\0");
+        // year 13 of the window
+        bytes[14] = 0x1d;
+        let cid = Cid::from_bytes(&bytes);
+        assert_eq!(cid.product_name_str(), Some("ABC"));
+        assert_eq!(cid.manufacturing_date(8), (2010, 1));
+    }
+}
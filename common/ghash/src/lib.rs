@@ -0,0 +1,184 @@
+//! GHASH, the universal hash function used by the Galois/Counter Mode (GCM)
+//!
+//! Reference: NIST SP 800-38D, section 6.4
+
+#![deny(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+/// Size of a GHASH block, in bytes
+pub const BLOCK_SIZE: usize = 16;
+
+// the reduction polynomial `x^128 + x^7 + x^2 + x + 1` in GCM's bit order (see Algorithm 1)
+const R: u128 = 0xe1 << 120;
+
+/// GHASH state
+#[derive(Clone)]
+pub struct GHash {
+    h: u128,
+    y: u128,
+}
+
+impl GHash {
+    /// Creates a new GHASH instance that uses the given hash subkey
+    ///
+    /// In GCM the hash subkey `H` is the encryption of the all-zeros block
+    pub fn new(h: &[u8; BLOCK_SIZE]) -> Self {
+        Self {
+            h: u128::from_be_bytes(*h),
+            y: 0,
+        }
+    }
+
+    /// Absorbs a single `block`
+    pub fn update_block(&mut self, block: &[u8; BLOCK_SIZE]) {
+        self.y = mul(self.y ^ u128::from_be_bytes(*block), self.h);
+    }
+
+    /// Absorbs `data`
+    ///
+    /// If the length of `data` is not a multiple of the block size the last block is padded with
+    /// zeros
+    pub fn update_padded(&mut self, data: &[u8]) {
+        for chunk in data.chunks(BLOCK_SIZE) {
+            let mut block = [0; BLOCK_SIZE];
+            block[..chunk.len()].copy_from_slice(chunk);
+            self.update_block(&block);
+        }
+    }
+
+    /// Returns the output of the hash function
+    pub fn finalize(self) -> [u8; BLOCK_SIZE] {
+        self.y.to_be_bytes()
+    }
+}
+
+// Multiplication in GF(2^128) -- SP 800-38D, Algorithm 1
+//
+// The loop has no data-dependent branches or memory accesses so its timing doesn't depend on the
+// operands
+fn mul(x: u128, y: u128) -> u128 {
+    let mut z = 0;
+    let mut v = y;
+    for i in 0..128 {
+        // all ones if bit `i` of `x` (MSB first) is set
+        let mask = ((x >> (127 - i)) & 1).wrapping_neg();
+        z ^= v & mask;
+
+        let lsb_mask = (v & 1).wrapping_neg();
+        v = (v >> 1) ^ (R & lsb_mask);
+    }
+    z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GHash;
+
+    // GHASH(H, A, C) as computed by GCM
+    fn ghash(h: &str, a: &str, c: &str) -> Vec<u8> {
+        let (a, c) = (hex(a), hex(c));
+
+        let mut key = [0; 16];
+        key.copy_from_slice(&hex(h));
+        let mut ghash = GHash::new(&key);
+        ghash.update_padded(&a);
+        ghash.update_padded(&c);
+
+        let mut lengths = [0; 16];
+        lengths[..8].copy_from_slice(&(a.len() as u64 * 8).to_be_bytes());
+        lengths[8..].copy_from_slice(&(c.len() as u64 * 8).to_be_bytes());
+        ghash.update_block(&lengths);
+
+        ghash.finalize().to_vec()
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // The test cases come from "The Galois/Counter Mode of Operation (GCM)" (McGrew & Viega), the
+    // specification referenced by NIST SP 800-38D. The intermediate GHASH values listed there can
+    // be reproduced with the following Python code
+    //
+    // ``` python
+    // # cryptography = "48.0"
+    // from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+    // from cryptography.hazmat.primitives.ciphers.aead import AESGCM
+    //
+    // def aes(key, block):
+    //     return Cipher(algorithms.AES(key), modes.ECB()).encryptor().update(block)
+    //
+    // tag = AESGCM(key).encrypt(iv, plaintext, aad)[-16:]
+    // h = aes(key, bytes(16))
+    // ghash = bytes(x ^ y for x, y in zip(tag, aes(key, iv + bytes([0, 0, 0, 1]))))
+    // ```
+
+    #[test]
+    fn test_case_1() {
+        assert_eq!(
+            ghash("66e94bd4ef8a2c3b884cfa59ca342b2e", "", ""),
+            vec![0; 16]
+        );
+    }
+
+    #[test]
+    fn test_case_2() {
+        assert_eq!(
+            ghash(
+                "66e94bd4ef8a2c3b884cfa59ca342b2e",
+                "",
+                "0388dace60b6a392f328c2b971b2fe78"
+            ),
+            hex("f38cbb1ad69223dcc3457ae5b6b0f885")
+        );
+    }
+
+    #[test]
+    fn test_case_3() {
+        assert_eq!(
+            ghash(
+                "b83b533708bf535d0aa6e52980d53b78",
+                "",
+                "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+                 21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091473f5985"
+            ),
+            hex("7f1b32b81b820d02614f8895ac1d4eac")
+        );
+    }
+
+    #[test]
+    fn test_case_4() {
+        // associated data and ciphertext are not a multiple of the block size
+        assert_eq!(
+            ghash(
+                "b83b533708bf535d0aa6e52980d53b78",
+                "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+                "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+                 21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091"
+            ),
+            hex("698e57f70e6ecc7fd9463b7260a9ae5f")
+        );
+    }
+
+    #[test]
+    fn incremental() {
+        // absorbing the input block by block is the same as absorbing it in one go
+        let h = [0x42; 16];
+        let data = [0x5a; 48];
+
+        let mut one_go = GHash::new(&h);
+        one_go.update_padded(&data);
+
+        let mut blocks = GHash::new(&h);
+        for chunk in data.chunks(16) {
+            let mut block = [0; 16];
+            block.copy_from_slice(chunk);
+            blocks.update_block(&block);
+        }
+
+        assert_eq!(one_go.finalize(), blocks.finalize());
+    }
+}
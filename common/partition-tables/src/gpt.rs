@@ -0,0 +1,945 @@
+//! GUID partition table (GPT)
+//!
+//! Reference: UEFI specification 2.8, section 5.3 "GUID Partition Table (GPT) Disk Layout"
+//!
+//! A GPT disk starts with a protective MBR (block 0) followed by the primary header (block 1) and
+//! the primary partition entry array. A backup of the entry array and of the header is kept at the
+//! end of the disk; the header is in the last block
+
+use core::{char, fmt, ops::Range};
+
+use crate::{crc32, u32_at, u64_at, Crc32, BLOCK_SIZE};
+
+/// Size of a partition entry, in bytes; this is the only size supported
+pub const ENTRY_SIZE: usize = 128;
+
+/// Number of entries in the partition entry arrays laid out by `Header::new`
+pub const NUM_ENTRIES: u32 = 128;
+
+/// Maximum length of a partition name, in UTF-16 code units
+pub const NAME_LEN: usize = 36;
+
+/// Number of blocks taken by the partition entry arrays laid out by `Header::new`
+const ENTRY_ARRAY_BLOCKS: u64 = NUM_ENTRIES as u64 * ENTRY_SIZE as u64 / BLOCK_SIZE as u64;
+
+const SIGNATURE: &[u8] = b"EFI PART";
+const REVISION_1_0: u32 = 0x0001_0000;
+const MIN_HEADER_SIZE: u32 = 92;
+
+// header fields (byte offsets); the multi-byte fields are little endian
+const REVISION: usize = 8;
+const HEADER_SIZE: usize = 12;
+const HEADER_CRC32: usize = 16;
+const MY_LBA: usize = 24;
+const ALTERNATE_LBA: usize = 32;
+const FIRST_USABLE_LBA: usize = 40;
+const LAST_USABLE_LBA: usize = 48;
+const DISK_GUID: usize = 56;
+const PARTITION_ENTRY_LBA: usize = 72;
+const NUMBER_OF_PARTITION_ENTRIES: usize = 80;
+const SIZE_OF_PARTITION_ENTRY: usize = 84;
+const PARTITION_ENTRY_ARRAY_CRC32: usize = 88;
+
+// partition entry fields (byte offsets)
+const PARTITION_TYPE_GUID: usize = 0;
+const UNIQUE_PARTITION_GUID: usize = 16;
+const STARTING_LBA: usize = 32;
+const ENDING_LBA: usize = 40;
+const ATTRIBUTES: usize = 48;
+const PARTITION_NAME: usize = 56;
+
+// protective MBR
+const MBR_PARTITION_TABLE: usize = 446;
+const MBR_PARTITION_ENTRY_SIZE: usize = 16;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xee;
+const MBR_MAX_SECTORS: u64 = 0xffff_ffff;
+
+/// Errors found while validating or building a GUID partition table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The header doesn't start with the `EFI PART` signature
+    InvalidSignature,
+
+    /// The header has an unsupported revision, size or entry size, or it describes a layout that
+    /// doesn't fit the disk
+    InvalidHeader,
+
+    /// The CRC-32 of the header doesn't match its contents
+    HeaderCrc,
+
+    /// The CRC-32 of the partition entry array doesn't match the one in the header
+    EntriesCrc,
+
+    /// A partition ends before it starts or lies outside the usable blocks of the disk
+    InvalidPartExtent,
+
+    /// Two partitions overlap
+    PartitionCollision,
+
+    /// There are more partitions than entries in the partition entry array
+    TooManyPartitions,
+
+    /// A partition name is longer than `NAME_LEN` UTF-16 code units
+    NameTooLong,
+
+    /// The disk is too small to hold a GUID partition table
+    DiskTooSmall,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::InvalidSignature => "GPT header signature invalid",
+            Error::InvalidHeader => "invalid GPT header",
+            Error::HeaderCrc => "GPT header CRC mismatch",
+            Error::EntriesCrc => "GPT partition entry array CRC mismatch",
+            Error::InvalidPartExtent => "invalid partition entry extent",
+            Error::PartitionCollision => "partitions overlap",
+            Error::TooManyPartitions => "too many partitions",
+            Error::NameTooLong => "partition name too long",
+            Error::DiskTooSmall => "disk too small for a GPT",
+        })
+    }
+}
+
+/// A globally unique identifier
+///
+/// The GUID is stored in its on-disk byte order, where the first three fields of the textual
+/// representation are little endian
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Guid {
+    bytes: [u8; 16],
+}
+
+impl Guid {
+    /// The all-zeros GUID; it's the partition type of unused partition entries
+    pub const UNUSED: Guid = Guid::new(0, 0, 0, 0);
+
+    /// Partition type of an EFI system partition
+    pub const EFI_SYSTEM: Guid = Guid::new(0xc12a_7328, 0xf81f, 0x11d2, 0xba4b_00a0_c93e_c93b);
+
+    /// Partition type of a Linux filesystem data partition
+    pub const LINUX_FILESYSTEM: Guid =
+        Guid::new(0x0fc6_3daf, 0x8483, 0x4772, 0x8e79_3d69_d847_7de4);
+
+    /// Partition type of a Microsoft basic data partition (e.g. FAT)
+    pub const BASIC_DATA: Guid = Guid::new(0xebd0_a0a2, 0xb9e5, 0x4433, 0x87c0_68b6_b726_99c7);
+
+    /// Creates a GUID from the fields of its textual representation,
+    /// `d1-d2-d3-d4[..4]-d4[4..]` (in hexadecimal digits)
+    pub const fn new(d1: u32, d2: u16, d3: u16, d4: u64) -> Self {
+        Guid {
+            bytes: [
+                d1 as u8,
+                (d1 >> 8) as u8,
+                (d1 >> 16) as u8,
+                (d1 >> 24) as u8,
+                d2 as u8,
+                (d2 >> 8) as u8,
+                d3 as u8,
+                (d3 >> 8) as u8,
+                (d4 >> 56) as u8,
+                (d4 >> 48) as u8,
+                (d4 >> 40) as u8,
+                (d4 >> 32) as u8,
+                (d4 >> 24) as u8,
+                (d4 >> 16) as u8,
+                (d4 >> 8) as u8,
+                d4 as u8,
+            ],
+        }
+    }
+
+    /// Creates a random (version 4) GUID from 16 random `bytes`
+    pub fn from_random_bytes(bytes: [u8; 16]) -> Self {
+        let mut bytes = bytes;
+        // version (in the most significant bits of `d3`) and variant
+        bytes[7] = bytes[7] & 0x0f | 0x40;
+        bytes[8] = bytes[8] & 0x3f | 0x80;
+        Guid { bytes }
+    }
+
+    /// Creates a GUID from its on-disk representation
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Guid { bytes }
+    }
+
+    /// Returns the on-disk representation of the GUID
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.bytes
+    }
+
+    /// Parses the textual representation of a GUID, e.g.
+    /// `C12A7328-F81F-11D2-BA4B-00A0C93EC93B`; both upper and lower case digits are accepted
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.as_bytes();
+        if s.len() != 36 {
+            return None;
+        }
+
+        let mut digits = [0; 32];
+        let mut n = 0;
+        for (i, c) in s.iter().enumerate() {
+            if i == 8 || i == 13 || i == 18 || i == 23 {
+                if *c != b'-' {
+                    return None;
+                }
+            } else {
+                digits[n] = char::from(*c).to_digit(16)? as u8;
+                n += 1;
+            }
+        }
+
+        let mut bytes = [0; 16];
+        for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+            *byte = pair[0] << 4 | pair[1];
+        }
+        // the first three fields are stored little endian
+        bytes[..4].reverse();
+        bytes[4..6].reverse();
+        bytes[6..8].reverse();
+
+        Some(Guid { bytes })
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.bytes;
+        write!(
+            f,
+            "{:02X}{:02X}{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-",
+            b[3], b[2], b[1], b[0], b[5], b[4], b[7], b[6], b[8], b[9]
+        )?;
+        for byte in &b[10..] {
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// GPT header
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Header {
+    /// Block that holds this copy of the header
+    pub my_lba: u64,
+    /// Block that holds the other copy of the header
+    pub alternate_lba: u64,
+    /// First block that partitions can use
+    pub first_usable_lba: u64,
+    /// Last block that partitions can use
+    pub last_usable_lba: u64,
+    /// GUID that identifies the disk
+    pub disk_guid: Guid,
+    /// First block of the partition entry array
+    pub entries_lba: u64,
+    /// Number of entries in the partition entry array
+    pub num_entries: u32,
+    /// CRC-32 of the partition entry array
+    pub entries_crc32: u32,
+}
+
+impl Header {
+    /// Lays out the primary header of a disk of `total_blocks` blocks
+    ///
+    /// The partition entry array has `NUM_ENTRIES` entries and follows the header; the backup copy
+    /// of the array precedes the backup header, in the last block. `entries_crc32` is left as 0
+    pub fn new(disk_guid: Guid, total_blocks: u64) -> Result<Self, Error> {
+        // protective MBR, two headers, two entry arrays and at least one usable block
+        if total_blocks < 4 + 2 * ENTRY_ARRAY_BLOCKS {
+            return Err(Error::DiskTooSmall);
+        }
+
+        Ok(Header {
+            my_lba: 1,
+            alternate_lba: total_blocks - 1,
+            first_usable_lba: 2 + ENTRY_ARRAY_BLOCKS,
+            last_usable_lba: total_blocks - 2 - ENTRY_ARRAY_BLOCKS,
+            disk_guid,
+            entries_lba: 2,
+            num_entries: NUM_ENTRIES,
+            entries_crc32: 0,
+        })
+    }
+
+    /// Parses the header stored in `block`, which was read from block `lba` of a disk of
+    /// `total_blocks` blocks, and checks its CRC and layout
+    ///
+    /// The CRC of the partition entry array is not checked as the array is not part of `block`
+    pub fn parse(block: &[u8; BLOCK_SIZE], lba: u64, total_blocks: u64) -> Result<Self, Error> {
+        if &block[..SIGNATURE.len()] != SIGNATURE {
+            return Err(Error::InvalidSignature);
+        }
+
+        let size = u32_at(block, HEADER_SIZE);
+        if u32_at(block, REVISION) >> 16 != REVISION_1_0 >> 16
+            || size < MIN_HEADER_SIZE
+            || size > BLOCK_SIZE as u32
+        {
+            return Err(Error::InvalidHeader);
+        }
+
+        // the CRC is computed with the CRC field zeroed
+        let mut crc = Crc32::new();
+        crc.update(&block[..HEADER_CRC32]);
+        crc.update(&[0; 4]);
+        crc.update(&block[HEADER_CRC32 + 4..size as usize]);
+        if crc.finish() != u32_at(block, HEADER_CRC32) {
+            return Err(Error::HeaderCrc);
+        }
+
+        if u32_at(block, SIZE_OF_PARTITION_ENTRY) != ENTRY_SIZE as u32 {
+            return Err(Error::InvalidHeader);
+        }
+
+        let mut disk_guid = [0; 16];
+        disk_guid.copy_from_slice(&block[DISK_GUID..][..16]);
+        let header = Header {
+            my_lba: u64_at(block, MY_LBA),
+            alternate_lba: u64_at(block, ALTERNATE_LBA),
+            first_usable_lba: u64_at(block, FIRST_USABLE_LBA),
+            last_usable_lba: u64_at(block, LAST_USABLE_LBA),
+            disk_guid: Guid::from_bytes(disk_guid),
+            entries_lba: u64_at(block, PARTITION_ENTRY_LBA),
+            num_entries: u32_at(block, NUMBER_OF_PARTITION_ENTRIES),
+            entries_crc32: u32_at(block, PARTITION_ENTRY_ARRAY_CRC32),
+        };
+
+        header.fits(lba, total_blocks)?;
+        Ok(header)
+    }
+
+    /// Returns the backup copy of this (primary) header
+    ///
+    /// The backup partition entry array is placed right before the backup header; an error is
+    /// returned if there's no room for it there
+    pub fn backup(&self) -> Result<Self, Error> {
+        let entries_lba = self
+            .alternate_lba
+            .checked_sub(self.entry_array_blocks())
+            .ok_or(Error::InvalidHeader)?;
+
+        Ok(Header {
+            my_lba: self.alternate_lba,
+            alternate_lba: self.my_lba,
+            entries_lba,
+            ..*self
+        })
+    }
+
+    /// Returns the number of blocks taken by the partition entry array
+    pub fn entry_array_blocks(&self) -> u64 {
+        let bytes = u64::from(self.num_entries) * ENTRY_SIZE as u64;
+        let partial = bytes % BLOCK_SIZE as u64;
+        // rounded up to whole blocks
+        bytes / BLOCK_SIZE as u64 + u64::from(partial > 0)
+    }
+
+    /// Returns the block of the partition entry array that holds entry `index`, and the offset of
+    /// the entry within that block
+    pub fn entry_location(&self, index: u32) -> (u64, usize) {
+        let offset = u64::from(index) * ENTRY_SIZE as u64;
+        (
+            self.entries_lba + offset / BLOCK_SIZE as u64,
+            (offset % BLOCK_SIZE as u64) as usize,
+        )
+    }
+
+    /// Serializes the header, computing its CRC-32
+    pub fn to_block(&self) -> [u8; BLOCK_SIZE] {
+        let mut block = [0; BLOCK_SIZE];
+        block[..SIGNATURE.len()].copy_from_slice(SIGNATURE);
+        block[REVISION..][..4].copy_from_slice(&REVISION_1_0.to_le_bytes());
+        block[HEADER_SIZE..][..4].copy_from_slice(&MIN_HEADER_SIZE.to_le_bytes());
+        block[MY_LBA..][..8].copy_from_slice(&self.my_lba.to_le_bytes());
+        block[ALTERNATE_LBA..][..8].copy_from_slice(&self.alternate_lba.to_le_bytes());
+        block[FIRST_USABLE_LBA..][..8].copy_from_slice(&self.first_usable_lba.to_le_bytes());
+        block[LAST_USABLE_LBA..][..8].copy_from_slice(&self.last_usable_lba.to_le_bytes());
+        block[DISK_GUID..][..16].copy_from_slice(self.disk_guid.as_bytes());
+        block[PARTITION_ENTRY_LBA..][..8].copy_from_slice(&self.entries_lba.to_le_bytes());
+        block[NUMBER_OF_PARTITION_ENTRIES..][..4].copy_from_slice(&self.num_entries.to_le_bytes());
+        block[SIZE_OF_PARTITION_ENTRY..][..4].copy_from_slice(&(ENTRY_SIZE as u32).to_le_bytes());
+        block[PARTITION_ENTRY_ARRAY_CRC32..][..4]
+            .copy_from_slice(&self.entries_crc32.to_le_bytes());
+
+        let crc = crc32(&block[..MIN_HEADER_SIZE as usize]);
+        block[HEADER_CRC32..][..4].copy_from_slice(&crc.to_le_bytes());
+        block
+    }
+
+    /// Checks that the layout described by the header, which was read from block `lba`, fits a
+    /// disk of `total_blocks` blocks
+    fn fits(&self, lba: u64, total_blocks: u64) -> Result<(), Error> {
+        // the fields come from disk so computing the end of a range may overflow
+        let end = |start: u64, len: u64| start.checked_add(len).ok_or(Error::InvalidHeader);
+        let entries = self.entries_lba..end(self.entries_lba, self.entry_array_blocks())?;
+        let usable = self.first_usable_lba..end(self.last_usable_lba, 1)?;
+        let disjoint = |a: &Range<u64>, b: &Range<u64>| a.end <= b.start || b.end <= a.start;
+
+        let fits = self.my_lba == lba
+            && self.my_lba != 0
+            && self.alternate_lba != 0
+            && self.alternate_lba != self.my_lba
+            && self.alternate_lba < total_blocks
+            && self.first_usable_lba <= self.last_usable_lba
+            && self.last_usable_lba < total_blocks
+            && self.entries_lba > 1
+            && entries.end <= total_blocks
+            && disjoint(&entries, &usable)
+            && !usable.contains(&self.my_lba)
+            && !entries.contains(&self.my_lba);
+
+        if fits {
+            Ok(())
+        } else {
+            Err(Error::InvalidHeader)
+        }
+    }
+}
+
+/// A partition entry
+#[derive(Clone, Copy)]
+pub struct Entry {
+    /// Partition type; `Guid::UNUSED` marks unused entries
+    pub type_guid: Guid,
+    /// GUID that identifies the partition
+    pub unique_guid: Guid,
+    /// First block of the partition
+    pub first_lba: u64,
+    /// Last block of the partition (inclusive)
+    pub last_lba: u64,
+    /// Attribute flags
+    pub attributes: u64,
+    name: [u16; NAME_LEN],
+}
+
+impl Entry {
+    /// An unused entry
+    pub const UNUSED: Entry = Entry {
+        type_guid: Guid::UNUSED,
+        unique_guid: Guid::UNUSED,
+        first_lba: 0,
+        last_lba: 0,
+        attributes: 0,
+        name: [0; NAME_LEN],
+    };
+
+    /// Creates an entry for a partition of type `type_guid` that spans the `blocks`
+    ///
+    /// `name` can be up to `NAME_LEN` UTF-16 code units long
+    pub fn new(
+        type_guid: Guid,
+        unique_guid: Guid,
+        blocks: Range<u64>,
+        name: &str,
+    ) -> Result<Self, Error> {
+        if blocks.start >= blocks.end {
+            return Err(Error::InvalidPartExtent);
+        }
+
+        let mut entry = Entry {
+            type_guid,
+            unique_guid,
+            first_lba: blocks.start,
+            last_lba: blocks.end - 1,
+            ..Entry::UNUSED
+        };
+        for (i, unit) in name.encode_utf16().enumerate() {
+            *entry.name.get_mut(i).ok_or(Error::NameTooLong)? = unit;
+        }
+
+        Ok(entry)
+    }
+
+    /// Parses an entry from the first `ENTRY_SIZE` bytes of `bytes`
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `bytes` is shorter than `ENTRY_SIZE`
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let bytes = &bytes[..ENTRY_SIZE];
+        let guid = |offset| {
+            let mut guid = [0; 16];
+            guid.copy_from_slice(&bytes[offset..][..16]);
+            Guid::from_bytes(guid)
+        };
+
+        let mut name = [0; NAME_LEN];
+        for (unit, pair) in name.iter_mut().zip(bytes[PARTITION_NAME..].chunks_exact(2)) {
+            *unit = u16::from_le_bytes([pair[0], pair[1]]);
+        }
+
+        Entry {
+            type_guid: guid(PARTITION_TYPE_GUID),
+            unique_guid: guid(UNIQUE_PARTITION_GUID),
+            first_lba: u64_at(bytes, STARTING_LBA),
+            last_lba: u64_at(bytes, ENDING_LBA),
+            attributes: u64_at(bytes, ATTRIBUTES),
+            name,
+        }
+    }
+
+    /// Serializes the entry
+    pub fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[PARTITION_TYPE_GUID..][..16].copy_from_slice(self.type_guid.as_bytes());
+        bytes[UNIQUE_PARTITION_GUID..][..16].copy_from_slice(self.unique_guid.as_bytes());
+        bytes[STARTING_LBA..][..8].copy_from_slice(&self.first_lba.to_le_bytes());
+        bytes[ENDING_LBA..][..8].copy_from_slice(&self.last_lba.to_le_bytes());
+        bytes[ATTRIBUTES..][..8].copy_from_slice(&self.attributes.to_le_bytes());
+        for (pair, unit) in bytes[PARTITION_NAME..].chunks_exact_mut(2).zip(&self.name) {
+            pair.copy_from_slice(&unit.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Returns `true` if the entry describes a partition
+    pub fn is_used(&self) -> bool {
+        self.type_guid != Guid::UNUSED
+    }
+
+    /// Returns the blocks of the partition
+    pub fn blocks(&self) -> Range<u64> {
+        self.first_lba..self.last_lba.saturating_add(1)
+    }
+
+    /// Returns the name of the partition; invalid UTF-16 is replaced with U+FFFD
+    pub fn name(&self) -> impl Iterator<Item = char> + '_ {
+        char::decode_utf16(self.name_units()).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    /// Returns `true` if the partition is named `name`
+    pub fn has_name(&self, name: &str) -> bool {
+        self.name_units().eq(name.encode_utf16())
+    }
+
+    /// Checks that the partition lies within the usable blocks described by `header`
+    pub fn check(&self, header: &Header) -> Result<(), Error> {
+        if self.is_used()
+            && (self.first_lba > self.last_lba
+                || self.first_lba < header.first_usable_lba
+                || self.last_lba > header.last_usable_lba)
+        {
+            Err(Error::InvalidPartExtent)
+        } else {
+            Ok(())
+        }
+    }
+
+    // the name is NUL terminated unless it takes all the available space
+    fn name_units(&self) -> impl Iterator<Item = u16> + Clone + '_ {
+        self.name.iter().cloned().take_while(|unit| *unit != 0)
+    }
+}
+
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Name<'a>(&'a Entry);
+
+        impl fmt::Debug for Name<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("\"")?;
+                for c in self.0.name() {
+                    write!(f, "{}", c.escape_debug())?;
+                }
+                f.write_str("\"")
+            }
+        }
+
+        f.debug_struct("Entry")
+            .field("type_guid", &self.type_guid)
+            .field("unique_guid", &self.unique_guid)
+            .field("first_lba", &self.first_lba)
+            .field("last_lba", &self.last_lba)
+            .field("attributes", &format_args!("{:#x}", self.attributes))
+            .field("name", &Name(self))
+            .finish()
+    }
+}
+
+/// Checks that the `partitions` fit the partition entry array and the usable blocks described by
+/// `header`, and that they don't overlap
+pub fn check_partitions(header: &Header, partitions: &[Entry]) -> Result<(), Error> {
+    if partitions.len() > header.num_entries as usize {
+        return Err(Error::TooManyPartitions);
+    }
+
+    let used = |partition: &&Entry| partition.is_used();
+    for (i, partition) in partitions.iter().enumerate().filter(|(_, p)| p.is_used()) {
+        partition.check(header)?;
+
+        check_overlap(
+            &partition.blocks(),
+            partitions[..i].iter().filter(used).map(Entry::blocks),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the `blocks` of a partition don't overlap the blocks of any of the `others`
+pub fn check_overlap(
+    blocks: &Range<u64>,
+    mut others: impl Iterator<Item = Range<u64>>,
+) -> Result<(), Error> {
+    if others.any(|other| blocks.start < other.end && other.start < blocks.end) {
+        Err(Error::PartitionCollision)
+    } else {
+        Ok(())
+    }
+}
+
+/// Serializes block `index` of a partition entry array that holds the `partitions` followed by
+/// unused entries
+pub fn entry_array_block(partitions: &[Entry], index: u64) -> [u8; BLOCK_SIZE] {
+    const ENTRIES_PER_BLOCK: usize = BLOCK_SIZE / ENTRY_SIZE;
+
+    let mut block = [0; BLOCK_SIZE];
+    let first = index as usize * ENTRIES_PER_BLOCK;
+    for (chunk, entry) in block
+        .chunks_exact_mut(ENTRY_SIZE)
+        .zip(partitions.iter().skip(first))
+    {
+        chunk.copy_from_slice(&entry.to_bytes());
+    }
+    block
+}
+
+/// Builds the protective MBR of a disk of `total_blocks` blocks
+///
+/// It holds a single partition, of type 0xEE, that covers the whole disk (or as much of it as a
+/// MBR can describe) so tools that don't understand GPT don't mistake the disk for an empty one
+pub fn protective_mbr(total_blocks: u64) -> [u8; BLOCK_SIZE] {
+    let sectors = (total_blocks - 1).min(MBR_MAX_SECTORS) as u32;
+
+    let mut block = [0; BLOCK_SIZE];
+    let entry = &mut block[MBR_PARTITION_TABLE..][..MBR_PARTITION_ENTRY_SIZE];
+    // start CHS: the block right after the MBR
+    entry[1..4].copy_from_slice(&[0x00, 0x02, 0x00]);
+    entry[4] = MBR_TYPE_GPT_PROTECTIVE;
+    // end CHS: not representable
+    entry[5..8].copy_from_slice(&[0xff, 0xff, 0xff]);
+    entry[8..12].copy_from_slice(&1u32.to_le_bytes());
+    entry[12..16].copy_from_slice(&sectors.to_le_bytes());
+    block[510] = 0x55;
+    block[511] = 0xaa;
+    block
+}
+
+/// Returns `true` if `block` is a protective MBR
+///
+/// Hybrid MBRs, which list other partitions next to the protective one, are accepted
+pub fn is_protective_mbr(block: &[u8; BLOCK_SIZE]) -> bool {
+    block[510..] == [0x55, 0xaa][..]
+        && block[MBR_PARTITION_TABLE..510]
+            .chunks_exact(MBR_PARTITION_ENTRY_SIZE)
+            .any(|entry| entry[4] == MBR_TYPE_GPT_PROTECTIVE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_partitions, entry_array_block, is_protective_mbr, protective_mbr, Entry, Error, Guid,
+        Header, NUM_ENTRIES,
+    };
+    use crate::{
+        fixtures::{block, hex},
+        Crc32,
+    };
+
+    // GPT of a 4096-block disk with an EFI system partition named "boot" and a Linux filesystem
+    // partition named "armory-data", laid out the way `sfdisk` does it; the CRCs were computed
+    // with an independent implementation (Python's `zlib.crc32`). 16 bytes per row
+    const PRIMARY_HEADER: &[(usize, &str)] = &[
+        (0, "4546492050415254000001005c000000"),
+        (16, "4f5324e7000000000100000000000000"),
+        (32, "ff0f0000000000002200000000000000"),
+        (48, "de0f0000000000004e3a1a5a1c6f7e4b"),
+        (64, "9c2d0123456789ab0200000000000000"),
+        (80, "8000000080000000884f3cb5"),
+    ];
+
+    const BACKUP_HEADER: &[(usize, &str)] = &[
+        (0, "4546492050415254000001005c000000"),
+        (16, "8d745c1000000000ff0f000000000000"),
+        (32, "01000000000000002200000000000000"),
+        (48, "de0f0000000000004e3a1a5a1c6f7e4b"),
+        (64, "9c2d0123456789abdf0f000000000000"),
+        (80, "8000000080000000884f3cb5"),
+    ];
+
+    // first two entries of the partition entry array; the other 126 are all zeros
+    const ENTRIES: &[(usize, &str)] = &[
+        (0, "28732ac11ff8d211ba4b00a0c93ec93b"),
+        (16, "2b1c3e8d2b1a3d4c8e4fa0b1c2d3e4f5"),
+        (32, "0008000000000000ff09000000000000"),
+        (48, "000000000000000062006f006f007400"),
+        (128, "af3dc60f838472478e793d69d8477de4"),
+        (144, "4433221166557847899aabbccddeeff0"),
+        (160, "000a000000000000de0f000000000000"),
+        (176, "0000000000000000610072006d006f00"),
+        (192, "720079002d0064006100740061000000"),
+    ];
+
+    const TOTAL_BLOCKS: u64 = 4096;
+    const ENTRIES_CRC32: u32 = 0xb53c_4f88;
+
+    fn disk_guid() -> Guid {
+        Guid::parse("5a1a3a4e-6f1c-4b7e-9c2d-0123456789ab").unwrap()
+    }
+
+    fn partitions() -> [Entry; 2] {
+        [
+            Entry::new(
+                Guid::EFI_SYSTEM,
+                Guid::parse("8D3E1C2B-1A2B-4C3D-8E4F-A0B1C2D3E4F5").unwrap(),
+                2048..2560,
+                "boot",
+            )
+            .unwrap(),
+            Entry::new(
+                Guid::LINUX_FILESYSTEM,
+                Guid::parse("11223344-5566-4778-899A-ABBCCDDEEFF0").unwrap(),
+                2560..4063,
+                "armory-data",
+            )
+            .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn guid() {
+        let guid = Guid::parse("C12A7328-F81F-11D2-BA4B-00A0C93EC93B").unwrap();
+        assert_eq!(guid, Guid::EFI_SYSTEM);
+        assert_eq!(
+            &guid.as_bytes()[..],
+            &hex("28732ac11ff8d211ba4b00a0c93ec93b")[..]
+        );
+        assert_eq!(guid.to_string(), "C12A7328-F81F-11D2-BA4B-00A0C93EC93B");
+        assert_eq!(
+            Guid::parse("0fc63daf-8483-4772-8e79-3d69d8477de4"),
+            Some(Guid::LINUX_FILESYSTEM)
+        );
+        assert_eq!(Guid::UNUSED.as_bytes(), &[0; 16]);
+        assert_eq!(
+            Guid::from_random_bytes([0xff; 16]).to_string(),
+            "FFFFFFFF-FFFF-4FFF-BFFF-FFFFFFFFFFFF"
+        );
+        assert_eq!(
+            Guid::from_random_bytes([0; 16]).to_string(),
+            "00000000-0000-4000-8000-000000000000"
+        );
+
+        assert_eq!(Guid::parse("C12A7328-F81F-11D2-BA4B-00A0C93EC93"), None);
+        assert_eq!(Guid::parse("C12A7328+F81F-11D2-BA4B-00A0C93EC93B"), None);
+        assert_eq!(Guid::parse("G12A7328-F81F-11D2-BA4B-00A0C93EC93B"), None);
+    }
+
+    #[test]
+    fn header() {
+        let primary = Header::parse(&block(PRIMARY_HEADER), 1, TOTAL_BLOCKS).unwrap();
+        assert_eq!(
+            primary,
+            Header {
+                my_lba: 1,
+                alternate_lba: 4095,
+                first_usable_lba: 34,
+                last_usable_lba: 4062,
+                disk_guid: disk_guid(),
+                entries_lba: 2,
+                num_entries: 128,
+                entries_crc32: ENTRIES_CRC32,
+            }
+        );
+        assert_eq!(primary.entry_array_blocks(), 32);
+        assert_eq!(primary.entry_location(0), (2, 0));
+        assert_eq!(primary.entry_location(5), (3, 128));
+
+        let backup = Header::parse(&block(BACKUP_HEADER), 4095, TOTAL_BLOCKS).unwrap();
+        assert_eq!(backup, primary.backup().unwrap());
+        assert_eq!(backup.entries_lba, 4063);
+    }
+
+    #[test]
+    fn header_layout() {
+        let mut header = Header::new(disk_guid(), TOTAL_BLOCKS).unwrap();
+        header.entries_crc32 = ENTRIES_CRC32;
+
+        assert_eq!(&header.to_block()[..], &block(PRIMARY_HEADER)[..]);
+        assert_eq!(
+            &header.backup().unwrap().to_block()[..],
+            &block(BACKUP_HEADER)[..]
+        );
+
+        assert!(Header::new(disk_guid(), 68).is_ok());
+        assert_eq!(Header::new(disk_guid(), 67), Err(Error::DiskTooSmall));
+    }
+
+    #[test]
+    fn invalid_header() {
+        let good = block(PRIMARY_HEADER);
+
+        let mut bad = good;
+        bad[0] = b'e';
+        assert_eq!(
+            Header::parse(&bad, 1, TOTAL_BLOCKS),
+            Err(Error::InvalidSignature)
+        );
+
+        let mut bad = good;
+        bad[60] ^= 1;
+        assert_eq!(Header::parse(&bad, 1, TOTAL_BLOCKS), Err(Error::HeaderCrc));
+
+        // the header was not read from where it says it's stored
+        assert_eq!(
+            Header::parse(&good, 2, TOTAL_BLOCKS),
+            Err(Error::InvalidHeader)
+        );
+        // the disk is smaller than the layout
+        assert_eq!(Header::parse(&good, 1, 4000), Err(Error::InvalidHeader));
+
+        // the entry array overlaps the usable blocks
+        let mut header = Header::parse(&good, 1, TOTAL_BLOCKS).unwrap();
+        header.num_entries = 256;
+        assert_eq!(
+            Header::parse(&header.to_block(), 1, TOTAL_BLOCKS),
+            Err(Error::InvalidHeader)
+        );
+
+        // the end of the usable blocks or of the entry array overflows
+        let mut header = Header::parse(&good, 1, TOTAL_BLOCKS).unwrap();
+        header.last_usable_lba = 0xffff_ffff_ffff_ffff;
+        assert_eq!(
+            Header::parse(&header.to_block(), 1, TOTAL_BLOCKS),
+            Err(Error::InvalidHeader)
+        );
+
+        let mut header = Header::parse(&good, 1, TOTAL_BLOCKS).unwrap();
+        header.entries_lba = 0xffff_ffff_ffff_ffff - 10;
+        assert_eq!(
+            Header::parse(&header.to_block(), 1, TOTAL_BLOCKS),
+            Err(Error::InvalidHeader)
+        );
+
+        // valid, but the backup entry array doesn't fit before the backup header
+        let mut header = Header::parse(&good, 1, TOTAL_BLOCKS).unwrap();
+        header.alternate_lba = 5;
+        let header = Header::parse(&header.to_block(), 1, TOTAL_BLOCKS).unwrap();
+        assert_eq!(header.backup(), Err(Error::InvalidHeader));
+    }
+
+    #[test]
+    fn entries() {
+        let bytes = block(ENTRIES);
+
+        let boot = Entry::from_bytes(&bytes[..128]);
+        assert!(boot.is_used());
+        assert_eq!(boot.type_guid, Guid::EFI_SYSTEM);
+        assert_eq!(boot.blocks(), 2048..2560);
+        assert_eq!(boot.name().collect::<String>(), "boot");
+        assert!(boot.has_name("boot"));
+        assert!(!boot.has_name("boo"));
+        assert!(!boot.has_name("boot2"));
+
+        let data = Entry::from_bytes(&bytes[128..256]);
+        assert_eq!(data.type_guid, Guid::LINUX_FILESYSTEM);
+        assert_eq!(data.blocks(), 2560..4063);
+        assert!(data.has_name("armory-data"));
+
+        assert!(!Entry::from_bytes(&bytes[256..384]).is_used());
+
+        let partitions = partitions();
+        assert_eq!(&partitions[0].to_bytes()[..], &bytes[..128]);
+        assert_eq!(&partitions[1].to_bytes()[..], &bytes[128..256]);
+    }
+
+    #[test]
+    fn entry_array() {
+        let partitions = partitions();
+        let header = Header::new(disk_guid(), TOTAL_BLOCKS).unwrap();
+
+        assert_eq!(&entry_array_block(&partitions, 0)[..], &block(ENTRIES)[..]);
+
+        let mut crc = Crc32::new();
+        for i in 0..header.entry_array_blocks() {
+            crc.update(&entry_array_block(&partitions, i));
+        }
+        assert_eq!(crc.finish(), ENTRIES_CRC32);
+    }
+
+    #[test]
+    fn partition_checks() {
+        let header = Header::new(disk_guid(), TOTAL_BLOCKS).unwrap();
+        let partitions = partitions();
+        assert_eq!(check_partitions(&header, &partitions), Ok(()));
+
+        let entry = |blocks| Entry::new(Guid::LINUX_FILESYSTEM, Guid::UNUSED, blocks, "");
+
+        // overlaps the first partition
+        let overlapping = [partitions[0], entry(2559..2600).unwrap()];
+        assert_eq!(
+            check_partitions(&header, &overlapping),
+            Err(Error::PartitionCollision)
+        );
+
+        // unused entries are ignored
+        let unused = [partitions[0], Entry::UNUSED, Entry::UNUSED];
+        assert_eq!(check_partitions(&header, &unused), Ok(()));
+
+        // outside the usable blocks
+        for blocks in &[33..100, 4000..4064] {
+            assert_eq!(
+                check_partitions(&header, &[entry(blocks.clone()).unwrap()]),
+                Err(Error::InvalidPartExtent)
+            );
+        }
+        assert_eq!(entry(100..100).err(), Some(Error::InvalidPartExtent));
+
+        let too_many = [Entry::UNUSED; NUM_ENTRIES as usize + 1];
+        assert_eq!(
+            check_partitions(&header, &too_many),
+            Err(Error::TooManyPartitions)
+        );
+
+        let name = "0123456789abcdef0123456789abcdef0123";
+        assert!(entry(100..200).is_ok());
+        assert!(Entry::new(Guid::BASIC_DATA, Guid::UNUSED, 100..200, name).is_ok());
+        assert_eq!(
+            Entry::new(
+                Guid::BASIC_DATA,
+                Guid::UNUSED,
+                100..200,
+                &[name, "4"].concat()
+            )
+            .err(),
+            Some(Error::NameTooLong)
+        );
+    }
+
+    #[test]
+    fn protective() {
+        let mbr = protective_mbr(TOTAL_BLOCKS);
+        assert_eq!(
+            &mbr[446..462],
+            &hex("000002 00ee ffffff 01000000 ff0f0000"
+                .replace(' ', "")
+                .as_str())[..]
+        );
+        assert_eq!(&mbr[510..], &[0x55, 0xaa]);
+        assert!(is_protective_mbr(&mbr));
+
+        // capped at the largest size a MBR can describe
+        let mbr = protective_mbr(1 << 40);
+        assert_eq!(&mbr[458..462], &[0xff; 4]);
+
+        let mut mbr = protective_mbr(TOTAL_BLOCKS);
+        mbr[450] = 0x83;
+        assert!(!is_protective_mbr(&mbr));
+    }
+}
@@ -0,0 +1,116 @@
+//! Partition table formats
+//!
+//! This crate parses, validates and builds the on-disk structures of partition tables. Reading and
+//! writing the blocks of the device is left to the storage driver; all the structures are
+//! described in units of 512-byte blocks
+
+#![deny(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+pub mod gpt;
+pub mod mbr;
+
+/// Size of a block, in bytes
+pub const BLOCK_SIZE: usize = 512;
+
+/// CRC-32 (IEEE 802.3), as used by the GUID partition table
+///
+/// The checksum can be computed over data that's not contiguous in memory by calling `update`
+/// once per chunk
+#[derive(Clone, Copy)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Crc32 {
+    /// Starts a new checksum computation
+    pub fn new() -> Self {
+        Crc32 { state: 0xffff_ffff }
+    }
+
+    /// Feeds `bytes` into the checksum
+    pub fn update(&mut self, bytes: &[u8]) {
+        // reflected polynomial
+        const POLY: u32 = 0xedb8_8320;
+
+        for byte in bytes {
+            self.state ^= u32::from(*byte);
+            for _ in 0..8 {
+                let mask = (self.state & 1).wrapping_neg();
+                self.state = (self.state >> 1) ^ (POLY & mask);
+            }
+        }
+    }
+
+    /// Returns the checksum of all the bytes fed so far
+    pub fn finish(self) -> u32 {
+        !self.state
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Computes the CRC-32 of `bytes`
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.finish()
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    let b = &bytes[offset..][..4];
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    let b = &bytes[offset..][..8];
+    u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])
+}
+
+/// Helpers to build the test fixtures
+#[cfg(test)]
+mod fixtures {
+    use super::BLOCK_SIZE;
+
+    /// Decodes a hexadecimal string; whitespace is not allowed
+    pub fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Builds a block from `(offset, hex)` rows; the bytes not covered by the rows are zeros
+    pub fn block(rows: &[(usize, &str)]) -> [u8; BLOCK_SIZE] {
+        let mut block = [0; BLOCK_SIZE];
+        for (offset, row) in rows {
+            let row = hex(row);
+            block[*offset..][..row.len()].copy_from_slice(&row);
+        }
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, Crc32};
+
+    #[test]
+    fn check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn incremental() {
+        let mut crc = Crc32::new();
+        crc.update(b"1234");
+        crc.update(b"");
+        crc.update(b"56789");
+        assert_eq!(crc.finish(), crc32(b"123456789"));
+    }
+}
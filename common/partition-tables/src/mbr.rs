@@ -0,0 +1,746 @@
+//! Master boot record (MBR) partition table
+//!
+//! The MBR (block 0) holds up to four primary partitions. One of them can be an extended
+//! partition: a container for logical partitions, each one described by an extended boot record
+//! (EBR). The EBRs form a linked list that starts at the first block of the extended partition;
+//! the first entry of an EBR is its logical partition, with a start relative to the EBR, and the
+//! second one points to the next EBR, with a start relative to the extended partition
+
+use core::ops::Range;
+
+use crate::{u32_at, BLOCK_SIZE};
+
+/// Partition types (also known as system IDs) commonly used in the `part_type` field
+pub mod part_type {
+    /// Unused entry
+    pub const EMPTY: u8 = 0x00;
+
+    /// Extended partition (CHS addressing)
+    pub const EXTENDED: u8 = 0x05;
+
+    /// FAT32 partition (LBA addressing)
+    pub const FAT32_LBA: u8 = 0x0c;
+
+    /// Extended partition (LBA addressing)
+    pub const EXTENDED_LBA: u8 = 0x0f;
+
+    /// Linux partition
+    pub const LINUX: u8 = 0x83;
+
+    /// Linux extended partition
+    pub const EXTENDED_LINUX: u8 = 0x85;
+}
+
+/// Maximum number of logical partitions a `PartitionTable` can hold
+pub const MAX_LOGICAL_PARTITIONS: usize = 12;
+
+/// Size of a partition entry, in bytes
+const ENTRY_SIZE: usize = 16;
+
+// offsets within a MBR / EBR
+const PARTITION_TABLE: usize = 446;
+const SIGNATURE: usize = 510;
+
+// partition entry fields (byte offsets); the multi-byte fields are little endian
+const STATUS: usize = 0;
+const START_CHS: usize = 1;
+const PART_TYPE: usize = 4;
+const END_CHS: usize = 5;
+const START_LBA: usize = 8;
+const NUM_SECTORS: usize = 12;
+
+/// `STATUS` of a bootable partition
+const STATUS_BOOTABLE: u8 = 0x80;
+
+// geometry used to compute the CHS addresses; the one `fdisk` and `sfdisk` use
+const HEADS: u32 = 255;
+const SECTORS_PER_TRACK: u32 = 63;
+
+/// Errors found while validating a MBR partition table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The MBR doesn't end with the `0x55 0xAA` signature
+    InvalidMagic,
+
+    /// A partition doesn't fit the disk or its extended partition
+    InvalidPartExtent,
+
+    /// An EBR points to a block that's outside the extended partition or that doesn't follow it
+    InvalidEbr,
+}
+
+/// Errors found while building a MBR partition table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PartError {
+    /// New partition collides with an existing partition
+    PartitionCollision,
+    /// The partition table has already 4 primary partitions, or `MAX_LOGICAL_PARTITIONS` logical
+    /// ones
+    TooManyPartitions,
+    /// A logical partition was added but there's no extended partition to hold it
+    NoExtendedPartition,
+    /// The table has an extended partition already, or an extended partition was added as a
+    /// logical partition
+    NestedExtendedPartition,
+    /// A logical partition doesn't fit its extended partition, leaving room for its EBR
+    InvalidPartExtent,
+}
+
+/// An entry in the MBR partition table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PartitionEntry {
+    status: u8,
+    start_chs: [u8; 3],
+    part_type: u8,
+    end_chs: [u8; 3],
+    start_lba: u32,
+    num_sectors: u32,
+}
+
+impl PartitionEntry {
+    /// An unused entry
+    pub const UNUSED: PartitionEntry = PartitionEntry {
+        status: 0,
+        start_chs: [0; 3],
+        part_type: part_type::EMPTY,
+        end_chs: [0; 3],
+        start_lba: 0,
+        num_sectors: 0,
+    };
+
+    /// Creates a new Linux partition entry that starts at `start_lba` and it's `num_sectors` big
+    pub fn new(start_lba: u32, num_sectors: u32) -> Self {
+        Self::at(part_type::LINUX, start_lba, num_sectors, start_lba)
+    }
+
+    /// Creates a new extended partition entry that starts at `start_lba` and it's `num_sectors`
+    /// big; logical partitions can be created inside it
+    pub fn extended(start_lba: u32, num_sectors: u32) -> Self {
+        Self::at(part_type::EXTENDED, start_lba, num_sectors, start_lba)
+    }
+
+    /// Parses an entry from the first 16 bytes of `bytes`
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `bytes` is shorter than 16 bytes
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let bytes = &bytes[..ENTRY_SIZE];
+        let chs = |offset: usize| [bytes[offset], bytes[offset + 1], bytes[offset + 2]];
+
+        PartitionEntry {
+            status: bytes[STATUS],
+            start_chs: chs(START_CHS),
+            part_type: bytes[PART_TYPE],
+            end_chs: chs(END_CHS),
+            start_lba: u32_at(bytes, START_LBA),
+            num_sectors: u32_at(bytes, NUM_SECTORS),
+        }
+    }
+
+    /// Serializes the entry
+    pub fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[STATUS] = self.status;
+        bytes[START_CHS..][..3].copy_from_slice(&self.start_chs);
+        bytes[PART_TYPE] = self.part_type;
+        bytes[END_CHS..][..3].copy_from_slice(&self.end_chs);
+        bytes[START_LBA..][..4].copy_from_slice(&self.start_lba.to_le_bytes());
+        bytes[NUM_SECTORS..][..4].copy_from_slice(&self.num_sectors.to_le_bytes());
+        bytes
+    }
+
+    /// Returns the partition type
+    pub fn part_type(&self) -> u8 {
+        self.part_type
+    }
+
+    /// Changes the partition type
+    pub fn set_part_type(&mut self, part_type: u8) {
+        self.part_type = part_type;
+    }
+
+    /// Returns `true` if the partition is marked as bootable (active)
+    pub fn is_bootable(&self) -> bool {
+        self.status & STATUS_BOOTABLE != 0
+    }
+
+    /// Marks the partition as bootable (active) or not
+    pub fn set_bootable(&mut self, bootable: bool) {
+        self.status = if bootable { STATUS_BOOTABLE } else { 0 };
+    }
+
+    /// Returns the first block of the partition
+    pub fn start_lba(&self) -> u32 {
+        self.start_lba
+    }
+
+    /// Returns the size of the partition, in blocks
+    pub fn num_sectors(&self) -> u32 {
+        self.num_sectors
+    }
+
+    /// Returns the blocks of the partition
+    pub fn blocks(&self) -> Range<u64> {
+        let start = u64::from(self.start_lba);
+        start..start + u64::from(self.num_sectors)
+    }
+
+    /// Returns `true` if the entry describes a partition
+    pub fn is_used(&self) -> bool {
+        self.part_type != part_type::EMPTY
+    }
+
+    /// Returns `true` if the entry describes an extended partition
+    pub fn is_extended(&self) -> bool {
+        matches!(
+            self.part_type,
+            part_type::EXTENDED | part_type::EXTENDED_LBA | part_type::EXTENDED_LINUX
+        )
+    }
+
+    /// Creates an entry whose `start_lba` field is `start_lba` but that actually starts at block
+    /// `abs_start` of the disk; EBR entries are relative to another block
+    fn at(part_type: u8, start_lba: u32, num_sectors: u32, abs_start: u32) -> Self {
+        let abs_end = abs_start.saturating_add(num_sectors.saturating_sub(1));
+        PartitionEntry {
+            status: 0,
+            start_chs: chs(abs_start),
+            part_type,
+            end_chs: chs(abs_end),
+            start_lba,
+            num_sectors,
+        }
+    }
+
+    /// Returns this entry moved to `start_lba`, relative to `base`
+    fn relative_to(&self, base: u32) -> Self {
+        let mut entry = Self::at(
+            self.part_type,
+            self.start_lba - base,
+            self.num_sectors,
+            self.start_lba,
+        );
+        entry.status = self.status;
+        entry
+    }
+}
+
+/// Converts block address `lba` into a cylinder-head-sector address, as stored in a partition
+/// entry; addresses past the first 1024 cylinders are all stored as the maximum address
+fn chs(lba: u32) -> [u8; 3] {
+    let cylinder = lba / (HEADS * SECTORS_PER_TRACK);
+    if cylinder > 1023 {
+        return [0xfe, 0xff, 0xff];
+    }
+
+    let head = (lba / SECTORS_PER_TRACK) % HEADS;
+    let sector = lba % SECTORS_PER_TRACK + 1;
+    // the two most significant bits of the cylinder are stored with the sector
+    [
+        head as u8,
+        (sector | ((cylinder >> 2) & 0xc0)) as u8,
+        cylinder as u8,
+    ]
+}
+
+/// Parses the MBR stored in `block`, the first block of a disk of `total_blocks` blocks, and
+/// returns its four primary partition entries
+pub fn parse_mbr(
+    block: &[u8; BLOCK_SIZE],
+    total_blocks: u64,
+) -> Result<[PartitionEntry; 4], Error> {
+    let entries = parse_boot_record(block)?;
+
+    for entry in entries.iter().filter(|entry| entry.is_used()) {
+        let blocks = entry.blocks();
+        if blocks.start == 0 || blocks.start == blocks.end || blocks.end > total_blocks {
+            return Err(Error::InvalidPartExtent);
+        }
+    }
+
+    if entries.iter().filter(|entry| entry.is_extended()).count() > 1 {
+        return Err(Error::InvalidPartExtent);
+    }
+
+    Ok(entries)
+}
+
+fn parse_boot_record(block: &[u8; BLOCK_SIZE]) -> Result<[PartitionEntry; 4], Error> {
+    if block[SIGNATURE..] != [0x55, 0xaa][..] {
+        return Err(Error::InvalidMagic);
+    }
+
+    let mut entries = [PartitionEntry::UNUSED; 4];
+    for (entry, bytes) in entries
+        .iter_mut()
+        .zip(block[PARTITION_TABLE..SIGNATURE].chunks_exact(ENTRY_SIZE))
+    {
+        *entry = PartitionEntry::from_bytes(bytes);
+    }
+    Ok(entries)
+}
+
+fn boot_record(entries: &[PartitionEntry]) -> [u8; BLOCK_SIZE] {
+    let mut block = [0; BLOCK_SIZE];
+    for (bytes, entry) in block[PARTITION_TABLE..SIGNATURE]
+        .chunks_exact_mut(ENTRY_SIZE)
+        .zip(entries)
+    {
+        bytes.copy_from_slice(&entry.to_bytes());
+    }
+    // magic number
+    block[SIGNATURE] = 0x55;
+    block[SIGNATURE + 1] = 0xaa;
+    block
+}
+
+/// Walks the chain of EBRs that describes the logical partitions of an extended partition
+///
+/// The caller reads the blocks: `next_ebr` returns the block that holds the next EBR, which is
+/// then handed to `parse`. The chain always moves forward so the walk terminates
+///
+/// ```ignore
+/// let mut chain = EbrChain::new(&extended);
+/// while let Some(lba) = chain.next_ebr() {
+///     read(lba, &mut block)?;
+///     if let Some(logical) = chain.parse(&block)? {
+///         // ..
+///     }
+/// }
+/// ```
+pub struct EbrChain {
+    extended: Range<u64>,
+    next: Option<u32>,
+}
+
+impl EbrChain {
+    /// Starts walking the EBR chain of the `extended` partition
+    pub fn new(extended: &PartitionEntry) -> Self {
+        EbrChain {
+            extended: extended.blocks(),
+            next: Some(extended.start_lba),
+        }
+    }
+
+    /// Returns the block that holds the next EBR, or `None` if the end of the chain was reached
+    pub fn next_ebr(&self) -> Option<u32> {
+        self.next
+    }
+
+    /// Parses the EBR stored in `block`, which was read from the block `next_ebr` returned, and
+    /// returns the logical partition it describes, with its start relative to the disk
+    ///
+    /// An EBR without the `0x55 0xAA` signature ends the chain; this is how an extended partition
+    /// without logical partitions looks like
+    pub fn parse(&mut self, block: &[u8; BLOCK_SIZE]) -> Result<Option<PartitionEntry>, Error> {
+        let lba = match self.next.take() {
+            Some(lba) => lba,
+            None => return Ok(None),
+        };
+
+        let entries = match parse_boot_record(block) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(None),
+        };
+
+        let logical = if entries[0].is_used() {
+            let mut logical = entries[0];
+            let start = u64::from(lba) + u64::from(logical.start_lba);
+            let end = start + u64::from(logical.num_sectors);
+            if start == u64::from(lba) || start == end || end > self.extended.end {
+                return Err(Error::InvalidPartExtent);
+            }
+            // NOTE(as) no truncation; within the extended partition
+            logical.start_lba = start as u32;
+            Some(logical)
+        } else {
+            None
+        };
+
+        if entries[1].is_extended() {
+            let next = self.extended.start + u64::from(entries[1].start_lba);
+            if next <= u64::from(lba) || next >= self.extended.end {
+                return Err(Error::InvalidEbr);
+            }
+            // NOTE(as) no truncation; within the extended partition
+            self.next = Some(next as u32);
+        }
+
+        Ok(logical)
+    }
+}
+
+/// MBR partition table
+pub struct PartitionTable {
+    entries: [PartitionEntry; 4],
+    index: usize,
+    logical: [PartitionEntry; MAX_LOGICAL_PARTITIONS],
+    logical_count: usize,
+}
+
+impl Default for PartitionTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartitionTable {
+    /// Creates an empty partition table
+    pub fn new() -> Self {
+        Self {
+            entries: [PartitionEntry::UNUSED; 4],
+            index: 0,
+            logical: [PartitionEntry::UNUSED; MAX_LOGICAL_PARTITIONS],
+            logical_count: 0,
+        }
+    }
+
+    /// Returns the primary partitions added so far
+    pub fn as_slice(&self) -> &[PartitionEntry] {
+        &self.entries[..self.index]
+    }
+
+    /// Returns the logical partitions added so far
+    pub fn logical(&self) -> &[PartitionEntry] {
+        &self.logical[..self.logical_count]
+    }
+
+    /// Adds a new primary partition to the table
+    ///
+    /// NOTE partitions must be added in order (increasing `start_lba`)
+    pub fn add(&mut self, entry: PartitionEntry) -> Result<(), PartError> {
+        let end = self
+            .as_slice()
+            .last()
+            .map(|entry| entry.blocks().end)
+            .unwrap_or(0);
+
+        if entry.blocks().start < end {
+            Err(PartError::PartitionCollision)
+        } else if entry.is_extended() && self.extended().is_some() {
+            Err(PartError::NestedExtendedPartition)
+        } else if self.index < self.entries.len() {
+            self.entries[self.index] = entry;
+            self.index += 1;
+            Ok(())
+        } else {
+            Err(PartError::TooManyPartitions)
+        }
+    }
+
+    /// Adds a new logical partition to the extended partition of the table
+    ///
+    /// The EBR that describes the partition is stored in the first unused block before it, so
+    /// there must be at least one such block within the extended partition
+    ///
+    /// NOTE partitions must be added in order (increasing `start_lba`)
+    pub fn add_logical(&mut self, entry: PartitionEntry) -> Result<(), PartError> {
+        let extended = self
+            .extended()
+            .ok_or(PartError::NoExtendedPartition)?
+            .blocks();
+        let ebr = self.next_ebr(&extended);
+        let blocks = entry.blocks();
+
+        if entry.is_extended() {
+            Err(PartError::NestedExtendedPartition)
+        } else if blocks.start <= ebr && ebr != extended.start {
+            Err(PartError::PartitionCollision)
+        } else if blocks.start <= ebr || blocks.start == blocks.end || blocks.end > extended.end {
+            Err(PartError::InvalidPartExtent)
+        } else if self.logical_count < self.logical.len() {
+            self.logical[self.logical_count] = entry;
+            self.logical_count += 1;
+            Ok(())
+        } else {
+            Err(PartError::TooManyPartitions)
+        }
+    }
+
+    /// Serializes the MBR
+    pub fn to_block(&self) -> [u8; BLOCK_SIZE] {
+        boot_record(&self.entries)
+    }
+
+    /// Returns the EBRs that describe the logical partitions, and the blocks they must be written
+    /// to, in chain order
+    ///
+    /// If there are no logical partitions this is a single EBR, without signature, that ends the
+    /// chain; the first block of the extended partition may hold leftovers of an old chain
+    pub fn ebrs(&self) -> impl Iterator<Item = (u32, [u8; BLOCK_SIZE])> + '_ {
+        let extended = self.extended().map(|entry| entry.start_lba);
+        let logical = self.logical();
+
+        // the first EBR is always at the start of the extended partition
+        let empty = if logical.is_empty() { extended } else { None };
+        let chain = extended.into_iter().flat_map(move |extended_start| {
+            (0..logical.len()).map(move |i| {
+                let ebr = |i: usize| {
+                    if i == 0 {
+                        extended_start
+                    } else {
+                        logical[i - 1].start_lba + logical[i - 1].num_sectors
+                    }
+                };
+
+                let lba = ebr(i);
+                let mut entries = [PartitionEntry::UNUSED; 2];
+                entries[0] = logical[i].relative_to(lba);
+                if let Some(next) = logical.get(i + 1) {
+                    // the link covers the next EBR and its logical partition
+                    let next_ebr = ebr(i + 1);
+                    let link = PartitionEntry::at(
+                        part_type::EXTENDED,
+                        next_ebr,
+                        next.start_lba + next.num_sectors - next_ebr,
+                        next_ebr,
+                    );
+                    entries[1] = link.relative_to(extended_start);
+                }
+
+                (lba, boot_record(&entries))
+            })
+        });
+
+        empty
+            .map(|lba| (lba, [0; BLOCK_SIZE]))
+            .into_iter()
+            .chain(chain)
+    }
+
+    fn extended(&self) -> Option<&PartitionEntry> {
+        self.as_slice().iter().find(|entry| entry.is_extended())
+    }
+
+    /// Returns the block the EBR of the next logical partition goes into
+    fn next_ebr(&self, extended: &Range<u64>) -> u64 {
+        self.logical()
+            .last()
+            .map(|entry| entry.blocks().end)
+            .unwrap_or(extended.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{
+        parse_mbr, part_type, EbrChain, Error, PartError, PartitionEntry, PartitionTable,
+        BLOCK_SIZE,
+    };
+    use crate::fixtures::{block, hex};
+
+    // MBR and EBRs of a 65536-block disk laid out the way `sfdisk` does it for this script:
+    //
+    // ``` text
+    // label: dos
+    // start=2048, size=8192, type=c, bootable
+    // start=10240, size=53248, type=5
+    // start=12288, size=16384, type=83
+    // start=30720, size=32768, type=83
+    // ```
+    //
+    // one partition entry per row; the rest of the blocks are zeros
+    const MBR: &[(usize, &str)] = &[
+        (446, "802021000ca222000008000000200000"),
+        (462, "00a2230005f22f030028000000d00000"),
+        (510, "55aa"),
+    ];
+
+    // EBR of the first logical partition; at the start of the extended partition
+    const EBR5: &[(usize, &str)] = &[
+        (446, "00c3040083c807010008000000400000"),
+        (462, "00c8080105f22f030048000000880000"),
+        (510, "55aa"),
+    ];
+
+    // EBR of the second logical partition; right after the first logical partition
+    const EBR6: &[(usize, &str)] = &[(446, "00e8280183f22f030008000000800000"), (510, "55aa")];
+
+    const TOTAL_BLOCKS: u64 = 65536;
+
+    /// The blocks of the disk that aren't zeros
+    fn disk() -> BTreeMap<u32, [u8; BLOCK_SIZE]> {
+        let mut disk = BTreeMap::new();
+        disk.insert(0, block(MBR));
+        disk.insert(10240, block(EBR5));
+        disk.insert(28672, block(EBR6));
+        disk
+    }
+
+    fn logical_partitions(
+        extended: &PartitionEntry,
+        disk: &BTreeMap<u32, [u8; BLOCK_SIZE]>,
+    ) -> Result<Vec<PartitionEntry>, Error> {
+        let mut partitions = vec![];
+        let mut chain = EbrChain::new(extended);
+        while let Some(lba) = chain.next_ebr() {
+            let block = disk.get(&lba).copied().unwrap_or([0; BLOCK_SIZE]);
+            partitions.extend(chain.parse(&block)?);
+        }
+        Ok(partitions)
+    }
+
+    fn table() -> PartitionTable {
+        let mut boot = PartitionEntry::new(2048, 8192);
+        boot.set_part_type(part_type::FAT32_LBA);
+        boot.set_bootable(true);
+
+        let mut table = PartitionTable::new();
+        table.add(boot).unwrap();
+        table.add(PartitionEntry::extended(10240, 53248)).unwrap();
+        table
+            .add_logical(PartitionEntry::new(12288, 16384))
+            .unwrap();
+        table
+            .add_logical(PartitionEntry::new(30720, 32768))
+            .unwrap();
+        table
+    }
+
+    #[test]
+    fn entry() {
+        let mut entry = PartitionEntry::from_bytes(&hex("802021000ca222000008000000200000"));
+        assert!(entry.is_used());
+        assert!(entry.is_bootable());
+        assert!(!entry.is_extended());
+        assert_eq!(entry.part_type(), part_type::FAT32_LBA);
+        assert_eq!(entry.start_lba(), 2048);
+        assert_eq!(entry.num_sectors(), 8192);
+        assert_eq!(entry.blocks(), 2048..10240);
+
+        entry.set_bootable(false);
+        entry.set_part_type(part_type::LINUX);
+        assert_eq!(entry, PartitionEntry::new(2048, 8192));
+        assert_eq!(
+            &entry.to_bytes()[..],
+            &hex("0020210083a222000008000000200000")[..]
+        );
+
+        assert!(PartitionEntry::extended(10240, 53248).is_extended());
+        assert!(!PartitionEntry::UNUSED.is_used());
+
+        // past the cylinders CHS addresses can express
+        let entry = PartitionEntry::new(16_450_560, 2048);
+        assert_eq!(&entry.to_bytes()[..8], &hex("00feffff83feffff")[..]);
+    }
+
+    #[test]
+    fn parse() {
+        let disk = disk();
+        let primary = parse_mbr(&disk[&0], TOTAL_BLOCKS).unwrap();
+        assert_eq!(primary, table().entries);
+        assert!(primary[0].is_bootable());
+        assert!(!primary[2].is_used());
+
+        let logical = logical_partitions(&primary[1], &disk).unwrap();
+        assert_eq!(logical.len(), 2);
+        assert_eq!(logical[0].blocks(), 12288..28672);
+        assert_eq!(logical[1].blocks(), 30720..63488);
+        assert_eq!(logical[1].part_type(), part_type::LINUX);
+        assert_eq!(logical, table().logical());
+
+        // extended partition without logical partitions
+        let empty = BTreeMap::new();
+        assert_eq!(logical_partitions(&primary[1], &empty), Ok(vec![]));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let mut mbr = block(MBR);
+        assert_eq!(parse_mbr(&mbr, 63487), Err(Error::InvalidPartExtent));
+
+        mbr[511] = 0;
+        assert_eq!(parse_mbr(&mbr, TOTAL_BLOCKS), Err(Error::InvalidMagic));
+
+        let extended = PartitionEntry::extended(10240, 53248);
+
+        // a link that points back to the same EBR
+        let mut disk = disk();
+        disk.insert(28672, block(EBR5));
+        assert_eq!(logical_partitions(&extended, &disk), Err(Error::InvalidEbr));
+
+        // a logical partition that sticks out of the extended partition
+        let mut disk = self::disk();
+        disk.get_mut(&28672).unwrap()[458] = 0x81;
+        assert_eq!(
+            logical_partitions(&extended, &disk),
+            Err(Error::InvalidPartExtent)
+        );
+    }
+
+    #[test]
+    fn build() {
+        let table = table();
+        assert_eq!(&table.to_block()[..], &block(MBR)[..]);
+
+        let ebrs = table.ebrs().collect::<Vec<_>>();
+        assert_eq!(ebrs.len(), 2);
+        assert_eq!(ebrs[0].0, 10240);
+        assert_eq!(&ebrs[0].1[..], &block(EBR5)[..]);
+        assert_eq!(ebrs[1].0, 28672);
+        assert_eq!(&ebrs[1].1[..], &block(EBR6)[..]);
+
+        // only primary partitions
+        let mut table = PartitionTable::new();
+        table.add(PartitionEntry::new(2048, 8192)).unwrap();
+        assert_eq!(table.ebrs().count(), 0);
+
+        // the EBR at the start of an empty extended partition ends the chain
+        table.add(PartitionEntry::extended(10240, 53248)).unwrap();
+        let ebrs = table.ebrs().collect::<Vec<_>>();
+        assert_eq!(ebrs.len(), 1);
+        assert_eq!(ebrs[0].0, 10240);
+        assert_eq!(&ebrs[0].1[..], &[0; BLOCK_SIZE][..]);
+    }
+
+    #[test]
+    fn build_errors() {
+        let mut table = PartitionTable::new();
+        assert_eq!(
+            table.add_logical(PartitionEntry::new(12288, 16384)),
+            Err(PartError::NoExtendedPartition)
+        );
+
+        table.add(PartitionEntry::new(2048, 8192)).unwrap();
+        assert_eq!(
+            table.add(PartitionEntry::new(4096, 8192)),
+            Err(PartError::PartitionCollision)
+        );
+        table.add(PartitionEntry::extended(10240, 53248)).unwrap();
+        assert_eq!(
+            table.add(PartitionEntry::extended(63488, 2048)),
+            Err(PartError::NestedExtendedPartition)
+        );
+
+        // no room for the EBR
+        assert_eq!(
+            table.add_logical(PartitionEntry::new(10240, 16384)),
+            Err(PartError::InvalidPartExtent)
+        );
+        table
+            .add_logical(PartitionEntry::new(12288, 16384))
+            .unwrap();
+        assert_eq!(
+            table.add_logical(PartitionEntry::new(28672, 2048)),
+            Err(PartError::PartitionCollision)
+        );
+        assert_eq!(
+            table.add_logical(PartitionEntry::new(30720, 32769)),
+            Err(PartError::InvalidPartExtent)
+        );
+        assert_eq!(
+            table.add_logical(PartitionEntry::extended(30720, 2048)),
+            Err(PartError::NestedExtendedPartition)
+        );
+
+        table.add(PartitionEntry::new(63488, 1024)).unwrap();
+        table.add(PartitionEntry::new(64512, 1024)).unwrap();
+        assert_eq!(
+            table.add(PartitionEntry::new(65536, 1)),
+            Err(PartError::TooManyPartitions)
+        );
+    }
+}
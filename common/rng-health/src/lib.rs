@@ -0,0 +1,280 @@
+//! Continuous health tests for entropy sources
+//!
+//! Reference: NIST SP 800-90B, section 4.4
+//!
+//! Samples are bytes. The cutoff values are derived from the claimed min-entropy per sample and a
+//! false positive probability (alpha) of `2^-20`
+
+#![deny(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+/// Number of samples that must pass the health tests before the output of the source can be used
+/// (SP 800-90B, section 4.3, requirement 9)
+pub const STARTUP_SAMPLES: u16 = 1024;
+
+/// Window size of the Adaptive Proportion Test for non-binary samples
+pub const WINDOW_SIZE: u16 = 512;
+
+// RCT cutoffs, `1 + ceil(20 / H)`, indexed by `H - 1`
+const RCT_CUTOFFS: [u16; 8] = [21, 11, 8, 6, 5, 5, 4, 4];
+
+// APT cutoffs, `1 + CRITBINOM(512, 2^-H, 1 - 2^-20)`, indexed by `H - 1`; these match Table 2 of
+// SP 800-90B
+const APT_CUTOFFS: [u16; 8] = [311, 177, 103, 62, 39, 25, 18, 13];
+
+/// A health test failure
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Failure {
+    /// The same sample was repeated too many times in a row
+    RepetitionCount,
+
+    /// A sample value occurred too many times within a window
+    AdaptiveProportion,
+}
+
+/// The Repetition Count Test and the Adaptive Proportion Test
+///
+/// A failure is latched: once a test fails all future samples are rejected. Create a new instance
+/// to start over
+#[derive(Clone)]
+pub struct HealthTests {
+    rct: RepetitionCount,
+    apt: AdaptiveProportion,
+    startup: u16,
+    failure: Option<Failure>,
+}
+
+impl HealthTests {
+    /// Creates a new set of health tests for a source that claims `min_entropy` bits of entropy
+    /// per byte
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `min_entropy` is not in the range `1..=8`
+    pub fn new(min_entropy: u8) -> Self {
+        assert!(
+            (1..=8).contains(&min_entropy),
+            "min-entropy must be between 1 and 8 bits"
+        );
+
+        let i = usize::from(min_entropy - 1);
+        Self {
+            rct: RepetitionCount::new(RCT_CUTOFFS[i]),
+            apt: AdaptiveProportion::new(APT_CUTOFFS[i]),
+            startup: STARTUP_SAMPLES,
+            failure: None,
+        }
+    }
+
+    /// Runs the tests on the next `sample`
+    pub fn feed(&mut self, sample: u8) -> Result<(), Failure> {
+        if let Some(failure) = self.failure {
+            return Err(failure);
+        }
+
+        // run both tests even if the first one fails
+        let rct = self.rct.feed(sample);
+        let apt = self.apt.feed(sample);
+
+        self.failure = if !rct {
+            Some(Failure::RepetitionCount)
+        } else if !apt {
+            Some(Failure::AdaptiveProportion)
+        } else {
+            None
+        };
+
+        if let Some(failure) = self.failure {
+            return Err(failure);
+        }
+
+        self.startup = self.startup.saturating_sub(1);
+        Ok(())
+    }
+
+    /// Runs the tests on the 4 bytes of `word`, in little endian order
+    pub fn feed_word(&mut self, word: u32) -> Result<(), Failure> {
+        for byte in word.to_le_bytes().iter() {
+            self.feed(*byte)?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the startup tests have completed and no test has failed
+    pub fn is_ready(&self) -> bool {
+        self.startup == 0 && self.failure.is_none()
+    }
+
+    /// Returns the failure that has been latched, if any
+    pub fn failure(&self) -> Option<Failure> {
+        self.failure
+    }
+}
+
+// SP 800-90B, section 4.4.1
+#[derive(Clone)]
+struct RepetitionCount {
+    cutoff: u16,
+    last: Option<u8>,
+    count: u16,
+}
+
+impl RepetitionCount {
+    fn new(cutoff: u16) -> Self {
+        Self {
+            cutoff,
+            last: None,
+            count: 0,
+        }
+    }
+
+    // returns `false` if the test failed
+    fn feed(&mut self, sample: u8) -> bool {
+        if self.last == Some(sample) {
+            self.count += 1;
+        } else {
+            self.last = Some(sample);
+            self.count = 1;
+        }
+
+        self.count < self.cutoff
+    }
+}
+
+// SP 800-90B, section 4.4.2
+#[derive(Clone)]
+struct AdaptiveProportion {
+    cutoff: u16,
+    first: u8,
+    count: u16,
+    // number of samples seen in the current window
+    seen: u16,
+}
+
+impl AdaptiveProportion {
+    fn new(cutoff: u16) -> Self {
+        Self {
+            cutoff,
+            first: 0,
+            count: 0,
+            seen: 0,
+        }
+    }
+
+    // returns `false` if the test failed
+    fn feed(&mut self, sample: u8) -> bool {
+        if self.seen == 0 {
+            self.first = sample;
+            self.count = 1;
+        } else if sample == self.first {
+            self.count += 1;
+        }
+
+        self.seen += 1;
+        if self.seen == WINDOW_SIZE {
+            self.seen = 0;
+        }
+
+        self.count < self.cutoff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Failure, HealthTests, STARTUP_SAMPLES, WINDOW_SIZE};
+
+    // a sequence that cycles through all byte values
+    fn counter(n: usize) -> impl Iterator<Item = u8> {
+        (0..n).map(|i| i as u8)
+    }
+
+    #[test]
+    fn startup() {
+        let mut tests = HealthTests::new(8);
+
+        for sample in counter(usize::from(STARTUP_SAMPLES) - 1) {
+            tests.feed(sample).unwrap();
+        }
+        assert!(!tests.is_ready());
+
+        tests.feed(0).unwrap();
+        assert!(tests.is_ready());
+    }
+
+    #[test]
+    fn repetition_count() {
+        // H = 1 -> C = 21
+        let mut tests = HealthTests::new(1);
+
+        tests.feed(1).unwrap();
+        for _ in 0..19 {
+            tests.feed(0).unwrap();
+        }
+        // a different sample resets the count
+        tests.feed(1).unwrap();
+        for _ in 0..20 {
+            tests.feed(0).unwrap();
+        }
+        assert_eq!(tests.feed(0), Err(Failure::RepetitionCount));
+    }
+
+    #[test]
+    fn adaptive_proportion() {
+        // H = 8 -> C = 13; repetitions stay below the RCT cutoff (4)
+        let mut tests = HealthTests::new(8);
+
+        for _ in 0..12 {
+            tests.feed(0xaa).unwrap();
+            tests.feed(0x55).unwrap();
+        }
+        assert_eq!(tests.feed(0xaa), Err(Failure::AdaptiveProportion));
+    }
+
+    #[test]
+    fn adaptive_proportion_window() {
+        // H = 8 -> C = 13; the count is reset at the start of each window
+        let mut tests = HealthTests::new(8);
+        let window = usize::from(WINDOW_SIZE);
+
+        for i in 0..2 * window {
+            // 12 occurrences of the first sample in each window
+            let sample = if i % window % 10 == 0 && i % window < 120 {
+                0xaa
+            } else {
+                (i % 100) as u8
+            };
+            tests.feed(sample).unwrap();
+        }
+    }
+
+    #[test]
+    fn latched() {
+        let mut tests = HealthTests::new(8);
+
+        for _ in 0..3 {
+            tests.feed(0).unwrap();
+        }
+        assert_eq!(tests.feed(0), Err(Failure::RepetitionCount));
+
+        // good samples don't clear the failure
+        for sample in counter(usize::from(STARTUP_SAMPLES)) {
+            assert_eq!(tests.feed(sample), Err(Failure::RepetitionCount));
+        }
+        assert!(!tests.is_ready());
+        assert_eq!(tests.failure(), Some(Failure::RepetitionCount));
+    }
+
+    #[test]
+    fn feed_word() {
+        let mut tests = HealthTests::new(8);
+
+        tests.feed_word(0x0302_0100).unwrap();
+        assert_eq!(tests.feed_word(0), Err(Failure::RepetitionCount));
+    }
+
+    #[test]
+    #[should_panic]
+    fn no_entropy() {
+        HealthTests::new(0);
+    }
+}
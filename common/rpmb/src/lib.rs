@@ -0,0 +1,646 @@
+//! Data frames of the eMMC Replay Protected Memory Block (RPMB)
+//!
+//! Reference: JEDEC JESD84-B51, section 6.6.22
+//!
+//! This crate encodes requests, checks responses and computes the MACs that authenticate them.
+//! Moving the frames to and from the device is left to the eMMC driver; HMAC-SHA256 is provided by
+//! the user through the `Mac` trait
+
+#![deny(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+/// Size of a data frame, in bytes
+pub const FRAME_SIZE: usize = 512;
+
+/// Size of the authentication key, in bytes
+pub const KEY_SIZE: usize = 32;
+
+/// Size of a MAC, in bytes
+pub const MAC_SIZE: usize = 32;
+
+/// Size of the data carried by a frame, in bytes; the RPMB is addressed in units of this size
+pub const DATA_SIZE: usize = 256;
+
+/// Size of a nonce, in bytes
+pub const NONCE_SIZE: usize = 16;
+
+// offsets of the fields of a frame; the multi-byte fields are big endian
+const KEY_MAC: usize = 196;
+const DATA: usize = KEY_MAC + MAC_SIZE;
+const NONCE: usize = DATA + DATA_SIZE;
+const WRITE_COUNTER: usize = NONCE + NONCE_SIZE;
+const ADDRESS: usize = WRITE_COUNTER + 4;
+const BLOCK_COUNT: usize = ADDRESS + 2;
+const RESULT: usize = BLOCK_COUNT + 2;
+const REQ_RESP: usize = RESULT + 2;
+
+// set in the result of every operation once the write counter has reached its maximum value
+const RESULT_COUNTER_EXPIRED: u16 = 1 << 7;
+
+/// HMAC-SHA256 keyed with the authentication key
+pub trait Mac {
+    /// Error reported by the MAC implementation
+    type Error;
+
+    /// Feeds `input` to the MAC
+    fn input(&mut self, input: &[u8]) -> Result<(), Self::Error>;
+
+    /// Returns the MAC of all the input data and gets ready to process a new message
+    fn finalize_reset(&mut self) -> Result<[u8; MAC_SIZE], Self::Error>;
+}
+
+/// Request types
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Request {
+    /// Authentication key programming
+    ProgramKey,
+
+    /// Reading of the write counter value
+    ReadCounter,
+
+    /// Authenticated data write
+    AuthenticatedWrite,
+
+    /// Authenticated data read
+    AuthenticatedRead,
+
+    /// Result read; it follows a key programming or an authenticated data write request and
+    /// returns the response to that request
+    ReadResult,
+}
+
+impl Request {
+    fn code(self) -> u16 {
+        match self {
+            Request::ProgramKey => 0x0001,
+            Request::ReadCounter => 0x0002,
+            Request::AuthenticatedWrite => 0x0003,
+            Request::AuthenticatedRead => 0x0004,
+            Request::ReadResult => 0x0005,
+        }
+    }
+}
+
+/// Failure reported by the device in the result of an operation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Failure {
+    /// General failure (this includes the reserved result codes)
+    General,
+
+    /// The MAC of the request didn't match the one computed by the device
+    Authentication,
+
+    /// The write counter of the request didn't match the one of the device
+    Counter,
+
+    /// The address was out of range or not aligned
+    Address,
+
+    /// The data could not be written
+    Write,
+
+    /// The data could not be read
+    Read,
+
+    /// The authentication key has not been programmed yet
+    KeyNotProgrammed,
+}
+
+/// Error found while checking a response
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The device reported a failure
+    Device(Failure),
+
+    /// The response type (included) doesn't match the request
+    UnexpectedResponse(u16),
+
+    /// The nonce of the response doesn't match the one of the request; the response may have been
+    /// replayed
+    NonceMismatch,
+
+    /// The MAC of the response is not valid
+    InvalidMac,
+
+    /// The address of the response doesn't match the one of the request
+    AddressMismatch,
+
+    /// The write counter of the response doesn't have the expected value
+    CounterMismatch,
+}
+
+/// A data frame
+///
+/// Requests are built with the constructors of this type; responses are reconstructed with
+/// `from_bytes`
+#[derive(Clone)]
+pub struct Frame {
+    bytes: [u8; FRAME_SIZE],
+}
+
+impl Frame {
+    /// Returns a frame whose fields are all zero
+    pub fn zeroed() -> Self {
+        Frame {
+            bytes: [0; FRAME_SIZE],
+        }
+    }
+
+    /// Reconstructs a frame, like a response, from its `bytes`
+    pub fn from_bytes(bytes: &[u8; FRAME_SIZE]) -> Self {
+        Frame { bytes: *bytes }
+    }
+
+    /// Returns the bytes of the frame, in the order they are sent to the device
+    pub fn as_bytes(&self) -> &[u8; FRAME_SIZE] {
+        &self.bytes
+    }
+
+    /// Builds a request that programs the authentication `key`
+    ///
+    /// The key can only be programmed once
+    pub fn program_key(key: &[u8; KEY_SIZE]) -> Self {
+        let mut frame = Frame::request(Request::ProgramKey);
+        frame.bytes[KEY_MAC..DATA].copy_from_slice(key);
+        frame
+    }
+
+    /// Builds a request that reads the write counter
+    pub fn read_counter(nonce: &[u8; NONCE_SIZE]) -> Self {
+        let mut frame = Frame::request(Request::ReadCounter);
+        frame.set_nonce(nonce);
+        frame
+    }
+
+    /// Builds a request that writes `data` at `address`
+    ///
+    /// `counter` must be the current value of the write counter. The request is authenticated with
+    /// `mac`
+    pub fn authenticated_write<M>(
+        mac: &mut M,
+        address: u16,
+        counter: u32,
+        data: &[u8; DATA_SIZE],
+    ) -> Result<Self, M::Error>
+    where
+        M: Mac,
+    {
+        let mut frame = Frame::request(Request::AuthenticatedWrite);
+        frame.bytes[DATA..NONCE].copy_from_slice(data);
+        frame.bytes[WRITE_COUNTER..ADDRESS].copy_from_slice(&counter.to_be_bytes());
+        frame.bytes[ADDRESS..BLOCK_COUNT].copy_from_slice(&address.to_be_bytes());
+        frame.bytes[BLOCK_COUNT..RESULT].copy_from_slice(&1u16.to_be_bytes());
+
+        let tag = compute_mac(mac, core::slice::from_ref(&frame))?;
+        frame.bytes[KEY_MAC..DATA].copy_from_slice(&tag);
+
+        Ok(frame)
+    }
+
+    /// Builds a request that reads the data stored at `address`
+    ///
+    /// The device answers with as many frames as were announced with the `SET_BLOCK_COUNT`
+    /// command
+    pub fn authenticated_read(address: u16, nonce: &[u8; NONCE_SIZE]) -> Self {
+        let mut frame = Frame::request(Request::AuthenticatedRead);
+        frame.set_nonce(nonce);
+        frame.bytes[ADDRESS..BLOCK_COUNT].copy_from_slice(&address.to_be_bytes());
+        frame
+    }
+
+    /// Builds a request that reads the result of the previous key programming or authenticated
+    /// data write request
+    pub fn read_result() -> Self {
+        Frame::request(Request::ReadResult)
+    }
+
+    /// Returns the MAC field
+    pub fn mac(&self) -> &[u8] {
+        &self.bytes[KEY_MAC..DATA]
+    }
+
+    /// Returns the data field
+    pub fn data(&self) -> &[u8] {
+        &self.bytes[DATA..NONCE]
+    }
+
+    /// Returns the nonce field
+    pub fn nonce(&self) -> &[u8] {
+        &self.bytes[NONCE..WRITE_COUNTER]
+    }
+
+    /// Returns the write counter field
+    pub fn write_counter(&self) -> u32 {
+        let b = &self.bytes;
+        u32::from_be_bytes([
+            b[WRITE_COUNTER],
+            b[WRITE_COUNTER + 1],
+            b[WRITE_COUNTER + 2],
+            b[WRITE_COUNTER + 3],
+        ])
+    }
+
+    /// Returns the address field
+    pub fn address(&self) -> u16 {
+        self.u16_at(ADDRESS)
+    }
+
+    /// Returns the block count field
+    pub fn block_count(&self) -> u16 {
+        self.u16_at(BLOCK_COUNT)
+    }
+
+    /// Returns `true` if the device reported that the write counter has expired, in which case the
+    /// RPMB can no longer be written
+    pub fn counter_expired(&self) -> bool {
+        self.u16_at(RESULT) & RESULT_COUNTER_EXPIRED != 0
+    }
+
+    /// Checks that this frame is a successful response to `request`
+    ///
+    /// If `nonce` is provided the nonce of the response must match it. This does *not* check the
+    /// MAC of the response; use `verify` for that
+    pub fn check(&self, request: Request, nonce: Option<&[u8; NONCE_SIZE]>) -> Result<(), Error> {
+        let response = self.u16_at(REQ_RESP);
+        if response != request.code() << 8 {
+            return Err(Error::UnexpectedResponse(response));
+        }
+
+        let failure = match self.u16_at(RESULT) & !RESULT_COUNTER_EXPIRED {
+            0x0000 => None,
+            0x0002 => Some(Failure::Authentication),
+            0x0003 => Some(Failure::Counter),
+            0x0004 => Some(Failure::Address),
+            0x0005 => Some(Failure::Write),
+            0x0006 => Some(Failure::Read),
+            0x0007 => Some(Failure::KeyNotProgrammed),
+            _ => Some(Failure::General),
+        };
+
+        if let Some(failure) = failure {
+            return Err(Error::Device(failure));
+        }
+
+        if let Some(nonce) = nonce {
+            if self.nonce() != &nonce[..] {
+                return Err(Error::NonceMismatch);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn request(request: Request) -> Self {
+        let mut frame = Frame::zeroed();
+        frame.bytes[REQ_RESP..].copy_from_slice(&request.code().to_be_bytes());
+        frame
+    }
+
+    fn set_nonce(&mut self, nonce: &[u8; NONCE_SIZE]) {
+        self.bytes[NONCE..WRITE_COUNTER].copy_from_slice(nonce);
+    }
+
+    fn u16_at(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.bytes[offset], self.bytes[offset + 1]])
+    }
+}
+
+/// Checks, in constant time, that the MAC stored in the last of `frames` authenticates all of
+/// them
+///
+/// Responses that carry data are made of several frames; all of them are covered by a single
+/// MAC. Returns `false` if `frames` is empty
+pub fn verify<M>(mac: &mut M, frames: &[Frame]) -> Result<bool, M::Error>
+where
+    M: Mac,
+{
+    let last = if let Some(last) = frames.last() {
+        last
+    } else {
+        return Ok(false);
+    };
+
+    let expected = compute_mac(mac, frames)?;
+    let diff = expected
+        .iter()
+        .zip(last.mac())
+        .fold(0, |diff, (x, y)| diff | (x ^ y));
+
+    Ok(diff == 0)
+}
+
+// the MAC covers every field that follows the key / MAC field
+fn compute_mac<M>(mac: &mut M, frames: &[Frame]) -> Result<[u8; MAC_SIZE], M::Error>
+where
+    M: Mac,
+{
+    for frame in frames {
+        mac.input(&frame.bytes[DATA..])?;
+    }
+
+    mac.finalize_reset()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify, Error, Failure, Frame, Mac, Request, DATA, FRAME_SIZE, KEY_MAC, MAC_SIZE};
+
+    // Straightforward software HMAC-SHA256 (RFC 2104, FIPS 180-4)
+    struct HmacSha256 {
+        key: [u8; 64],
+        message: Vec<u8>,
+    }
+
+    impl HmacSha256 {
+        fn new(key: &[u8]) -> Self {
+            assert!(key.len() <= 64);
+
+            let mut padded = [0; 64];
+            padded[..key.len()].copy_from_slice(key);
+            HmacSha256 {
+                key: padded,
+                message: vec![],
+            }
+        }
+    }
+
+    impl Mac for HmacSha256 {
+        type Error = ();
+
+        fn input(&mut self, input: &[u8]) -> Result<(), ()> {
+            self.message.extend_from_slice(input);
+            Ok(())
+        }
+
+        fn finalize_reset(&mut self) -> Result<[u8; MAC_SIZE], ()> {
+            let mut inner = self.key.iter().map(|b| b ^ 0x36).collect::<Vec<_>>();
+            inner.extend_from_slice(&self.message);
+            let mut outer = self.key.iter().map(|b| b ^ 0x5c).collect::<Vec<_>>();
+            outer.extend_from_slice(&sha256(&inner));
+
+            self.message.clear();
+            Ok(sha256(&outer))
+        }
+    }
+
+    fn sha256(message: &[u8]) -> [u8; 32] {
+        const K: [u32; 64] = [
+            0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+            0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+            0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+            0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+            0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+            0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+            0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+            0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+            0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+            0xc67178f2,
+        ];
+
+        let mut h: [u32; 8] = [
+            0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+            0x5be0cd19,
+        ];
+
+        let mut padded = message.to_vec();
+        padded.push(0x80);
+        while padded.len() % 64 != 56 {
+            padded.push(0);
+        }
+        padded.extend_from_slice(&(message.len() as u64 * 8).to_be_bytes());
+
+        for chunk in padded.chunks(64) {
+            let mut w = [0u32; 64];
+            for i in 0..16 {
+                w[i] = u32::from_be_bytes([
+                    chunk[4 * i],
+                    chunk[4 * i + 1],
+                    chunk[4 * i + 2],
+                    chunk[4 * i + 3],
+                ]);
+            }
+            for i in 16..64 {
+                let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+                let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+                w[i] = w[i - 16]
+                    .wrapping_add(s0)
+                    .wrapping_add(w[i - 7])
+                    .wrapping_add(s1);
+            }
+
+            let mut v = h;
+            for i in 0..64 {
+                let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+                let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+                let t1 = v[7]
+                    .wrapping_add(s1)
+                    .wrapping_add(ch)
+                    .wrapping_add(K[i])
+                    .wrapping_add(w[i]);
+                let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+                let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+                let t2 = s0.wrapping_add(maj);
+
+                v = [
+                    t1.wrapping_add(t2),
+                    v[0],
+                    v[1],
+                    v[2],
+                    v[3].wrapping_add(t1),
+                    v[4],
+                    v[5],
+                    v[6],
+                ];
+            }
+
+            for (h, v) in h.iter_mut().zip(v.iter()) {
+                *h = h.wrapping_add(*v);
+            }
+        }
+
+        let mut digest = [0; 32];
+        for (chunk, h) in digest.chunks_mut(4).zip(h.iter()) {
+            chunk.copy_from_slice(&h.to_be_bytes());
+        }
+        digest
+    }
+
+    // turns a request into the response the device would send back
+    fn respond(request: &Frame, code: u16, result: u16) -> Frame {
+        let mut bytes = *request.as_bytes();
+        bytes[508..510].copy_from_slice(&result.to_be_bytes());
+        bytes[510..512].copy_from_slice(&(code << 8).to_be_bytes());
+        Frame::from_bytes(&bytes)
+    }
+
+    fn sign(mac: &mut HmacSha256, frames: &mut [Frame]) {
+        for frame in frames.iter() {
+            mac.input(&frame.as_bytes()[DATA..]).unwrap();
+        }
+        let tag = mac.finalize_reset().unwrap();
+
+        let last = frames.last_mut().unwrap();
+        let mut bytes = *last.as_bytes();
+        bytes[KEY_MAC..DATA].copy_from_slice(&tag);
+        *last = Frame::from_bytes(&bytes);
+    }
+
+    #[test]
+    fn hmac() {
+        // RFC 4231, test case 2
+        let mut mac = HmacSha256::new(b"Jefe");
+        mac.input(b"what do ya want for nothing?").unwrap();
+        assert_eq!(
+            mac.finalize_reset().unwrap(),
+            [
+                0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95,
+                0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9,
+                0x64, 0xec, 0x38, 0x43,
+            ]
+        );
+    }
+
+    #[test]
+    fn encoding() {
+        let key = [0xaa; 32];
+        let frame = Frame::program_key(&key);
+        let bytes = frame.as_bytes();
+        assert!(bytes[..196].iter().all(|b| *b == 0));
+        assert_eq!(&bytes[196..228], &key[..]);
+        assert!(bytes[228..510].iter().all(|b| *b == 0));
+        assert_eq!(&bytes[510..], &[0x00, 0x01]);
+
+        let nonce = [0x55; 16];
+        let frame = Frame::read_counter(&nonce);
+        let bytes = frame.as_bytes();
+        assert_eq!(&bytes[484..500], &nonce[..]);
+        assert_eq!(&bytes[510..], &[0x00, 0x02]);
+
+        let frame = Frame::authenticated_read(0x1234, &nonce);
+        let bytes = frame.as_bytes();
+        assert_eq!(&bytes[484..500], &nonce[..]);
+        assert_eq!(&bytes[504..506], &[0x12, 0x34]);
+        assert_eq!(&bytes[506..508], &[0, 0]);
+        assert_eq!(&bytes[510..], &[0x00, 0x04]);
+
+        let frame = Frame::read_result();
+        assert!(frame.as_bytes()[..510].iter().all(|b| *b == 0));
+        assert_eq!(&frame.as_bytes()[510..], &[0x00, 0x05]);
+    }
+
+    #[test]
+    fn authenticated_write() {
+        let mut mac = HmacSha256::new(&[0x42; 32]);
+        let data = [0x99; 256];
+        let frame = Frame::authenticated_write(&mut mac, 0x0102, 0x0a0b_0c0d, &data).unwrap();
+        let bytes = frame.as_bytes();
+
+        assert_eq!(&bytes[228..484], &data[..]);
+        assert!(bytes[484..500].iter().all(|b| *b == 0));
+        assert_eq!(&bytes[500..504], &[0x0a, 0x0b, 0x0c, 0x0d]);
+        assert_eq!(&bytes[504..506], &[0x01, 0x02]);
+        assert_eq!(&bytes[506..508], &[0x00, 0x01]);
+        assert_eq!(&bytes[510..], &[0x00, 0x03]);
+
+        // the MAC covers bytes 228 to 511
+        mac.input(&bytes[228..]).unwrap();
+        assert_eq!(&bytes[196..228], &mac.finalize_reset().unwrap()[..]);
+
+        assert_eq!(frame.address(), 0x0102);
+        assert_eq!(frame.write_counter(), 0x0a0b_0c0d);
+        assert_eq!(frame.block_count(), 1);
+        assert_eq!(frame.data(), &data[..]);
+    }
+
+    #[test]
+    fn verification() {
+        let mut mac = HmacSha256::new(&[0x42; 32]);
+        let nonce = [7; 16];
+
+        let mut response = [
+            respond(&Frame::authenticated_read(3, &nonce), 0x04, 0),
+            respond(&Frame::authenticated_read(3, &nonce), 0x04, 0),
+        ];
+        sign(&mut mac, &mut response);
+        assert_eq!(verify(&mut mac, &response), Ok(true));
+
+        // the stuff bytes are not authenticated
+        let mut bytes = *response[0].as_bytes();
+        bytes[0] ^= 1;
+        let stuffed = [Frame::from_bytes(&bytes), response[1].clone()];
+        assert_eq!(verify(&mut mac, &stuffed), Ok(true));
+
+        // but the rest of the frame is, in every frame
+        for offset in &[228, 484, 500, 504, 511] {
+            let mut bytes = *response[0].as_bytes();
+            bytes[*offset] ^= 1;
+            let tampered = [Frame::from_bytes(&bytes), response[1].clone()];
+            assert_eq!(verify(&mut mac, &tampered), Ok(false));
+        }
+
+        // wrong key
+        let mut other = HmacSha256::new(&[0x24; 32]);
+        assert_eq!(verify(&mut other, &response), Ok(false));
+
+        assert_eq!(verify(&mut mac, &[]), Ok(false));
+    }
+
+    #[test]
+    fn check() {
+        let nonce = [1; 16];
+        let request = Frame::read_counter(&nonce);
+
+        let ok = respond(&request, 0x02, 0x0000);
+        assert_eq!(ok.check(Request::ReadCounter, Some(&nonce)), Ok(()));
+        assert!(!ok.counter_expired());
+
+        // the counter expired flag doesn't turn a success into a failure
+        let expired = respond(&request, 0x02, 0x0080);
+        assert_eq!(expired.check(Request::ReadCounter, Some(&nonce)), Ok(()));
+        assert!(expired.counter_expired());
+
+        assert_eq!(
+            ok.check(Request::ReadCounter, Some(&[2; 16])),
+            Err(Error::NonceMismatch)
+        );
+        assert_eq!(
+            ok.check(Request::AuthenticatedRead, None),
+            Err(Error::UnexpectedResponse(0x0200))
+        );
+
+        for (result, failure) in &[
+            (0x0001, Failure::General),
+            (0x0002, Failure::Authentication),
+            (0x0003, Failure::Counter),
+            (0x0004, Failure::Address),
+            (0x0005, Failure::Write),
+            (0x0006, Failure::Read),
+            (0x0007, Failure::KeyNotProgrammed),
+            (0x0085, Failure::Write),
+            (0x0042, Failure::General),
+        ] {
+            let response = respond(&request, 0x02, *result);
+            assert_eq!(
+                response.check(Request::ReadCounter, Some(&nonce)),
+                Err(Error::Device(*failure))
+            );
+        }
+    }
+
+    #[test]
+    fn from_bytes() {
+        let mut bytes = [0; FRAME_SIZE];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let frame = Frame::from_bytes(&bytes);
+        assert_eq!(&frame.as_bytes()[..], &bytes[..]);
+        assert_eq!(frame.mac(), &bytes[196..228]);
+        assert_eq!(frame.nonce(), &bytes[484..500]);
+        assert_eq!(
+            frame.address(),
+            u16::from_be_bytes([bytes[504], bytes[505]])
+        );
+    }
+}
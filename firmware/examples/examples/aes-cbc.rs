@@ -0,0 +1,95 @@
+//! Sanity check the AES-128 CBC API using a 4 KiB buffer
+//!
+//! Encryption has been checked against the following Python code
+//!
+//! ``` python
+//! # cryptography = "48.0"
+//! from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+//!
+//! key = bytes([185, ..., 72])
+//! iv = bytes(range(16))
+//! plaintext = bytes(i % 256 for i in range(4096))
+//! encryptor = Cipher(algorithms.AES(key), modes.CBC(iv)).encryptor()
+//! ciphertext = encryptor.update(plaintext) + encryptor.finalize()
+//! ```
+//!
+//! Expected output:
+//!
+//! ```
+//! first block: [174, 155, 8, 163, 69, 200, 177, 155, 29, 239, 134, 174, 81, 219, 1, 214]
+//! last block:  [245, 184, 184, 52, 232, 154, 120, 40, 177, 71, 211, 197, 31, 20, 157, 134]
+//! encrypt: OK
+//! decrypt: OK
+//! ```
+
+#![deny(unused_must_use)]
+#![no_main]
+#![no_std]
+
+use block_cipher::NewBlockCipher;
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{dcp::Aes128, memlog, memlog_flush_and_reset};
+
+const SIZE: usize = 4 * 1024;
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let key = [
+        185, 105, 131, 156, 95, 42, 127, 229, 107, 50, 14, 134, 232, 8, 250, 72,
+    ];
+    let iv = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+    let cipher = Aes128::new(&key.into());
+
+    let mut buf = [0; SIZE];
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+
+    // the whole buffer is processed in a single DCP operation
+    if let Err(e) = cipher.encrypt_cbc(&iv, &mut buf) {
+        memlog!("error: {:?}", e);
+        memlog_flush_and_reset!();
+    }
+
+    let first = [
+        174, 155, 8, 163, 69, 200, 177, 155, 29, 239, 134, 174, 81, 219, 1, 214,
+    ];
+    let last = [
+        245, 184, 184, 52, 232, 154, 120, 40, 177, 71, 211, 197, 31, 20, 157, 134,
+    ];
+
+    memlog!("first block: {:?}", &buf[..16]);
+    memlog!("last block:  {:?}", &buf[SIZE - 16..]);
+
+    if buf[..16] == first[..] && buf[SIZE - 16..] == last[..] {
+        memlog!("encrypt: OK");
+    } else {
+        memlog!("output didn't match the expected value");
+        memlog_flush_and_reset!();
+    }
+
+    // decrypt in non-contiguous pieces; the result must be the same as decrypting the whole
+    // buffer at once
+    {
+        let (head, tail) = buf.split_at_mut(16);
+        let (middle, tail) = tail.split_at_mut(1024);
+        if let Err(e) = cipher.decrypt_cbc_segments(&iv, &mut [head, middle, tail]) {
+            memlog!("error: {:?}", e);
+            memlog_flush_and_reset!();
+        }
+    }
+
+    if buf.iter().enumerate().all(|(i, byte)| *byte == i as u8) {
+        memlog!("decrypt: OK");
+    } else {
+        memlog!("decryption didn't return the input plaintext");
+        memlog_flush_and_reset!();
+    }
+
+    memlog_flush_and_reset!();
+}
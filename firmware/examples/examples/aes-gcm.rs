@@ -0,0 +1,134 @@
+//! Sanity check the AES-128 CTR and GCM APIs
+//!
+//! The CTR test vector comes from NIST SP 800-38A (F.5.1); the GCM test vector is test case #4 of
+//! "The Galois/Counter Mode of Operation (GCM)" (McGrew & Viega). Both have been checked against
+//! the `cryptography` Python package (v48.0)
+//!
+//! Expected output:
+//!
+//! ```
+//! ctr: OK
+//! ctr (in pieces): OK
+//! gcm encrypt: OK
+//! gcm decrypt: OK
+//! gcm forgery rejected: OK
+//! ```
+
+#![deny(unused_must_use)]
+#![no_main]
+#![no_std]
+
+use aead::{AeadInPlace, NewAead};
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use stream_cipher::{NewStreamCipher, SyncStreamCipher, SyncStreamCipherSeek};
+use usbarmory::{
+    dcp::{Aes128Ctr, Aes128Gcm},
+    memlog, memlog_flush_and_reset,
+};
+
+const CTR_KEY: [u8; 16] = [
+    43, 126, 21, 22, 40, 174, 210, 166, 171, 247, 21, 136, 9, 207, 79, 60,
+];
+const CTR_NONCE: [u8; 16] = [
+    240, 241, 242, 243, 244, 245, 246, 247, 248, 249, 250, 251, 252, 253, 254, 255,
+];
+const CTR_PLAINTEXT: [u8; 64] = [
+    107, 193, 190, 226, 46, 64, 159, 150, 233, 61, 126, 17, 115, 147, 23, 42, 174, 45, 138, 87, 30,
+    3, 172, 156, 158, 183, 111, 172, 69, 175, 142, 81, 48, 200, 28, 70, 163, 92, 228, 17, 229, 251,
+    193, 25, 26, 10, 82, 239, 246, 159, 36, 69, 223, 79, 155, 23, 173, 43, 65, 123, 230, 108, 55,
+    16,
+];
+const CTR_CIPHERTEXT: [u8; 64] = [
+    135, 77, 97, 145, 182, 32, 227, 38, 27, 239, 104, 100, 153, 13, 182, 206, 152, 6, 246, 107,
+    121, 112, 253, 255, 134, 23, 24, 123, 185, 255, 253, 255, 90, 228, 223, 62, 219, 213, 211, 94,
+    91, 79, 9, 2, 13, 176, 62, 171, 30, 3, 29, 218, 47, 190, 3, 209, 121, 33, 112, 160, 243, 0,
+    156, 238,
+];
+
+const GCM_KEY: [u8; 16] = [
+    254, 255, 233, 146, 134, 101, 115, 28, 109, 106, 143, 148, 103, 48, 131, 8,
+];
+const GCM_NONCE: [u8; 12] = [202, 254, 186, 190, 250, 206, 219, 173, 222, 202, 248, 136];
+const GCM_AAD: [u8; 20] = [
+    254, 237, 250, 206, 222, 173, 190, 239, 254, 237, 250, 206, 222, 173, 190, 239, 171, 173, 218,
+    210,
+];
+const GCM_PLAINTEXT: [u8; 60] = [
+    217, 49, 50, 37, 248, 132, 6, 229, 165, 89, 9, 197, 175, 245, 38, 154, 134, 167, 169, 83, 21,
+    52, 247, 218, 46, 76, 48, 61, 138, 49, 138, 114, 28, 60, 12, 149, 149, 104, 9, 83, 47, 207, 14,
+    36, 73, 166, 181, 37, 177, 106, 237, 245, 170, 13, 230, 87, 186, 99, 123, 57,
+];
+const GCM_CIPHERTEXT: [u8; 60] = [
+    66, 131, 30, 194, 33, 119, 116, 36, 75, 114, 33, 183, 132, 208, 212, 156, 227, 170, 33, 47, 44,
+    2, 164, 224, 53, 193, 126, 35, 41, 172, 161, 46, 33, 213, 20, 178, 84, 102, 147, 28, 125, 143,
+    106, 90, 172, 132, 170, 5, 27, 163, 11, 57, 106, 10, 172, 151, 61, 88, 224, 145,
+];
+const GCM_TAG: [u8; 16] = [
+    91, 201, 79, 188, 50, 33, 165, 219, 148, 250, 233, 90, 231, 18, 26, 71,
+];
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    /* CTR */
+    let mut ctr = Aes128Ctr::new(&CTR_KEY.into(), &CTR_NONCE.into());
+
+    let mut buf = CTR_PLAINTEXT;
+    ctr.apply_keystream(&mut buf);
+    check("ctr", buf[..] == CTR_CIPHERTEXT[..]);
+
+    // process the data in pieces that don't line up with the AES blocks
+    ctr.seek(0);
+    let mut buf = CTR_PLAINTEXT;
+    let (head, tail) = buf.split_at_mut(7);
+    let (middle, tail) = tail.split_at_mut(30);
+    ctr.apply_keystream(head);
+    ctr.apply_keystream(middle);
+    ctr.apply_keystream(tail);
+    check("ctr (in pieces)", buf[..] == CTR_CIPHERTEXT[..]);
+
+    // release the key slot
+    drop(ctr);
+
+    /* GCM */
+    let gcm = Aes128Gcm::new(&GCM_KEY.into());
+
+    let mut buf = GCM_PLAINTEXT;
+    let tag = gcm
+        .encrypt_in_place_detached(&GCM_NONCE.into(), &GCM_AAD, &mut buf)
+        .unwrap_or_else(|_| {
+            memlog!("gcm encrypt: error");
+            memlog_flush_and_reset!()
+        });
+    check(
+        "gcm encrypt",
+        buf[..] == GCM_CIPHERTEXT[..] && tag[..] == GCM_TAG[..],
+    );
+
+    let res = gcm.decrypt_in_place_detached(&GCM_NONCE.into(), &GCM_AAD, &mut buf, &tag);
+    check("gcm decrypt", res.is_ok() && buf[..] == GCM_PLAINTEXT[..]);
+
+    // flip a bit of the tag
+    let mut forged = tag;
+    forged[0] ^= 1;
+    let mut buf = GCM_CIPHERTEXT;
+    let res = gcm.decrypt_in_place_detached(&GCM_NONCE.into(), &GCM_AAD, &mut buf, &forged);
+    check(
+        "gcm forgery rejected",
+        res.is_err() && buf[..] == GCM_CIPHERTEXT[..],
+    );
+
+    memlog_flush_and_reset!();
+}
+
+fn check(what: &str, ok: bool) {
+    if ok {
+        memlog!("{}: OK", what);
+    } else {
+        memlog!("{}: output didn't match the expected value", what);
+        memlog_flush_and_reset!();
+    }
+}
@@ -0,0 +1,105 @@
+//! Use all four DCP key slots at the same time
+//!
+//! Encryption has been checked against the following Python code
+//!
+//! ``` python
+//! # cryptography = "48.0"
+//! from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+//!
+//! plaintext = bytes([179, ..., 242])
+//! for i in range(1, 5):
+//!     encryptor = Cipher(algorithms.AES(bytes([i]) * 16), modes.ECB()).encryptor()
+//!     ciphertext = encryptor.update(plaintext)
+//! ```
+//!
+//! Expected output:
+//!
+//! ```
+//! key #1: OK
+//! key #2: OK
+//! key #3: OK
+//! key #4: OK
+//! DONE
+//! ```
+
+#![deny(unused_must_use)]
+#![no_main]
+#![no_std]
+
+use block_cipher::{BlockCipher, NewBlockCipher};
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{dcp::Aes128, memlog, memlog_flush_and_reset};
+
+const PLAINTEXT: [u8; 16] = [
+    179, 176, 19, 230, 198, 237, 169, 162, 83, 237, 103, 21, 175, 240, 64, 242,
+];
+
+// ciphertexts produced by the keys `[1; 16]`, `[2; 16]`, `[3; 16]` and `[4; 16]`
+const EXPECTED: [[u8; 16]; 4] = [
+    [
+        24, 133, 207, 247, 102, 43, 238, 19, 87, 224, 5, 38, 129, 60, 255, 38,
+    ],
+    [
+        165, 115, 71, 175, 113, 120, 118, 84, 103, 10, 180, 93, 193, 235, 79, 55,
+    ],
+    [
+        65, 206, 119, 69, 254, 71, 17, 51, 254, 189, 20, 147, 150, 45, 255, 202,
+    ],
+    [
+        100, 183, 236, 81, 122, 24, 231, 10, 83, 187, 213, 204, 210, 201, 208, 179,
+    ],
+];
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let ciphers = [
+        Aes128::new(&[1; 16].into()),
+        Aes128::new(&[2; 16].into()),
+        Aes128::new(&[3; 16].into()),
+        Aes128::new(&[4; 16].into()),
+    ];
+
+    // all the key slots are in use
+    if Aes128::new_ram(&[5; 16].into()).is_some() {
+        memlog!("error: was able to create a fifth RAM-key cipher");
+        memlog_flush_and_reset!();
+    }
+
+    // interleave the use of the different keys
+    for _ in 0..2 {
+        for (i, (cipher, expected)) in ciphers.iter().zip(EXPECTED.iter()).enumerate() {
+            let mut block = PLAINTEXT.into();
+            cipher.encrypt_block(&mut block);
+
+            if block[..] != expected[..] {
+                memlog!("key #{}: output didn't match the expected value", i + 1);
+                memlog_flush_and_reset!();
+            }
+
+            cipher.decrypt_block(&mut block);
+
+            if block[..] != PLAINTEXT[..] {
+                memlog!("key #{}: decryption didn't return the plaintext", i + 1);
+                memlog_flush_and_reset!();
+            }
+        }
+    }
+
+    for i in 0..ciphers.len() {
+        memlog!("key #{}: OK", i + 1);
+    }
+
+    // dropping the ciphers frees their key slots
+    drop(ciphers);
+    if Aes128::new_ram(&[5; 16].into()).is_none() {
+        memlog!("error: key slots were not released");
+        memlog_flush_and_reset!();
+    }
+
+    memlog!("DONE");
+    memlog_flush_and_reset!();
+}
@@ -0,0 +1,86 @@
+//! Hashes a 4 KiB buffer in the background using a DCP `Job`
+//!
+//! The job is submitted in `init`; its completion is handled by a hardware task bound to the DCP
+//! interrupt
+//!
+//! Expected output:
+//!
+//! ```
+//! output:   [173, 127, 172, 178, 88, 111, 198, 233, 102, 192, 4, 215, 209, 209, 107, 2, 79, 88, 5, 255, 124, 180, 124, 122, 133, 218, 189, 139, 72, 137, 44, 167]
+//! expected: [173, 127, 172, 178, 88, 111, 198, 233, 102, 192, 4, 215, 209, 209, 107, 2, 79, 88, 5, 255, 124, 180, 124, 122, 133, 218, 189, 139, 72, 137, 44, 167]
+//! OK
+//! ```
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use digest::FixedOutput;
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    dcp::{Job, Sha256},
+    memlog, memlog_flush_and_reset,
+};
+
+// The `expected` value comes from running the following code on a x86_64 machine
+//
+// ``` rust
+// use sha2::{Digest, Sha256};
+// let mut hasher = Sha256::new();
+// hasher.input(&[0; 4096][..]);
+// let expected = hasher.result();
+// ```
+const EXPECTED: [u8; 32] = [
+    173, 127, 172, 178, 88, 111, 198, 233, 102, 192, 4, 215, 209, 209, 107, 2, 79, 88, 5, 255, 124,
+    180, 124, 122, 133, 218, 189, 139, 72, 137, 44, 167,
+];
+
+#[rtic::app]
+const APP: () = {
+    struct Resources {
+        // `None` once the job has been completed
+        job: Option<Job<(Sha256, &'static [u8])>>,
+    }
+
+    #[init]
+    fn init(_cx: init::Context) -> init::LateResources {
+        static mut INPUT: [u8; 4 * 1024] = [0; 4 * 1024];
+
+        let hasher = Sha256::take().expect("UNREACHABLE");
+
+        // this returns immediately; the DCP interrupt fires when the hashing is done
+        let job = hasher.input_job(INPUT);
+
+        init::LateResources { job: Some(job) }
+    }
+
+    #[task(binds = DCP, resources = [job])]
+    fn on_dcp(cx: on_dcp::Context) {
+        let job = cx.resources.job;
+
+        if job.as_ref().map(|job| job.is_done()).unwrap_or(false) {
+            let ((hasher, _input), res) = job.take().expect("UNREACHABLE").wait();
+
+            if let Err(e) = res {
+                memlog!("error: {:?}", e);
+                memlog_flush_and_reset!()
+            }
+
+            // the leftover (if any) is hashed in a blocking fashion
+            let output = hasher.fixed_result();
+
+            memlog!("output:   {:?}", output);
+            memlog!("expected: {:?}", EXPECTED);
+
+            if output[..] == EXPECTED[..] {
+                memlog!("OK");
+            } else {
+                memlog!("error: incorrect result");
+            }
+
+            memlog_flush_and_reset!()
+        }
+    }
+};
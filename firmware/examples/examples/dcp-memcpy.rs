@@ -0,0 +1,99 @@
+//! Copies and fills a 16 KiB buffer using the DCP
+//!
+//! Expected output (the timings will vary):
+//!
+//! ```
+//! fill: OK (..)
+//! fill (unaligned): OK
+//! memcpy: OK (..)
+//! fill_job: OK
+//! ```
+
+#![deny(unused_must_use)]
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    dcp::{self, Memcpy},
+    memlog, memlog_flush_and_reset,
+    time::Instant,
+};
+
+const SIZE: usize = 16 * 1024;
+const PATTERN: u32 = 0xdead_beef;
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    static mut SRC: [u8; SIZE] = [0; SIZE];
+    static mut DST: [u8; SIZE] = [0; SIZE];
+
+    let src = unsafe { &mut SRC };
+    let dst = unsafe { &mut DST };
+
+    let start = Instant::now();
+    if let Err(e) = dcp::fill(src, PATTERN) {
+        memlog!("error: {:?}", e);
+        memlog_flush_and_reset!();
+    }
+    let elapsed = start.elapsed();
+
+    // the pattern is aligned to word addresses
+    let bytes = PATTERN.to_le_bytes();
+    let base = src.as_ptr() as usize;
+    let ok = src
+        .iter()
+        .enumerate()
+        .all(|(i, byte)| *byte == bytes[(base + i) % 4]);
+    check("fill", ok);
+    memlog!("fill: OK ({:?})", elapsed);
+
+    // zero a region whose start and end are not word aligned
+    if let Err(e) = dcp::fill(&mut src[3..SIZE - 5], 0) {
+        memlog!("error: {:?}", e);
+        memlog_flush_and_reset!();
+    }
+    let ok = src.iter().enumerate().all(|(i, byte)| {
+        if i >= 3 && i < SIZE - 5 {
+            *byte == 0
+        } else {
+            *byte == bytes[(base + i) % 4]
+        }
+    });
+    check("fill (unaligned)", ok);
+    memlog!("fill (unaligned): OK");
+
+    let start = Instant::now();
+    if let Err(e) = dcp::memcpy(dst, src) {
+        memlog!("error: {:?}", e);
+        memlog_flush_and_reset!();
+    }
+    let elapsed = start.elapsed();
+    check("memcpy", dst[..] == src[..]);
+    memlog!("memcpy: OK ({:?})", elapsed);
+
+    // zeroize in the background
+    let memcpy = Memcpy::take().expect("UNREACHABLE");
+    let job = memcpy.fill_job(dst, 0);
+    // .. do other work here ..
+    let ((_memcpy, dst), res) = job.wait();
+    if let Err(e) = res {
+        memlog!("error: {:?}", e);
+        memlog_flush_and_reset!();
+    }
+    check("fill_job", dst.iter().all(|byte| *byte == 0));
+    memlog!("fill_job: OK");
+
+    memlog_flush_and_reset!();
+}
+
+fn check(what: &str, ok: bool) {
+    if !ok {
+        memlog!("{}: output didn't match the expected value", what);
+        memlog_flush_and_reset!();
+    }
+}
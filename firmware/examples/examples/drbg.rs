@@ -0,0 +1,58 @@
+//! Random number generation with the CTR_DRBG
+//!
+//! Expected output:
+//!
+//! ```
+//! try_generate: [..]
+//! next_u64: [..]
+//! reseed: OK
+//! try_generate: [..]
+//! ```
+//!
+//! Where `[..]` are random numbers
+
+#![deny(unused_must_use)]
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use rand_core::RngCore;
+use usbarmory::{
+    dcp::Aes128,
+    memlog, memlog_flush_and_reset,
+    rng::{CtrDrbg, Error, Rng},
+};
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    if let Err(e) = run() {
+        memlog!("error: {:?}", e);
+    }
+
+    memlog_flush_and_reset!();
+}
+
+fn run() -> Result<(), Error> {
+    let rng = Rng::initialize().expect("UNREACHABLE");
+    // the key is replaced when the DRBG is instantiated
+    let cipher = Aes128::new_ram(&[0; 16].into()).expect("UNREACHABLE");
+    let mut drbg = CtrDrbg::from_cipher(cipher, rng, b"drbg example")?;
+
+    let mut buf = [0; 16];
+    drbg.try_generate(&mut buf, b"additional input")?;
+    memlog!("try_generate: {:?}", buf);
+
+    memlog!("next_u64: {}", drbg.next_u64());
+
+    drbg.reseed(&[])?;
+    memlog!("reseed: OK");
+
+    drbg.try_generate(&mut buf, &[])?;
+    memlog!("try_generate: {:?}", buf);
+
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! Multi-block eMMC reads
+//!
+//! Reads 16 consecutive (512B) memory blocks using a single command and then checks them against
+//! the same blocks read one at a time, and against the same blocks scattered into two buffers. This
+//! program doesn't write to the eMMC
+//!
+//! Expected output:
+//!
+//! ```
+//! read_blocks: 16 blocks in [..]
+//! read: 16 blocks in [..]
+//! read_vectored: 16 blocks in [..]
+//! OK
+//! ```
+
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{emmc::eMMC, memlog, memlog_flush_and_reset, storage::Block, time::Instant};
+
+const BLOCK_NR: u32 = 204800; // an offset of 100MB
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let emmc = eMMC::take().expect("eMMC").unwrap();
+
+    let mut blocks: [Block; 16] = Default::default();
+
+    let start = Instant::now();
+    emmc.read_blocks(BLOCK_NR, &mut blocks).unwrap();
+    memlog!(
+        "read_blocks: {} blocks in {:?}",
+        blocks.len(),
+        start.elapsed()
+    );
+
+    let mut block = Block::zeroed();
+    let mut ok = true;
+    let start = Instant::now();
+    for (i, expected) in blocks.iter().enumerate() {
+        emmc.read(BLOCK_NR + i as u32, &mut block).unwrap();
+        ok &= block.bytes[..] == expected.bytes[..];
+    }
+    memlog!("read: {} blocks in {:?}", blocks.len(), start.elapsed());
+
+    let mut head = Aligned([0; 4 * 512]);
+    let mut tail = Aligned([0; 12 * 512]);
+    let start = Instant::now();
+    emmc.read_vectored(BLOCK_NR, &mut [&mut head.0, &mut tail.0])
+        .unwrap();
+    memlog!(
+        "read_vectored: {} blocks in {:?}",
+        (head.0.len() + tail.0.len()) / 512,
+        start.elapsed()
+    );
+
+    let scattered = head.0.chunks(512).chain(tail.0.chunks(512));
+    for (bytes, expected) in scattered.zip(blocks.iter()) {
+        ok &= bytes[..] == expected.bytes[..];
+    }
+
+    memlog!("{}", if ok { "OK" } else { "MISMATCH" });
+
+    // then reset the board to return to the u-boot console
+    memlog_flush_and_reset!();
+}
+
+// the DMA requires 4-byte aligned buffers
+#[repr(align(4))]
+struct Aligned<T>(T);
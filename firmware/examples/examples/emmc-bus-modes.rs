@@ -0,0 +1,83 @@
+//! Reads the same blocks in every bus mode the eMMC supports and checks that
+//! the data matches
+//!
+//! Expected output:
+//!
+//! ```
+//! negotiated: BusMode { timing: [..], width: [..] }
+//! BusMode { timing: [..], width: [..] }: 32 blocks in [..] (OK)
+//! [..]
+//! BusMode { timing: Legacy, width: B1 }: 32 blocks in [..] (OK)
+//! ```
+
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    emmc::{eMMC, BusMode, BusWidth, Timing},
+    memlog, memlog_flush_and_reset,
+    storage::Block,
+    time::Instant,
+};
+
+const BLOCK_NR: u32 = 0;
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let emmc = eMMC::take().expect("eMMC").unwrap();
+
+    memlog!("negotiated: {:?}", emmc.bus_mode());
+
+    // reference data, read in the slowest mode
+    let mut expected: [Block; 32] = Default::default();
+    emmc.set_bus_mode(BusMode::LEGACY).unwrap();
+    emmc.read_blocks(BLOCK_NR, &mut expected).unwrap();
+
+    let mut blocks: [Block; 32] = Default::default();
+    for timing in &[
+        Timing::Hs200,
+        Timing::Ddr52,
+        Timing::HighSpeed,
+        Timing::Legacy,
+    ] {
+        for width in &[BusWidth::B8, BusWidth::B4, BusWidth::B1] {
+            let mode = BusMode {
+                timing: *timing,
+                width: *width,
+            };
+
+            if !emmc.supports_bus_mode(mode) {
+                continue;
+            }
+
+            if let Err(e) = emmc.set_bus_mode(mode) {
+                memlog!("{:?}: {}", mode, e);
+                continue;
+            }
+
+            let start = Instant::now();
+            emmc.read_blocks(BLOCK_NR, &mut blocks).unwrap();
+            let elapsed = start.elapsed();
+
+            let ok = blocks
+                .iter()
+                .zip(expected.iter())
+                .all(|(block, expected)| block.bytes[..] == expected.bytes[..]);
+            memlog!(
+                "{:?}: {} blocks in {:?} ({})",
+                emmc.bus_mode(),
+                blocks.len(),
+                elapsed,
+                if ok { "OK" } else { "MISMATCH" }
+            );
+        }
+    }
+
+    // then reset the board to return to the u-boot console
+    memlog_flush_and_reset!();
+}
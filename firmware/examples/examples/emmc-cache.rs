@@ -0,0 +1,70 @@
+//! Compares the time it takes to write blocks of the eMMC with and without its
+//! volatile cache, and with reliable writes
+//!
+//! Expected output:
+//!
+//! ```
+//! cache disabled: 32 blocks in [..]
+//! cache enabled: 32 blocks in [..] (+ [..] flush)
+//! reliable: 32 blocks in [..]
+//! ```
+//!
+//! **WARNING** this will overwrite the blocks that start at `BLOCK_NR`
+
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{emmc::eMMC, memlog, memlog_flush_and_reset, storage::Block, time::Instant};
+
+const BLOCK_NR: u32 = 204800;
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let emmc = eMMC::take().expect("eMMC").unwrap();
+
+    let blocks: [Block; 32] = Default::default();
+
+    if emmc.cache_enabled() {
+        emmc.set_cache_enabled(false).unwrap();
+    }
+    let start = Instant::now();
+    emmc.write_blocks(BLOCK_NR, &blocks).unwrap();
+    memlog!(
+        "cache disabled: {} blocks in {:?}",
+        blocks.len(),
+        start.elapsed()
+    );
+
+    match emmc.set_cache_enabled(true) {
+        Ok(()) => {
+            let start = Instant::now();
+            emmc.write_blocks(BLOCK_NR, &blocks).unwrap();
+            let write = start.elapsed();
+
+            let start = Instant::now();
+            emmc.flush().unwrap();
+            memlog!(
+                "cache enabled: {} blocks in {:?} (+ {:?} flush)",
+                blocks.len(),
+                write,
+                start.elapsed()
+            );
+        }
+
+        Err(e) => {
+            memlog!("cache: {}", e);
+        }
+    }
+
+    let start = Instant::now();
+    emmc.write_blocks_reliable(BLOCK_NR, &blocks).unwrap();
+    memlog!("reliable: {} blocks in {:?}", blocks.len(), start.elapsed());
+
+    // then reset the board to return to the u-boot console
+    memlog_flush_and_reset!();
+}
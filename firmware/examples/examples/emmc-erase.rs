@@ -0,0 +1,48 @@
+//! Writes a block of the eMMC, securely erases it and reads it back
+//!
+//! Expected output:
+//!
+//! ```
+//! before: [..]
+//! after: [..]
+//! ```
+//!
+//! The contents of an erased block are undefined; most eMMCs read them back as
+//! all zeros or all ones
+//!
+//! **WARNING** this will overwrite the block at `BLOCK_NR`
+
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{emmc::eMMC, memlog, memlog_flush_and_reset, storage::Block};
+
+const BLOCK_NR: u32 = 204800;
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let emmc = eMMC::take().expect("eMMC").unwrap();
+
+    let mut block = Block::zeroed();
+    for byte in block.bytes.iter_mut() {
+        *byte = 0x5a;
+    }
+    emmc.write(BLOCK_NR, &block).unwrap();
+
+    emmc.read(BLOCK_NR, &mut block).unwrap();
+    memlog!("before: {:?}", &block.bytes[..8]);
+
+    let lba = u64::from(BLOCK_NR);
+    emmc.secure_erase(lba..lba + 1).unwrap();
+
+    emmc.read(BLOCK_NR, &mut block).unwrap();
+    memlog!("after: {:?}", &block.bytes[..8]);
+
+    // then reset the board to return to the u-boot console
+    memlog_flush_and_reset!();
+}
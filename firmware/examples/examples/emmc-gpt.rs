@@ -0,0 +1,58 @@
+//! Lists the partitions of the GPT-formatted eMMC and looks up the `armory-data` partition by
+//! name
+//!
+//! Expected output (after running `emmc-new-gpt`):
+//!
+//! ```
+//! disk [..]
+//! 0: Entry { type_guid: C12A7328-F81F-11D2-BA4B-00A0C93EC93B, [..], name: "boot" }
+//! 1: Entry { type_guid: 0FC63DAF-8483-4772-8E79-3D69D8477DE4, [..], name: "armory-data" }
+//! armory-data is [..] MiB
+//! ```
+
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    emmc::eMMC,
+    memlog, memlog_flush_and_reset,
+    storage::{GptDevice, ManagedBlockDevice, BLOCK_SIZE},
+};
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let emmc = eMMC::take().expect("eMMC").unwrap();
+    let mut gpt = GptDevice::open(emmc).unwrap();
+
+    memlog!(
+        "disk {}{}",
+        gpt.disk_guid(),
+        if gpt.is_degraded() { " (degraded)" } else { "" }
+    );
+
+    for index in 0..gpt.num_entries() {
+        let entry = gpt.entry(index).unwrap();
+        if entry.is_used() {
+            memlog!("{}: {:?}", index, entry);
+        }
+    }
+
+    match gpt.partition_by_name("armory-data") {
+        Ok(part) => {
+            let bytes = part.total_blocks() * u64::from(BLOCK_SIZE);
+            memlog!("armory-data is {} MiB", bytes / 1024 / 1024);
+        }
+
+        Err(e) => {
+            memlog!("armory-data: {}", e);
+        }
+    }
+
+    // then reset the board
+    memlog_flush_and_reset!();
+}
@@ -0,0 +1,45 @@
+//! Reports the identity and the wear of the eMMC
+//!
+//! Expected output:
+//!
+//! ```
+//! Cid { manufacturer_id: [..], oem_id: [..], product_name: Some("[..]"), [..] }
+//! manufactured: [..]/[..]
+//! EXT_CSD rev: [..]
+//! life time estimate: A=[..] B=[..]
+//! pre-EOL: [..]
+//! cache size: [..] bytes
+//! ```
+
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{emmc::eMMC, memlog, memlog_flush_and_reset};
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let emmc = eMMC::take().expect("eMMC").unwrap();
+
+    let cid = emmc.cid();
+    let ext_csd = emmc.ext_csd().unwrap();
+
+    memlog!("{:?}", cid);
+    let (year, month) = cid.manufacturing_date(ext_csd.rev());
+    memlog!("manufactured: {}/{}", year, month);
+    memlog!("EXT_CSD rev: {}", ext_csd.rev());
+    memlog!(
+        "life time estimate: A={:?} B={:?}",
+        ext_csd.life_time_estimate_a(),
+        ext_csd.life_time_estimate_b()
+    );
+    memlog!("pre-EOL: {:?}", ext_csd.pre_eol_info());
+    memlog!("cache size: {} bytes", ext_csd.cache_size());
+
+    // then reset the board to return to the u-boot console
+    memlog_flush_and_reset!();
+}
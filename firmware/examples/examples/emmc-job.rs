@@ -0,0 +1,107 @@
+//! Reads 16 eMMC blocks in the background using a `Transfer`
+//!
+//! The transfer is started in `init`; its completion is handled by a hardware task bound to the
+//! uSDHC2 interrupt. The blocks are then read again, in a blocking fashion, for comparison
+//!
+//! Expected output:
+//!
+//! ```
+//! read_job: 16 blocks in [..]
+//! OK
+//! ```
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    emmc::{eMMC, Transfer},
+    memlog, memlog_flush_and_reset,
+    storage::Block,
+    time::Instant,
+};
+
+const BLOCK_NR: u32 = 0;
+
+#[rtic::app]
+const APP: () = {
+    struct Resources {
+        // `None` once the transfer has been completed
+        transfer: Option<Transfer<&'static mut [Block]>>,
+        start: Instant,
+    }
+
+    #[init]
+    fn init(_cx: init::Context) -> init::LateResources {
+        static mut BLOCKS: [Block; 16] = [
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+            Block::zeroed(),
+        ];
+
+        let emmc = eMMC::take()
+            .expect("eMMC")
+            .expect("eMMC initialization failed");
+
+        // this returns immediately; the uSDHC2 interrupt fires when the transfer is done
+        let start = Instant::now();
+        let transfer = emmc.read_job(BLOCK_NR, BLOCKS);
+
+        init::LateResources {
+            transfer: Some(transfer),
+            start,
+        }
+    }
+
+    #[task(binds = uSDHC2, resources = [transfer, start])]
+    fn on_usdhc2(cx: on_usdhc2::Context) {
+        let transfer = cx.resources.transfer;
+
+        if transfer
+            .as_ref()
+            .map(|transfer| transfer.is_done())
+            .unwrap_or(false)
+        {
+            let ((emmc, blocks), res) = transfer.take().expect("UNREACHABLE").wait();
+            let elapsed = cx.resources.start.elapsed();
+
+            if let Err(e) = res {
+                memlog!("error: {}", e);
+                memlog_flush_and_reset!()
+            }
+
+            memlog!("read_job: {} blocks in {:?}", blocks.len(), elapsed);
+
+            let mut ok = true;
+            let mut expected = Block::zeroed();
+            for (i, block) in blocks.iter().enumerate() {
+                emmc.read(BLOCK_NR + i as u32, &mut expected).unwrap();
+                ok &= block.bytes[..] == expected.bytes[..];
+            }
+
+            if ok {
+                memlog!("OK");
+            } else {
+                memlog!("error: data mismatch");
+            }
+
+            memlog_flush_and_reset!()
+        }
+    }
+};
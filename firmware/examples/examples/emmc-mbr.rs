@@ -1,4 +1,4 @@
-//! Prints partition sizes of the MBR-formatted eMMC.
+//! Prints the partitions of the MBR-formatted eMMC, logical partitions included.
 
 #![no_main]
 #![no_std]
@@ -21,10 +21,29 @@ fn main() -> ! {
 
     memlog!("{:#?}", mbr.debug());
 
-    for part_idx in 0..4 {
-        if let Ok(part) = mbr.partition(part_idx) {
+    for part_idx in 0..=255 {
+        let entry = match mbr.entry(part_idx) {
+            Ok(entry) => entry,
+            // logical partitions are numbered without gaps
+            Err(_) if part_idx >= 4 => break,
+            Err(_) => continue,
+        };
+
+        if entry.is_extended() {
+            memlog!("Partition {} is an extended partition", part_idx);
+        } else if let Ok(part) = mbr.partition(part_idx) {
             let bytes = part.total_blocks() * u64::from(BLOCK_SIZE);
-            memlog!("Partition {} is {} MiB", part_idx, bytes / 1024 / 1024);
+            memlog!(
+                "Partition {} is {} MiB (type {:#04x}{})",
+                part_idx,
+                bytes / 1024 / 1024,
+                entry.part_type(),
+                if entry.is_bootable() {
+                    ", bootable"
+                } else {
+                    ""
+                }
+            );
         }
     }
 
@@ -0,0 +1,66 @@
+//! Creates a new GPT partition table on the eMMC
+//!
+//! The table has a 32 MiB `boot` partition followed by an `armory-data` partition that takes the
+//! rest of the eMMC
+//!
+//! **WARNING** this will overwrite the partition table of the eMMC
+
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    emmc::eMMC,
+    memlog, memlog_flush_and_reset,
+    rng::Rng,
+    storage::{GptDevice, GptEntry, Guid, ManagedBlockDevice, BLOCK_SIZE},
+};
+
+#[allow(non_upper_case_globals)]
+const MiB: u64 = 1024 * 1024;
+/// Start sector = 1 MiB, the alignment `sfdisk` uses
+const START: u64 = MiB / BLOCK_SIZE as u64;
+/// Size of the boot partition in sectors
+const BOOT_SIZE: u64 = 32 * MiB / BLOCK_SIZE as u64;
+/// Blocks at the end of the eMMC taken by the backup partition table
+const BACKUP_BLOCKS: u64 = 33;
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let mut rng = Rng::initialize().expect("UNREACHABLE");
+    let mut guid = || {
+        let mut bytes = [0; 16];
+        if let Err(e) = rng.fill(&mut bytes) {
+            // don't create a table with predictable GUIDs
+            memlog!("RNG error: {:?}", e);
+            memlog_flush_and_reset!();
+        }
+        Guid::from_random_bytes(bytes)
+    };
+
+    let emmc = eMMC::take().expect("eMMC").unwrap();
+    let end = emmc.total_blocks() - BACKUP_BLOCKS;
+    let partitions = [
+        GptEntry::new(Guid::EFI_SYSTEM, guid(), START..START + BOOT_SIZE, "boot").unwrap(),
+        GptEntry::new(
+            Guid::LINUX_FILESYSTEM,
+            guid(),
+            START + BOOT_SIZE..end,
+            "armory-data",
+        )
+        .unwrap(),
+    ];
+    let gpt = GptDevice::create(emmc, guid(), &partitions).unwrap();
+
+    memlog!("created GPT on disk {}", gpt.disk_guid());
+    for partition in &partitions {
+        memlog!("{:?}", partition);
+    }
+
+    // then reset the board
+    memlog_flush_and_reset!();
+}
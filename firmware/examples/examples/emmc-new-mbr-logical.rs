@@ -0,0 +1,58 @@
+//! Creates a new MBR partition table, with logical partitions, on the eMMC
+
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    emmc::eMMC,
+    memlog, memlog_flush_and_reset,
+    storage::{part_type, MbrDevice, PartitionEntry, PartitionTable, BLOCK_SIZE},
+};
+
+#[allow(non_upper_case_globals)]
+const MiB: u32 = 1024 * 1024;
+/// Size of 1 MiB in sectors; partitions are aligned to this boundary
+const ALIGN: u32 = MiB / BLOCK_SIZE as u32;
+/// Start sector = 32 MiB; those first 32 MiB are reserved for the boot image
+const START: u32 = 32 * ALIGN;
+/// Size of each logical partition in sectors
+const SIZE: u32 = 100 * ALIGN;
+/// Number of logical partitions; more than the 4 primary partitions the MBR can hold
+const LOGICAL: u32 = 6;
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let mut table = PartitionTable::new();
+
+    // a bootable FAT32 partition
+    let mut boot = PartitionEntry::new(START, SIZE);
+    boot.set_part_type(part_type::FAT32_LBA);
+    boot.set_bootable(true);
+    table.add(boot).unwrap();
+
+    // the EBR of each logical partition goes in the 1 MiB before it
+    let extended = START + SIZE;
+    table
+        .add(PartitionEntry::extended(extended, LOGICAL * (ALIGN + SIZE)))
+        .unwrap();
+    for i in 0..LOGICAL {
+        let start = extended + i * (ALIGN + SIZE) + ALIGN;
+        table.add_logical(PartitionEntry::new(start, SIZE)).unwrap();
+    }
+
+    let emmc = eMMC::take().expect("eMMC").unwrap();
+    let mbr = MbrDevice::create(emmc, &table).unwrap();
+
+    memlog!("{:#?}", mbr.debug());
+    for part_idx in 4..4 + LOGICAL as u8 {
+        memlog!("{:?}", mbr.entry(part_idx));
+    }
+
+    // then reset the board
+    memlog_flush_and_reset!();
+}
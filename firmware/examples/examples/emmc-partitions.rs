@@ -0,0 +1,60 @@
+//! Lists the hardware partitions of the eMMC and the boot configuration
+//!
+//! Expected output:
+//!
+//! ```
+//! User: [..] blocks
+//! Boot0: [..] blocks
+//! Boot1: [..] blocks
+//! boot partition: [..] (ack=[..])
+//! first byte of Boot0: [..]
+//! ```
+//!
+//! The general purpose partitions are listed only if they have been configured
+
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    emmc::{eMMC, Partition},
+    memlog, memlog_flush_and_reset,
+    storage::{Block, ManagedBlockDevice},
+};
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let emmc = eMMC::take().expect("eMMC").unwrap();
+
+    for partition in &[
+        Partition::User,
+        Partition::Boot0,
+        Partition::Boot1,
+        Partition::Gp0,
+        Partition::Gp1,
+        Partition::Gp2,
+        Partition::Gp3,
+    ] {
+        if let Some(part) = emmc.partition(*partition) {
+            memlog!("{:?}: {} blocks", partition, part.total_blocks());
+        }
+    }
+
+    memlog!(
+        "boot partition: {:?} (ack={})",
+        emmc.boot_partition(),
+        emmc.boot_ack()
+    );
+
+    let boot0 = emmc.partition(Partition::Boot0).expect("no boot partition");
+    let mut block = Block::zeroed();
+    boot0.read(&mut block, 0).unwrap();
+    memlog!("first byte of Boot0: {}", block.bytes[0]);
+
+    // then reset the board to return to the u-boot console
+    memlog_flush_and_reset!();
+}
@@ -0,0 +1,72 @@
+//! Tamper-evident storage using the RPMB partition of the eMMC
+//!
+//! Reads the write counter and the first 256 bytes of the RPMB partition, increases the value of
+//! the first byte and writes the data back. The write counter increases on every run.
+//!
+//! Expected output:
+//!
+//! ```
+//! RPMB size: [..] units
+//! write counter: [..]
+//! first byte: [..]
+//! new write counter: [..]
+//! ```
+//!
+//! **WARNING** the authentication key can only be programmed once in the lifetime of the eMMC. Set
+//! `PROGRAM_KEY` to `true` only if the key has not been programmed yet and replace `KEY` with a
+//! secret value that you'll keep
+
+#![deny(unused_must_use)]
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    dcp::Sha256,
+    emmc::{eMMC, RpmbError},
+    memlog, memlog_flush_and_reset,
+    rng::Rng,
+};
+
+const KEY: [u8; 32] = [0; 32];
+const PROGRAM_KEY: bool = false;
+
+const ADDRESS: u16 = 0;
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    if let Err(e) = run() {
+        memlog!("error: {}", e);
+    }
+
+    memlog_flush_and_reset!();
+}
+
+fn run() -> Result<(), RpmbError> {
+    let emmc = eMMC::take().expect("eMMC").unwrap();
+    let mut rng = Rng::initialize().expect("UNREACHABLE");
+    let hasher = Sha256::take().expect("UNREACHABLE");
+
+    let mut rpmb = emmc.rpmb(hasher, &KEY)?;
+    memlog!("RPMB size: {} units", rpmb.size());
+
+    if PROGRAM_KEY {
+        rpmb.program_key()?;
+    }
+
+    memlog!("write counter: {}", rpmb.write_counter(&mut rng)?);
+
+    let mut data = [0; 256];
+    rpmb.read(ADDRESS, &mut data, &mut rng)?;
+    memlog!("first byte: {}", data[0]);
+
+    data[0] = data[0].wrapping_add(1);
+    let counter = rpmb.write(ADDRESS, &data, &mut rng)?;
+    memlog!("new write counter: {}", counter);
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+//! Temporarily write protects a block of the eMMC, tries to write it and then
+//! clears the protection
+//!
+//! Expected output:
+//!
+//! ```
+//! group of [..] blocks: Temporary
+//! write: [..]
+//! group of [..] blocks: None
+//! write: OK
+//! ```
+//!
+//! **WARNING** this will overwrite the block at `BLOCK_NR`
+
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    emmc::{eMMC, WriteProtection},
+    memlog, memlog_flush_and_reset,
+    storage::Block,
+};
+
+const BLOCK_NR: u32 = 204800;
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let emmc = eMMC::take().expect("eMMC").unwrap();
+
+    let group_blocks = emmc.write_protect_group_blocks();
+    let lba = u64::from(BLOCK_NR);
+    let block = Block::zeroed();
+
+    emmc.set_write_protection(lba..lba + 1, WriteProtection::Temporary)
+        .unwrap();
+    memlog!(
+        "group of {} blocks: {:?}",
+        group_blocks,
+        emmc.write_protection(BLOCK_NR).unwrap()
+    );
+    // the eMMC rejects the write
+    match emmc.write(BLOCK_NR, &block) {
+        Ok(()) => {
+            memlog!("write: OK");
+        }
+        Err(e) => {
+            memlog!("write: {}", e);
+        }
+    }
+
+    emmc.clear_write_protection(lba..lba + 1).unwrap();
+    memlog!(
+        "group of {} blocks: {:?}",
+        group_blocks,
+        emmc.write_protection(BLOCK_NR).unwrap()
+    );
+    match emmc.write(BLOCK_NR, &block) {
+        Ok(()) => {
+            memlog!("write: OK");
+        }
+        Err(e) => {
+            memlog!("write: {}", e);
+        }
+    }
+
+    // then reset the board to return to the u-boot console
+    memlog_flush_and_reset!();
+}
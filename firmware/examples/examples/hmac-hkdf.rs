@@ -0,0 +1,83 @@
+//! Sanity check the HMAC-SHA256 and HKDF APIs
+//!
+//! The test vectors come from RFC 4231 (test case 2) and RFC 5869 (test case 1)
+//!
+//! Expected output:
+//!
+//! ```
+//! hmac: OK
+//! hmac (reused): OK
+//! hkdf: OK
+//! ```
+
+#![deny(unused_must_use)]
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    dcp::{Error, HmacSha256, Sha256},
+    kdf::Hkdf,
+    memlog, memlog_flush_and_reset,
+};
+
+const HMAC_EXPECTED: [u8; 32] = [
+    91, 220, 193, 70, 191, 96, 117, 78, 106, 4, 36, 38, 8, 149, 117, 199, 90, 0, 63, 8, 157, 39,
+    57, 131, 157, 236, 88, 185, 100, 236, 56, 67,
+];
+
+const HKDF_EXPECTED: [u8; 42] = [
+    60, 178, 95, 37, 250, 172, 213, 122, 144, 67, 79, 100, 208, 54, 47, 42, 45, 45, 10, 144, 207,
+    26, 90, 76, 93, 176, 45, 86, 236, 196, 197, 191, 52, 0, 114, 8, 213, 184, 135, 24, 88, 101,
+];
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    if let Err(e) = run() {
+        memlog!("error: {:?}", e);
+    }
+
+    memlog_flush_and_reset!();
+}
+
+fn run() -> Result<(), Error> {
+    let hasher = Sha256::take().expect("UNREACHABLE");
+    let mut hmac = HmacSha256::from_hasher(hasher, b"Jefe")?;
+
+    hmac.input(b"what do ya want for nothing?")?;
+    check("hmac", hmac.finalize_reset()? == HMAC_EXPECTED);
+
+    // the same key can be used to authenticate several messages
+    hmac.input(b"what do ya want ")?;
+    hmac.input(b"for nothing?")?;
+    check("hmac (reused)", hmac.verify_reset(&HMAC_EXPECTED)?);
+
+    // release the SHA-256 channel
+    drop(hmac);
+
+    let hasher = Sha256::take().expect("UNREACHABLE");
+    let salt = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+    let mut hkdf = Hkdf::extract(hasher, Some(&salt), &[0x0b; 22])?;
+
+    let mut okm = [0; 42];
+    hkdf.expand(
+        &[240, 241, 242, 243, 244, 245, 246, 247, 248, 249],
+        &mut okm,
+    )?;
+    check("hkdf", okm[..] == HKDF_EXPECTED[..]);
+
+    Ok(())
+}
+
+fn check(what: &str, ok: bool) {
+    if ok {
+        memlog!("{}: OK", what);
+    } else {
+        memlog!("{}: output didn't match the expected value", what);
+        memlog_flush_and_reset!();
+    }
+}
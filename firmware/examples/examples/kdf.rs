@@ -0,0 +1,65 @@
+//! Derives keys from the unreadable UNIQUE key
+//!
+//! The derived keys depend on the OTP key and a device unique value so their values will be
+//! different on each device
+//!
+//! Expected output:
+//!
+//! ```
+//! storage:     [..]
+//! attestation: [..]
+//! deterministic: OK
+//! labels: OK
+//! ```
+
+#![deny(unused_must_use)]
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{kdf::Kdf, memlog, memlog_flush_and_reset};
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let kdf = Kdf::new_unique().expect("AES engine already in use");
+
+    let mut storage = [0; 16];
+    let mut attestation = [0; 16];
+    let mut again = [0; 16];
+
+    for (label, key) in [
+        (&b"storage"[..], &mut storage),
+        (b"attestation", &mut attestation),
+        (b"storage", &mut again),
+    ]
+    .iter_mut()
+    {
+        if let Err(e) = kdf.derive(label, b"example", &mut key[..]) {
+            memlog!("error: {:?}", e);
+            memlog_flush_and_reset!();
+        }
+    }
+
+    memlog!("storage:     {:?}", storage);
+    memlog!("attestation: {:?}", attestation);
+
+    if storage == again {
+        memlog!("deterministic: OK");
+    } else {
+        memlog!("the same label produced different keys");
+        memlog_flush_and_reset!();
+    }
+
+    if storage != attestation {
+        memlog!("labels: OK");
+    } else {
+        memlog!("different labels produced the same key");
+        memlog_flush_and_reset!();
+    }
+
+    memlog_flush_and_reset!();
+}
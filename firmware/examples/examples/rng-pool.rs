@@ -0,0 +1,77 @@
+//! Takes random data from the interrupt-driven entropy pool
+//!
+//! The `RNGB` interrupt handler fills the pool; `idle` never waits on the hardware
+//!
+//! Expected output:
+//!
+//! ```
+//! key 0: [..]
+//! key 1: [..]
+//! key 2: [..]
+//! key 3: [..]
+//! pool was empty [..] times
+//! ```
+//!
+//! Where `[..]` are random numbers
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    memlog, memlog_flush_and_reset,
+    rng::{Error, Harvester, Pool, Rng},
+};
+
+#[rtic::app]
+const APP: () = {
+    struct Resources {
+        harvester: Harvester,
+        pool: Pool,
+    }
+
+    #[init]
+    fn init(_cx: init::Context) -> init::LateResources {
+        let rng = Rng::initialize().expect("UNREACHABLE");
+        let (harvester, pool) = rng.into_interrupt_mode();
+
+        init::LateResources { harvester, pool }
+    }
+
+    #[idle(resources = [pool])]
+    fn idle(cx: idle::Context) -> ! {
+        let pool = cx.resources.pool;
+
+        let mut empty = 0;
+        let mut key = [0; 32];
+        for i in 0..4 {
+            loop {
+                match pool.try_fill(&mut key) {
+                    Ok(()) => break,
+                    // .. do other work here ..
+                    Err(Error::WouldBlock) => empty += 1,
+                    Err(e) => {
+                        memlog!("error: {:?}", e);
+                        memlog_flush_and_reset!()
+                    }
+                }
+            }
+
+            memlog!("key {}: {:?}", i, key);
+        }
+
+        memlog!("pool was empty {} times", empty);
+
+        memlog_flush_and_reset!()
+    }
+
+    #[task(binds = RNGB, resources = [harvester])]
+    fn on_rngb(cx: on_rngb::Context) {
+        if let Err(e) = cx.resources.harvester.on_interrupt() {
+            memlog!("RNG failure: {:?}", e);
+        }
+    }
+};
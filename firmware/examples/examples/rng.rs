@@ -4,8 +4,9 @@
 //!
 //! ```
 //! first seed generated in 3.932952ms
-//! Rng::next_u32 -> 3395530786
-//! Rng::write -> [2290995670, 2824611633, 1576451376, 2540853980]
+//! Rng::self_test -> Ok(())
+//! Rng::try_next_u32 -> Ok(3395530786)
+//! Rng::write -> Ok([2290995670, 2824611633, 1576451376, 2540853980])
 //! Stats {
 //!     monobit_test_failed: false,
 //!     length_1_run_test_failed: false,
@@ -38,14 +39,16 @@ use usbarmory::{
 #[no_mangle]
 fn main() -> ! {
     let start = Instant::now();
-    let rng = Rng::initialize().expect("UNREACHABLE");
+    let mut rng = Rng::initialize().expect("UNREACHABLE");
 
     rng.wait_for_initial_seed();
     let elapsed = start.elapsed();
 
     println!("first seed generated in {:?}", elapsed);
 
-    println!("Rng::next_u32 -> {}", rng.next_u32());
+    println!("Rng::self_test -> {:?}", rng.self_test());
+
+    println!("Rng::try_next_u32 -> {:?}", rng.try_next_u32());
 
     let mut buf = [0; rng::FIFO_SIZE];
     println!("Rng::write -> {:?}", rng.write(&mut buf));
@@ -0,0 +1,40 @@
+//! Basic persistent storage using the microSD card
+//!
+//! Reads one (512B) memory block, increases the value of the first byte of the sector and then
+//! writes the updated sector back into the card. The byte stored in non-volatile memory keeps a
+//! count of how many times this program has run.
+//!
+//! **WARNING** this may corrupt data on the microSD card; make a back up or double check that this
+//! won't overwrite existing data
+
+#![no_main]
+#![no_std]
+
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{memlog, memlog_flush_and_reset, sdcard::SdCard, storage::Block};
+
+const BLOCK_NR: u32 = 204800; // an offset of 100MB
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let sd = SdCard::take().expect("SdCard").unwrap();
+
+    memlog!("card capacity: {} blocks", sd.blocks());
+
+    let mut block = Block::zeroed();
+    sd.read_blocks(BLOCK_NR, core::slice::from_mut(&mut block))
+        .unwrap();
+
+    memlog!("first byte of block {:x}: {}", BLOCK_NR, block.bytes[0]);
+    block.bytes[0] = block.bytes[0].wrapping_add(1);
+
+    sd.write_blocks(BLOCK_NR, core::slice::from_ref(&block))
+        .unwrap();
+
+    // then reset the board to return to the u-boot console
+    memlog_flush_and_reset!();
+}
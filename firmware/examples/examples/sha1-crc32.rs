@@ -0,0 +1,90 @@
+//! Sanity check the `Sha1` and `Crc32` APIs
+//!
+//! The expected values come from running the following Python code on a x86_64 machine
+//!
+//! ``` python
+//! import hashlib
+//! hashlib.sha1(b'abc').digest()
+//!
+//! # CRC-32/MPEG-2
+//! def crc32(data):
+//!     crc = 0xffffffff
+//!     for byte in data:
+//!         crc ^= byte << 24
+//!         for _ in range(8):
+//!             crc = (crc << 1) ^ 0x04c11db7 if crc & 0x80000000 else crc << 1
+//!             crc &= 0xffffffff
+//!     return crc
+//!
+//! crc32(b'123456789')
+//! crc32(bytes(100))
+//! ```
+//!
+//! Expected output:
+//!
+//! ```
+//! SHA-1:    [169, 153, 62, 54, 71, 6, 129, 106, 186, 62, 37, 113, 120, 80, 194, 108, 156, 208, 216, 157]
+//! expected: [169, 153, 62, 54, 71, 6, 129, 106, 186, 62, 37, 113, 120, 80, 194, 108, 156, 208, 216, 157]
+//! CRC32:    0x0376e6e7
+//! expected: 0x0376e6e7
+//! CRC32:    0xac9cee66
+//! expected: 0xac9cee66
+//! OK
+//! ```
+
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use digest::{FixedOutput, Input};
+use exception_reset as _; // default exception handler
+use panic_serial as _; // panic handler
+use usbarmory::{
+    dcp::{Crc32, Sha1},
+    memlog, memlog_flush_and_reset,
+};
+
+static CRC32_TESTS: &[(/* input: */ &[u8], /* expected: */ u32)] =
+    &[(b"123456789", 0x0376_e6e7), (&[0; 100], 0xac9c_ee66)];
+
+// NOTE binary interfaces, using `no_mangle` and `extern`, are extremely unsafe
+// as no type checking is performed by the compiler; stick to safe interfaces
+// like `#[rtic::app]`
+#[no_mangle]
+fn main() -> ! {
+    let expected = [
+        169, 153, 62, 54, 71, 6, 129, 106, 186, 62, 37, 113, 120, 80, 194, 108, 156, 208, 216, 157,
+    ];
+
+    let mut hasher = Sha1::take().expect("taken");
+    hasher.input(b"abc");
+    let output = hasher.fixed_result();
+
+    memlog!("SHA-1:    {:?}", output);
+    memlog!("expected: {:?}", expected);
+
+    if output[..] != expected[..] {
+        memlog!("error: incorrect SHA-1 digest");
+        memlog_flush_and_reset!()
+    }
+
+    for (input, expected) in CRC32_TESTS {
+        // feed the input in two chunks to exercise the buffering logic
+        let (head, tail) = input.split_at(input.len() / 2);
+        let mut crc = Crc32::take().expect("taken");
+        crc.input(head);
+        crc.input(tail);
+        let output = crc.finalize();
+
+        memlog!("CRC32:    {:#010x}", output);
+        memlog!("expected: {:#010x}", expected);
+
+        if output != *expected {
+            memlog!("error: incorrect CRC32");
+            memlog_flush_and_reset!()
+        }
+    }
+
+    memlog!("OK");
+    memlog_flush_and_reset!()
+}
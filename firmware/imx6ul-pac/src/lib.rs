@@ -6787,8 +6787,16 @@ pub mod hw_dcp {
         _not_sync: PhantomData<*mut ()>,
         #[doc = "DCP Control Register 0"]
         pub CTRL: CTRL,
+        #[doc = "DCP Control Register 0"]
+        pub CTRL_SET: CTRL_SET,
+        #[doc = "DCP Control Register 0"]
+        pub CTRL_CLR: CTRL_CLR,
         #[doc = "DCP Status Register"]
         pub STAT: STAT,
+        #[doc = "DCP Status Register"]
+        pub STAT_SET: STAT_SET,
+        #[doc = "DCP Status Register"]
+        pub STAT_CLR: STAT_CLR,
         #[doc = "DCP Channel Control Register"]
         pub CHANNELCTRL: CHANNELCTRL,
         #[doc = "DCP Channel Control Register"]
@@ -6825,6 +6833,10 @@ pub mod hw_dcp {
         pub CH0SEMA: CH0SEMA,
         #[doc = "DCP Channel 0 Status Register"]
         pub CH0STAT: CH0STAT,
+        #[doc = "DCP Channel 0 Status Register"]
+        pub CH0STAT_SET: CH0STAT_SET,
+        #[doc = "DCP Channel 0 Status Register"]
+        pub CH0STAT_CLR: CH0STAT_CLR,
         #[doc = "DCP Channel 0 Options Register"]
         pub CH0OPTS: CH0OPTS,
         #[doc = "DCP Channel 1 Command Pointer Address Register"]
@@ -6833,6 +6845,10 @@ pub mod hw_dcp {
         pub CH1SEMA: CH1SEMA,
         #[doc = "DCP Channel 1 Status Register"]
         pub CH1STAT: CH1STAT,
+        #[doc = "DCP Channel 1 Status Register"]
+        pub CH1STAT_SET: CH1STAT_SET,
+        #[doc = "DCP Channel 1 Status Register"]
+        pub CH1STAT_CLR: CH1STAT_CLR,
         #[doc = "DCP Channel 1 Options Register"]
         pub CH1OPTS: CH1OPTS,
         #[doc = "DCP Channel 2 Command Pointer Address Register"]
@@ -6841,6 +6857,10 @@ pub mod hw_dcp {
         pub CH2SEMA: CH2SEMA,
         #[doc = "DCP Channel 2 Status Register"]
         pub CH2STAT: CH2STAT,
+        #[doc = "DCP Channel 2 Status Register"]
+        pub CH2STAT_SET: CH2STAT_SET,
+        #[doc = "DCP Channel 2 Status Register"]
+        pub CH2STAT_CLR: CH2STAT_CLR,
         #[doc = "DCP Channel 2 Options Register"]
         pub CH2OPTS: CH2OPTS,
         #[doc = "DCP Channel 3 Command Pointer Address Register"]
@@ -6849,6 +6869,10 @@ pub mod hw_dcp {
         pub CH3SEMA: CH3SEMA,
         #[doc = "DCP Channel 3 Status Register"]
         pub CH3STAT: CH3STAT,
+        #[doc = "DCP Channel 3 Status Register"]
+        pub CH3STAT_SET: CH3STAT_SET,
+        #[doc = "DCP Channel 3 Status Register"]
+        pub CH3STAT_CLR: CH3STAT_CLR,
         #[doc = "DCP Channel 3 Options Register"]
         pub CH3OPTS: CH3OPTS,
         #[doc = "DCP Debug Select Register"]
@@ -6898,6 +6922,58 @@ pub mod hw_dcp {
             unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
         }
     }
+    #[doc = "DCP Control Register 0"]
+    #[allow(non_camel_case_types)]
+    pub struct CTRL_SET {
+        _not_send_or_sync: PhantomData<*mut ()>,
+    }
+    impl CTRL_SET {
+        const OFFSET: usize = 0x04;
+        #[doc = r" Reset value"]
+        pub const RESET_VALUE: u32 = 0x0000_0000;
+        #[doc = r" Performs a single store operation on the memory-mapped register"]
+        #[allow(unused_unsafe)]
+        pub fn write(&self, bits: u32) {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32).write_volatile(bits) }
+        }
+        #[doc = r" Writes the reset value"]
+        #[allow(unused_unsafe)]
+        pub fn reset(&self) {
+            self.write(Self::RESET_VALUE)
+        }
+        #[doc = r" Returns the address of this register"]
+        #[allow(unused_parens)]
+        #[allow(unused_unsafe)]
+        pub fn address() -> *mut u32 {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
+        }
+    }
+    #[doc = "DCP Control Register 0"]
+    #[allow(non_camel_case_types)]
+    pub struct CTRL_CLR {
+        _not_send_or_sync: PhantomData<*mut ()>,
+    }
+    impl CTRL_CLR {
+        const OFFSET: usize = 0x08;
+        #[doc = r" Reset value"]
+        pub const RESET_VALUE: u32 = 0x0000_0000;
+        #[doc = r" Performs a single store operation on the memory-mapped register"]
+        #[allow(unused_unsafe)]
+        pub fn write(&self, bits: u32) {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32).write_volatile(bits) }
+        }
+        #[doc = r" Writes the reset value"]
+        #[allow(unused_unsafe)]
+        pub fn reset(&self) {
+            self.write(Self::RESET_VALUE)
+        }
+        #[doc = r" Returns the address of this register"]
+        #[allow(unused_parens)]
+        #[allow(unused_unsafe)]
+        pub fn address() -> *mut u32 {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
+        }
+    }
     #[doc = "DCP Status Register"]
     #[allow(non_camel_case_types)]
     pub struct STAT {
@@ -6935,6 +7011,58 @@ pub mod hw_dcp {
             unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
         }
     }
+    #[doc = "DCP Status Register"]
+    #[allow(non_camel_case_types)]
+    pub struct STAT_SET {
+        _not_send_or_sync: PhantomData<*mut ()>,
+    }
+    impl STAT_SET {
+        const OFFSET: usize = 0x14;
+        #[doc = r" Reset value"]
+        pub const RESET_VALUE: u32 = 0x0000_0000;
+        #[doc = r" Performs a single store operation on the memory-mapped register"]
+        #[allow(unused_unsafe)]
+        pub fn write(&self, bits: u32) {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32).write_volatile(bits) }
+        }
+        #[doc = r" Writes the reset value"]
+        #[allow(unused_unsafe)]
+        pub fn reset(&self) {
+            self.write(Self::RESET_VALUE)
+        }
+        #[doc = r" Returns the address of this register"]
+        #[allow(unused_parens)]
+        #[allow(unused_unsafe)]
+        pub fn address() -> *mut u32 {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
+        }
+    }
+    #[doc = "DCP Status Register"]
+    #[allow(non_camel_case_types)]
+    pub struct STAT_CLR {
+        _not_send_or_sync: PhantomData<*mut ()>,
+    }
+    impl STAT_CLR {
+        const OFFSET: usize = 0x18;
+        #[doc = r" Reset value"]
+        pub const RESET_VALUE: u32 = 0x0000_0000;
+        #[doc = r" Performs a single store operation on the memory-mapped register"]
+        #[allow(unused_unsafe)]
+        pub fn write(&self, bits: u32) {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32).write_volatile(bits) }
+        }
+        #[doc = r" Writes the reset value"]
+        #[allow(unused_unsafe)]
+        pub fn reset(&self) {
+            self.write(Self::RESET_VALUE)
+        }
+        #[doc = r" Returns the address of this register"]
+        #[allow(unused_parens)]
+        #[allow(unused_unsafe)]
+        pub fn address() -> *mut u32 {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
+        }
+    }
     #[doc = "DCP Channel Control Register"]
     #[allow(non_camel_case_types)]
     pub struct CHANNELCTRL {
@@ -7579,6 +7707,58 @@ pub mod hw_dcp {
             unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
         }
     }
+    #[doc = "DCP Channel 0 Status Register"]
+    #[allow(non_camel_case_types)]
+    pub struct CH0STAT_SET {
+        _not_send_or_sync: PhantomData<*mut ()>,
+    }
+    impl CH0STAT_SET {
+        const OFFSET: usize = 0x0124;
+        #[doc = r" Reset value"]
+        pub const RESET_VALUE: u32 = 0x0000_0000;
+        #[doc = r" Performs a single store operation on the memory-mapped register"]
+        #[allow(unused_unsafe)]
+        pub fn write(&self, bits: u32) {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32).write_volatile(bits) }
+        }
+        #[doc = r" Writes the reset value"]
+        #[allow(unused_unsafe)]
+        pub fn reset(&self) {
+            self.write(Self::RESET_VALUE)
+        }
+        #[doc = r" Returns the address of this register"]
+        #[allow(unused_parens)]
+        #[allow(unused_unsafe)]
+        pub fn address() -> *mut u32 {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
+        }
+    }
+    #[doc = "DCP Channel 0 Status Register"]
+    #[allow(non_camel_case_types)]
+    pub struct CH0STAT_CLR {
+        _not_send_or_sync: PhantomData<*mut ()>,
+    }
+    impl CH0STAT_CLR {
+        const OFFSET: usize = 0x0128;
+        #[doc = r" Reset value"]
+        pub const RESET_VALUE: u32 = 0x0000_0000;
+        #[doc = r" Performs a single store operation on the memory-mapped register"]
+        #[allow(unused_unsafe)]
+        pub fn write(&self, bits: u32) {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32).write_volatile(bits) }
+        }
+        #[doc = r" Writes the reset value"]
+        #[allow(unused_unsafe)]
+        pub fn reset(&self) {
+            self.write(Self::RESET_VALUE)
+        }
+        #[doc = r" Returns the address of this register"]
+        #[allow(unused_parens)]
+        #[allow(unused_unsafe)]
+        pub fn address() -> *mut u32 {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
+        }
+    }
     #[doc = "DCP Channel 0 Options Register"]
     #[allow(non_camel_case_types)]
     pub struct CH0OPTS {
@@ -7727,6 +7907,58 @@ pub mod hw_dcp {
             unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
         }
     }
+    #[doc = "DCP Channel 1 Status Register"]
+    #[allow(non_camel_case_types)]
+    pub struct CH1STAT_SET {
+        _not_send_or_sync: PhantomData<*mut ()>,
+    }
+    impl CH1STAT_SET {
+        const OFFSET: usize = 0x0164;
+        #[doc = r" Reset value"]
+        pub const RESET_VALUE: u32 = 0x0000_0000;
+        #[doc = r" Performs a single store operation on the memory-mapped register"]
+        #[allow(unused_unsafe)]
+        pub fn write(&self, bits: u32) {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32).write_volatile(bits) }
+        }
+        #[doc = r" Writes the reset value"]
+        #[allow(unused_unsafe)]
+        pub fn reset(&self) {
+            self.write(Self::RESET_VALUE)
+        }
+        #[doc = r" Returns the address of this register"]
+        #[allow(unused_parens)]
+        #[allow(unused_unsafe)]
+        pub fn address() -> *mut u32 {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
+        }
+    }
+    #[doc = "DCP Channel 1 Status Register"]
+    #[allow(non_camel_case_types)]
+    pub struct CH1STAT_CLR {
+        _not_send_or_sync: PhantomData<*mut ()>,
+    }
+    impl CH1STAT_CLR {
+        const OFFSET: usize = 0x0168;
+        #[doc = r" Reset value"]
+        pub const RESET_VALUE: u32 = 0x0000_0000;
+        #[doc = r" Performs a single store operation on the memory-mapped register"]
+        #[allow(unused_unsafe)]
+        pub fn write(&self, bits: u32) {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32).write_volatile(bits) }
+        }
+        #[doc = r" Writes the reset value"]
+        #[allow(unused_unsafe)]
+        pub fn reset(&self) {
+            self.write(Self::RESET_VALUE)
+        }
+        #[doc = r" Returns the address of this register"]
+        #[allow(unused_parens)]
+        #[allow(unused_unsafe)]
+        pub fn address() -> *mut u32 {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
+        }
+    }
     #[doc = "DCP Channel 1 Options Register"]
     #[allow(non_camel_case_types)]
     pub struct CH1OPTS {
@@ -7875,6 +8107,58 @@ pub mod hw_dcp {
             unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
         }
     }
+    #[doc = "DCP Channel 2 Status Register"]
+    #[allow(non_camel_case_types)]
+    pub struct CH2STAT_SET {
+        _not_send_or_sync: PhantomData<*mut ()>,
+    }
+    impl CH2STAT_SET {
+        const OFFSET: usize = 0x01a4;
+        #[doc = r" Reset value"]
+        pub const RESET_VALUE: u32 = 0x0000_0000;
+        #[doc = r" Performs a single store operation on the memory-mapped register"]
+        #[allow(unused_unsafe)]
+        pub fn write(&self, bits: u32) {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32).write_volatile(bits) }
+        }
+        #[doc = r" Writes the reset value"]
+        #[allow(unused_unsafe)]
+        pub fn reset(&self) {
+            self.write(Self::RESET_VALUE)
+        }
+        #[doc = r" Returns the address of this register"]
+        #[allow(unused_parens)]
+        #[allow(unused_unsafe)]
+        pub fn address() -> *mut u32 {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
+        }
+    }
+    #[doc = "DCP Channel 2 Status Register"]
+    #[allow(non_camel_case_types)]
+    pub struct CH2STAT_CLR {
+        _not_send_or_sync: PhantomData<*mut ()>,
+    }
+    impl CH2STAT_CLR {
+        const OFFSET: usize = 0x01a8;
+        #[doc = r" Reset value"]
+        pub const RESET_VALUE: u32 = 0x0000_0000;
+        #[doc = r" Performs a single store operation on the memory-mapped register"]
+        #[allow(unused_unsafe)]
+        pub fn write(&self, bits: u32) {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32).write_volatile(bits) }
+        }
+        #[doc = r" Writes the reset value"]
+        #[allow(unused_unsafe)]
+        pub fn reset(&self) {
+            self.write(Self::RESET_VALUE)
+        }
+        #[doc = r" Returns the address of this register"]
+        #[allow(unused_parens)]
+        #[allow(unused_unsafe)]
+        pub fn address() -> *mut u32 {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
+        }
+    }
     #[doc = "DCP Channel 2 Options Register"]
     #[allow(non_camel_case_types)]
     pub struct CH2OPTS {
@@ -8023,6 +8307,58 @@ pub mod hw_dcp {
             unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
         }
     }
+    #[doc = "DCP Channel 3 Status Register"]
+    #[allow(non_camel_case_types)]
+    pub struct CH3STAT_SET {
+        _not_send_or_sync: PhantomData<*mut ()>,
+    }
+    impl CH3STAT_SET {
+        const OFFSET: usize = 0x01e4;
+        #[doc = r" Reset value"]
+        pub const RESET_VALUE: u32 = 0x0000_0000;
+        #[doc = r" Performs a single store operation on the memory-mapped register"]
+        #[allow(unused_unsafe)]
+        pub fn write(&self, bits: u32) {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32).write_volatile(bits) }
+        }
+        #[doc = r" Writes the reset value"]
+        #[allow(unused_unsafe)]
+        pub fn reset(&self) {
+            self.write(Self::RESET_VALUE)
+        }
+        #[doc = r" Returns the address of this register"]
+        #[allow(unused_parens)]
+        #[allow(unused_unsafe)]
+        pub fn address() -> *mut u32 {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
+        }
+    }
+    #[doc = "DCP Channel 3 Status Register"]
+    #[allow(non_camel_case_types)]
+    pub struct CH3STAT_CLR {
+        _not_send_or_sync: PhantomData<*mut ()>,
+    }
+    impl CH3STAT_CLR {
+        const OFFSET: usize = 0x01e8;
+        #[doc = r" Reset value"]
+        pub const RESET_VALUE: u32 = 0x0000_0000;
+        #[doc = r" Performs a single store operation on the memory-mapped register"]
+        #[allow(unused_unsafe)]
+        pub fn write(&self, bits: u32) {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32).write_volatile(bits) }
+        }
+        #[doc = r" Writes the reset value"]
+        #[allow(unused_unsafe)]
+        pub fn reset(&self) {
+            self.write(Self::RESET_VALUE)
+        }
+        #[doc = r" Returns the address of this register"]
+        #[allow(unused_parens)]
+        #[allow(unused_unsafe)]
+        pub fn address() -> *mut u32 {
+            unsafe { ((BASE_ADDRESS + Self::OFFSET) as *mut u32) }
+        }
+    }
     #[doc = "DCP Channel 3 Options Register"]
     #[allow(non_camel_case_types)]
     pub struct CH3OPTS {
@@ -8219,9 +8555,21 @@ pub mod hw_dcp {
                 CTRL: CTRL {
                     _not_send_or_sync: PhantomData,
                 },
+                CTRL_SET: CTRL_SET {
+                    _not_send_or_sync: PhantomData,
+                },
+                CTRL_CLR: CTRL_CLR {
+                    _not_send_or_sync: PhantomData,
+                },
                 STAT: STAT {
                     _not_send_or_sync: PhantomData,
                 },
+                STAT_SET: STAT_SET {
+                    _not_send_or_sync: PhantomData,
+                },
+                STAT_CLR: STAT_CLR {
+                    _not_send_or_sync: PhantomData,
+                },
                 CHANNELCTRL: CHANNELCTRL {
                     _not_send_or_sync: PhantomData,
                 },
@@ -8276,6 +8624,12 @@ pub mod hw_dcp {
                 CH0STAT: CH0STAT {
                     _not_send_or_sync: PhantomData,
                 },
+                CH0STAT_SET: CH0STAT_SET {
+                    _not_send_or_sync: PhantomData,
+                },
+                CH0STAT_CLR: CH0STAT_CLR {
+                    _not_send_or_sync: PhantomData,
+                },
                 CH0OPTS: CH0OPTS {
                     _not_send_or_sync: PhantomData,
                 },
@@ -8288,6 +8642,12 @@ pub mod hw_dcp {
                 CH1STAT: CH1STAT {
                     _not_send_or_sync: PhantomData,
                 },
+                CH1STAT_SET: CH1STAT_SET {
+                    _not_send_or_sync: PhantomData,
+                },
+                CH1STAT_CLR: CH1STAT_CLR {
+                    _not_send_or_sync: PhantomData,
+                },
                 CH1OPTS: CH1OPTS {
                     _not_send_or_sync: PhantomData,
                 },
@@ -8300,6 +8660,12 @@ pub mod hw_dcp {
                 CH2STAT: CH2STAT {
                     _not_send_or_sync: PhantomData,
                 },
+                CH2STAT_SET: CH2STAT_SET {
+                    _not_send_or_sync: PhantomData,
+                },
+                CH2STAT_CLR: CH2STAT_CLR {
+                    _not_send_or_sync: PhantomData,
+                },
                 CH2OPTS: CH2OPTS {
                     _not_send_or_sync: PhantomData,
                 },
@@ -8312,6 +8678,12 @@ pub mod hw_dcp {
                 CH3STAT: CH3STAT {
                     _not_send_or_sync: PhantomData,
                 },
+                CH3STAT_SET: CH3STAT_SET {
+                    _not_send_or_sync: PhantomData,
+                },
+                CH3STAT_CLR: CH3STAT_CLR {
+                    _not_send_or_sync: PhantomData,
+                },
                 CH3OPTS: CH3OPTS {
                     _not_send_or_sync: PhantomData,
                 },
@@ -8357,9 +8729,21 @@ pub mod hw_dcp {
                 CTRL: CTRL {
                     _not_send_or_sync: PhantomData,
                 },
+                CTRL_SET: CTRL_SET {
+                    _not_send_or_sync: PhantomData,
+                },
+                CTRL_CLR: CTRL_CLR {
+                    _not_send_or_sync: PhantomData,
+                },
                 STAT: STAT {
                     _not_send_or_sync: PhantomData,
                 },
+                STAT_SET: STAT_SET {
+                    _not_send_or_sync: PhantomData,
+                },
+                STAT_CLR: STAT_CLR {
+                    _not_send_or_sync: PhantomData,
+                },
                 CHANNELCTRL: CHANNELCTRL {
                     _not_send_or_sync: PhantomData,
                 },
@@ -8414,6 +8798,12 @@ pub mod hw_dcp {
                 CH0STAT: CH0STAT {
                     _not_send_or_sync: PhantomData,
                 },
+                CH0STAT_SET: CH0STAT_SET {
+                    _not_send_or_sync: PhantomData,
+                },
+                CH0STAT_CLR: CH0STAT_CLR {
+                    _not_send_or_sync: PhantomData,
+                },
                 CH0OPTS: CH0OPTS {
                     _not_send_or_sync: PhantomData,
                 },
@@ -8426,6 +8816,12 @@ pub mod hw_dcp {
                 CH1STAT: CH1STAT {
                     _not_send_or_sync: PhantomData,
                 },
+                CH1STAT_SET: CH1STAT_SET {
+                    _not_send_or_sync: PhantomData,
+                },
+                CH1STAT_CLR: CH1STAT_CLR {
+                    _not_send_or_sync: PhantomData,
+                },
                 CH1OPTS: CH1OPTS {
                     _not_send_or_sync: PhantomData,
                 },
@@ -8438,6 +8834,12 @@ pub mod hw_dcp {
                 CH2STAT: CH2STAT {
                     _not_send_or_sync: PhantomData,
                 },
+                CH2STAT_SET: CH2STAT_SET {
+                    _not_send_or_sync: PhantomData,
+                },
+                CH2STAT_CLR: CH2STAT_CLR {
+                    _not_send_or_sync: PhantomData,
+                },
                 CH2OPTS: CH2OPTS {
                     _not_send_or_sync: PhantomData,
                 },
@@ -8450,6 +8852,12 @@ pub mod hw_dcp {
                 CH3STAT: CH3STAT {
                     _not_send_or_sync: PhantomData,
                 },
+                CH3STAT_SET: CH3STAT_SET {
+                    _not_send_or_sync: PhantomData,
+                },
+                CH3STAT_CLR: CH3STAT_CLR {
+                    _not_send_or_sync: PhantomData,
+                },
                 CH3OPTS: CH3OPTS {
                     _not_send_or_sync: PhantomData,
                 },
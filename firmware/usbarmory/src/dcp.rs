@@ -5,8 +5,14 @@
 //! - AES-128, in ECB and CBC modes
 //! - SHA-256
 //! - SHA-1
+//! - CRC32
+//! - memory copy and constant fill
 //!
-//! Only SHA-256 & AES-128 in ECB mode are currently exposed
+//! All of them are exposed by this module. AES-128 in CTR and GCM modes, and HMAC-SHA256 are also
+//! provided; the GCM authentication function (GHASH) is computed in software
+//!
+//! Operations can be performed in a blocking fashion or submitted as a `Job` that completes in
+//! the background. Job completion is signaled through the `DCP` interrupt
 //!
 //! **NOTE** The DCP is only available on the i.MX6UL**Z** chip
 
@@ -14,32 +20,68 @@
 
 use core::{
     cell::{Cell, UnsafeCell},
+    mem,
     ptr::NonNull,
-    sync::atomic::{AtomicBool, Ordering},
+    sync::atomic::{self, AtomicBool, Ordering},
     time::Duration,
 };
 
 use pac::HW_DCP;
 
+use crate::{memlog, util};
+
 pub use aes128::Aes128;
+pub use crc32::Crc32;
+pub use ctr::Aes128Ctr;
+pub use gcm::Aes128Gcm;
+pub use hmac::HmacSha256;
+pub use job::Job;
+pub use memcpy::{fill, memcpy, Memcpy};
+pub use sha1::Sha1;
 pub use sha256::Sha256;
 
 mod aes128;
+mod crc32;
+mod ctr;
+mod gcm;
+mod hash;
+mod hmac;
+mod job;
+mod memcpy;
+mod sha1;
 mod sha256;
 
 fn default_timeout() -> Duration {
     Duration::from_millis(100)
 }
 
-const STAT_ERROR_MASK: u32 = (0xff << 16) | (1 << 6) | (1 << 5) | (1 << 4) | (1 << 3) | (1 << 2);
+// error code, error flags and HASH_MISMATCH; the hardware only sets HASH_MISMATCH when the command
+// has `check_hash` enabled
+const STAT_ERROR_MASK: u32 =
+    (0xff << 16) | (1 << 6) | (1 << 5) | (1 << 4) | (1 << 3) | (1 << 2) | (1 << 1);
+
+// number of pending command chains (CHnSEMA.VALUE)
+const SEMA_VALUE_MASK: u32 = 0xff << 16;
+const SEMA_VALUE_OFFSET: u32 = 16;
+
+// channel that's currently processing commands (STAT.CUR_CHANNEL); `0` means none, `n + 1` means
+// channel `n`
+const STAT_CUR_CHANNEL_MASK: u32 = 0xf << 24;
+const STAT_CUR_CHANNEL_OFFSET: u32 = 24;
 
 // start from the highest numbered channel to reduce the size of the `Context` struct
 const AES128_HW_CHANNEL: u8 = 3;
-const SHA256_CHANNEL: u8 = 2;
+// shared by all the hash algorithms: SHA-256, SHA-1 and CRC32
+const HASH_CHANNEL: u8 = 2;
 const AES128_RAM_CHANNEL: u8 = 1;
+// memory copies and fills
+const MEMCPY_CHANNEL: u8 = 0;
+
+/// Maximum number of commands that are chained together and handed to the DCP in one go
+const MAX_CHAIN_LEN: usize = 8;
 
-// Big enough for three channels (channels #1, #2 & #3) -- see table 13-1
-const CTXT_SZ: usize = 52 * 3;
+// Big enough for all four channels -- see table 13-1
+const CTXT_SZ: usize = 52 * 4;
 
 // word-aligned for performance
 #[repr(align(4))]
@@ -71,6 +113,8 @@ fn init() -> bool {
         );
 
         let supports_aes128 = 1;
+        let supports_sha1 = 1 << 16;
+        let supports_crc32 = 1 << 17;
         let supports_sha256 = 1 << 18;
         let capability = dcp.CAPABILITY1.read();
         assert!(
@@ -83,6 +127,16 @@ fn init() -> bool {
             "this DCP doesn't support SHA256"
         );
 
+        assert!(
+            capability & supports_sha1 != 0,
+            "this DCP doesn't support SHA1"
+        );
+
+        assert!(
+            capability & supports_crc32 != 0,
+            "this DCP doesn't support CRC32"
+        );
+
         // NOTE(static mut) this code runs at most once; CTXT will never be aliased
         #[link_section = ".uninit.aes_dcp_init_CTXT"]
         static mut CTXT: UnsafeCell<Context> = UnsafeCell::new(Context::empty());
@@ -97,6 +151,291 @@ fn init() -> bool {
     }
 }
 
+/// DCP errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The command was not set up properly (e.g. invalid combination of control bits)
+    Setup,
+    /// The command chain was malformed (e.g. invalid next command address)
+    Packet,
+    /// Bus error while reading from the source buffer
+    Source,
+    /// Bus error while writing to the destination buffer
+    Destination,
+    /// The computed hash didn't match the expected one
+    HashMismatch,
+    /// The operation didn't complete within the expected time
+    ///
+    /// The channel is reset and disabled when this happens; the handle (e.g. `Aes128`) needs to
+    /// be dropped and acquired again to use the channel again
+    Timeout,
+    /// Some other error; contains the `ERROR_CODE` field of the channel status
+    Other(u8),
+}
+
+impl Error {
+    /// Decodes the error flags of a channel (`CHnSTAT`) or command status word
+    fn from_status(status: u32) -> Self {
+        const HASH_MISMATCH: u32 = 1 << 1;
+        const ERROR_SETUP: u32 = 1 << 2;
+        const ERROR_PACKET: u32 = 1 << 3;
+        const ERROR_SRC: u32 = 1 << 4;
+        const ERROR_DST: u32 = 1 << 5;
+
+        if status & ERROR_SETUP != 0 {
+            Error::Setup
+        } else if status & ERROR_PACKET != 0 {
+            Error::Packet
+        } else if status & ERROR_SRC != 0 {
+            Error::Source
+        } else if status & ERROR_DST != 0 {
+            Error::Destination
+        } else if status & HASH_MISMATCH != 0 {
+            Error::HashMismatch
+        } else {
+            Error::Other((status >> 16) as u8)
+        }
+    }
+}
+
+/// Links the commands in `chain` and hands them to the specified DCP `channel`
+///
+/// The caller must keep the commands, and the buffers they point to, alive and untouched until
+/// the hardware is done with them
+///
+/// Returns `Error::Timeout`, without submitting the chain, if the channel is still busy with a
+/// previous chain after `timeout`
+fn start(channel: u8, chain: &[Cmd], timeout: Duration) -> Result<(), Error> {
+    let last = chain.last().expect("UNREACHABLE");
+
+    for (cmd, next) in chain.iter().zip(chain.iter().skip(1)) {
+        cmd.next_cmd_addr.set(Some(NonNull::from(next)));
+        cmd.control0
+            .set(*cmd.control0.get().chain(true).decr_semaphore(false));
+    }
+    // only the last command decrements the semaphore; that way a single semaphore increment
+    // processes the whole chain
+    last.next_cmd_addr.set(None);
+    last.control0
+        .set(*last.control0.get().chain(false).decr_semaphore(true));
+
+    // a channel may be shared by several handles (e.g. the RAM-key ciphers) so wait until it's
+    // done with the previous chain before handing it a new one
+    // NOTE the check and the submission must not be interleaved with a submission from a
+    // different context
+    let submit = || {
+        crate::no_interrupts(|| {
+            HW_DCP::borrow_unchecked(|dcp| {
+                let sema = semaphore(channel);
+
+                if channel_status(channel) & STAT_ERROR_MASK != 0 {
+                    // the previous chain failed and the channel halted; the owner of that chain
+                    // learns about the failure from the status of its commands
+                    clear_channel_status(channel);
+                } else if sema & SEMA_VALUE_MASK != 0 {
+                    // busy
+                    return false;
+                }
+
+                let first = &chain[0] as *const Cmd as u32;
+                match channel {
+                    0 => dcp.CH0CMDPTR.write(first),
+                    1 => dcp.CH1CMDPTR.write(first),
+                    2 => dcp.CH2CMDPTR.write(first),
+                    _ => dcp.CH3CMDPTR.write(first),
+                }
+
+                // the write below transfers ownership of the commands (and the buffers they point to)
+                // to the crypto engine; this fence drives all pending writes to them to completion
+                atomic::fence(Ordering::Release);
+
+                // start processing the chain
+                match channel {
+                    0 => dcp.CH0SEMA.write(1),
+                    1 => dcp.CH1SEMA.write(1),
+                    2 => dcp.CH2SEMA.write(1),
+                    _ => dcp.CH3SEMA.write(1),
+                }
+
+                true
+            })
+        })
+    };
+    if util::wait_for_or_timeout(submit, timeout).is_err() {
+        memlog!(
+            "DCP channel {} busy (SEMA={:#010x})",
+            channel,
+            semaphore(channel)
+        );
+
+        return Err(Error::Timeout);
+    }
+
+    Ok(())
+}
+
+/// Returns the value of the status register of the specified `channel`
+fn channel_status(channel: u8) -> u32 {
+    // NOTE(borrow_unchecked) single-instruction read of a read-only register
+    HW_DCP::borrow_unchecked(|dcp| match channel {
+        0 => dcp.CH0STAT.read(),
+        1 => dcp.CH1STAT.read(),
+        2 => dcp.CH2STAT.read(),
+        _ => dcp.CH3STAT.read(),
+    })
+}
+
+/// Clears the error flags of the specified `channel` so it can be used again
+fn clear_channel_status(channel: u8) {
+    // NOTE(borrow_unchecked) single-instruction write to a stateless register
+    HW_DCP::borrow_unchecked(|dcp| match channel {
+        0 => dcp.CH0STAT_CLR.write(!0),
+        1 => dcp.CH1STAT_CLR.write(!0),
+        2 => dcp.CH2STAT_CLR.write(!0),
+        _ => dcp.CH3STAT_CLR.write(!0),
+    })
+}
+
+/// Stops the specified `channel` and makes it forget the command chain it was working on
+///
+/// Afterwards the channel is disabled, idle and no longer references the chain or the buffers it
+/// points to, so it's safe to enable it again
+fn reset_channel(channel: u8) {
+    // NOTE(borrow_unchecked) single-instruction write to a stateless register
+    HW_DCP::borrow_unchecked(|dcp| dcp.CHANNELCTRL_CLR.write(1 << channel));
+
+    // the channel may be in the middle of a command; let it finish
+    let is_idle = || {
+        // NOTE(borrow_unchecked) single-instruction read of a read-only register
+        let stat = HW_DCP::borrow_unchecked(|dcp| dcp.STAT.read());
+        (stat & STAT_CUR_CHANNEL_MASK) >> STAT_CUR_CHANNEL_OFFSET != u32::from(channel) + 1
+    };
+    if util::wait_for_or_timeout(is_idle, default_timeout()).is_err() {
+        memlog!("DCP channel {} didn't stop", channel);
+    }
+
+    crate::no_interrupts(|| {
+        // NOTE(borrow_unchecked) the channel is disabled and interrupts are masked, which keeps
+        // `start` from submitting a new chain to it while it's being reset
+        HW_DCP::borrow_unchecked(|dcp| {
+            // the semaphore is an 8-bit counter and writes add to it; adding its two's complement
+            // drops all the pending chains
+            let pending = (semaphore(channel) & SEMA_VALUE_MASK) >> SEMA_VALUE_OFFSET;
+            let decrement = 0x100u32.wrapping_sub(pending) & 0xff;
+            match channel {
+                0 => dcp.CH0SEMA.write(decrement),
+                1 => dcp.CH1SEMA.write(decrement),
+                2 => dcp.CH2SEMA.write(decrement),
+                _ => dcp.CH3SEMA.write(decrement),
+            }
+
+            match channel {
+                0 => dcp.CH0CMDPTR.write(0),
+                1 => dcp.CH1CMDPTR.write(0),
+                2 => dcp.CH2CMDPTR.write(0),
+                _ => dcp.CH3CMDPTR.write(0),
+            }
+        });
+
+        clear_channel_status(channel);
+    });
+
+    if semaphore(channel) & SEMA_VALUE_MASK != 0 {
+        memlog!(
+            "DCP channel {} still has pending commands after a reset",
+            channel
+        );
+    }
+}
+
+/// Returns the value of the semaphore register of the specified `channel`
+fn semaphore(channel: u8) -> u32 {
+    // NOTE(borrow_unchecked) single-instruction read of a register
+    HW_DCP::borrow_unchecked(|dcp| match channel {
+        0 => dcp.CH0SEMA.read(),
+        1 => dcp.CH1SEMA.read(),
+        2 => dcp.CH2SEMA.read(),
+        _ => dcp.CH3SEMA.read(),
+    })
+}
+
+/// Checks if the hardware is done with `chain`, which was submitted to `channel`
+///
+/// Returns `None` if the chain is still being processed
+fn check(channel: u8, chain: &[Cmd]) -> Option<Result<(), Error>> {
+    let mut status = 0;
+    for cmd in chain {
+        // NOTE(read_volatile) we want this statement to always perform a load instruction rather
+        // than read memory once and cache the value in a (CPU) register
+        status = unsafe { cmd.status.get().read_volatile() };
+
+        // the hardware reports errors in the status word of the command that failed
+        if status & STAT_ERROR_MASK != 0 {
+            return Some(Err(Error::from_status(status)));
+        }
+    }
+
+    // `status` is the status of the last command
+    if status & 1 != 0 {
+        return Some(Ok(()));
+    }
+
+    // also check the channel, in case the error wasn't reported in the status word; this is only
+    // meaningful if the channel stopped at one of *our* commands
+    let stat = channel_status(channel);
+    let current = HW_DCP::borrow_unchecked(|dcp| match channel {
+        0 => dcp.CH0CMDPTR.read(),
+        1 => dcp.CH1CMDPTR.read(),
+        2 => dcp.CH2CMDPTR.read(),
+        _ => dcp.CH3CMDPTR.read(),
+    }) as usize;
+    let start = chain.as_ptr() as usize;
+    let end = start + chain.len() * mem::size_of::<Cmd>();
+    if stat & STAT_ERROR_MASK != 0 && current >= start && current < end {
+        Some(Err(Error::from_status(stat)))
+    } else {
+        None
+    }
+}
+
+/// [Blocking] Hands the commands in `chain` to the specified DCP `channel` and waits until the
+/// hardware has processed all of them
+///
+/// If the operation times out the channel is reset and left disabled
+fn run(channel: u8, chain: &[Cmd]) -> Result<(), Error> {
+    run_timeout(channel, chain, default_timeout())
+}
+
+/// Like `run` but with a custom `timeout`
+fn run_timeout(channel: u8, chain: &[Cmd], timeout: Duration) -> Result<(), Error> {
+    start(channel, chain, timeout)?;
+
+    // wait for channel to signal it's done
+    let mut res = None;
+    let is_done_or_error = || {
+        res = check(channel, chain);
+        res.is_some()
+    };
+    if util::wait_for_or_timeout(is_done_or_error, timeout).is_err() {
+        memlog!(
+            "DCP timeout (channel={}, STAT={:#010x})",
+            channel,
+            channel_status(channel)
+        );
+
+        // stop the channel from touching `chain` (and the buffers it points to) after we return,
+        // even if the channel is enabled again later
+        reset_channel(channel);
+
+        return Err(Error::Timeout);
+    }
+
+    // the crypto engine is done with the commands and has transferred ownership back to us
+    atomic::fence(Ordering::Acquire);
+
+    res.unwrap_or(Ok(()))
+}
+
 // MX28RM section 13.2.6.4
 // NOTE instances of this will be shared with the hardware (the hardware can modify its fields) so
 // one should never create an exclusive reference (`&mut`) to this struct
@@ -126,6 +465,33 @@ impl Cmd {
             status: UnsafeCell::new(0),
         }
     }
+
+    /// Restores the command to the state returned by `Cmd::new`
+    fn clear(&self) {
+        self.next_cmd_addr.set(None);
+        self.control0.set(Control0::new());
+        self.control1.set(Control1::new());
+        self.src_buffer_addr.set(None);
+        self.dest_buffer_addr.set(None);
+        self.buffer_size.set(0);
+        self.payload_pointer.set(None);
+        // NOTE(write_volatile) the hardware may read this field
+        unsafe { self.status.get().write_volatile(0) }
+    }
+
+    /// Creates enough commands to build the longest supported chain
+    const fn new_chain() -> [Self; MAX_CHAIN_LEN] {
+        [
+            Self::new(),
+            Self::new(),
+            Self::new(),
+            Self::new(),
+            Self::new(),
+            Self::new(),
+            Self::new(),
+            Self::new(),
+        ]
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -232,7 +598,7 @@ enum CipherSelect {
     Aes128 = 0,
 }
 
-#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq)]
 enum CipherMode {
     Ecb = 0,
     Cbc = 1,
@@ -241,15 +607,16 @@ enum CipherMode {
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum KeySelect {
     Key0 = 0,
-    // Key1 = 1,
-    // Key2 = 2,
-    // Key3 = 3,
+    Key1 = 1,
+    Key2 = 2,
+    Key3 = 3,
     UniqueKey = 0xfe,
     OtpKey = 0xff,
 }
 
+#[derive(Clone, Copy)]
 enum HashSelect {
-    // Sha1 = 0,
-    // Crc32 = 1,
+    Sha1 = 0,
+    Crc32 = 1,
     Sha256 = 2,
 }
@@ -1,24 +1,31 @@
 use core::{
     marker::PhantomData,
     ptr::NonNull,
-    sync::atomic::{self, AtomicBool, Ordering},
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
 };
 
 use arrayref::array_ref;
 use block_cipher::{
     generic_array::{typenum::consts, GenericArray},
-    BlockCipher, NewBlockCipher,
+    BlockCipher, NewBlockCipher, ParBlocks,
 };
+use heapless::Vec;
 use pac::HW_DCP;
 
 use crate::{
     dcp::{
-        self, CipherMode, CipherSelect, Cmd, Control0, Control1, KeySelect, AES128_HW_CHANNEL,
-        AES128_RAM_CHANNEL, STAT_ERROR_MASK,
+        self,
+        job::{self, Slot},
+        CipherMode, CipherSelect, Cmd, Control0, Control1, Error, Job, KeySelect,
+        AES128_HW_CHANNEL, AES128_RAM_CHANNEL, MAX_CHAIN_LEN,
     },
-    memlog, memlog_flush_and_reset, util,
+    memlog, memlog_flush_and_reset,
+    util::{self, Align4},
 };
 
+/// Size of an AES block, in bytes
+pub(super) const BLOCK_SIZE: usize = 16;
+
 /// AES-128 channel
 pub struct Aes128 {
     key: KeySelect,
@@ -31,11 +38,13 @@ unsafe impl Send for Aes128 {}
 
 impl Drop for Aes128 {
     fn drop(&mut self) {
-        match self.key {
-            KeySelect::Key0 => RAM_AES_IN_USE.store(false, Ordering::Release),
-            KeySelect::UniqueKey | KeySelect::OtpKey => {
-                HW_AES_IN_USE.store(false, Ordering::Release)
-            }
+        if let Some(index) = self.key_index() {
+            // don't leave the key behind
+            write_key(index, &[0; 16]);
+
+            RAM_KEYS_IN_USE.fetch_and(!(1 << index), Ordering::Release);
+        } else {
+            HW_AES_IN_USE.store(false, Ordering::Release)
         }
     }
 }
@@ -44,25 +53,86 @@ impl NewBlockCipher for Aes128 {
     type KeySize = consts::U16;
 
     fn new(key: &GenericArray<u8, consts::U16>) -> Self {
-        Self::new_ram(key).expect("the `Aes128` is currently in use")
+        Self::new_ram(key).expect("all the DCP key slots are in use")
     }
 }
 
 impl BlockCipher for Aes128 {
     type BlockSize = consts::U16;
-    type ParBlocks = consts::U1;
+    // this matches the maximum length of a command chain
+    type ParBlocks = consts::U8;
 
     fn decrypt_block(&self, block: &mut GenericArray<u8, consts::U16>) {
-        self.xcrypt(block, false)
+        self.try_decrypt(block).expect("DCP error")
     }
 
     fn encrypt_block(&self, block: &mut GenericArray<u8, consts::U16>) {
-        self.xcrypt(block, true)
+        self.try_encrypt(block).expect("DCP error")
+    }
+
+    fn decrypt_blocks(&self, blocks: &mut ParBlocks<Self>) {
+        let mut segments = Vec::<_, consts::U8>::new();
+        for block in blocks.iter_mut() {
+            let _ = segments.push(&mut block[..]);
+        }
+        self.xcrypt(CipherMode::Ecb, false, None, &mut segments)
+            .expect("DCP error")
+    }
+
+    fn encrypt_blocks(&self, blocks: &mut ParBlocks<Self>) {
+        let mut segments = Vec::<_, consts::U8>::new();
+        for block in blocks.iter_mut() {
+            let _ = segments.push(&mut block[..]);
+        }
+        self.xcrypt(CipherMode::Ecb, true, None, &mut segments)
+            .expect("DCP error")
     }
 }
 
 static HW_AES_IN_USE: AtomicBool = AtomicBool::new(false);
-static RAM_AES_IN_USE: AtomicBool = AtomicBool::new(false);
+
+/// Number of DCP key slots available for RAM keys
+const NKEYS: u8 = 4;
+
+// bit `n` is set when key slot `n` is in use
+static RAM_KEYS_IN_USE: AtomicU8 = AtomicU8::new(0);
+
+// Reserves one of the key slots
+fn alloc_key() -> Option<u8> {
+    let mut in_use = RAM_KEYS_IN_USE.load(Ordering::Acquire);
+    loop {
+        let index = (0..NKEYS).find(|index| in_use & (1 << index) == 0)?;
+
+        match RAM_KEYS_IN_USE.compare_exchange_weak(
+            in_use,
+            in_use | (1 << index),
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => return Some(index),
+            Err(current) => in_use = current,
+        }
+    }
+}
+
+// Installs `key` in the write-only key slot `index`
+fn write_key(index: u8, key: &[u8; 16]) {
+    // the writes to `KEYDATA` advance an internal pointer that's set by the write to `KEY` so
+    // this sequence must not be interleaved with the installation of a different key
+    crate::no_interrupts(|| {
+        HW_DCP::borrow_unchecked(|dcp| {
+            dcp.KEY.write(u32::from(index) << 4);
+            dcp.KEYDATA
+                .write(u32::from_le_bytes(*array_ref!(key, 0, 4)));
+            dcp.KEYDATA
+                .write(u32::from_le_bytes(*array_ref!(key, 4, 4)));
+            dcp.KEYDATA
+                .write(u32::from_le_bytes(*array_ref!(key, 8, 4)));
+            dcp.KEYDATA
+                .write(u32::from_le_bytes(*array_ref!(key, 12, 4)));
+        })
+    })
+}
 
 enum HardwareKey {
     Unique,
@@ -133,106 +203,368 @@ impl Aes128 {
         }
     }
 
-    fn new_ram(key: &GenericArray<u8, consts::U16>) -> Option<Self> {
-        if RAM_AES_IN_USE
-            .compare_exchange_weak(false, true, Ordering::AcqRel, Ordering::Acquire)
-            .is_ok()
-        {
-            dcp::init();
+    /// Gets a handle to the AES-128 channel and configures it to use the given `key`
+    ///
+    /// The key is installed in one of the DCP key slots, which are write-only. There are four
+    /// such slots so up to four of these ciphers can exist at the same time; this function
+    /// returns `None` if all the slots are in use
+    ///
+    /// **NOTE** these ciphers share a single channel; operations on different ciphers are
+    /// processed one after the other
+    pub fn new_ram(key: &GenericArray<u8, consts::U16>) -> Option<Self> {
+        let index = alloc_key()?;
 
-            HW_DCP::borrow_unchecked(|dcp| {
-                // install key in the write-only register
-                // NOTE to support multiple cipher instances this would need a mutex (e.g. spinlock)
-                // to do a exclusive write to these registers
-                dcp.KEY.write(u32::from(AES128_RAM_CHANNEL) << 6);
-                dcp.KEYDATA
-                    .write(u32::from_le_bytes(*array_ref!(key, 0, 4)));
-                dcp.KEYDATA
-                    .write(u32::from_le_bytes(*array_ref!(key, 4, 4)));
-                dcp.KEYDATA
-                    .write(u32::from_le_bytes(*array_ref!(key, 8, 4)));
-                dcp.KEYDATA
-                    .write(u32::from_le_bytes(*array_ref!(key, 12, 4)));
-
-                // enable channel
-                // NOTE single instruction write to a stateless register
-                // NOTE(| 1 << 3) this is not documented (silicon bug? software restriction?) but
-                // appears that a higher numbered channel needs to be enabled for this channel to
-                // work (probably the hardware expects the channels to be enabled from higher
-                // numbered to lower numbered)
-                dcp.CHANNELCTRL_SET
-                    .write((1 << AES128_RAM_CHANNEL) | (1 << 3));
-            });
+        dcp::init();
 
-            Some(Aes128 {
-                key: KeySelect::Key0,
-                _not_send_or_sync: PhantomData,
-            })
+        write_key(index, array_ref!(key, 0, 16));
+
+        HW_DCP::borrow_unchecked(|dcp| {
+            // enable channel
+            // NOTE single instruction write to a stateless register
+            // NOTE(| 1 << 3) this is not documented (silicon bug? software restriction?) but
+            // appears that a higher numbered channel needs to be enabled for this channel to
+            // work (probably the hardware expects the channels to be enabled from higher
+            // numbered to lower numbered)
+            dcp.CHANNELCTRL_SET
+                .write((1 << AES128_RAM_CHANNEL) | (1 << 3));
+        });
+
+        Some(Aes128 {
+            key: match index {
+                0 => KeySelect::Key0,
+                1 => KeySelect::Key1,
+                2 => KeySelect::Key2,
+                _ => KeySelect::Key3,
+            },
+            _not_send_or_sync: PhantomData,
+        })
+    }
+
+    /// Replaces the key of a cipher created with `new_ram`
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the cipher uses a hardware key
+    pub(crate) fn set_ram_key(&mut self, key: &[u8; 16]) {
+        let index = self
+            .key_index()
+            .expect("the key of this cipher can't be changed");
+        write_key(index, key);
+    }
+
+    /// Encrypts a single `block` in place
+    ///
+    /// This is the fallible version of `BlockCipher::encrypt_block`
+    pub fn try_encrypt(&self, block: &mut GenericArray<u8, consts::U16>) -> Result<(), Error> {
+        self.xcrypt(CipherMode::Ecb, true, None, &mut [&mut block[..]])
+    }
+
+    /// Decrypts a single `block` in place
+    ///
+    /// This is the fallible version of `BlockCipher::decrypt_block`
+    pub fn try_decrypt(&self, block: &mut GenericArray<u8, consts::U16>) -> Result<(), Error> {
+        self.xcrypt(CipherMode::Ecb, false, None, &mut [&mut block[..]])
+    }
+
+    /// Encrypts `data` in place using AES-128 in ECB mode
+    ///
+    /// All the blocks in `data` are processed in a single DCP operation
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the length of `data` is not a multiple of the AES block size (16
+    /// bytes)
+    pub fn encrypt_ecb(&self, data: &mut [u8]) -> Result<(), Error> {
+        self.xcrypt(CipherMode::Ecb, true, None, &mut [data])
+    }
+
+    /// Decrypts `data` in place using AES-128 in ECB mode
+    ///
+    /// All the blocks in `data` are processed in a single DCP operation
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the length of `data` is not a multiple of the AES block size (16
+    /// bytes)
+    pub fn decrypt_ecb(&self, data: &mut [u8]) -> Result<(), Error> {
+        self.xcrypt(CipherMode::Ecb, false, None, &mut [data])
+    }
+
+    /// Encrypts `data` in place using AES-128 in CBC mode with the given initialization vector
+    ///
+    /// All the blocks in `data` are processed in a single DCP operation
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the length of `data` is not a multiple of the AES block size (16
+    /// bytes)
+    pub fn encrypt_cbc(&self, iv: &[u8; BLOCK_SIZE], data: &mut [u8]) -> Result<(), Error> {
+        self.xcrypt(CipherMode::Cbc, true, Some(iv), &mut [data])
+    }
+
+    /// Decrypts `data` in place using AES-128 in CBC mode with the given initialization vector
+    ///
+    /// All the blocks in `data` are processed in a single DCP operation
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the length of `data` is not a multiple of the AES block size (16
+    /// bytes)
+    pub fn decrypt_cbc(&self, iv: &[u8; BLOCK_SIZE], data: &mut [u8]) -> Result<(), Error> {
+        self.xcrypt(CipherMode::Cbc, false, Some(iv), &mut [data])
+    }
+
+    /// Encrypts, in place, the concatenation of all `segments` using AES-128 in CBC mode with the
+    /// given initialization vector
+    ///
+    /// The segments don't need to be contiguous in memory. Each segment becomes one command in a
+    /// chain; up to 8 commands are handed to the DCP in a single operation
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the length of any segment is not a multiple of the AES block size
+    /// (16 bytes)
+    pub fn encrypt_cbc_segments(
+        &self,
+        iv: &[u8; BLOCK_SIZE],
+        segments: &mut [&mut [u8]],
+    ) -> Result<(), Error> {
+        self.xcrypt(CipherMode::Cbc, true, Some(iv), segments)
+    }
+
+    /// Decrypts, in place, the concatenation of all `segments` using AES-128 in CBC mode with the
+    /// given initialization vector
+    ///
+    /// The segments don't need to be contiguous in memory. Each segment becomes one command in a
+    /// chain; up to 8 commands are handed to the DCP in a single operation
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the length of any segment is not a multiple of the AES block size
+    /// (16 bytes)
+    pub fn decrypt_cbc_segments(
+        &self,
+        iv: &[u8; BLOCK_SIZE],
+        segments: &mut [&mut [u8]],
+    ) -> Result<(), Error> {
+        self.xcrypt(CipherMode::Cbc, false, Some(iv), segments)
+    }
+
+    /// Encrypts `buffer` in place using AES-128 in ECB mode
+    ///
+    /// The operation is performed in the background; see `Job` for details
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the length of `buffer` is not a multiple of the AES block size (16
+    /// bytes)
+    pub fn encrypt_ecb_job(self, buffer: &'static mut [u8]) -> Job<(Self, &'static mut [u8])> {
+        self.xcrypt_job(CipherMode::Ecb, true, None, buffer)
+    }
+
+    /// Decrypts `buffer` in place using AES-128 in ECB mode
+    ///
+    /// The operation is performed in the background; see `Job` for details
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the length of `buffer` is not a multiple of the AES block size (16
+    /// bytes)
+    pub fn decrypt_ecb_job(self, buffer: &'static mut [u8]) -> Job<(Self, &'static mut [u8])> {
+        self.xcrypt_job(CipherMode::Ecb, false, None, buffer)
+    }
+
+    /// Encrypts `buffer` in place using AES-128 in CBC mode with the given initialization vector
+    ///
+    /// The operation is performed in the background; see `Job` for details
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the length of `buffer` is not a multiple of the AES block size (16
+    /// bytes)
+    pub fn encrypt_cbc_job(
+        self,
+        iv: &[u8; BLOCK_SIZE],
+        buffer: &'static mut [u8],
+    ) -> Job<(Self, &'static mut [u8])> {
+        self.xcrypt_job(CipherMode::Cbc, true, Some(iv), buffer)
+    }
+
+    /// Decrypts `buffer` in place using AES-128 in CBC mode with the given initialization vector
+    ///
+    /// The operation is performed in the background; see `Job` for details
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the length of `buffer` is not a multiple of the AES block size (16
+    /// bytes)
+    pub fn decrypt_cbc_job(
+        self,
+        iv: &[u8; BLOCK_SIZE],
+        buffer: &'static mut [u8],
+    ) -> Job<(Self, &'static mut [u8])> {
+        self.xcrypt_job(CipherMode::Cbc, false, Some(iv), buffer)
+    }
+
+    // Returns the index of the key slot used by this cipher, if it uses a RAM key
+    fn key_index(&self) -> Option<u8> {
+        match self.key {
+            KeySelect::Key0 => Some(0),
+            KeySelect::Key1 => Some(1),
+            KeySelect::Key2 => Some(2),
+            KeySelect::Key3 => Some(3),
+            KeySelect::UniqueKey | KeySelect::OtpKey => None,
+        }
+    }
+
+    // the RAM keys and the hardware keys use different channels so they can be used concurrently
+    fn channel(&self) -> u8 {
+        if self.key_index().is_some() {
+            AES128_RAM_CHANNEL
         } else {
-            None
+            AES128_HW_CHANNEL
         }
     }
 
-    fn xcrypt(&self, block: &mut GenericArray<u8, consts::U16>, encrypt: bool) {
-        let cmd = Cmd::new();
+    // Returns the job memory owned by this cipher
+    fn slot(&self) -> &'static Slot {
+        job::slot(
+            self.key_index()
+                .map(|index| job::AES128_RAM_SLOT + usize::from(index))
+                .unwrap_or(job::AES128_HW_SLOT),
+        )
+    }
 
+    // Configures `cmd` to encrypt or decrypt `data` in place
+    fn setup(&self, cmd: &Cmd, mode: CipherMode, encrypt: bool, init: bool, data: &mut [u8]) {
         cmd.control0.set(
             *Control0::new()
-                .decr_semaphore(true)
                 .enable_cipher(true)
                 .cipher_encrypt(encrypt)
+                .cipher_init(init)
                 .otp_key(self.key == KeySelect::OtpKey),
         );
 
         cmd.control1.set(
             *Control1::new()
                 .cipher_select(CipherSelect::Aes128)
-                .cipher_mode(CipherMode::Ecb)
+                .cipher_mode(mode)
                 .key_select(self.key),
         );
 
-        cmd.src_buffer_addr.set(Some(NonNull::from(&block[0])));
-        cmd.dest_buffer_addr.set(Some(NonNull::from(&mut block[0])));
-        cmd.buffer_size.set(block.len());
+        cmd.src_buffer_addr.set(Some(NonNull::from(&data[0])));
+        cmd.dest_buffer_addr.set(Some(NonNull::from(&mut data[0])));
+        cmd.buffer_size.set(data.len());
+    }
 
-        HW_DCP::borrow_unchecked(|dcp| {
-            // TODO generalize over channel index
-            dcp.CH3CMDPTR.write(&cmd as *const Cmd as u32);
-
-            // the write below transfers ownership of `block` and `cmd` to the crypto engine; this
-            // fence drives all pending writes to `block` & `cmd` to completion
-            atomic::fence(Ordering::Release);
-
-            // start encryption
-            dcp.CH3SEMA.write(1);
-
-            // wait for channel to signal it's done -- the trait interface requires this operation
-            // to be blocking
-            let mut stat = 0;
-            let mut status = 0;
-            let is_done_or_error = || {
-                // NOTE(read_volatile) we want this statement to always perform a load
-                // instruction rather than read memory once and cache the value in a (CPU)
-                // register
-                status = unsafe { cmd.status.get().read_volatile() };
-                stat = dcp.CH3STAT.read();
-
-                // done or error
-                status & 1 != 0 || stat & STAT_ERROR_MASK != 0
-            };
-            if util::wait_for_or_timeout(is_done_or_error, dcp::default_timeout()).is_err() {
-                memlog!("encryption timeout (STAT={:#010x})", stat);
-                memlog_flush_and_reset!()
+    fn xcrypt_job(
+        self,
+        mode: CipherMode,
+        encrypt: bool,
+        iv: Option<&[u8; BLOCK_SIZE]>,
+        buffer: &'static mut [u8],
+    ) -> Job<(Self, &'static mut [u8])> {
+        assert!(
+            buffer.len() % BLOCK_SIZE == 0,
+            "input length must be a multiple of the AES block size"
+        );
+
+        let channel = self.channel();
+        let slot = self.slot();
+        let len = if buffer.is_empty() {
+            // nothing to do
+            0
+        } else {
+            let cmd = &slot.chain()[0];
+            let iv = iv.filter(|_| mode == CipherMode::Cbc);
+
+            self.setup(cmd, mode, encrypt, iv.is_some(), buffer);
+
+            if let Some(iv) = iv {
+                let payload = slot.scratch();
+                unsafe { (&mut *payload)[..BLOCK_SIZE].copy_from_slice(iv) }
+                cmd.payload_pointer.set(NonNull::new(payload as *mut u8));
+            }
+
+            1
+        };
+
+        Job::start(channel, slot, len, dcp::default_timeout(), (self, buffer))
+    }
+
+    // Encrypts or decrypts, in place, the concatenation of all `segments`
+    //
+    // `iv` is only used in CBC mode
+    fn xcrypt(
+        &self,
+        mode: CipherMode,
+        encrypt: bool,
+        iv: Option<&[u8; BLOCK_SIZE]>,
+        segments: &mut [&mut [u8]],
+    ) -> Result<(), Error> {
+        for segment in segments.iter() {
+            assert!(
+                segment.len() % BLOCK_SIZE == 0,
+                "input length must be a multiple of the AES block size"
+            );
+        }
+
+        let cbc = mode == CipherMode::Cbc;
+
+        // the IV is passed to the hardware through the command payload
+        // word aligned for performance
+        let mut payload = Align4 {
+            inner: iv.cloned().unwrap_or([0; BLOCK_SIZE]),
+        };
+
+        for group in segments.chunks_mut(MAX_CHAIN_LEN) {
+            // the IV of the next group is the last ciphertext block of this group; when decrypting
+            // that block will be overwritten so grab it now
+            let mut next_iv = [0; BLOCK_SIZE];
+            if cbc && !encrypt {
+                if let Some(last) = group.iter().rev().find(|segment| !segment.is_empty()) {
+                    next_iv.copy_from_slice(&last[last.len() - BLOCK_SIZE..]);
+                }
+            }
+
+            let chain = Cmd::new_chain();
+            let mut len = 0;
+            for segment in group.iter_mut().filter(|segment| !segment.is_empty()) {
+                let cmd = &chain[len];
+                // only the first command in the chain loads the IV; the following ones continue
+                // from where the previous one left off
+                let init = cbc && len == 0;
+
+                self.setup(cmd, mode, encrypt, init, segment);
+
+                if init {
+                    cmd.payload_pointer
+                        .set(Some(NonNull::from(&mut payload.inner[0])));
+                }
+
+                len += 1;
             }
 
-            // the crypto engine is done with the block and has transferred ownership back to us
-            atomic::fence(Ordering::Acquire);
+            if len == 0 {
+                // nothing to do
+                continue;
+            }
 
-            // the interface won't let us report the error so just abort the program
-            if stat & STAT_ERROR_MASK != 0 {
-                memlog!("error (STAT={:#010x}, Cmd.status={:#010x})", stat, status);
-                memlog_flush_and_reset!()
+            dcp::run(self.channel(), &chain[..len])?;
+
+            if cbc {
+                if encrypt {
+                    if let Some(last) = group.iter().rev().find(|segment| !segment.is_empty()) {
+                        payload
+                            .inner
+                            .copy_from_slice(&last[last.len() - BLOCK_SIZE..]);
+                    }
+                } else {
+                    payload.inner = next_iv;
+                }
             }
-        })
+        }
+
+        Ok(())
     }
 }
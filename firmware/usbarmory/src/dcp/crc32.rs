@@ -0,0 +1,78 @@
+use digest::{Input, Reset};
+
+use crate::dcp::{self, hash::Engine, job, Error, HashSelect, Job, HASH_CHANNEL};
+
+/// CRC32 channel
+///
+/// The DCP computes the CRC32 with polynomial `0x04C11DB7` and initial value `0xFFFFFFFF`; input
+/// and output are not reflected and no final XOR is applied (this variant is also known as
+/// CRC-32/MPEG-2)
+pub struct Crc32 {
+    engine: Engine,
+}
+
+impl Crc32 {
+    /// Gets a handle to the CRC32 channel
+    ///
+    /// This function returns `None` if the channel is currently in use
+    ///
+    /// **NOTE** `Sha256`, `Sha1` and `Crc32` share the same channel so only one of them can be
+    /// in use at any given time
+    pub fn take() -> Option<Self> {
+        Engine::take(HashSelect::Crc32).map(|engine| Crc32 { engine })
+    }
+
+    /// Feeds `input` to the hasher
+    ///
+    /// All the complete blocks are processed in the background; see `Job` for details
+    pub fn input_job(mut self, input: &'static [u8]) -> Job<(Self, &'static [u8])> {
+        let len = self.engine.input_job(input);
+        Job::start(
+            HASH_CHANNEL,
+            job::slot(job::HASH_SLOT),
+            len,
+            dcp::default_timeout(),
+            (self, input),
+        )
+    }
+
+    /// Feeds `input` to the hasher
+    ///
+    /// This is the fallible version of `Input::input`
+    pub fn try_input(&mut self, input: &[u8]) -> Result<(), Error> {
+        self.engine.input(input)
+    }
+
+    /// Returns the checksum of all the input data
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the DCP reports an error; see `try_finalize`
+    pub fn finalize(self) -> u32 {
+        self.try_finalize().expect("DCP error")
+    }
+
+    /// Returns the checksum of all the input data
+    pub fn try_finalize(self) -> Result<u32, Error> {
+        let output = self.engine.finalize()?;
+
+        Ok(u32::from_le_bytes([
+            output[0], output[1], output[2], output[3],
+        ]))
+    }
+}
+
+impl Input for Crc32 {
+    fn input<B>(&mut self, bytes: B)
+    where
+        B: AsRef<[u8]>,
+    {
+        self.try_input(bytes.as_ref()).expect("DCP error")
+    }
+}
+
+impl Reset for Crc32 {
+    fn reset(&mut self) {
+        self.engine.reset()
+    }
+}
@@ -0,0 +1,177 @@
+use core::cmp;
+
+use block_cipher::NewBlockCipher;
+use stream_cipher::{
+    generic_array::{typenum::consts, GenericArray},
+    LoopError, NewStreamCipher, SyncStreamCipher, SyncStreamCipherSeek,
+};
+
+use crate::{
+    dcp::{aes128::BLOCK_SIZE, Aes128, Error},
+    util::Align4,
+};
+
+/// Number of counter blocks that are encrypted in a single DCP operation
+const BATCH_BLOCKS: usize = 32;
+
+/// AES-128 in counter (CTR) mode
+///
+/// The counter block is a 128-bit big endian integer that's incremented by one after every block.
+/// The keystream is produced by the DCP, which encrypts batches of up to 32 counter blocks in a
+/// single operation
+///
+/// Any `Aes128` cipher can be used: OTP, UNIQUE or RAM key
+pub struct Aes128Ctr {
+    cipher: Aes128,
+    // initial counter block
+    nonce: u128,
+    // position in the keystream, in bytes
+    pos: u64,
+    // keystream block that contains the byte at `pos`; only used when `pos` is not a multiple of
+    // the block size
+    keystream: Option<[u8; BLOCK_SIZE]>,
+}
+
+impl Aes128Ctr {
+    /// Uses `cipher` in CTR mode; `nonce` is the initial counter block
+    pub fn from_cipher(cipher: Aes128, nonce: &[u8; BLOCK_SIZE]) -> Self {
+        Self {
+            cipher,
+            nonce: u128::from_be_bytes(*nonce),
+            pos: 0,
+            keystream: None,
+        }
+    }
+
+    /// XORs `data` with the keystream and advances the keystream position
+    ///
+    /// This is the fallible version of `SyncStreamCipher::apply_keystream`. If an error is
+    /// reported the keystream position is left unchanged but `data` may have been partially
+    /// modified
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the keystream position overflows a `u64`
+    pub fn try_process(&mut self, data: &mut [u8]) -> Result<(), Error> {
+        let end = self
+            .pos
+            .checked_add(data.len() as u64)
+            .expect("keystream position overflow");
+
+        let mut done = 0;
+        let mut keystream = None;
+
+        // finish the current block
+        let offset = (self.pos % BLOCK_SIZE as u64) as usize;
+        if offset != 0 && !data.is_empty() {
+            let block = match self.keystream {
+                Some(block) => block,
+                None => self.keystream_block(self.pos)?,
+            };
+            done = cmp::min(BLOCK_SIZE - offset, data.len());
+            xor(&mut data[..done], &block[offset..]);
+            keystream = Some(block);
+        }
+
+        // the rest of the data starts at a block boundary
+        let rest = &mut data[done..];
+        if !rest.is_empty() {
+            apply_keystream(&self.cipher, self.counter(self.pos + done as u64), rest)?;
+
+            // the keystream of a trailing partial block is recomputed on demand
+            keystream = None;
+        }
+
+        self.keystream = if end % BLOCK_SIZE as u64 == 0 {
+            None
+        } else {
+            keystream
+        };
+        self.pos = end;
+
+        Ok(())
+    }
+
+    // Returns the counter block used to produce the keystream byte at `pos`
+    fn counter(&self, pos: u64) -> u128 {
+        self.nonce.wrapping_add(u128::from(pos / BLOCK_SIZE as u64))
+    }
+
+    // Returns the keystream block that contains the byte at `pos`
+    fn keystream_block(&self, pos: u64) -> Result<[u8; BLOCK_SIZE], Error> {
+        let mut block = self.counter(pos).to_be_bytes();
+        self.cipher.encrypt_ecb(&mut block)?;
+        Ok(block)
+    }
+}
+
+impl NewStreamCipher for Aes128Ctr {
+    type KeySize = consts::U16;
+    type NonceSize = consts::U16;
+
+    fn new(key: &GenericArray<u8, consts::U16>, nonce: &GenericArray<u8, consts::U16>) -> Self {
+        let mut counter = [0; BLOCK_SIZE];
+        counter.copy_from_slice(nonce);
+
+        Self::from_cipher(Aes128::new(key), &counter)
+    }
+}
+
+impl SyncStreamCipher for Aes128Ctr {
+    fn try_apply_keystream(&mut self, data: &mut [u8]) -> Result<(), LoopError> {
+        if self.pos.checked_add(data.len() as u64).is_none() {
+            return Err(LoopError);
+        }
+
+        self.try_process(data).expect("DCP error");
+        Ok(())
+    }
+}
+
+impl SyncStreamCipherSeek for Aes128Ctr {
+    fn current_pos(&self) -> u64 {
+        self.pos
+    }
+
+    fn seek(&mut self, pos: u64) {
+        self.pos = pos;
+        // computed on demand
+        self.keystream = None;
+    }
+}
+
+/// XORs `data` with the keystream that starts at the `counter` block
+pub(super) fn apply_keystream(
+    cipher: &Aes128,
+    mut counter: u128,
+    data: &mut [u8],
+) -> Result<(), Error> {
+    // word-aligned for performance
+    let mut keystream = Align4 {
+        inner: [0; BATCH_BLOCKS * BLOCK_SIZE],
+    };
+
+    for chunk in data.chunks_mut(BATCH_BLOCKS * BLOCK_SIZE) {
+        // round up to include a trailing partial block
+        let nblocks = (chunk.len() + BLOCK_SIZE - 1) / BLOCK_SIZE;
+        let keystream = &mut keystream.inner[..nblocks * BLOCK_SIZE];
+
+        for block in keystream.chunks_mut(BLOCK_SIZE) {
+            block.copy_from_slice(&counter.to_be_bytes());
+            counter = counter.wrapping_add(1);
+        }
+
+        cipher.encrypt_ecb(keystream)?;
+
+        xor(chunk, keystream);
+    }
+
+    Ok(())
+}
+
+// NOTE only `data.len()` bytes of `keystream` are used
+fn xor(data: &mut [u8], keystream: &[u8]) {
+    for (byte, key) in data.iter_mut().zip(keystream) {
+        *byte ^= key;
+    }
+}
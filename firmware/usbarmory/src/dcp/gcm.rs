@@ -0,0 +1,138 @@
+use core::ptr;
+
+use aead::{
+    consts, generic_array::GenericArray, AeadInPlace, Error as AeadError, NewAead, Nonce, Tag,
+};
+use block_cipher::NewBlockCipher;
+use ghash::GHash;
+
+use crate::dcp::{aes128::BLOCK_SIZE, ctr, Aes128, Error};
+
+/// Size of the nonce (IV), in bytes
+const NONCE_SIZE: usize = 12;
+
+/// Maximum plaintext length, in bytes -- see section 5.2.1.1 of NIST SP 800-38D
+const MAX_PLAINTEXT_LEN: u64 = (1 << 36) - 32;
+
+/// AES-128 in Galois/Counter Mode (GCM)
+///
+/// Encryption is performed by the DCP (see `Aes128Ctr`) whereas the GHASH authentication function
+/// is computed in software
+///
+/// Any `Aes128` cipher can be used: OTP, UNIQUE or RAM key. Only 96-bit nonces and 128-bit tags are
+/// supported
+///
+/// **NOTE** DCP errors are reported as `aead::Error` by the `AeadInPlace` implementation
+pub struct Aes128Gcm {
+    cipher: Aes128,
+    // hash subkey
+    h: [u8; BLOCK_SIZE],
+}
+
+impl Drop for Aes128Gcm {
+    fn drop(&mut self) {
+        // don't leave the hash subkey behind
+        unsafe { ptr::write_volatile(&mut self.h, [0; BLOCK_SIZE]) }
+    }
+}
+
+impl Aes128Gcm {
+    /// Uses `cipher` in GCM mode
+    pub fn from_cipher(cipher: Aes128) -> Result<Self, Error> {
+        let mut h = [0; BLOCK_SIZE];
+        cipher.encrypt_ecb(&mut h)?;
+
+        Ok(Self { cipher, h })
+    }
+
+    // Returns the first counter block (`J0`)
+    fn j0(nonce: &Nonce<consts::U12>) -> u128 {
+        let mut block = [0; BLOCK_SIZE];
+        block[..NONCE_SIZE].copy_from_slice(nonce);
+        block[BLOCK_SIZE - 1] = 1;
+        u128::from_be_bytes(block)
+    }
+
+    // Computes the authentication tag of the `ciphertext`
+    fn tag(&self, j0: u128, associated_data: &[u8], ciphertext: &[u8]) -> Result<[u8; 16], Error> {
+        let mut ghash = GHash::new(&self.h);
+        ghash.update_padded(associated_data);
+        ghash.update_padded(ciphertext);
+
+        let mut lengths = [0; BLOCK_SIZE];
+        lengths[..8].copy_from_slice(&(associated_data.len() as u64 * 8).to_be_bytes());
+        lengths[8..].copy_from_slice(&(ciphertext.len() as u64 * 8).to_be_bytes());
+        ghash.update_block(&lengths);
+
+        let mut tag = j0.to_be_bytes();
+        self.cipher.encrypt_ecb(&mut tag)?;
+        for (byte, s) in tag.iter_mut().zip(ghash.finalize().iter()) {
+            *byte ^= s;
+        }
+
+        Ok(tag)
+    }
+}
+
+impl NewAead for Aes128Gcm {
+    type KeySize = consts::U16;
+
+    fn new(key: &GenericArray<u8, consts::U16>) -> Self {
+        Self::from_cipher(Aes128::new(key)).expect("DCP error")
+    }
+}
+
+impl AeadInPlace for Aes128Gcm {
+    type NonceSize = consts::U12;
+    type TagSize = consts::U16;
+    type CiphertextOverhead = consts::U0;
+
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<consts::U12>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<Tag<consts::U16>, AeadError> {
+        if buffer.len() as u64 > MAX_PLAINTEXT_LEN {
+            return Err(AeadError);
+        }
+
+        let j0 = Self::j0(nonce);
+        ctr::apply_keystream(&self.cipher, j0.wrapping_add(1), buffer).map_err(|_| AeadError)?;
+
+        let tag = self
+            .tag(j0, associated_data, buffer)
+            .map_err(|_| AeadError)?;
+        Ok(tag.into())
+    }
+
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<consts::U12>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &Tag<consts::U16>,
+    ) -> Result<(), AeadError> {
+        if buffer.len() as u64 > MAX_PLAINTEXT_LEN {
+            return Err(AeadError);
+        }
+
+        let j0 = Self::j0(nonce);
+        let expected = self
+            .tag(j0, associated_data, buffer)
+            .map_err(|_| AeadError)?;
+
+        // constant-time comparison
+        let diff = expected
+            .iter()
+            .zip(tag.iter())
+            .fold(0, |diff, (x, y)| diff | (x ^ y));
+
+        if diff != 0 {
+            // the buffer is left untouched
+            return Err(AeadError);
+        }
+
+        ctr::apply_keystream(&self.cipher, j0.wrapping_add(1), buffer).map_err(|_| AeadError)
+    }
+}
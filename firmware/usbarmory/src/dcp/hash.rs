@@ -0,0 +1,294 @@
+//! Hashing logic shared by `Sha256`, `Sha1` and `Crc32`
+
+use core::{
+    marker::PhantomData,
+    ptr::NonNull,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use digest::generic_array::typenum::consts;
+use heapless::Vec;
+use pac::hw_dcp::HW_DCP;
+use typenum::marker_traits::Unsigned;
+
+use crate::{
+    dcp::{self, job, Cmd, Control0, Control1, Error, HashSelect, HASH_CHANNEL},
+    util::{self, Align4},
+};
+
+// The DCP has a 32 *bit* counter and won't accept more than 512 MB of input
+const MAX_COUNT: usize = 512 * 1024 * 1024;
+
+/// Size of the blocks fed to the hardware, in bytes
+///
+/// This is the block size of both SHA-1 and SHA-256
+pub type BlockSize = consts::U64;
+
+/// Size of the output payload; big enough for the largest digest (SHA-256)
+pub const OUTPUT_SIZE: usize = 32;
+
+// The hashing channel can only be used by one algorithm at a time
+static IN_USE: AtomicBool = AtomicBool::new(false);
+
+/// Handle to the hashing channel configured to use one of the hash algorithms
+pub struct Engine {
+    _not_send_or_sync: PhantomData<*const ()>,
+    algorithm: HashSelect,
+    next_is_first_block: bool,
+    // We collect partial blocks until we have a complete block
+    buffer: Vec<u8, BlockSize>,
+    /// Input counter, in *bytes*
+    count: usize,
+}
+
+unsafe impl Send for Engine {}
+
+impl Engine {
+    /// Gets a handle to the hashing channel
+    ///
+    /// This function returns `None` if the channel is currently in use
+    pub fn take(algorithm: HashSelect) -> Option<Self> {
+        if IN_USE
+            .compare_exchange_weak(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            dcp::init();
+
+            // NOTE(borrow_unchecked) single-instruction write to a stateless register
+            HW_DCP::borrow_unchecked(|dcp| {
+                dcp.CHANNELCTRL_SET.write(1 << HASH_CHANNEL);
+            });
+
+            Some(Engine {
+                _not_send_or_sync: PhantomData,
+                algorithm,
+                buffer: Vec::new(),
+                count: 0,
+                next_is_first_block: true,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn input(&mut self, input: &[u8]) -> Result<(), Error> {
+        if input.is_empty() {
+            // no-op
+            return Ok(());
+        }
+
+        self.count(input.len());
+
+        if self.buffer.is_empty() {
+            // process leading blocks then store the leftover
+            self.process_and_push(input)
+        } else if self.buffer.len() + input.len() < BlockSize::USIZE {
+            // not enough to fill a block
+            let _ = self.buffer.extend_from_slice(input);
+            Ok(())
+        } else {
+            // complete the partial block and then process the rest
+            self.push_and_process(input)
+        }
+    }
+
+    /// Processes the buffered input and returns the raw output payload
+    ///
+    /// Interpreting the payload (digest size, byte order) is left to the caller
+    pub fn finalize(mut self) -> Result<[u8; OUTPUT_SIZE], Error> {
+        self.finalize_reset()
+    }
+
+    /// Like `finalize` but keeps the channel and leaves the engine ready to hash a new message
+    pub fn finalize_reset(&mut self) -> Result<[u8; OUTPUT_SIZE], Error> {
+        // word aligned for performance
+        let mut output = Align4 {
+            inner: [0; OUTPUT_SIZE],
+        };
+
+        let cmd = Cmd::new();
+
+        cmd.control0.set(
+            *Control0::new()
+                .enable_hash(true)
+                .hash_output(false) // hash input data
+                .hash_init(self.next_is_first_block)
+                .hash_term(true),
+        );
+
+        cmd.control1
+            .set(*Control1::new().hash_select(self.algorithm));
+
+        if !self.buffer.is_empty() {
+            cmd.src_buffer_addr
+                .set(Some(NonNull::from(&self.buffer[0])));
+            cmd.buffer_size.set(self.buffer.len());
+        }
+
+        cmd.payload_pointer
+            .set(Some(NonNull::from(&mut output.inner[0])));
+
+        let res = dcp::run(HASH_CHANNEL, core::slice::from_ref(&cmd));
+        self.reset();
+        res?;
+
+        Ok(output.inner)
+    }
+
+    /// Prepares the commands that process all the complete blocks in `input` and buffers the
+    /// leftover bytes
+    ///
+    /// Returns the number of commands that were written to the channel's job chain
+    pub fn input_job(&mut self, input: &'static [u8]) -> usize {
+        self.count(input.len());
+
+        if self.buffer.len() + input.len() < BlockSize::USIZE {
+            // not enough to fill a block
+            let _ = self.buffer.extend_from_slice(input);
+            return 0;
+        }
+
+        let slot = job::slot(job::HASH_SLOT);
+        let chain = slot.chain();
+        let mut len = 0;
+
+        let rest = if self.buffer.is_empty() {
+            input
+        } else {
+            // complete the partial block; `self` may move while the job is in progress so the
+            // block is copied into memory that stays put
+            let (head, tail) = input.split_at(BlockSize::USIZE - self.buffer.len());
+            let _ = self.buffer.extend_from_slice(head);
+            let scratch = unsafe { &mut *slot.scratch() };
+            scratch.copy_from_slice(&self.buffer);
+            self.buffer.clear();
+
+            setup(
+                self.algorithm,
+                &mut self.next_is_first_block,
+                &chain[len],
+                scratch,
+            );
+            len += 1;
+
+            tail
+        };
+
+        let (blocks, leftover) = rest.split_at(util::round_down(rest.len(), BlockSize::USIZE));
+        if !blocks.is_empty() {
+            setup(
+                self.algorithm,
+                &mut self.next_is_first_block,
+                &chain[len],
+                blocks,
+            );
+            len += 1;
+        }
+
+        let _ = self.buffer.extend_from_slice(leftover);
+
+        len
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.count = 0;
+        self.next_is_first_block = true;
+    }
+
+    // updates the input counter
+    fn count(&mut self, len: usize) {
+        if self
+            .count
+            .checked_add(len)
+            .map(|total| total > MAX_COUNT)
+            .unwrap_or(true)
+        {
+            panic!("total hash input exceeds the DCP capacity");
+        }
+
+        self.count += len;
+    }
+
+    fn process_and_push(&mut self, input: &[u8]) -> Result<(), Error> {
+        debug_assert!(self.buffer.is_empty());
+
+        let leftover = if input.len() >= BlockSize::USIZE {
+            let (blocks, leftover) =
+                input.split_at(util::round_down(input.len(), BlockSize::USIZE));
+
+            self.process_blocks(blocks)?;
+            leftover
+        } else {
+            input
+        };
+
+        let _ = self.buffer.extend_from_slice(leftover);
+
+        Ok(())
+    }
+
+    fn push_and_process(&mut self, input: &[u8]) -> Result<(), Error> {
+        debug_assert!(!self.buffer.is_empty());
+
+        let (head, tail) = input.split_at(BlockSize::USIZE - self.buffer.len());
+
+        // complete a block
+        let _ = self.buffer.extend_from_slice(head);
+        let res = process_blocks(self.algorithm, &mut self.next_is_first_block, &self.buffer);
+        self.buffer.clear();
+        res?;
+
+        self.process_and_push(tail)
+    }
+
+    fn process_blocks(&mut self, blocks: &[u8]) -> Result<(), Error> {
+        process_blocks(self.algorithm, &mut self.next_is_first_block, blocks)
+    }
+}
+
+// NOTE only the last block (`hash_term = true`) can be smaller than 512 bits
+fn process_blocks(
+    algorithm: HashSelect,
+    next_is_first_block: &mut bool,
+    blocks: &[u8],
+) -> Result<(), Error> {
+    let cmd = Cmd::new();
+
+    setup(algorithm, next_is_first_block, &cmd, blocks);
+
+    dcp::run(HASH_CHANNEL, core::slice::from_ref(&cmd))
+}
+
+// Configures `cmd` to hash the complete `blocks`
+fn setup(algorithm: HashSelect, next_is_first_block: &mut bool, cmd: &Cmd, blocks: &[u8]) {
+    debug_assert!(!blocks.is_empty());
+    debug_assert_eq!(blocks.len() % BlockSize::USIZE, 0);
+
+    cmd.control0.set(
+        *Control0::new()
+            .enable_hash(true)
+            .hash_output(false) // hash input data
+            .hash_init(*next_is_first_block)
+            .hash_term(false),
+    );
+    *next_is_first_block = false;
+
+    cmd.control1.set(*Control1::new().hash_select(algorithm));
+
+    cmd.src_buffer_addr.set(Some(NonNull::from(&blocks[0])));
+    // redundant: `Cmd::new` sets `dest_buffer_addr` to `None`
+    // cmd.dest_buffer_addr.set(None);
+    cmd.buffer_size.set(blocks.len());
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        // NOTE(borrow_unchecked) single-instruction write to a stateless register
+        HW_DCP::borrow_unchecked(|dcp| {
+            dcp.CHANNELCTRL_CLR.write(1 << HASH_CHANNEL);
+        });
+
+        IN_USE.store(false, Ordering::Release);
+    }
+}
@@ -0,0 +1,116 @@
+use core::ptr;
+
+use digest::Reset;
+
+use crate::dcp::{Error, Sha256};
+
+/// Block size of SHA-256, in bytes
+const BLOCK_SIZE: usize = 64;
+
+/// Size of the MAC, in bytes
+const OUTPUT_SIZE: usize = 32;
+
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+/// HMAC-SHA256 (RFC 2104) on top of the SHA-256 channel
+///
+/// Both the inner and the outer hash are computed by the DCP; the SHA-256 state is reset between
+/// them. The key is kept in RAM for as long as this value exists
+///
+/// If an operation reports an error the MAC state is undefined; call `reset` before computing a
+/// new MAC
+pub struct HmacSha256 {
+    hasher: Sha256,
+    // the key XOR-ed with the inner padding
+    inner_key: [u8; BLOCK_SIZE],
+}
+
+impl HmacSha256 {
+    /// Uses the SHA-256 channel to compute MACs with the given `key`
+    ///
+    /// Keys longer than 64 bytes are hashed, as per RFC 2104
+    pub fn from_hasher(hasher: Sha256, key: &[u8]) -> Result<Self, Error> {
+        let mut hmac = HmacSha256 {
+            hasher,
+            inner_key: [0; BLOCK_SIZE],
+        };
+        hmac.set_key(key)?;
+        Ok(hmac)
+    }
+
+    /// Feeds `input` to the MAC
+    pub fn input(&mut self, input: &[u8]) -> Result<(), Error> {
+        self.hasher.try_input(input)
+    }
+
+    /// Returns the MAC of all the input data and gets ready to process a new message with the same
+    /// key
+    pub fn finalize_reset(&mut self) -> Result<[u8; OUTPUT_SIZE], Error> {
+        let inner = self.hasher.try_finalize_reset()?;
+
+        let mut outer_key = self.inner_key;
+        for byte in outer_key.iter_mut() {
+            *byte ^= IPAD ^ OPAD;
+        }
+
+        let res = self.hasher.try_input(&outer_key);
+        unsafe { ptr::write_volatile(&mut outer_key, [0; BLOCK_SIZE]) }
+        res?;
+
+        self.hasher.try_input(&inner)?;
+        let output = self.hasher.try_finalize_reset()?;
+
+        self.hasher.try_input(&self.inner_key)?;
+
+        let mut mac = [0; OUTPUT_SIZE];
+        mac.copy_from_slice(&output);
+        Ok(mac)
+    }
+
+    /// Checks, in constant time, that the MAC of all the input data matches `mac` and gets ready
+    /// to process a new message with the same key
+    pub fn verify_reset(&mut self, mac: &[u8; OUTPUT_SIZE]) -> Result<bool, Error> {
+        let expected = self.finalize_reset()?;
+
+        let diff = expected
+            .iter()
+            .zip(mac.iter())
+            .fold(0, |diff, (x, y)| diff | (x ^ y));
+
+        Ok(diff == 0)
+    }
+
+    /// Discards the input data
+    pub fn reset(&mut self) -> Result<(), Error> {
+        self.hasher.reset();
+        self.hasher.try_input(&self.inner_key)
+    }
+
+    /// Changes the key used to compute the MAC; this also discards the input data
+    pub(crate) fn set_key(&mut self, key: &[u8]) -> Result<(), Error> {
+        self.hasher.reset();
+
+        self.inner_key = [0; BLOCK_SIZE];
+        if key.len() > BLOCK_SIZE {
+            self.hasher.try_input(key)?;
+            let digest = self.hasher.try_finalize_reset()?;
+            self.inner_key[..digest.len()].copy_from_slice(&digest);
+        } else {
+            self.inner_key[..key.len()].copy_from_slice(key);
+        }
+
+        for byte in self.inner_key.iter_mut() {
+            *byte ^= IPAD;
+        }
+
+        self.hasher.try_input(&self.inner_key)
+    }
+}
+
+impl Drop for HmacSha256 {
+    fn drop(&mut self) {
+        // don't leave the key behind
+        unsafe { ptr::write_volatile(&mut self.inner_key, [0; BLOCK_SIZE]) }
+    }
+}
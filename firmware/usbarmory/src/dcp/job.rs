@@ -0,0 +1,233 @@
+//! Non-blocking DCP operations
+
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{self, Ordering},
+    time::Duration,
+};
+
+use pac::HW_DCP;
+use usbarmory_rt::Interrupt;
+
+use crate::{
+    dcp::{self, Cmd, Error, MAX_CHAIN_LEN},
+    memlog,
+    util::{self, Align4},
+};
+
+/// Size of the per-channel scratch buffer, in bytes
+///
+/// Big enough for one hash block or one AES IV
+pub(super) const SCRATCH_SIZE: usize = 64;
+
+/// Job memory used by the `Sha256`, `Sha1` and `Crc32` handles
+pub(super) const HASH_SLOT: usize = 0;
+/// Job memory used by the cipher that uses one of the hardware keys
+pub(super) const AES128_HW_SLOT: usize = 1;
+/// Job memory used by the ciphers that use RAM keys; the cipher that uses key slot `n` uses
+/// `AES128_RAM_SLOT + n`
+pub(super) const AES128_RAM_SLOT: usize = 2;
+/// Job memory used by the `Memcpy` handle
+pub(super) const MEMCPY_SLOT: usize = AES128_RAM_SLOT + 4;
+
+const NSLOTS: usize = MEMCPY_SLOT + 1;
+
+/// Memory shared with the hardware while a job is in progress
+pub(super) struct Slot {
+    chain: [Cmd; MAX_CHAIN_LEN],
+    // NOTE(UnsafeCell) may be modified by the hardware
+    scratch: UnsafeCell<Align4<[u8; SCRATCH_SIZE]>>,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            chain: Cmd::new_chain(),
+            scratch: UnsafeCell::new(Align4 {
+                inner: [0; SCRATCH_SIZE],
+            }),
+        }
+    }
+
+    /// Returns the command chain, with all commands cleared
+    pub(super) fn chain(&self) -> &[Cmd; MAX_CHAIN_LEN] {
+        for cmd in self.chain.iter() {
+            cmd.clear();
+        }
+        &self.chain
+    }
+
+    /// Returns a pointer to the (word aligned) scratch buffer
+    pub(super) fn scratch(&self) -> *mut [u8; SCRATCH_SIZE] {
+        unsafe { &mut (*self.scratch.get()).inner as *mut _ }
+    }
+}
+
+struct Slots {
+    inner: [Slot; NSLOTS],
+}
+
+// NOTE(Sync) each slot is tied to a handle (e.g. `Aes128`) and there's at most one instance of
+// each handle; `Job` holds on to that handle while the slot is in use
+unsafe impl Sync for Slots {}
+
+static SLOTS: Slots = Slots {
+    inner: [
+        Slot::new(),
+        Slot::new(),
+        Slot::new(),
+        Slot::new(),
+        Slot::new(),
+        Slot::new(),
+        Slot::new(),
+    ],
+};
+
+/// Returns the job memory with the specified `index`
+///
+/// The caller must be the owner of the slot and must not have a job in progress on it
+pub(super) fn slot(index: usize) -> &'static Slot {
+    &SLOTS.inner[index]
+}
+
+/// A DCP operation that's being processed in the background
+///
+/// `R` are the resources, e.g. the cipher handle and the data buffer, that the hardware is working
+/// on; they are handed back by `wait` when the hardware is done with them.
+///
+/// Completion of the job triggers an interrupt that depends on the DCP channel that processes the
+/// job: `DCP_VMI` for channel 0 and `DCP` for channels 1 to 3; `interrupt` returns the right one.
+/// To avoid busy waiting bind a (RTIC) hardware task to that interrupt and call `is_done` followed
+/// by `wait` from it; `wait` acknowledges the interrupt so it must be called from the interrupt
+/// handler.
+///
+/// Dropping a `Job` blocks until the hardware is done with it, or until the job times out
+#[must_use = "dropping a `Job` blocks until the hardware is done with it"]
+pub struct Job<R> {
+    channel: u8,
+    slot: &'static Slot,
+    // number of commands that were submitted; `0` if nothing was submitted
+    len: usize,
+    // the error that kept the commands from being submitted, if any
+    error: Option<Error>,
+    // how long the hardware may take to process the commands
+    timeout: Duration,
+    resources: Option<R>,
+}
+
+impl<R> Job<R> {
+    /// Submits the first `len` commands of the `slot`'s chain to the specified `channel`
+    ///
+    /// The hardware is given `timeout` to process the commands
+    pub(super) fn start(
+        channel: u8,
+        slot: &'static Slot,
+        len: usize,
+        timeout: Duration,
+        resources: R,
+    ) -> Self {
+        let mut error = None;
+        let mut len = len;
+        if len != 0 {
+            let chain = &slot.chain[..len];
+            let last = chain.last().expect("UNREACHABLE");
+            last.control0.set(*last.control0.get().interrupt(true));
+
+            // NOTE(borrow_unchecked) single-instruction write to a stateless register
+            HW_DCP::borrow_unchecked(|dcp| dcp.CTRL_SET.write(1 << channel));
+
+            if let Err(e) = dcp::start(channel, chain, timeout) {
+                // nothing was submitted
+                error = Some(e);
+                len = 0;
+            }
+        }
+
+        Job {
+            channel,
+            slot,
+            len,
+            error,
+            timeout,
+            resources: Some(resources),
+        }
+    }
+
+    /// Returns the interrupt that signals the completion of this job
+    pub fn interrupt(&self) -> Interrupt {
+        if self.channel == 0 {
+            Interrupt::DCP_VMI
+        } else {
+            Interrupt::DCP
+        }
+    }
+
+    /// Returns `true` if the hardware is done with this job
+    pub fn is_done(&self) -> bool {
+        self.check().is_some()
+    }
+
+    /// [Blocking] Waits until the hardware is done with this job and returns the resources used
+    /// by the job, plus the outcome of the operation
+    ///
+    /// If the hardware reported an error the contents of the data buffer are unspecified. If the
+    /// hardware doesn't finish the job in time `Error::Timeout` is returned and the channel is
+    /// reset; see `Error::Timeout`
+    pub fn wait(mut self) -> (R, Result<(), Error>) {
+        let res = self.finish();
+        let resources = self.resources.take().expect("UNREACHABLE");
+        (resources, res)
+    }
+
+    fn check(&self) -> Option<Result<(), Error>> {
+        if self.len == 0 {
+            Some(self.error.map_or(Ok(()), Err))
+        } else {
+            dcp::check(self.channel, &self.slot.chain[..self.len])
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        let mut res = None;
+        let is_done = || {
+            res = self.check();
+            res.is_some()
+        };
+        if util::wait_for_or_timeout(is_done, self.timeout).is_err() {
+            memlog!(
+                "DCP job timeout (channel={}, STAT={:#010x})",
+                self.channel,
+                dcp::channel_status(self.channel)
+            );
+
+            // stop the channel from touching the job memory and the resources after we hand them
+            // back
+            dcp::reset_channel(self.channel);
+            res = Some(Err(Error::Timeout));
+        }
+        let res = res.unwrap_or(Ok(()));
+
+        if self.len != 0 {
+            self.len = 0;
+
+            // the crypto engine is done with the commands and has transferred ownership back to
+            // us
+            atomic::fence(Ordering::Acquire);
+
+            // acknowledge the interrupt
+            // NOTE(borrow_unchecked) single-instruction write to a stateless register
+            HW_DCP::borrow_unchecked(|dcp| dcp.STAT_CLR.write(1 << self.channel));
+        }
+
+        res
+    }
+}
+
+impl<R> Drop for Job<R> {
+    fn drop(&mut self) {
+        if self.resources.is_some() {
+            // the hardware may still be using the resources
+            let _ = self.finish();
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use core::{
+    cmp,
+    marker::PhantomData,
+    ptr::NonNull,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use pac::HW_DCP;
+
+use crate::dcp::{self, job, Cmd, Control0, Error, Job, MEMCPY_CHANNEL};
+
+// The `Memcpy` handle owns the job memory of the memory copy channel
+static IN_USE: AtomicBool = AtomicBool::new(false);
+
+/// [Blocking] Copies all the bytes in `src` into `dst` using the DCP
+///
+/// The DCP channel used for this operation is shared by all callers; a call made while the
+/// channel is busy waits for the current operation to complete
+///
+/// # Panics
+///
+/// This function panics if the two slices have different lengths
+pub fn memcpy(dst: &mut [u8], src: &[u8]) -> Result<(), Error> {
+    assert_eq!(
+        dst.len(),
+        src.len(),
+        "source and destination have different lengths"
+    );
+
+    if dst.is_empty() {
+        return Ok(());
+    }
+
+    enable();
+
+    let cmd = Cmd::new();
+    setup_memcpy(&cmd, dst, src);
+    dcp::run_timeout(
+        MEMCPY_CHANNEL,
+        core::slice::from_ref(&cmd),
+        timeout(dst.len()),
+    )
+}
+
+/// [Blocking] Fills `dst` with the 32-bit `pattern` using the DCP
+///
+/// The pattern is stored in little endian order at every word-aligned address so `fill(dst, 0)`
+/// zeroes `dst`. Bytes at the unaligned start and end of `dst` are written by the CPU
+///
+/// The DCP channel used for this operation is shared by all callers; a call made while the
+/// channel is busy waits for the current operation to complete
+pub fn fill(dst: &mut [u8], pattern: u32) -> Result<(), Error> {
+    let words = fill_unaligned(dst, pattern);
+
+    if words.is_empty() {
+        return Ok(());
+    }
+
+    enable();
+
+    let cmd = Cmd::new();
+    setup_fill(&cmd, words, pattern);
+    dcp::run_timeout(
+        MEMCPY_CHANNEL,
+        core::slice::from_ref(&cmd),
+        timeout(words.len()),
+    )
+}
+
+/// Handle to the memory copy channel that's used to perform copies and fills in the background
+///
+/// See `Job` for details. The memory copy channel is DCP channel 0 so the completion of its jobs
+/// triggers the `DCP_VMI` interrupt, not the `DCP` one; bind the hardware task that waits for the
+/// jobs to `DCP_VMI`
+pub struct Memcpy {
+    _not_send_or_sync: PhantomData<*mut ()>,
+}
+
+// the handle only owns memory in `job::SLOTS`
+unsafe impl Send for Memcpy {}
+
+impl Memcpy {
+    /// Gets a handle to the memory copy channel
+    ///
+    /// This function returns `None` if the handle has already been taken. The blocking `memcpy`
+    /// and `fill` functions can be used regardless of the existence of this handle
+    pub fn take() -> Option<Self> {
+        if IN_USE
+            .compare_exchange_weak(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            Some(Memcpy {
+                _not_send_or_sync: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Copies all the bytes in `src` into `dst`
+    ///
+    /// The operation is performed in the background and its completion triggers the `DCP_VMI`
+    /// interrupt; see `Job` for details
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the two slices have different lengths
+    pub fn memcpy_job(
+        self,
+        dst: &'static mut [u8],
+        src: &'static [u8],
+    ) -> Job<(Self, &'static mut [u8], &'static [u8])> {
+        assert_eq!(
+            dst.len(),
+            src.len(),
+            "source and destination have different lengths"
+        );
+
+        let slot = job::slot(job::MEMCPY_SLOT);
+        let len = if dst.is_empty() {
+            // nothing to do
+            0
+        } else {
+            enable();
+            setup_memcpy(&slot.chain()[0], dst, src);
+            1
+        };
+
+        let timeout = timeout(dst.len());
+        Job::start(MEMCPY_CHANNEL, slot, len, timeout, (self, dst, src))
+    }
+
+    /// Fills `dst` with the 32-bit `pattern`
+    ///
+    /// The operation is performed in the background and its completion triggers the `DCP_VMI`
+    /// interrupt; see `Job` for details. See `fill` for the layout of the pattern in memory
+    pub fn fill_job(self, dst: &'static mut [u8], pattern: u32) -> Job<(Self, &'static mut [u8])> {
+        let slot = job::slot(job::MEMCPY_SLOT);
+        let words = fill_unaligned(dst, pattern);
+        let timeout = timeout(words.len());
+        let len = if words.is_empty() {
+            // nothing to do
+            0
+        } else {
+            enable();
+            setup_fill(&slot.chain()[0], words, pattern);
+            1
+        };
+
+        Job::start(MEMCPY_CHANNEL, slot, len, timeout, (self, dst))
+    }
+}
+
+impl Drop for Memcpy {
+    fn drop(&mut self) {
+        IN_USE.store(false, Ordering::Release)
+    }
+}
+
+// The channel is never disabled, except when an operation times out, so it's (re-)enabled before
+// every operation
+fn enable() {
+    dcp::init();
+
+    // NOTE single instruction write to a stateless register
+    // NOTE(| 1 << 3) see `Aes128::new_ram`
+    HW_DCP::borrow_unchecked(|dcp| dcp.CHANNELCTRL_SET.write((1 << MEMCPY_CHANNEL) | (1 << 3)));
+}
+
+// Large copies take long; allow the default timeout for each started MiB
+fn timeout(len: usize) -> Duration {
+    dcp::default_timeout() * (1 + (len >> 20) as u32)
+}
+
+fn setup_memcpy(cmd: &Cmd, dst: &mut [u8], src: &[u8]) {
+    cmd.control0.set(*Control0::new().enable_memcpy(true));
+    cmd.src_buffer_addr.set(Some(NonNull::from(&src[0])));
+    cmd.dest_buffer_addr.set(Some(NonNull::from(&mut dst[0])));
+    cmd.buffer_size.set(dst.len());
+}
+
+fn setup_fill(cmd: &Cmd, words: &mut [u8], pattern: u32) {
+    cmd.control0
+        .set(*Control0::new().enable_memcpy(true).constant_fill(true));
+    // in constant fill mode the source address field holds the pattern
+    cmd.src_buffer_addr.set(NonNull::new(pattern as *mut u8));
+    cmd.dest_buffer_addr.set(Some(NonNull::from(&mut words[0])));
+    cmd.buffer_size.set(words.len());
+}
+
+// Writes `pattern` into the unaligned start and end of `dst` and returns the word-aligned part
+// that's left to the hardware
+fn fill_unaligned(dst: &mut [u8], pattern: u32) -> &mut [u8] {
+    let bytes = pattern.to_le_bytes();
+    let misalignment = dst.as_ptr() as usize % 4;
+
+    let head_len = cmp::min((4 - misalignment) % 4, dst.len());
+    let (head, body) = dst.split_at_mut(head_len);
+    for (byte, value) in head.iter_mut().zip(&bytes[misalignment..]) {
+        *byte = *value;
+    }
+
+    let words_len = body.len() - body.len() % 4;
+    let (words, tail) = body.split_at_mut(words_len);
+    for (byte, value) in tail.iter_mut().zip(&bytes) {
+        *byte = *value;
+    }
+
+    words
+}
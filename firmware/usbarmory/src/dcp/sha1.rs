@@ -0,0 +1,85 @@
+use digest::generic_array::{typenum::consts, GenericArray};
+use digest::{BlockInput, FixedOutput, Input, Reset};
+
+use crate::dcp::{self, hash::Engine, job, Error, HashSelect, Job, HASH_CHANNEL};
+
+/// SHA-1 channel
+///
+/// **WARNING** SHA-1 is no longer considered secure; only use it to interoperate with existing
+/// protocols
+pub struct Sha1 {
+    engine: Engine,
+}
+
+impl Sha1 {
+    /// Gets a handle to the SHA-1 channel
+    ///
+    /// This function returns `None` if the channel is currently in use
+    ///
+    /// **NOTE** `Sha256`, `Sha1` and `Crc32` share the same channel so only one of them can be
+    /// in use at any given time
+    pub fn take() -> Option<Self> {
+        Engine::take(HashSelect::Sha1).map(|engine| Sha1 { engine })
+    }
+
+    /// Feeds `input` to the hasher
+    ///
+    /// This is the fallible version of `Input::input`
+    pub fn try_input(&mut self, input: &[u8]) -> Result<(), Error> {
+        self.engine.input(input)
+    }
+
+    /// Returns the digest of all the input data
+    ///
+    /// This is the fallible version of `FixedOutput::fixed_result`
+    pub fn try_finalize(self) -> Result<GenericArray<u8, consts::U20>, Error> {
+        let output = self.engine.finalize()?;
+
+        // the engine returns data in reverse order (big endian?)
+        let mut digest = GenericArray::default();
+        digest.copy_from_slice(&output[..20]);
+        digest.reverse();
+        Ok(digest)
+    }
+
+    /// Feeds `input` to the hasher
+    ///
+    /// All the complete blocks are processed in the background; see `Job` for details
+    pub fn input_job(mut self, input: &'static [u8]) -> Job<(Self, &'static [u8])> {
+        let len = self.engine.input_job(input);
+        Job::start(
+            HASH_CHANNEL,
+            job::slot(job::HASH_SLOT),
+            len,
+            dcp::default_timeout(),
+            (self, input),
+        )
+    }
+}
+
+impl Input for Sha1 {
+    fn input<B>(&mut self, bytes: B)
+    where
+        B: AsRef<[u8]>,
+    {
+        self.try_input(bytes.as_ref()).expect("DCP error")
+    }
+}
+
+impl FixedOutput for Sha1 {
+    type OutputSize = consts::U20;
+
+    fn fixed_result(self) -> GenericArray<u8, consts::U20> {
+        self.try_finalize().expect("DCP error")
+    }
+}
+
+impl Reset for Sha1 {
+    fn reset(&mut self) {
+        self.engine.reset()
+    }
+}
+
+impl BlockInput for Sha1 {
+    type BlockSize = consts::U64;
+}
@@ -1,128 +1,73 @@
 //! Low level access to the eMMC
 
-use core::{
-    num::NonZeroU16,
-    sync::atomic::{self, Ordering},
-};
+use core::{cell::Cell, fmt, ops::Range, slice, time::Duration};
 
+use emmc_regs::{CID_SIZE, EXT_CSD_SIZE};
 use pac::{uSDHC2, SRC};
 
-mod card;
-mod cmd;
-
-use core::{fmt, time::Duration};
-
 use crate::{
-    memlog, memlog_flush_and_reset,
+    memlog,
     storage::{Block, ManagedBlockDevice, BLOCK_SIZE},
     time::{self, Instant},
-    util,
+    usdhc::{blocks_in, cmd::Command, default_timeout, fits_in, Frequency, Rca, Usdhc},
 };
-use cmd::Command;
+use recovery::{backoff, MAX_RETRIES};
 
-fn default_timeout() -> Duration {
-    Duration::from_millis(100)
-}
+pub use crate::usdhc::{
+    card::{State, Status},
+    Error as ErrorKind,
+};
+pub use bus::{BusMode, BusWidth, Timing};
+pub use emmc_regs::{Cid, DeviceType, ExtCsd, LifeTime, PreEol};
+pub use partition::{BootPartition, Partition, PartitionRef};
+pub use rpmb::{Error as RpmbError, Rpmb};
+pub use transfer::Transfer;
+pub use write_protect::WriteProtection;
+
+mod bus;
+mod cache;
+mod erase;
+mod partition;
+mod recovery;
+mod rpmb;
+mod transfer;
+mod write_protect;
 
 /// [Singleton] Access to the on-board eMMC
 #[allow(non_camel_case_types)]
 pub struct eMMC {
-    usdhc: uSDHC2,
-    /// Currently selected card
-    selected: Option<Rca>,
+    usdhc: Usdhc<pac::usdhc::_2>,
     blocks: u32,
-    verbose: bool,
-    width: Width,
-}
-
-#[derive(Clone, Copy)]
-enum Width {
-    /// 1-bit
-    B1,
-    /// 8-bit
-    B8,
-    // modes not listed: 4-bit, 4-bit DDR
+    /// Last value written to the PARTITION_CONFIG field of the EXT_CSD register
+    partition_config: Cell<u8>,
+    /// EXT_CSD register, as read during initialization
+    ext_csd: ExtCsd,
+    cid: Cid,
+    bus_mode: Cell<BusMode>,
+    cache_enabled: Cell<bool>,
 }
 
-impl Width {
-    fn dtw(self) -> u8 {
-        match self {
-            Width::B1 => 0b00,
-            Width::B8 => 0b10,
-        }
-    }
+// NOTE(Send) the `eMMC` owns the `uSDHC2` peripheral; no other context can
+// access its registers
+unsafe impl Send for eMMC {}
 
-    fn ddr(self) -> bool {
-        false
-    }
-}
+// EXT_CSD fields (byte offsets)
+const EXT_CSD_ERASE_GROUP_DEF: u8 = 175;
+const EXT_CSD_PARTITION_CONFIG: u8 = 179;
 
-type Rca = NonZeroU16;
-
-/// Command complete
-const INT_STATUS_CC: u32 = 1; // bit 0
-/// Transfer complete
-const INT_STATUS_TC: u32 = 1 << 1;
-/// DMA Interrupt
-const INT_STATUS_DINT: u32 = 1 << 3;
-/// Command Timeout Error
-const INT_STATUS_CTOE: u32 = 1 << 16;
-/// Command CRC Error
-const INT_STATUS_CCE: u32 = 1 << 17;
-/// Command End Bit Error
-const INT_STATUS_CEBE: u32 = 1 << 18;
-/// Command Index Error
-const INT_STATUS_CIE: u32 = 1 << 19;
-/// Data Timeout Error
-const INT_STATUS_DTOE: u32 = 1 << 20;
-/// Data CRC Error
-const INT_STATUS_DCE: u32 = 1 << 21;
-/// Data End Bit Error
-const INT_STATUS_DEBE: u32 = 1 << 22;
-/// DMA error
-const INT_STATUS_DMAE: u32 = 1 << 28;
-
-const INT_STATUS_ANY_ERROR: u32 = INT_STATUS_CTOE
-    | INT_STATUS_CCE
-    | INT_STATUS_CEBE
-    | INT_STATUS_CIE
-    | INT_STATUS_DTOE
-    | INT_STATUS_DCE
-    | INT_STATUS_DEBE
-    | INT_STATUS_DMAE;
-
-/// Command Inhibit (DATA)
-const PRES_STATE_CDIHB: u32 = 1 << 1;
-/// Command Inhibit
-const PRES_STATE_CIHB: u32 = 1; // bit 0
-/// Data Line Active
-const PRES_STATE_DLA: u32 = 1 << 2;
-
-/// Bits that must always be ones
-const MIX_CTRL_RESERVED: u32 = 1 << 31;
-/// Single block
-const MIX_CTRL_MBSEL_SINGLE: u32 = 0 << 5;
-/// Enable the DMA
-const MIX_CTRL_DMAEN_ENABLE: u32 = 1; // bit 0
-const MIX_CTRL_DDR_EN: u32 = 1 << 3;
-
-const VEND_SPEC_CKEN: u32 = 1 << 14;
+/// `SWITCH` access mode: write the value byte to the EXT_CSD field
+const SWITCH_WRITE_BYTE: u32 = 0b11 << 24;
 
 /// Relative address assigned to the eMMC (by the ROM bootloader)
 // must be greater than the default (0x01)
 const RCA: Rca = unsafe { Rca::new_unchecked(0x02) };
 
-#[derive(Debug)]
-enum Frequency {
-    K400,
-    M20,
-}
-
 impl eMMC {
     /// Gets a handle to the `eMMC` singleton
     ///
-    /// This method returns the `Some` only once. When successful this method
-    /// consumes the `uSDHC2` peripheral
+    /// This method returns the `Some` only once and consumes the `uSDHC2`
+    /// peripheral. A failed initialization is retried, starting from a reset
+    /// of the host, a few times before the error is returned
     pub fn take() -> Option<Result<Self, Error>> {
         uSDHC2::take().map(|usdhc| {
             // boot mode register
@@ -130,141 +75,69 @@ impl eMMC {
 
             // booted from the uSD
             if sbmr1 & 0b1110_0000 == 0b0100_0000 {
-                panic!("the eMMC HAL doesn't work when booting from the uSD");
+                memlog!("the eMMC HAL doesn't work when booting from the uSD");
+                return Err(ErrorKind::Other.into());
             }
 
-            // clear any pending status
-            usdhc.INT_STATUS.clear(!0);
-            // we'll *not* use the advanced DMA (ADMA)
-            usdhc.PROT_CTRL.rmw(|r| r & !(0b11 << 8));
-
-            // NOTE the ROM bootloader has already configured the card to run at high speed and to
-            // work with an 8-bit bus
             let mut emmc = eMMC {
-                usdhc,
+                usdhc: Usdhc::new(usdhc),
                 blocks: 0,
-                selected: Some(RCA),
-                verbose: false,
-                width: Width::B8,
+                partition_config: Cell::new(0),
+                ext_csd: ExtCsd::from_bytes(&[0; EXT_CSD_SIZE]),
+                cid: Cid::from_bytes(&[0; CID_SIZE]),
+                bus_mode: Cell::new(BusMode::LEGACY),
+                cache_enabled: Cell::new(false),
             };
 
-            emmc.software_reset();
-            emmc.reset_cards().expect("fatal");
-            emmc.voltage_validation().expect("fatal");
-            emmc.register_cards().expect("fatal");
-            emmc.select_card(RCA).expect("fatal");
-            emmc.change_bus_width(Width::B8, true).expect("fatal");
-            emmc.change_frequency(Frequency::M20);
+            let mut retries = 0;
+            loop {
+                match emmc.init() {
+                    Ok(()) => return Ok(emmc),
 
-            let mut ext_csd = [0; 512];
-            emmc.read_single_block(None, ext_csd.as_mut_ptr())
-                .expect("fatal");
+                    Err(e) if retries < MAX_RETRIES => {
+                        retries += 1;
+                        memlog!("initialization failed: {}; retry {}", e, retries);
+                        time::wait(backoff(retries));
+                    }
 
-            emmc.blocks =
-                u32::from_le_bytes([ext_csd[212], ext_csd[213], ext_csd[214], ext_csd[215]]);
-
-            memlog!("card has {} blocks", emmc.blocks);
-
-            Ok(emmc)
+                    Err(e) => return Err(e.retried(retries)),
+                }
+            }
         })
     }
 
-    /// Changes the verbosity of the driver (default: false)
-    pub fn verbose(&mut self, verbose: bool) {
-        self.verbose = verbose;
-    }
-
-    /// Gets a handle to the `eMMC` singleton
-    ///
-    /// This method returns the `Some` only once. When successful this method
-    /// consumes the `uSDHC2` peripheral
-    // this implementation ought to support both the "eMMC" and "uSD" boot modes but it doesn't
-    // work ATM
-    #[cfg(TODO = "eMMC_and_uSD_support")]
-    pub fn take() -> Option<Result<Self, Error>> {
-        uSDHC2::take().map(|usdhc| {
-            let mut emmc = Self {
-                usdhc,
-                blocks: 0,
-                selected: None,
-                verbose: false,
-                width: Width::B1,
-            };
-
-            emmc.software_reset();
-            emmc.reset_cards();
-            emmc.voltage_validation();
-            emmc.register_cards()?;
-            let csd = emmc.identify_card(RCA);
-
-            assert_eq!(csd.version(), 4, "only eMMC v4.x is supported");
-            for block_size in &[csd.max_read_block_size(), csd.max_write_block_size()] {
-                assert!(
-                    *block_size >= BLOCK_SIZE,
-                    "unsupported block size ({})",
-                    block_size
-                );
-            }
-            emmc.select_card(RCA)?;
-
-            let mut ext_csd = [0; 512];
-            emmc.read_single_block(None, ext_csd.as_mut_ptr())
-                .expect("EXT_CSD read failed");
-
-            let card_type = ext_csd[196];
-            assert_ne!(
-                card_type & 2,
-                0,
-                "card doesn't support up to 52 MHz frequencies"
-            );
-
-            emmc.blocks =
-                u32::from_le_bytes([ext_csd[212], ext_csd[213], ext_csd[214], ext_csd[215]]);
-
-            emmc.send_command(Command::Switch { data: 0x3B9_0100 }, false);
-            emmc.wait_response()?;
-            card::Status::from(emmc.usdhc.CMD_RSP0.read())?;
-
-            // wait for the busy line to clear
-            emmc.get_card_status(RCA, false)?;
-
-            memlog!("put card in high speed mode @ {:?}", time::uptime(),);
-
-            // // TODO change bus width to DDR 4-bit mode
-            // emmc.send_command(Command::Switch { data: 0x3B70100 }, false);
-            // emmc.wait_response().unwrap();
-            // let status1 = card::Status::from(emmc.usdhc.CMD_RSP0.read()).unwrap();
-
-            // // wait for the busy line to clear
-            // let status2 = emmc.get_card_status(RCA, false).unwrap();
-
-            // emmc.ddr = true;
-
-            // memlog!(
-            //     "changed card's bus width @ {:?} ({:?}, {:?})",
-            //     time::uptime(),
-            //     status1,
-            //     status2
-            // );
-
-            // emmc.usdhc.SYS_CTRL.rmw(|mut r| {
-            //     // lowest 4 bits must always be ones
-            //     r |= 0xf;
-
-            //     const DTW_MASK: u32 = 0b11 << 1;
-            //     const DTW_B4: u32 = 0b01 << 1;
-            //     // const DTW_B8: u32 = 0b10 << 1;
-
-            //     r = (r & !DTW_MASK) | DTW_B4;
+    /// Brings up the eMMC, starting from a reset of the host
+    fn init(&mut self) -> Result<(), Error> {
+        self.usdhc.software_reset();
+        self.bus_mode.set(BusMode::LEGACY);
+        self.reset_cards()?;
+        self.voltage_validation()?;
+        self.register_cards()?;
+        self.select_card(RCA)?;
+        // the card is in the 1-bit legacy mode after the reset
+        self.usdhc.change_frequency(Frequency::M20);
+
+        self.ext_csd = self.ext_csd()?;
+        // the erase and write protect group sizes the driver uses are the high
+        // capacity ones; this setting is lost on reset
+        if self.ext_csd.supports_high_capacity_groups() && !self.ext_csd.high_capacity_groups() {
+            self.switch(EXT_CSD_ERASE_GROUP_DEF, 1)?;
+            self.ext_csd = self.ext_csd()?;
+        }
+        self.blocks = self.ext_csd.sectors();
+        self.partition_config.set(self.ext_csd.partition_config());
+        self.cache_enabled.set(self.ext_csd.cache_enabled());
+        self.negotiate_bus_mode()?;
 
-            //     memlog!("> SYS_CTRL: {:#010x}", r);
-            //     r
-            // });
+        memlog!("card has {} blocks", self.blocks);
 
-            emmc.change_frequency(Frequency::M48);
+        // the bootloader may have left a boot partition selected
+        self.select_partition(Partition::User)
+    }
 
-            Ok(emmc)
-        })
+    /// Changes the verbosity of the driver (default: false)
+    pub fn verbose(&mut self, verbose: bool) {
+        self.usdhc.verbose = verbose;
     }
 
     // TODO partial reads and write (if the card supports them)
@@ -272,173 +145,221 @@ impl eMMC {
     pub fn read(&self, block_nr: u32, block: &mut Block) -> Result<(), Error> {
         assert!(block_nr < self.blocks, "block doesn't exist");
 
-        if self.verbose {
+        if self.usdhc.verbose {
             memlog!("read(block_nr={} @ {:?}", block_nr, time::uptime());
         }
 
-        self.read_single_block(Some(block_nr), block.bytes.as_mut_ptr())
+        self.with_recovery(|| {
+            self.select_partition(Partition::User)?;
+            Ok(self.usdhc.read_blocks(block_nr, slice::from_mut(block))?)
+        })
     }
 
     /// Transfers a block of memory to the card for it to be programmed to flash
     pub fn write(&self, block_nr: u32, block: &Block) -> Result<(), Error> {
         assert!(block_nr < self.blocks, "block doesn't exist");
 
-        if self.verbose {
+        if self.usdhc.verbose {
             memlog!("write(block_nr={}) @ {:?}", block_nr, time::uptime());
         }
 
-        self.write_single_block(block_nr, block.bytes.as_ptr())
+        self.with_recovery(|| {
+            self.select_partition(Partition::User)?;
+            Ok(self.usdhc.write_blocks(block_nr, slice::from_ref(block))?)
+        })
     }
 
-    // mid-level API
-    // NOTE(`block_nr`) this assumes that the card is a high-capacity device
-    // NOTE `block_nr=None` reads the EXT_CSD register
-    fn read_single_block(&self, block_nr: Option<u32>, addr: *mut u8) -> Result<(), Error> {
-        let rca = if let Some(rca) = self.selected {
-            rca
-        } else {
-            memlog!("a card must be selected first");
-            memlog_flush_and_reset!();
-        };
-
-        debug_assert_eq!(addr as usize % 4, 0);
-
-        let status = self.get_card_status(rca)?;
-        if self.verbose {
-            memlog!("{:?}", status);
-        }
-
-        if !status.ready_for_data || status.state != card::State::Transfer {
-            return Err(Error::NotInTransferState);
+    /// Reads consecutive blocks of memory, starting at block `lba`
+    ///
+    /// Up to 65535 blocks are read using a single command and DMA transfer
+    pub fn read_blocks(&self, lba: u32, blocks: &mut [Block]) -> Result<(), Error> {
+        self.assert_blocks_exist(lba, blocks.len());
+
+        if self.usdhc.verbose {
+            memlog!(
+                "read_blocks(lba={}, n={}) @ {:?}",
+                lba,
+                blocks.len(),
+                time::uptime()
+            );
         }
 
-        /// Data Transfer Direction Select = Read (Card to Host)
-        const MIX_CTRL_DTDSEL_READ: u32 = 1 << 4;
-        self.usdhc.MIX_CTRL.write(
-            MIX_CTRL_RESERVED
-                | if self.width.ddr() { MIX_CTRL_DDR_EN } else { 0 }
-                | MIX_CTRL_MBSEL_SINGLE
-                | MIX_CTRL_DTDSEL_READ
-                | MIX_CTRL_DMAEN_ENABLE,
-        );
+        self.with_recovery(|| {
+            self.select_partition(Partition::User)?;
+            Ok(self.usdhc.read_blocks(lba, blocks)?)
+        })
+    }
 
-        // must wait until DLA is cleared
-        while self.usdhc.PRES_STATE.read() & PRES_STATE_DLA != 0 {
-            crate::memlog_try_flush()
+    /// Transfers consecutive blocks of memory to the card, starting at block
+    /// `lba`, for them to be programmed to flash
+    ///
+    /// Up to 65535 blocks are written using a single command and DMA transfer
+    pub fn write_blocks(&self, lba: u32, blocks: &[Block]) -> Result<(), Error> {
+        self.assert_blocks_exist(lba, blocks.len());
+
+        if self.usdhc.verbose {
+            memlog!(
+                "write_blocks(lba={}, n={}) @ {:?}",
+                lba,
+                blocks.len(),
+                time::uptime()
+            );
         }
 
-        // TC must be cleared before writing to DS_ADDR
-        self.usdhc.INT_STATUS.clear(INT_STATUS_TC);
-
-        // semantically, the next register write transfers ownership of the
-        // buffer to the DMA so ensure all previous memory operations complete
-        // before the ownership transfer (NOTE this could be done a bit later:
-        // in the middle of `send_command(WriteSingleBlock)`)
-        atomic::fence(Ordering::Release);
-        self.usdhc.DS_ADDR.write(addr as usize as u32);
-        self.usdhc.BLK_ATT.write(1 << 16 | u32::from(BLOCK_SIZE));
-        self.usdhc.WTMK_LVL.reset(); // use a reasonable watermark level
-
-        // start the transfer
-        let cmd = block_nr
-            .map(|block_nr| Command::ReadSingleBlock { block_nr })
-            .unwrap_or(Command::SendExtCsd);
-        if self.verbose {
-            memlog!("read START @ {:?}", time::uptime());
+        self.with_recovery(|| {
+            self.select_partition(Partition::User)?;
+            Ok(self.usdhc.write_blocks(lba, blocks)?)
+        })
+    }
+
+    /// Like `write_blocks` but uses reliable writes: if the write is
+    /// interrupted, by a power loss for example, each block holds either its
+    /// old or its new contents, never a mix of the two
+    ///
+    /// Devices without enhanced reliable write only guarantee this for
+    /// single-block writes and for writes of `REL_WR_SEC_C` blocks aligned to
+    /// that size so the blocks are split into such writes; this is slower than
+    /// `write_blocks`
+    pub fn write_blocks_reliable(&self, lba: u32, blocks: &[Block]) -> Result<(), Error> {
+        self.assert_blocks_exist(lba, blocks.len());
+
+        if self.usdhc.verbose {
+            memlog!(
+                "write_blocks_reliable(lba={}, n={}) @ {:?}",
+                lba,
+                blocks.len(),
+                time::uptime()
+            );
         }
-        let rsp = self.send_command(cmd)?;
-        let status = card::Status::from(rsp)?;
 
-        // NOTE `send_command` will block until the data transfer is finished
+        let enhanced = self.ext_csd.enhanced_reliable_write();
+        let sectors = usize::from(self.ext_csd.reliable_write_sectors().max(1));
+        let mut lba = lba;
+        let mut blocks = blocks;
+        while !blocks.is_empty() {
+            let count = if enhanced {
+                blocks.len().min(usize::from(u16::max_value()))
+            } else if lba as usize % sectors == 0 && blocks.len() >= sectors {
+                sectors
+            } else {
+                1
+            };
+            let (chunk, rest) = blocks.split_at(count);
 
-        // TODO accessing `buf` requires cache invalidation
-        // let the DMA finish its memory operations before `block` is read
-        atomic::fence(Ordering::Acquire);
+            self.with_recovery(|| {
+                self.select_partition(Partition::User)?;
+                Ok(self.usdhc.write_blocks_counted(lba, chunk, true)?)
+            })?;
 
-        if self.verbose {
-            memlog!("read DONE ({:?}) @ {:?}", status, time::uptime());
+            // NOTE(as) no truncation; `count` is at most 65535
+            lba += count as u32;
+            blocks = rest;
         }
+
         Ok(())
     }
 
-    fn write_single_block(&self, block_nr: u32, addr: *const u8) -> Result<(), Error> {
-        let rca = if let Some(rca) = self.selected {
-            rca
-        } else {
-            memlog!("a card must be selected first");
-            memlog_flush_and_reset!();
-        };
-
-        debug_assert_eq!(addr as usize % 4, 0);
-
-        let status = self.get_card_status(rca)?;
-        if !status.ready_for_data || status.state != card::State::Transfer {
-            if self.verbose {
-                memlog!("error: {:?}", status);
-            }
-            return Err(Error::NotInTransferState);
+    /// Reads consecutive blocks of memory, starting at block `lba`, into
+    /// several buffers
+    ///
+    /// The buffers are filled in order. The data is moved by the advanced DMA
+    /// (ADMA2) so the buffers need not be contiguous; a single command is used
+    /// for up to 32 buffers, or 65535 blocks, whichever comes first
+    ///
+    /// # Panics
+    ///
+    /// This method panics if a buffer is not 4-byte aligned or if its length
+    /// is not a multiple of `BLOCK_SIZE`
+    pub fn read_vectored(&self, lba: u32, bufs: &mut [&mut [u8]]) -> Result<(), Error> {
+        let count = blocks_in(bufs.iter().map(|buf| buf.len()).sum());
+        self.assert_blocks_exist(lba, count as usize);
+
+        if self.usdhc.verbose {
+            memlog!(
+                "read_vectored(lba={}, n={}) @ {:?}",
+                lba,
+                count,
+                time::uptime()
+            );
         }
 
-        /// Data Transfer Direction Select = Write (Host to Card)
-        const MIX_CTRL_DTDSEL_WRITE: u32 = 0 << 4;
-        self.usdhc.MIX_CTRL.write(
-            MIX_CTRL_RESERVED
-                | if self.width.ddr() { MIX_CTRL_DDR_EN } else { 0 }
-                | MIX_CTRL_MBSEL_SINGLE
-                | MIX_CTRL_DTDSEL_WRITE
-                | MIX_CTRL_DMAEN_ENABLE,
-        );
-
-        // must wait until DLA is cleared
-        while self.usdhc.PRES_STATE.read() & PRES_STATE_DLA != 0 {
-            crate::memlog_try_flush()
-        }
+        self.with_recovery(|| {
+            self.select_partition(Partition::User)?;
+            Ok(self.usdhc.read_vectored(lba, bufs)?)
+        })
+    }
 
-        // TC must be cleared before writing to DS_ADDR
-        self.usdhc.INT_STATUS.clear(INT_STATUS_TC);
-
-        // semantically, the next register write transfers ownership of the
-        // buffer to the DMA so ensure all previous memory operations complete
-        // before the ownership transfer (NOTE this could be done a bit later:
-        // in the middle of `send_command(WriteSingleBlock)`)
-        atomic::fence(Ordering::Release);
-        self.usdhc.DS_ADDR.write(addr as usize as u32);
-        self.usdhc.BLK_ATT.write(1 << 16 | u32::from(BLOCK_SIZE));
-        self.usdhc.WTMK_LVL.reset(); // use a reasonable watermark level
-
-        // start the transfer
-        if self.verbose {
-            memlog!("write START @ {:?}", time::uptime());
+    /// Transfers the contents of several buffers to consecutive blocks of the
+    /// card, starting at block `lba`, for them to be programmed to flash
+    ///
+    /// See `read_vectored` for the requirements on the buffers
+    pub fn write_vectored(&self, lba: u32, bufs: &[&[u8]]) -> Result<(), Error> {
+        let count = blocks_in(bufs.iter().map(|buf| buf.len()).sum());
+        self.assert_blocks_exist(lba, count as usize);
+
+        if self.usdhc.verbose {
+            memlog!(
+                "write_vectored(lba={}, n={}) @ {:?}",
+                lba,
+                count,
+                time::uptime()
+            );
         }
-        let rsp = self.send_command(Command::WriteSingleBlock { block_nr })?;
-        let status = card::Status::from(rsp)?;
 
-        // NOTE `send_command` will block until the data transfer is finished
+        self.with_recovery(|| {
+            self.select_partition(Partition::User)?;
+            Ok(self.usdhc.write_vectored(lba, bufs)?)
+        })
+    }
 
-        // buffer handled back to us
-        atomic::fence(Ordering::Acquire);
+    /// Reads the EXT_CSD register of the eMMC
+    ///
+    /// The register is read anew on every call so the fields that change over
+    /// time, like the life time estimates, are up to date
+    pub fn ext_csd(&self) -> Result<ExtCsd, Error> {
+        let mut block = Block::zeroed();
+        self.usdhc
+            .read_register(Command::SendExtCsd, &mut block, BLOCK_SIZE)?;
+        Ok(ExtCsd::from_bytes(&block.bytes))
+    }
 
-        // flush the write
-        if status.state == card::State::Programming {
-            // wait until the card finishes programming the data it received
-            let start = Instant::now();
-            while self.get_card_status(rca)?.state != card::State::Transfer {
-                crate::memlog_try_flush();
+    /// Returns the CID register of the eMMC, which identifies the device
+    pub fn cid(&self) -> Cid {
+        self.cid
+    }
 
-                if Instant::now() - start > default_timeout() {
-                    memlog!("card took too long to program to flash the data it received");
-                    memlog_flush_and_reset!();
-                }
-            }
+    /// Tells the eMMC that the data stored in the `blocks` range is no longer
+    /// needed
+    ///
+    /// The eMMC may reclaim the blocks, which makes their contents undefined,
+    /// but the data may still be recovered from the flash; see `secure_erase`
+    pub fn discard(&self, blocks: Range<u64>) -> Result<(), Error> {
+        self.discard_blocks(Partition::User, blocks, self.blocks)
+    }
 
-            memlog!("write flushed @ {:?}", time::uptime());
-        }
+    /// Physically erases the data stored in the `blocks` range, including any
+    /// copy of it the eMMC may have kept around due to wear leveling
+    ///
+    /// An error is returned if the eMMC supports neither secure trim nor
+    /// sanitize
+    pub fn secure_erase(&self, blocks: Range<u64>) -> Result<(), Error> {
+        self.secure_erase_batch(slice::from_ref(&blocks))
+    }
 
-        if self.verbose {
-            memlog!("write DONE @ {:?}", time::uptime());
-        }
+    /// Physically erases the data stored in all the `ranges` of blocks
+    ///
+    /// This is faster than calling `secure_erase` on each range: when the
+    /// eMMC doesn't support secure trim a single sanitize operation covers
+    /// all the ranges
+    pub fn secure_erase_batch(&self, ranges: &[Range<u64>]) -> Result<(), Error> {
+        self.secure_erase_blocks(Partition::User, ranges, self.blocks)
+    }
 
-        Ok(())
+    fn assert_blocks_exist(&self, lba: u32, count: usize) {
+        assert!(
+            u64::from(lba) + count as u64 <= u64::from(self.blocks),
+            "block doesn't exist"
+        );
     }
 
     /// "Selects" the card with the specific relative address `rca`
@@ -451,56 +372,31 @@ impl eMMC {
         assert_eq!(rca, RCA);
 
         // select the card
-        self.send_command(Command::SelectCard { rca: Some(rca) })?;
+        self.usdhc
+            .send_command(Command::SelectCard { rca: Some(rca) })?;
 
         // set block size (card side)
-        self.send_command(Command::SetBlockLen {
+        self.usdhc.send_command(Command::SetBlockLen {
             len: BLOCK_SIZE.into(),
         })?;
 
-        self.selected = Some(rca);
+        self.usdhc.selected = Some(rca);
 
         Ok(())
     }
 
-    /// Returns the card specific data of the card with the specified relative
-    /// address
-    #[cfg(TODO = "eMMC_and_uSD_support")]
-    fn identify_card(&self, rca: Rca) -> card::Csd {
-        // a more general implementation would check this address against the
-        // ones registered in `register_cards`
-        assert_eq!(rca, RCA);
-
-        self.send_command(Command::SendCsd { rca }, true);
-        if let Err(e) = self.wait_response() {
-            memlog!("command response error: {:?}", e);
-            memlog_flush_and_reset!();
-        }
-
-        card::Csd::from([
-            self.usdhc.CMD_RSP0.read(),
-            self.usdhc.CMD_RSP1.read(),
-            self.usdhc.CMD_RSP2.read(),
-            self.usdhc.CMD_RSP3.read(),
-        ])
-    }
-
     /// Registers (gives them a relative address) all cards on the bus
-    fn register_cards(&self) -> Result<(), Error> {
+    fn register_cards(&mut self) -> Result<(), Error> {
         // NOTE here we assume that only the eMMC is connected to this uSDHC bus
         // if there were more cards on the bus then this should be a loop that
         // assigns a different relative address to each one
         memlog!("registering cards on the bus");
-        let cid0 = self.send_command(Command::AllSendCid)?;
+        self.usdhc.send_command(Command::AllSendCid)?;
 
-        let _cid = [
-            cid0,
-            self.usdhc.CMD_RSP1.read(),
-            self.usdhc.CMD_RSP2.read(),
-            self.usdhc.CMD_RSP3.read(),
-        ];
+        self.cid = cid(self.usdhc.long_response());
 
-        self.send_command(Command::SetRelativeAddr { rca: RCA })?;
+        self.usdhc
+            .send_command(Command::SetRelativeAddr { rca: RCA })?;
         memlog!("registered a card with RCA={:#04x}", RCA);
 
         // we omit this because we know there's only one card
@@ -531,7 +427,9 @@ impl eMMC {
         // broadcast operating voltage; wait until cards have finished powering up
         let start = Instant::now();
         let ocr = loop {
-            let ocr = self.send_command(Command::SendOpCond { ocr: target_ocr })?;
+            let ocr = self
+                .usdhc
+                .send_command(Command::SendOpCond { ocr: target_ocr })?;
 
             if ocr & OCR_RDY != 0 {
                 break ocr;
@@ -539,7 +437,7 @@ impl eMMC {
 
             if start.elapsed() > default_timeout() {
                 memlog!("timeout while waiting for card to be ready");
-                memlog_flush_and_reset!();
+                return Err(ErrorKind::Timeout.into());
             }
 
             // let's not spam the card
@@ -553,308 +451,176 @@ impl eMMC {
 
     /// Puts all the cards on the bus in the idle state
     fn reset_cards(&self) -> Result<(), Error> {
-        self.send_command(Command::GoIdleState)?;
+        self.usdhc.send_command(Command::GoIdleState)?;
         memlog!("sent all cards to idle state @ {:?}", time::uptime());
         Ok(())
     }
 
-    fn change_bus_width(&mut self, width: Width, switch: bool) -> Result<(), Error> {
-        if switch {
-            let data = match width {
-                Width::B1 => 0x03B7_0000,
-                // Width::B4 => 0x03B7_0100,
-                Width::B8 => 0x03B7_0200,
-            };
-            self.send_command(Command::Switch { data })?;
-        }
-
-        const DTW_OFFSET: u8 = 1;
-        const DTW_MASK: u32 = 0b11 << DTW_OFFSET;
-        self.usdhc
-            .PROT_CTRL
-            .rmw(|r| (r & !DTW_MASK) | (u32::from(width.dtw()) << DTW_OFFSET));
-        self.width = width;
+    /// Writes `value` into the PARTITION_CONFIG field of the EXT_CSD register
+    fn write_partition_config(&self, value: u8) -> Result<(), Error> {
+        self.switch(EXT_CSD_PARTITION_CONFIG, value)?;
+        self.partition_config.set(value);
 
         Ok(())
     }
 
-    fn change_frequency(&self, f: Frequency) {
-        memlog!("change_frequency({:?}) @ {:?}", f, time::uptime());
-
-        // wait for the clock to stabilize
-        while self.usdhc.PRES_STATE.read() & SDSTB == 0 {
-            crate::memlog_try_flush();
-        }
-
-        // gate the SD clock before changing it
-        self.usdhc
-            .VEND_SPEC
-            .rmw(|vend_spec| vend_spec & !VEND_SPEC_CKEN);
-
-        let (sdclkfs, dvs) = match f {
-            // 200 MHz / 64 / 9 = 347.222 KHz
-            Frequency::K400 => (0x20, 8),
-            // 200 MHz / 2 / 5 = 20 MHz
-            Frequency::M20 => (0x01, 4),
-        };
-
-        self.usdhc.SYS_CTRL.rmw(|mut r| {
-            const DVS_OFFSET: u8 = 4;
-            const DVS_MASK: u32 = 0b1111 << DVS_OFFSET;
-            const SDCLKFS_OFFSET: u8 = 8;
-            const SDCLKFS_MASK: u32 = 0xff << SDCLKFS_OFFSET;
+    /// Writes `value` into the EXT_CSD field at byte offset `index` and waits
+    /// until the eMMC has applied the change
+    fn switch(&self, index: u8, value: u8) -> Result<(), Error> {
+        self.switch_with_timeout(index, value, default_timeout())
+    }
 
-            // set divisor
-            r &= !DVS_MASK;
-            r |= dvs << DVS_OFFSET;
+    /// Like `switch` but for changes that may take longer to apply; `timeout`
+    /// bounds the time the eMMC may take
+    fn switch_with_timeout(&self, index: u8, value: u8, timeout: Duration) -> Result<(), Error> {
+        self.usdhc.send_command(Command::Switch {
+            data: SWITCH_WRITE_BYTE | u32::from(index) << 16 | u32::from(value) << 8,
+        })?;
+        self.usdhc.wait_for_programming(RCA, timeout)?;
 
-            // set prescaler
-            r &= !SDCLKFS_MASK;
-            r |= sdclkfs << SDCLKFS_OFFSET;
+        Ok(())
+    }
+}
 
-            r
-        });
+impl ManagedBlockDevice for eMMC {
+    type Error = Error;
 
-        const SDSTB: u32 = 1 << 3;
+    fn total_blocks(&self) -> u64 {
+        u64::from(self.blocks)
+    }
 
-        // wait for the clock to stabilize
-        while self.usdhc.PRES_STATE.read() & SDSTB == 0 {
-            crate::memlog_try_flush();
+    fn read(&self, block: &mut Block, lba: u64) -> Result<(), Self::Error> {
+        if lba > self.total_blocks() {
+            return Err(ErrorKind::Other.into());
         }
 
-        // ungate the SD clock
-        self.usdhc
-            .VEND_SPEC
-            .rmw(|vend_spec| vend_spec | VEND_SPEC_CKEN);
+        Self::read(self, lba as u32, block)?;
+        Ok(())
     }
 
-    fn software_reset(&mut self) {
-        memlog!("software reset START @ {:?}", time::uptime());
-
-        // wait for CMD and DATA lines to become free
-        let busy = PRES_STATE_CDIHB | PRES_STATE_CIHB;
-        while self.usdhc.PRES_STATE.read() & busy != 0 {
-            crate::memlog_try_flush();
+    fn write(&mut self, block: &Block, lba: u64) -> Result<(), Self::Error> {
+        if lba > self.total_blocks() {
+            return Err(ErrorKind::Other.into());
         }
 
-        // gate off the SD clock
-        self.usdhc
-            .VEND_SPEC
-            .rmw(|vend_spec| vend_spec & !VEND_SPEC_CKEN);
-
-        // reset the uSDHC peripheral
-        const RSTA: u32 = 1 << 24;
-        self.usdhc.SYS_CTRL.rmw(|r| r | RSTA);
+        Self::write(self, lba as u32, block)?;
+        Ok(())
+    }
 
-        // wait for reset
-        while self.usdhc.SYS_CTRL.read() & RSTA != 0 {
-            crate::memlog_try_flush();
+    fn read_vectored(&self, bufs: &mut [&mut [u8]], lba: u64) -> Result<(), Self::Error> {
+        if !fits_in(
+            lba,
+            bufs.iter().map(|buf| buf.len()).sum(),
+            self.total_blocks(),
+        ) {
+            return Err(ErrorKind::Other.into());
         }
 
-        // no error because it doesn't communicate with the card
-        let _ = self.change_bus_width(Width::B1, false);
-        self.change_frequency(Frequency::K400);
-
-        // recommended in the eMMC spec
-        time::wait(Duration::from_millis(1));
+        self.with_recovery(|| {
+            self.select_partition(Partition::User)?;
+            Ok(self.usdhc.read_vectored_any(lba as u32, bufs)?)
+        })
+    }
 
-        // send 80 clock cycles to the card
-        const INITA: u32 = 1 << 27;
-        self.usdhc.SYS_CTRL.rmw(|r| r | INITA);
+    fn write_vectored(&mut self, bufs: &[&[u8]], lba: u64) -> Result<(), Self::Error> {
+        if !fits_in(
+            lba,
+            bufs.iter().map(|buf| buf.len()).sum(),
+            self.total_blocks(),
+        ) {
+            return Err(ErrorKind::Other.into());
+        }
 
-        memlog!("software reset DONE @ {:?}", time::uptime());
+        self.with_recovery(|| {
+            self.select_partition(Partition::User)?;
+            Ok(self.usdhc.write_vectored_any(lba as u32, bufs)?)
+        })
     }
 
-    fn get_card_status(&self, rca: Rca) -> Result<card::Status, Error> {
-        let rsp = self.send_command(Command::SendStatus { rca })?;
-        let status = card::Status::from(rsp);
-        Ok(status?)
+    fn discard(&mut self, blocks: Range<u64>) -> Result<(), Self::Error> {
+        Self::discard(self, blocks)
     }
 
-    // low-level API
-    /// When a command time outs the command inhibition bit needs to be cleared
-    /// before attempting to send a new command
-    #[allow(dead_code)]
-    fn clear_command_inhibit(&self) {
-        const SYS_CTRL_RSTC: u32 = 1 << 25;
-
-        // clear the Command Inhibit bit
-        self.usdhc.SYS_CTRL.rmw(|r| r | SYS_CTRL_RSTC);
-        if util::wait_for_or_timeout(
-            || self.usdhc.SYS_CTRL.read() & SYS_CTRL_RSTC == 0,
-            default_timeout(),
-        )
-        .is_err()
-        {
-            memlog!("RSTC timeout");
-            memlog_flush_and_reset!();
-        }
+    fn secure_erase(&mut self, blocks: Range<u64>) -> Result<(), Self::Error> {
+        Self::secure_erase(self, blocks)
     }
 
-    /// [Blocking] send a command to the card
-    fn send_command(&self, cmd: Command) -> Result<u32, Error> {
-        if self.verbose {
-            memlog!("send_command(cmd={:?}) @ {:?}", cmd, time::uptime());
-        }
-        debug_assert!(
-            self.usdhc.INT_STATUS.read() & INT_STATUS_CC == 0,
-            "INT_STATUS.CC bit was not cleared"
-        );
-
-        // wait until the bus is idle
-        let busy = PRES_STATE_CIHB | PRES_STATE_CDIHB | PRES_STATE_DLA;
-        while self.usdhc.PRES_STATE.read() & busy != 0 {
-            crate::memlog_try_flush();
-        }
-
-        const RSTYP_OFFSET: u8 = 16;
-        const CICEN: u32 = 1 << 20;
-        const CCCEN: u32 = 1 << 19;
-        const DPSEL: u32 = 1 << 21;
-        const CMDTYP_OFFSET: u8 = 22;
-        const CMDINX_OFFSET: u8 = 24;
-
-        let mut w_cmd = u32::from(cmd.index()) << CMDINX_OFFSET;
-        w_cmd |= u32::from(cmd.cmdtyp()) << CMDTYP_OFFSET;
-        if cmd.data_present() {
-            w_cmd |= DPSEL
-        }
-        if cmd.cicen() {
-            w_cmd |= CICEN;
-        }
-        if cmd.cccen() {
-            w_cmd |= CCCEN;
-        }
-        w_cmd |= u32::from(cmd.response_type()) << RSTYP_OFFSET;
-
-        let cmd_arg = cmd.arg();
-
-        // the timing between commands must be at least 8 SD clock cycles according to the spec
-        // @ 20 MHz that's 400 ns; @ 400 KHz that's 20 us
-        time::wait(Duration::from_micros(20));
-
-        // command argument
-        self.usdhc.CMD_ARG.write(cmd_arg);
-        // issue the command
-        self.usdhc.CMD_XFR_TYP.write(w_cmd);
-
-        // then wait for a response
-        let any_error = INT_STATUS_ANY_ERROR;
-        let mut int_status = 0;
-        let has_command_completed = || {
-            int_status = self.usdhc.INT_STATUS.read();
-            int_status & (any_error | INT_STATUS_CC) != 0
-        };
-        if util::wait_for_or_timeout(has_command_completed, default_timeout()).is_err() {
-            return Err(Error::Timeout);
-        }
-
-        if int_status & INT_STATUS_CTOE != 0 {
-            self.usdhc.INT_STATUS.clear(INT_STATUS_CTOE);
-            Err(Error::Timeout)
-        } else if int_status & any_error != 0 {
-            self.usdhc.INT_STATUS.clear(int_status & any_error);
-            Err(Error::Other)
-        } else {
-            self.usdhc.INT_STATUS.clear(INT_STATUS_CC);
-
-            let rsp = self.usdhc.CMD_RSP0.read();
-            let rspty = cmd.response();
-
-            if rspty == cmd::Response::R1 || rspty == cmd::Response::R1b {
-                let status = card::Status::from(rsp)?;
-                if self.verbose {
-                    memlog!("{:?}", status);
-                }
-            }
-
-            // let the data transfer complete (if any)
-            if cmd.data_present() {
-                let any_error = INT_STATUS_ANY_ERROR;
-                let transfer_done = INT_STATUS_TC | INT_STATUS_DINT;
-                let mut int_status = 0;
-                let has_command_completed = || {
-                    int_status = self.usdhc.INT_STATUS.read();
-                    int_status & any_error != 0 || int_status & transfer_done == transfer_done
-                };
-                if util::wait_for_or_timeout(has_command_completed, default_timeout()).is_err() {
-                    return Err(Error::Timeout);
-                }
+    fn secure_erase_batch(&mut self, ranges: &mut [Range<u64>]) -> Result<(), Self::Error> {
+        Self::secure_erase_batch(self, ranges)
+    }
 
-                if int_status & INT_STATUS_CTOE != 0 {
-                    self.usdhc.INT_STATUS.clear(INT_STATUS_CTOE);
-                    Err(Error::Timeout)
-                } else if int_status & any_error != 0 {
-                    self.usdhc.INT_STATUS.clear(int_status & any_error);
-                    Err(Error::Other)
-                } else {
-                    self.usdhc.INT_STATUS.clear(transfer_done);
-                    Ok(rsp)
-                }
-            } else {
-                Ok(rsp)
-            }
-        }
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Self::flush(self)
     }
 }
 
-/// Command error
+/// eMMC error
 #[derive(Clone, Copy, Debug, PartialEq)]
-pub enum Error {
-    /// Card did not respond in time.
-    Timeout,
-    /// Card is not in the transfer state (data cannot be accessed)
-    NotInTransferState,
-    /// Unclassified error.
-    Other,
-    /// Error condition in the card
-    Card(card::Status),
+pub struct Error {
+    kind: ErrorKind,
+    retries: u8,
+    status: Option<Status>,
 }
 
-impl From<card::Status> for Error {
-    fn from(s: card::Status) -> Self {
-        Error::Card(s)
+impl Error {
+    /// Returns the error of the last attempt
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
     }
-}
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Error::Timeout => f.write_str("timeout"),
-            Error::NotInTransferState => f.write_str("card not in transfer state"),
-            Error::Other => f.write_str("unclassified error"),
-            Error::Card(s) => write!(f, "card error (code: {:#010x})", s.bits),
-        }
+    /// Returns how many times the operation was retried before giving up
+    pub fn retries(&self) -> u8 {
+        self.retries
     }
-}
 
-impl ManagedBlockDevice for eMMC {
-    type Error = Error;
+    /// Returns the status the card reported after the failure, if it could be
+    /// read
+    pub fn status(&self) -> Option<Status> {
+        self.status
+    }
 
-    fn total_blocks(&self) -> u64 {
-        u64::from(self.blocks)
+    fn retried(self, retries: u8) -> Self {
+        Error { retries, ..self }
     }
+}
 
-    fn read(&self, block: &mut Block, lba: u64) -> Result<(), Self::Error> {
-        if lba > self.total_blocks() {
-            return Err(Error::Other);
-        }
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        let status = if let ErrorKind::Card(status) = kind {
+            Some(status)
+        } else {
+            None
+        };
 
-        Self::read(self, lba as u32, block)?;
-        Ok(())
+        Error {
+            kind,
+            retries: 0,
+            status,
+        }
     }
+}
 
-    fn write(&mut self, block: &Block, lba: u64) -> Result<(), Self::Error> {
-        if lba > self.total_blocks() {
-            return Err(Error::Other);
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if self.retries != 0 {
+            write!(f, " after {} retries", self.retries)?;
+        }
+        if let Some(status) = &self.status {
+            write!(f, " ({:?})", status)?;
         }
-
-        Self::write(self, lba as u32, block)?;
         Ok(())
     }
+}
 
-    fn flush(&mut self) -> Result<(), Self::Error> {
-        // no-operation
-        Ok(())
-    }
+/// Converts the response to `ALL_SEND_CID` into the CID register
+fn cid(rsps: [u32; 4]) -> Cid {
+    // RSP0[0] is CID[8]; the CRC is not included in the response
+    let bits = rsps
+        .iter()
+        .rev()
+        .fold(0, |bits, rsp| bits << 32 | u128::from(*rsp))
+        << 8;
+
+    Cid::from_bytes(&bits.to_be_bytes())
 }
@@ -0,0 +1,242 @@
+//! Bus width and timing modes
+//!
+//! Reference: JEDEC JESD84-B51, sections 6.6.4 "Bus width selection" and 6.6.5
+//! "High-speed modes selection"
+
+use crate::{
+    emmc::{eMMC, Error, ErrorKind},
+    memlog,
+    usdhc::{Frequency, Width},
+};
+
+// EXT_CSD fields (byte offsets)
+const EXT_CSD_BUS_WIDTH: u8 = 183;
+const EXT_CSD_HS_TIMING: u8 = 185;
+
+/// EXT_CSD bytes that describe the device; they are compared against the copy
+/// read during initialization to check that a new bus mode works
+const EXT_CSD_PROPERTIES_START: usize = 192;
+const EXT_CSD_PROPERTIES_END: usize = 242;
+
+/// Bus timing mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timing {
+    /// Backwards compatible timing; up to 26 MHz
+    Legacy,
+    /// High speed single data rate; up to 52 MHz
+    HighSpeed,
+    /// High speed dual data rate; up to 52 MHz
+    Ddr52,
+    /// HS200 single data rate; up to 200 MHz. Requires tuning and 1.8V I/O
+    Hs200,
+}
+
+/// Data bus width
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BusWidth {
+    /// 1-bit
+    B1,
+    /// 4-bit
+    B4,
+    /// 8-bit
+    B8,
+}
+
+/// Bus mode: timing and data bus width
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BusMode {
+    /// Timing mode
+    pub timing: Timing,
+    /// Data bus width
+    pub width: BusWidth,
+}
+
+impl BusMode {
+    /// The mode every eMMC supports; used during initialization
+    pub const LEGACY: BusMode = BusMode::new(Timing::Legacy, BusWidth::B1);
+
+    const fn new(timing: Timing, width: BusWidth) -> Self {
+        BusMode { timing, width }
+    }
+
+    /// Value of the BUS_WIDTH field
+    fn bus_width(self) -> u8 {
+        let ddr = self.timing == Timing::Ddr52;
+        match self.width {
+            BusWidth::B1 => 0,
+            BusWidth::B4 if ddr => 5,
+            BusWidth::B8 if ddr => 6,
+            BusWidth::B4 => 1,
+            BusWidth::B8 => 2,
+        }
+    }
+
+    /// Value of the BUS_WIDTH field that selects the same width but single
+    /// data rate
+    fn sdr_bus_width(self) -> u8 {
+        match self.width {
+            BusWidth::B1 => 0,
+            BusWidth::B4 => 1,
+            BusWidth::B8 => 2,
+        }
+    }
+
+    /// Value of the HS_TIMING field
+    fn hs_timing(self) -> u8 {
+        match self.timing {
+            Timing::Legacy => 0,
+            Timing::HighSpeed | Timing::Ddr52 => 1,
+            Timing::Hs200 => 2,
+        }
+    }
+
+    fn frequency(self) -> Frequency {
+        match self.timing {
+            Timing::Legacy => Frequency::M20,
+            Timing::HighSpeed | Timing::Ddr52 => Frequency::M50,
+            Timing::Hs200 => Frequency::M200,
+        }
+    }
+
+    fn width(self) -> Width {
+        match self.width {
+            BusWidth::B1 => Width::B1,
+            BusWidth::B4 => Width::B4,
+            BusWidth::B8 => Width::B8,
+        }
+    }
+}
+
+/// All the bus modes, from the fastest to the slowest
+const MODES: [BusMode; 10] = [
+    BusMode::new(Timing::Hs200, BusWidth::B8),
+    BusMode::new(Timing::Hs200, BusWidth::B4),
+    BusMode::new(Timing::Ddr52, BusWidth::B8),
+    BusMode::new(Timing::Ddr52, BusWidth::B4),
+    BusMode::new(Timing::HighSpeed, BusWidth::B8),
+    BusMode::new(Timing::HighSpeed, BusWidth::B4),
+    BusMode::new(Timing::HighSpeed, BusWidth::B1),
+    BusMode::new(Timing::Legacy, BusWidth::B8),
+    BusMode::new(Timing::Legacy, BusWidth::B4),
+    BusMode::LEGACY,
+];
+
+impl eMMC {
+    /// Returns the bus mode currently in use
+    pub fn bus_mode(&self) -> BusMode {
+        self.bus_mode.get()
+    }
+
+    /// Returns `true` if the eMMC supports the given bus `mode`, according to
+    /// its DEVICE_TYPE field
+    ///
+    /// NOTE the HS200 mode also requires the I/O lines to be powered at 1.8V;
+    /// the tuning procedure fails if that's not the case
+    pub fn supports_bus_mode(&self, mode: BusMode) -> bool {
+        let device_type = self.ext_csd.device_type();
+        let wide = mode.width != BusWidth::B1;
+
+        match mode.timing {
+            Timing::Legacy => true,
+            Timing::HighSpeed => device_type.hs52(),
+            Timing::Ddr52 => wide && device_type.ddr52(),
+            Timing::Hs200 => wide && device_type.hs200(),
+        }
+    }
+
+    /// Switches the bus to the given `mode`
+    ///
+    /// The fastest mode the eMMC supports is selected during initialization;
+    /// this method can be used to force a slower one. On failure the bus is
+    /// switched back to the 1-bit legacy mode
+    pub fn set_bus_mode(&self, mode: BusMode) -> Result<(), Error> {
+        if !self.supports_bus_mode(mode) {
+            return Err(ErrorKind::Other.into());
+        }
+
+        let res = self.apply(mode);
+        if res.is_err() && mode != BusMode::LEGACY {
+            self.apply(BusMode::LEGACY)?;
+        }
+        res
+    }
+
+    /// Switches the bus to the fastest mode that works
+    pub(super) fn negotiate_bus_mode(&self) -> Result<(), Error> {
+        self.switch_to_first_of(&MODES)
+    }
+
+    /// Switches the bus to the next slower mode that works; returns `false`
+    /// if the bus is already in the slowest mode
+    pub(super) fn fall_back(&self) -> Result<bool, Error> {
+        let current = self.bus_mode.get();
+        match MODES.iter().position(|mode| *mode == current) {
+            Some(i) if i + 1 < MODES.len() => {
+                memlog!("falling back from bus mode {:?}", current);
+                self.switch_to_first_of(&MODES[i + 1..])?;
+                Ok(true)
+            }
+
+            _ => Ok(false),
+        }
+    }
+
+    /// Switches to the first of the `modes` that's supported and works
+    fn switch_to_first_of(&self, modes: &[BusMode]) -> Result<(), Error> {
+        let mut res = Err(ErrorKind::Other.into());
+        for mode in modes.iter().filter(|mode| self.supports_bus_mode(**mode)) {
+            res = self.apply(*mode);
+            if res.is_ok() {
+                break;
+            }
+        }
+
+        res
+    }
+
+    fn apply(&self, mode: BusMode) -> Result<(), Error> {
+        let res = self.try_apply(mode);
+        match res {
+            Ok(()) => memlog!("bus mode: {:?}", mode),
+            Err(e) => memlog!("bus mode {:?} failed: {}", mode, e),
+        }
+        res
+    }
+
+    fn try_apply(&self, mode: BusMode) -> Result<(), Error> {
+        // 20 MHz works in all timing modes so the card can be reconfigured
+        // regardless of the mode it's currently in
+        self.usdhc.reset_tuning();
+        self.usdhc.set_ddr(false);
+        self.usdhc.change_frequency(Frequency::M20);
+        // the bus mode is unknown until all the steps have succeeded
+        self.bus_mode.set(BusMode::LEGACY);
+
+        // the width must be set before HS200 is selected and the data rate
+        // after the high speed timing is selected
+        self.switch(EXT_CSD_BUS_WIDTH, mode.sdr_bus_width())?;
+        self.usdhc.set_bus_width(mode.width());
+        self.switch(EXT_CSD_HS_TIMING, mode.hs_timing())?;
+        if mode.timing == Timing::Ddr52 {
+            self.switch(EXT_CSD_BUS_WIDTH, mode.bus_width())?;
+            self.usdhc.set_ddr(true);
+        }
+        self.usdhc.change_frequency(mode.frequency());
+
+        if mode.timing == Timing::Hs200 {
+            self.usdhc.execute_tuning()?;
+        }
+
+        // check that data can be transferred in the new mode
+        let ext_csd = self.ext_csd()?;
+        let range = EXT_CSD_PROPERTIES_START..EXT_CSD_PROPERTIES_END;
+        if ext_csd.as_bytes()[range.clone()] != self.ext_csd.as_bytes()[range] {
+            memlog!("EXT_CSD mismatch");
+            return Err(ErrorKind::Other.into());
+        }
+
+        self.bus_mode.set(mode);
+
+        Ok(())
+    }
+}
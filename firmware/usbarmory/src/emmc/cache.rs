@@ -0,0 +1,65 @@
+//! Volatile cache
+//!
+//! Reference: JEDEC JESD84-B51, section "Cache"
+
+use core::time::Duration;
+
+use crate::{
+    emmc::{eMMC, Error, ErrorKind},
+    memlog, time,
+};
+
+// EXT_CSD fields (byte offsets)
+const EXT_CSD_FLUSH_CACHE: u8 = 32;
+const EXT_CSD_CACHE_CTRL: u8 = 33;
+
+/// The specification doesn't bound the duration of a cache flush; this is the
+/// timeout Linux uses
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl eMMC {
+    /// Returns `true` if the volatile cache of the eMMC is enabled
+    pub fn cache_enabled(&self) -> bool {
+        self.cache_enabled.get()
+    }
+
+    /// Enables or disables the volatile cache of the eMMC
+    ///
+    /// With the cache enabled writes complete sooner but the data they carry
+    /// is lost if power is lost before `flush` is called. Disabling the cache
+    /// flushes it. Returns an error if the eMMC has no cache
+    pub fn set_cache_enabled(&self, enabled: bool) -> Result<(), Error> {
+        if self.ext_csd.cache_size() == 0 {
+            return Err(ErrorKind::Other.into());
+        }
+
+        self.with_recovery(|| {
+            self.switch_with_timeout(EXT_CSD_CACHE_CTRL, enabled as u8, FLUSH_TIMEOUT)
+        })?;
+        self.cache_enabled.set(enabled);
+        memlog!("cache {}", if enabled { "enabled" } else { "disabled" });
+
+        Ok(())
+    }
+
+    /// Writes the data held in the volatile cache of the eMMC to flash
+    ///
+    /// This is a no-operation if the cache is disabled
+    pub fn flush(&self) -> Result<(), Error> {
+        if !self.cache_enabled.get() {
+            return Ok(());
+        }
+
+        if self.usdhc.verbose {
+            memlog!("flush START @ {:?}", time::uptime());
+        }
+
+        self.with_recovery(|| self.switch_with_timeout(EXT_CSD_FLUSH_CACHE, 1, FLUSH_TIMEOUT))?;
+
+        if self.usdhc.verbose {
+            memlog!("flush DONE @ {:?}", time::uptime());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+//! Discard, trim and secure erase
+//!
+//! Reference: JEDEC JESD84-B51, sections 6.6.9 "Erase" to 6.6.14 "Sanitize"
+
+use core::{ops::Range, time::Duration};
+
+use crate::{
+    emmc::{eMMC, Error, ErrorKind, Partition},
+    memlog,
+    storage::BLOCK_SIZE,
+    time,
+    usdhc::default_timeout,
+};
+
+// `ERASE` arguments
+const ARG_TRIM: u32 = 0x0000_0001;
+const ARG_DISCARD: u32 = 0x0000_0003;
+const ARG_SECURE_TRIM1: u32 = 0x8000_0001;
+const ARG_SECURE_TRIM2: u32 = 0x8000_8000;
+
+// EXT_CSD fields (byte offsets)
+const EXT_CSD_SANITIZE_START: u8 = 165;
+
+/// The specification doesn't bound the duration of a sanitize operation;
+/// this is the timeout Linux uses
+const SANITIZE_TIMEOUT: Duration = Duration::from_secs(240);
+
+impl eMMC {
+    /// Tells the eMMC that the `blocks` of `partition` are no longer in use
+    ///
+    /// The DISCARD operation is used if supported, TRIM otherwise; on older
+    /// eMMCs that support neither this does nothing
+    pub(super) fn discard_blocks(
+        &self,
+        partition: Partition,
+        blocks: Range<u64>,
+        total: u32,
+    ) -> Result<(), Error> {
+        let (first, count) = if let Some(range) = check_range(blocks, total)? {
+            range
+        } else {
+            return Ok(());
+        };
+
+        if self.usdhc.verbose {
+            memlog!(
+                "{:?}.discard(lba={}, n={}) @ {:?}",
+                partition,
+                first,
+                count,
+                time::uptime()
+            );
+        }
+
+        let ext_csd = &self.ext_csd;
+        let arg = if ext_csd.supports_discard() {
+            ARG_DISCARD
+        } else if ext_csd.supports_trim() {
+            ARG_TRIM
+        } else {
+            return Ok(());
+        };
+
+        let timeout = self.erase_timeout(ext_csd.trim_timeout(), count);
+        self.with_recovery(|| {
+            self.select_partition(partition)?;
+            Ok(self.usdhc.erase(first, first + count - 1, arg, timeout)?)
+        })
+    }
+
+    /// Physically erases the `ranges` of blocks of `partition`, including any
+    /// copy of them the eMMC may have made while moving data around
+    ///
+    /// The SECURE TRIM operation is used if supported. Otherwise all the
+    /// ranges are trimmed and then a single SANITIZE operation, which
+    /// physically erases all the unmapped blocks of the device, is started;
+    /// this can take a while so callers should batch as many ranges as they
+    /// can into one call
+    pub(super) fn secure_erase_blocks(
+        &self,
+        partition: Partition,
+        ranges: &[Range<u64>],
+        total: u32,
+    ) -> Result<(), Error> {
+        // validate all the ranges before erasing anything
+        for blocks in ranges {
+            check_range(blocks.clone(), total)?;
+        }
+
+        let ext_csd = &self.ext_csd;
+        let secure_trim = ext_csd.supports_trim() && ext_csd.supports_secure_erase();
+        if !secure_trim && !ext_csd.supports_sanitize() {
+            memlog!("the eMMC doesn't support secure erase");
+            return Err(ErrorKind::Other.into());
+        }
+
+        let mut trimmed = false;
+        for blocks in ranges {
+            let (first, count) = if let Some(range) = check_range(blocks.clone(), total)? {
+                range
+            } else {
+                continue;
+            };
+
+            if self.usdhc.verbose {
+                memlog!(
+                    "{:?}.secure_erase(lba={}, n={}) @ {:?}",
+                    partition,
+                    first,
+                    count,
+                    time::uptime()
+                );
+            }
+
+            let last = first + count - 1;
+            if secure_trim {
+                let timeout = self.erase_timeout(ext_csd.secure_trim_timeout(), count);
+
+                self.with_recovery(|| {
+                    self.select_partition(partition)?;
+                    // step 1 marks the blocks; step 2 erases all the marked blocks
+                    self.usdhc.erase(first, last, ARG_SECURE_TRIM1, timeout)?;
+                    Ok(self.usdhc.erase(first, last, ARG_SECURE_TRIM2, timeout)?)
+                })?;
+            } else {
+                let timeout = self.erase_timeout(ext_csd.trim_timeout(), count);
+                self.with_recovery(|| {
+                    self.select_partition(partition)?;
+                    Ok(self.usdhc.erase(first, last, ARG_TRIM, timeout)?)
+                })?;
+
+                trimmed = true;
+            }
+        }
+
+        if trimmed {
+            self.sanitize()?;
+        }
+
+        Ok(())
+    }
+
+    /// Time allowed to an operation that takes up to `per_group` per erase
+    /// group over `count` blocks
+    fn erase_timeout(&self, per_group: Duration, count: u32) -> Duration {
+        let group_blocks = self.ext_csd.erase_group_size() / u32::from(BLOCK_SIZE);
+        let groups = if group_blocks == 0 {
+            1
+        } else {
+            (count + group_blocks - 1) / group_blocks
+        };
+
+        default_timeout() + per_group * groups
+    }
+
+    /// Physically erases all the unmapped blocks of the device
+    fn sanitize(&self) -> Result<(), Error> {
+        memlog!("sanitize START @ {:?}", time::uptime());
+
+        self.switch_with_timeout(EXT_CSD_SANITIZE_START, 1, SANITIZE_TIMEOUT)?;
+
+        memlog!("sanitize DONE @ {:?}", time::uptime());
+
+        Ok(())
+    }
+}
+
+/// Checks that `blocks` lies within a partition of `total` blocks; returns the
+/// first block and the number of blocks, or `None` if the range is empty
+pub(super) fn check_range(blocks: Range<u64>, total: u32) -> Result<Option<(u32, u32)>, Error> {
+    if blocks.start > blocks.end || blocks.end > u64::from(total) {
+        return Err(ErrorKind::Other.into());
+    }
+
+    if blocks.start == blocks.end {
+        Ok(None)
+    } else {
+        // NOTE(as) no truncation; see the check above
+        Ok(Some((
+            blocks.start as u32,
+            (blocks.end - blocks.start) as u32,
+        )))
+    }
+}
@@ -0,0 +1,343 @@
+//! Hardware partitions
+//!
+//! Reference: JEDEC JESD84-B51, section 6.2 "Partition Management"
+
+use core::{cmp, ops::Range, slice};
+
+use crate::{
+    emmc::{eMMC, Error, ErrorKind},
+    memlog,
+    storage::{Block, ManagedBlockDevice, BLOCK_SIZE},
+    time,
+    usdhc::fits_in,
+};
+
+// PARTITION_CONFIG fields
+const ACCESS_MASK: u8 = 0b111;
+const ACCESS_RPMB: u8 = 0b011;
+const BOOT_ENABLE_OFFSET: u8 = 3;
+const BOOT_ENABLE_MASK: u8 = 0b111 << BOOT_ENABLE_OFFSET;
+const BOOT_ACK: u8 = 1 << 6;
+
+/// A hardware partition of the eMMC
+///
+/// The names follow those used by Linux; e.g. `Boot0` is the partition the
+/// eMMC specification calls "boot area partition 1"
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Partition {
+    /// User data area
+    User,
+    /// First boot area partition
+    Boot0,
+    /// Second boot area partition
+    Boot1,
+    /// First general purpose partition
+    Gp0,
+    /// Second general purpose partition
+    Gp1,
+    /// Third general purpose partition
+    Gp2,
+    /// Fourth general purpose partition
+    Gp3,
+}
+
+impl Partition {
+    /// Value of the PARTITION_ACCESS field that routes data commands to this
+    /// partition
+    fn access(self) -> u8 {
+        match self {
+            Partition::User => 0b000,
+            Partition::Boot0 => 0b001,
+            Partition::Boot1 => 0b010,
+            // NOTE 0b011 is the RPMB
+            Partition::Gp0 => 0b100,
+            Partition::Gp1 => 0b101,
+            Partition::Gp2 => 0b110,
+            Partition::Gp3 => 0b111,
+        }
+    }
+}
+
+/// Partition the eMMC presents to the ROM bootloader
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BootPartition {
+    /// First boot area partition
+    Boot0,
+    /// Second boot area partition
+    Boot1,
+    /// User data area
+    User,
+}
+
+impl BootPartition {
+    /// Value of the BOOT_PARTITION_ENABLE field
+    fn enable(self) -> u8 {
+        match self {
+            BootPartition::Boot0 => 0b001,
+            BootPartition::Boot1 => 0b010,
+            BootPartition::User => 0b111,
+        }
+    }
+}
+
+impl eMMC {
+    /// Returns a handle to the hardware `partition`, or `None` if the eMMC
+    /// doesn't have that partition
+    ///
+    /// Several handles can exist at the same time; the eMMC is switched to the
+    /// right partition before each access
+    pub fn partition(&self, partition: Partition) -> Option<PartitionRef<'_>> {
+        let ext_csd = &self.ext_csd;
+        let gp_blocks = |index| {
+            // the general purpose partitions can only be used once their
+            // configuration has been completed
+            if ext_csd.partition_setting_completed() {
+                cmp::min(
+                    ext_csd.gp_size(index) / u64::from(BLOCK_SIZE),
+                    u64::from(self.blocks),
+                ) as u32
+            } else {
+                0
+            }
+        };
+        let blocks = match partition {
+            Partition::User => self.blocks,
+            Partition::Boot0 | Partition::Boot1 => ext_csd.boot_size() / u32::from(BLOCK_SIZE),
+            Partition::Gp0 => gp_blocks(0),
+            Partition::Gp1 => gp_blocks(1),
+            Partition::Gp2 => gp_blocks(2),
+            Partition::Gp3 => gp_blocks(3),
+        };
+
+        if blocks == 0 {
+            None
+        } else {
+            Some(PartitionRef {
+                emmc: self,
+                partition,
+                blocks,
+            })
+        }
+    }
+
+    /// Returns the partition the ROM bootloader boots from, or `None` if
+    /// booting from the eMMC is disabled
+    pub fn boot_partition(&self) -> Option<BootPartition> {
+        match (self.partition_config.get() & BOOT_ENABLE_MASK) >> BOOT_ENABLE_OFFSET {
+            0b001 => Some(BootPartition::Boot0),
+            0b010 => Some(BootPartition::Boot1),
+            0b111 => Some(BootPartition::User),
+            // 0b000 = disabled; the other values are reserved
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the eMMC sends a boot acknowledge when the ROM
+    /// bootloader starts a boot operation
+    pub fn boot_ack(&self) -> bool {
+        self.partition_config.get() & BOOT_ACK != 0
+    }
+
+    /// Changes the partition the ROM bootloader boots from (`None` disables
+    /// booting from the eMMC) and whether the eMMC sends a boot acknowledge
+    ///
+    /// **WARNING** these settings are stored in non-volatile memory; a wrong
+    /// setting can leave the device unable to boot from the eMMC
+    pub fn set_boot_partition(
+        &self,
+        partition: Option<BootPartition>,
+        ack: bool,
+    ) -> Result<(), Error> {
+        let enable = partition.map(BootPartition::enable).unwrap_or(0);
+
+        // NOTE the PARTITION_ACCESS bits are kept
+        let config = (self.partition_config.get() & ACCESS_MASK)
+            | enable << BOOT_ENABLE_OFFSET
+            | if ack { BOOT_ACK } else { 0 };
+        self.write_partition_config(config)?;
+
+        memlog!("boot partition set to {:?} (ack={})", partition, ack);
+
+        Ok(())
+    }
+
+    /// Routes the data commands that follow to the given `partition`
+    pub(super) fn select_partition(&self, partition: Partition) -> Result<(), Error> {
+        self.set_partition_access(partition.access())
+    }
+
+    /// Routes the data commands that follow to the RPMB
+    pub(super) fn select_rpmb(&self) -> Result<(), Error> {
+        self.set_partition_access(ACCESS_RPMB)
+    }
+
+    fn set_partition_access(&self, access: u8) -> Result<(), Error> {
+        let config = self.partition_config.get();
+        if config & ACCESS_MASK == access {
+            return Ok(());
+        }
+
+        // NOTE keep the boot configuration, which lives in the same field
+        self.write_partition_config((config & !ACCESS_MASK) | access)?;
+
+        if self.usdhc.verbose {
+            memlog!("selected partition {} @ {:?}", access, time::uptime());
+        }
+
+        Ok(())
+    }
+}
+
+/// A hardware partition of the eMMC
+pub struct PartitionRef<'a> {
+    emmc: &'a eMMC,
+    partition: Partition,
+    blocks: u32,
+}
+
+impl PartitionRef<'_> {
+    /// Returns the partition this handle gives access to
+    pub fn partition(&self) -> Partition {
+        self.partition
+    }
+
+    /// Reads consecutive blocks of memory, starting at block `lba` of the
+    /// partition
+    pub fn read_blocks(&self, lba: u32, blocks: &mut [Block]) -> Result<(), Error> {
+        self.assert_blocks_exist(lba, blocks.len());
+
+        if self.emmc.usdhc.verbose {
+            memlog!(
+                "{:?}.read_blocks(lba={}, n={}) @ {:?}",
+                self.partition,
+                lba,
+                blocks.len(),
+                time::uptime()
+            );
+        }
+
+        self.emmc.with_recovery(|| {
+            self.emmc.select_partition(self.partition)?;
+            Ok(self.emmc.usdhc.read_blocks(lba, blocks)?)
+        })
+    }
+
+    /// Transfers consecutive blocks of memory to the card, starting at block
+    /// `lba` of the partition, for them to be programmed to flash
+    pub fn write_blocks(&self, lba: u32, blocks: &[Block]) -> Result<(), Error> {
+        self.assert_blocks_exist(lba, blocks.len());
+
+        if self.emmc.usdhc.verbose {
+            memlog!(
+                "{:?}.write_blocks(lba={}, n={}) @ {:?}",
+                self.partition,
+                lba,
+                blocks.len(),
+                time::uptime()
+            );
+        }
+
+        self.emmc.with_recovery(|| {
+            self.emmc.select_partition(self.partition)?;
+            Ok(self.emmc.usdhc.write_blocks(lba, blocks)?)
+        })
+    }
+
+    /// Tells the eMMC that the data stored in the `blocks` range of the
+    /// partition is no longer needed; see `eMMC::discard`
+    pub fn discard(&self, blocks: Range<u64>) -> Result<(), Error> {
+        self.emmc
+            .discard_blocks(self.partition, blocks, self.blocks)
+    }
+
+    /// Physically erases the data stored in the `blocks` range of the
+    /// partition; see `eMMC::secure_erase`
+    pub fn secure_erase(&self, blocks: Range<u64>) -> Result<(), Error> {
+        self.secure_erase_batch(slice::from_ref(&blocks))
+    }
+
+    /// Physically erases the data stored in all the `ranges` of blocks of the
+    /// partition; see `eMMC::secure_erase_batch`
+    pub fn secure_erase_batch(&self, ranges: &[Range<u64>]) -> Result<(), Error> {
+        self.emmc
+            .secure_erase_blocks(self.partition, ranges, self.blocks)
+    }
+
+    fn assert_blocks_exist(&self, lba: u32, count: usize) {
+        assert!(
+            u64::from(lba) + count as u64 <= u64::from(self.blocks),
+            "block doesn't exist"
+        );
+    }
+}
+
+impl ManagedBlockDevice for PartitionRef<'_> {
+    type Error = Error;
+
+    fn total_blocks(&self) -> u64 {
+        u64::from(self.blocks)
+    }
+
+    fn read(&self, block: &mut Block, lba: u64) -> Result<(), Self::Error> {
+        if lba >= self.total_blocks() {
+            return Err(ErrorKind::Other.into());
+        }
+
+        self.read_blocks(lba as u32, slice::from_mut(block))
+    }
+
+    fn write(&mut self, block: &Block, lba: u64) -> Result<(), Self::Error> {
+        if lba >= self.total_blocks() {
+            return Err(ErrorKind::Other.into());
+        }
+
+        self.write_blocks(lba as u32, slice::from_ref(block))
+    }
+
+    fn read_vectored(&self, bufs: &mut [&mut [u8]], lba: u64) -> Result<(), Self::Error> {
+        if !fits_in(
+            lba,
+            bufs.iter().map(|buf| buf.len()).sum(),
+            self.total_blocks(),
+        ) {
+            return Err(ErrorKind::Other.into());
+        }
+
+        self.emmc.with_recovery(|| {
+            self.emmc.select_partition(self.partition)?;
+            Ok(self.emmc.usdhc.read_vectored_any(lba as u32, bufs)?)
+        })
+    }
+
+    fn write_vectored(&mut self, bufs: &[&[u8]], lba: u64) -> Result<(), Self::Error> {
+        if !fits_in(
+            lba,
+            bufs.iter().map(|buf| buf.len()).sum(),
+            self.total_blocks(),
+        ) {
+            return Err(ErrorKind::Other.into());
+        }
+
+        self.emmc.with_recovery(|| {
+            self.emmc.select_partition(self.partition)?;
+            Ok(self.emmc.usdhc.write_vectored_any(lba as u32, bufs)?)
+        })
+    }
+
+    fn discard(&mut self, blocks: Range<u64>) -> Result<(), Self::Error> {
+        PartitionRef::discard(self, blocks)
+    }
+
+    fn secure_erase(&mut self, blocks: Range<u64>) -> Result<(), Self::Error> {
+        PartitionRef::secure_erase(self, blocks)
+    }
+
+    fn secure_erase_batch(&mut self, ranges: &mut [Range<u64>]) -> Result<(), Self::Error> {
+        PartitionRef::secure_erase_batch(self, ranges)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        // the cache is shared by all the partitions
+        self.emmc.flush()
+    }
+}
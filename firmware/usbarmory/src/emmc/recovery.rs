@@ -0,0 +1,79 @@
+//! Recovery from failed commands and data transfers
+
+use core::time::Duration;
+
+use crate::{
+    emmc::{eMMC, Error, ErrorKind, RCA},
+    memlog, time,
+};
+
+/// Number of times a failed operation is retried before giving up
+pub(super) const MAX_RETRIES: u8 = 3;
+
+/// Time to wait before the `retry`-th retry (1-based)
+pub(super) fn backoff(retry: u8) -> Duration {
+    // 1 ms, 2 ms, 4 ms, ..
+    Duration::from_millis(1 << u32::from(retry.saturating_sub(1)))
+}
+
+impl eMMC {
+    /// Runs `f` and, if it fails, brings the host and the eMMC back to the
+    /// transfer state and tries again, up to `MAX_RETRIES` times
+    ///
+    /// `f` must be idempotent. Errors the eMMC reports in its status, like an
+    /// out of range address, are not retried. If CRC errors persist across
+    /// retries the bus is switched to the next slower mode
+    pub(super) fn with_recovery<T>(
+        &self,
+        mut f: impl FnMut() -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut retries = 0;
+        let mut crc_errors = 0;
+        loop {
+            let mut error = match f() {
+                Ok(x) => return Ok(x),
+                Err(e) => e.retried(retries),
+            };
+
+            if let ErrorKind::Card(_) = error.kind {
+                return Err(error);
+            }
+
+            // leave the host and the eMMC in a known state, even when giving up
+            if !self.recover(&mut error) || retries == MAX_RETRIES {
+                return Err(error);
+            }
+
+            if error.kind == ErrorKind::Crc {
+                crc_errors += 1;
+
+                // a single CRC error may be a glitch; more mean the bus is not
+                // reliable in the current mode
+                if crc_errors > 1 && self.fall_back().map_err(|e| e.retried(retries))? {
+                    crc_errors = 0;
+                }
+            }
+
+            retries += 1;
+            memlog!("{}; retry {}", error, retries);
+            time::wait(backoff(retries));
+        }
+    }
+
+    /// Brings the host and the eMMC back to the transfer state after `error`
+    /// and attaches the status the eMMC reported to it; returns `false` if the
+    /// eMMC could not be recovered
+    pub(super) fn recover(&self, error: &mut Error) -> bool {
+        match self.usdhc.recover(RCA) {
+            Ok(status) => {
+                error.status = Some(status);
+                true
+            }
+
+            Err(e) => {
+                memlog!("recovery failed: {}", e);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Replay Protected Memory Block
+//!
+//! Reference: JEDEC JESD84-B51, section 6.6.22
+
+use core::{fmt, num::NonZeroU32, ptr, slice};
+
+use ::rpmb::{Frame, Mac, Request, DATA_SIZE, KEY_SIZE, MAC_SIZE, NONCE_SIZE};
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    dcp::{self, HmacSha256, Sha256},
+    emmc::{self, eMMC},
+    memlog,
+    storage::Block,
+    time,
+};
+
+/// Access to the RPMB partition of the eMMC
+///
+/// Reads and writes are authenticated with a MAC computed, by the DCP, with the authentication key
+/// given to `eMMC::rpmb`. Every write increases the write counter of the device; the counter can't
+/// be decreased or reset, which makes it usable as a monotonic counter
+///
+/// The RPMB is addressed in 256-byte units; see `size`
+pub struct Rpmb<'a> {
+    emmc: &'a eMMC,
+    hmac: Hmac,
+    key: [u8; KEY_SIZE],
+}
+
+impl eMMC {
+    /// Gives access to the RPMB partition
+    ///
+    /// The SHA-256 channel of the DCP is used to authenticate the frames exchanged with the eMMC
+    /// using `key`. This doesn't program the key into the eMMC; see `Rpmb::program_key`
+    pub fn rpmb(&self, hasher: Sha256, key: &[u8; KEY_SIZE]) -> Result<Rpmb<'_>, Error> {
+        Ok(Rpmb {
+            emmc: self,
+            hmac: Hmac(HmacSha256::from_hasher(hasher, key)?),
+            key: *key,
+        })
+    }
+}
+
+impl Rpmb<'_> {
+    /// Returns the size of the RPMB partition, in 256-byte units
+    pub fn size(&self) -> u32 {
+        self.emmc.ext_csd.rpmb_size() / DATA_SIZE as u32
+    }
+
+    /// Programs the authentication key into the eMMC
+    ///
+    /// **WARNING** this can only be done once in the lifetime of the eMMC; the key can't be
+    /// changed or read back afterwards
+    pub fn program_key(&mut self) -> Result<(), Error> {
+        if self.emmc.usdhc.verbose {
+            memlog!("rpmb.program_key() @ {:?}", time::uptime());
+        }
+
+        self.send(&Frame::program_key(&self.key), true)?;
+        self.send(&Frame::read_result(), false)?;
+
+        // NOTE the response to this request is not authenticated
+        let response = self.receive()?;
+        response.check(Request::ProgramKey, None)?;
+
+        memlog!("programmed the RPMB authentication key");
+
+        Ok(())
+    }
+
+    /// Reads the write counter of the eMMC
+    ///
+    /// A fresh nonce is taken from `rng` to ensure that the response is not a replay of a previous
+    /// one; if `rng` fails to produce it an `Rng` error is returned
+    pub fn write_counter<R>(&mut self, rng: &mut R) -> Result<u32, Error>
+    where
+        R: CryptoRng + RngCore,
+    {
+        let nonce = nonce(rng)?;
+        self.send(&Frame::read_counter(&nonce), false)?;
+
+        let response = self.receive()?;
+        response.check(Request::ReadCounter, Some(&nonce))?;
+        self.verify(&response)?;
+
+        Ok(response.write_counter())
+    }
+
+    /// Reads the 256 bytes of data stored at `address` into `data`
+    ///
+    /// See `write_counter` for the use of `rng`
+    pub fn read<R>(
+        &mut self,
+        address: u16,
+        data: &mut [u8; DATA_SIZE],
+        rng: &mut R,
+    ) -> Result<(), Error>
+    where
+        R: CryptoRng + RngCore,
+    {
+        if self.emmc.usdhc.verbose {
+            memlog!("rpmb.read(address={}) @ {:?}", address, time::uptime());
+        }
+
+        let nonce = nonce(rng)?;
+        self.send(&Frame::authenticated_read(address, &nonce), false)?;
+
+        let response = self.receive()?;
+        response.check(Request::AuthenticatedRead, Some(&nonce))?;
+        self.verify(&response)?;
+
+        if response.address() != address {
+            return Err(Error::Rpmb(::rpmb::Error::AddressMismatch));
+        }
+
+        data.copy_from_slice(response.data());
+
+        Ok(())
+    }
+
+    /// Writes the 256 bytes of `data` at `address`; returns the new value of the write counter
+    ///
+    /// The current value of the write counter is read first; see `write_counter` for the use of
+    /// `rng`
+    pub fn write<R>(
+        &mut self,
+        address: u16,
+        data: &[u8; DATA_SIZE],
+        rng: &mut R,
+    ) -> Result<u32, Error>
+    where
+        R: CryptoRng + RngCore,
+    {
+        if self.emmc.usdhc.verbose {
+            memlog!("rpmb.write(address={}) @ {:?}", address, time::uptime());
+        }
+
+        let counter = self.write_counter(rng)?;
+        let request = Frame::authenticated_write(&mut self.hmac, address, counter, data)?;
+
+        // NOTE writes to the RPMB must be reliable writes
+        self.send(&request, true)?;
+        self.send(&Frame::read_result(), false)?;
+
+        let response = self.receive()?;
+        response.check(Request::AuthenticatedWrite, None)?;
+        self.verify(&response)?;
+
+        if response.address() != address {
+            return Err(Error::Rpmb(::rpmb::Error::AddressMismatch));
+        }
+
+        // the counter is increased by one on every successful write
+        if Some(response.write_counter()) != counter.checked_add(1) {
+            return Err(Error::Rpmb(::rpmb::Error::CounterMismatch));
+        }
+
+        Ok(response.write_counter())
+    }
+
+    fn verify(&mut self, response: &Frame) -> Result<(), Error> {
+        if ::rpmb::verify(&mut self.hmac, slice::from_ref(response))? {
+            Ok(())
+        } else {
+            Err(Error::Rpmb(::rpmb::Error::InvalidMac))
+        }
+    }
+
+    /// Sends a request frame to the eMMC
+    fn send(&self, frame: &Frame, reliable: bool) -> Result<(), Error> {
+        self.emmc.select_rpmb()?;
+
+        let block = Block {
+            bytes: *frame.as_bytes(),
+        };
+        // NOTE the address argument of the data commands is ignored by the RPMB
+        self.emmc
+            .usdhc
+            .write_blocks_counted(0, slice::from_ref(&block), reliable)?;
+
+        Ok(())
+    }
+
+    /// Receives a response frame from the eMMC
+    fn receive(&self) -> Result<Frame, Error> {
+        self.emmc.select_rpmb()?;
+
+        let mut block = Block::zeroed();
+        self.emmc
+            .usdhc
+            .read_blocks_counted(0, slice::from_mut(&mut block))?;
+
+        Ok(Frame::from_bytes(&block.bytes))
+    }
+}
+
+impl Drop for Rpmb<'_> {
+    fn drop(&mut self) {
+        // don't leave the key behind
+        unsafe { ptr::write_volatile(&mut self.key, [0; KEY_SIZE]) }
+    }
+}
+
+/// RPMB error
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The eMMC reported an error
+    Emmc(emmc::Error),
+
+    /// The DCP reported an error while computing a MAC
+    Dcp(dcp::Error),
+
+    /// The eMMC reported a failure or its response could not be authenticated
+    Rpmb(::rpmb::Error),
+
+    /// The RNG failed to produce a nonce; contains the error code it reported, if any
+    Rng(Option<NonZeroU32>),
+}
+
+impl From<emmc::Error> for Error {
+    fn from(e: emmc::Error) -> Self {
+        Error::Emmc(e)
+    }
+}
+
+impl From<emmc::ErrorKind> for Error {
+    fn from(e: emmc::ErrorKind) -> Self {
+        Error::Emmc(e.into())
+    }
+}
+
+impl From<dcp::Error> for Error {
+    fn from(e: dcp::Error) -> Self {
+        Error::Dcp(e)
+    }
+}
+
+impl From<::rpmb::Error> for Error {
+    fn from(e: ::rpmb::Error) -> Self {
+        Error::Rpmb(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Emmc(e) => write!(f, "eMMC error: {}", e),
+            Error::Dcp(e) => write!(f, "DCP error: {:?}", e),
+            Error::Rpmb(e) => write!(f, "RPMB error: {:?}", e),
+            Error::Rng(Some(code)) => write!(f, "RNG error (code {:#x})", code),
+            Error::Rng(None) => f.write_str("RNG error"),
+        }
+    }
+}
+
+struct Hmac(HmacSha256);
+
+impl Mac for Hmac {
+    type Error = dcp::Error;
+
+    fn input(&mut self, input: &[u8]) -> Result<(), dcp::Error> {
+        self.0.input(input)
+    }
+
+    fn finalize_reset(&mut self) -> Result<[u8; MAC_SIZE], dcp::Error> {
+        self.0.finalize_reset()
+    }
+}
+
+fn nonce<R>(rng: &mut R) -> Result<[u8; NONCE_SIZE], Error>
+where
+    R: CryptoRng + RngCore,
+{
+    let mut nonce = [0; NONCE_SIZE];
+    rng.try_fill_bytes(&mut nonce)
+        .map_err(|e| Error::Rng(e.code()))?;
+    Ok(nonce)
+}
@@ -0,0 +1,195 @@
+//! Non-blocking data transfers
+
+use core::cell::Cell;
+
+use crate::{
+    emmc::{eMMC, Error, Partition, RCA},
+    memlog,
+    storage::Block,
+    time::{self, Instant},
+    usdhc::{self, transfer_timeout},
+};
+
+impl eMMC {
+    /// Starts reading consecutive blocks of memory, starting at block `lba`,
+    /// into `blocks`; see `Transfer` for details
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `blocks` is empty or longer than 65535 blocks, or
+    /// if the blocks don't exist
+    pub fn read_job(
+        self,
+        lba: u32,
+        blocks: &'static mut [Block],
+    ) -> Transfer<&'static mut [Block]> {
+        let count = blocks.len();
+        let addr = blocks.as_mut_ptr() as usize;
+        self.start_job(true, lba, count, addr, blocks)
+    }
+
+    /// Starts transferring consecutive blocks of memory to the card, starting
+    /// at block `lba`, for them to be programmed to flash; see `Transfer` for
+    /// details
+    ///
+    /// See `read_job` for the panicking conditions
+    pub fn write_job(self, lba: u32, blocks: &'static [Block]) -> Transfer<&'static [Block]> {
+        let count = blocks.len();
+        let addr = blocks.as_ptr() as usize;
+        self.start_job(false, lba, count, addr, blocks)
+    }
+
+    fn start_job<B>(self, read: bool, lba: u32, count: usize, addr: usize, buf: B) -> Transfer<B> {
+        assert!(
+            count != 0 && count <= usize::from(u16::max_value()),
+            "a transfer must move between 1 and 65535 blocks"
+        );
+        self.assert_blocks_exist(lba, count);
+
+        if self.usdhc.verbose {
+            memlog!(
+                "{}_job(lba={}, n={}) @ {:?}",
+                if read { "read" } else { "write" },
+                lba,
+                count,
+                time::uptime()
+            );
+        }
+
+        // NOTE(as) no truncation; see the assertion above
+        let res = self.with_recovery(|| {
+            self.select_partition(Partition::User)?;
+            Ok(self.usdhc.start_transfer(read, lba, count as u16, addr)?)
+        });
+
+        Transfer {
+            outcome: Cell::new(res.err().map(Err)),
+            read,
+            count: count as u16,
+            start: Instant::now(),
+            resources: Some((self, buf)),
+        }
+    }
+}
+
+/// An eMMC data transfer that's being processed in the background
+///
+/// The transfer holds on to the `eMMC` handle and the buffer `B` the DMA is
+/// working on; they are handed back by `wait` when the transfer is over.
+///
+/// Completion of the transfer triggers the `uSDHC2` interrupt. To avoid busy
+/// waiting bind a (RTIC) hardware task to that interrupt and call `is_done`
+/// followed by `wait` from it; `is_done` acknowledges the interrupt once the
+/// transfer is over so it must be called from the interrupt handler. If the
+/// transfer could not be started it's over right away and no interrupt fires.
+///
+/// `is_done` never blocks. After a write the eMMC may still be programming the
+/// data it received into flash; `wait` waits for that.
+///
+/// Failed transfers are not retried but the host and the eMMC are left ready
+/// for the next transfer.
+///
+/// Dropping a `Transfer` blocks until the DMA is done with the buffer. A
+/// transfer that takes longer than expected is aborted, which also stops the
+/// DMA, and reported as a timeout
+#[must_use = "dropping a `Transfer` blocks until the DMA is done with the buffer"]
+pub struct Transfer<B> {
+    // `Some` once the transfer is over
+    outcome: Cell<Option<Result<(), Error>>>,
+    read: bool,
+    // number of blocks
+    count: u16,
+    // when the transfer was started
+    start: Instant,
+    resources: Option<(eMMC, B)>,
+}
+
+impl<B> Transfer<B> {
+    /// Returns `true` if the transfer is over, i.e. the DMA is done with the
+    /// buffer
+    pub fn is_done(&self) -> bool {
+        self.check().is_some()
+    }
+
+    /// [Blocking] Waits until the transfer is over and returns the `eMMC`
+    /// handle and the buffer, plus the outcome of the transfer
+    ///
+    /// For writes this also waits until the eMMC has programmed the data to
+    /// flash. A transfer that doesn't complete in time is aborted and reported
+    /// as a timeout. If the transfer failed the contents of the buffer are
+    /// unspecified
+    pub fn wait(mut self) -> ((eMMC, B), Result<(), Error>) {
+        let res = self.finish();
+        let resources = self.resources.take().expect("UNREACHABLE");
+        (resources, res)
+    }
+
+    fn check(&self) -> Option<Result<(), Error>> {
+        if let Some(res) = self.outcome.get() {
+            return Some(res);
+        }
+
+        let emmc = &self.resources.as_ref().expect("UNREACHABLE").0;
+        // NOTE this may run in the interrupt handler so it must not block
+        let res = match emmc.usdhc.poll_transfer()? {
+            Ok(()) => Ok(()),
+
+            Err(e) => {
+                let mut e = Error::from(e);
+                emmc.recover(&mut e);
+                Err(e)
+            }
+        };
+        self.outcome.set(Some(res));
+
+        Some(res)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        let timeout = transfer_timeout(self.count);
+        let res = loop {
+            if let Some(res) = self.check() {
+                break res;
+            }
+
+            if self.start.elapsed() > timeout {
+                memlog!("transfer timeout @ {:?}", time::uptime());
+
+                // stops the DMA and gets the host and the card ready for the
+                // next transfer
+                let emmc = &self.resources.as_ref().expect("UNREACHABLE").0;
+                emmc.usdhc.abort_transfer();
+                let mut e = Error::from(usdhc::Error::Timeout);
+                emmc.recover(&mut e);
+
+                self.outcome.set(Some(Err(e)));
+                break Err(e);
+            }
+
+            // instead of busy waiting flush the in-memory logger
+            crate::memlog_try_flush();
+        };
+
+        match res {
+            // the card may still be busy programming the data it received;
+            // this is also a final check of its status
+            Ok(()) if !self.read => {
+                let emmc = &self.resources.as_ref().expect("UNREACHABLE").0;
+                emmc.usdhc
+                    .wait_for_programming(RCA, transfer_timeout(self.count))
+                    .map_err(Error::from)
+            }
+
+            res => res,
+        }
+    }
+}
+
+impl<B> Drop for Transfer<B> {
+    fn drop(&mut self) {
+        if self.resources.is_some() {
+            // the DMA may still be using the buffer
+            let _ = self.finish();
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Write protection of the user data area
+//!
+//! Reference: JEDEC JESD84-B51, section "Write protect management"
+
+use core::ops::Range;
+
+use crate::{
+    emmc::{eMMC, erase::check_range, Error, ErrorKind, Partition},
+    memlog,
+    storage::BLOCK_SIZE,
+};
+
+// EXT_CSD fields (byte offsets)
+const EXT_CSD_USER_WP: u8 = 171;
+
+// USER_WP bits
+/// `SET_WRITE_PROT` applies power-on write protection
+const US_PWR_WP_EN: u8 = 1;
+/// `SET_WRITE_PROT` applies permanent write protection
+const US_PERM_WP_EN: u8 = 1 << 2;
+/// Power-on write protection is disabled (until the next power cycle)
+const US_PWR_WP_DIS: u8 = 1 << 3;
+/// Permanent write protection is disabled (forever)
+const US_PERM_WP_DIS: u8 = 1 << 4;
+
+/// Write protection of a write protect group
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WriteProtection {
+    /// Not write protected
+    None,
+    /// Write protected until cleared with `clear_write_protection`
+    Temporary,
+    /// Write protected until the eMMC is power cycled
+    PowerOn,
+    /// Write protected forever
+    Permanent,
+}
+
+impl eMMC {
+    /// Returns the size of a write protect group, in blocks
+    ///
+    /// Write protection is applied to whole groups; this is 0 if the eMMC
+    /// doesn't support write protection of the user data area
+    pub fn write_protect_group_blocks(&self) -> u32 {
+        // NOTE(as) no truncation in practice; at most 255 * 255 erase groups
+        (self.ext_csd.wp_group_size() / u64::from(BLOCK_SIZE)) as u32
+    }
+
+    /// Write protects the groups of the user data area that contain the
+    /// `blocks`
+    ///
+    /// `WriteProtection::None` is rejected; use `clear_write_protection`
+    /// instead
+    ///
+    /// **WARNING** `WriteProtection::Permanent` can NOT be undone; the groups
+    /// will be read-only for the lifetime of the device
+    pub fn set_write_protection(
+        &self,
+        blocks: Range<u64>,
+        protection: WriteProtection,
+    ) -> Result<(), Error> {
+        // the kind of protection `SET_WRITE_PROT` applies is selected through
+        // the USER_WP field
+        let (enable, disable) = match protection {
+            WriteProtection::None => return Err(ErrorKind::Other.into()),
+            WriteProtection::Temporary => (0, 0),
+            WriteProtection::PowerOn => (US_PWR_WP_EN, US_PWR_WP_DIS),
+            WriteProtection::Permanent => (US_PERM_WP_EN, US_PERM_WP_DIS),
+        };
+
+        let groups = self.write_protect_groups(blocks)?;
+        let user_wp = self.ext_csd()?.user_wp();
+        if user_wp & disable != 0 {
+            memlog!("{:?} write protection has been disabled", protection);
+            return Err(ErrorKind::Other.into());
+        }
+
+        memlog!("write protecting blocks {:?} ({:?})", groups, protection);
+
+        self.select_partition(Partition::User)?;
+        let enabled = user_wp & !(US_PWR_WP_EN | US_PERM_WP_EN) | enable;
+        self.with_recovery(|| self.switch(EXT_CSD_USER_WP, enabled))?;
+        let res = self.protect_groups(groups, true);
+        // later protections default to temporary ones
+        self.with_recovery(|| self.switch(EXT_CSD_USER_WP, user_wp))?;
+
+        res
+    }
+
+    /// Clears the temporary write protection of the groups of the user data
+    /// area that contain the `blocks`
+    ///
+    /// Power-on and permanent write protections can't be cleared
+    pub fn clear_write_protection(&self, blocks: Range<u64>) -> Result<(), Error> {
+        let groups = self.write_protect_groups(blocks)?;
+
+        memlog!("clearing write protection of blocks {:?}", groups);
+
+        self.select_partition(Partition::User)?;
+        self.protect_groups(groups, false)
+    }
+
+    /// Returns the write protection of the group of the user data area that
+    /// contains block `lba`
+    pub fn write_protection(&self, lba: u32) -> Result<WriteProtection, Error> {
+        self.assert_blocks_exist(lba, 1);
+
+        let types = self.with_recovery(|| {
+            self.select_partition(Partition::User)?;
+            Ok(self.usdhc.write_protect_type(lba)?)
+        })?;
+
+        Ok(match types & 0b11 {
+            0b00 => WriteProtection::None,
+            0b01 => WriteProtection::Temporary,
+            0b10 => WriteProtection::PowerOn,
+            _ => WriteProtection::Permanent,
+        })
+    }
+
+    /// Sets or clears the write protection of every group in `groups`, a range
+    /// of blocks that starts at a group boundary
+    fn protect_groups(&self, groups: Range<u32>, protect: bool) -> Result<(), Error> {
+        let group_blocks = self.write_protect_group_blocks() as usize;
+        for lba in groups.step_by(group_blocks) {
+            self.with_recovery(|| Ok(self.usdhc.write_protect(lba, protect)?))?;
+        }
+
+        Ok(())
+    }
+
+    /// Extends `blocks` down to the start of its first write protect group
+    fn write_protect_groups(&self, blocks: Range<u64>) -> Result<Range<u32>, Error> {
+        let group_blocks = self.write_protect_group_blocks();
+        if group_blocks == 0 {
+            return Err(ErrorKind::Other.into());
+        }
+
+        Ok(match check_range(blocks, self.blocks)? {
+            Some((first, count)) => first - first % group_blocks..first + count,
+            None => 0..0,
+        })
+    }
+}
@@ -1,8 +1,8 @@
 //! File system access.
 
-use core::cell::RefCell;
+use core::{cell::RefCell, ops::Range, ptr};
 
-use crate::storage::{Block, ManagedBlockDevice, BLOCK_SIZE};
+use crate::storage::{ManagedBlockDevice, BLOCK_SIZE};
 use littlefs2::{
     consts,
     driver::Storage,
@@ -21,6 +21,9 @@ use memlog::memlog;
 // must be bigger than this number
 const BLOCK_COUNT: usize = 131_072; // 64 MiB / 512 (=block_size)
 
+/// Maximum number of discontiguous block ranges a file can span for `LittleFs::remove_secure`.
+const MAX_FILE_RANGES: usize = 32;
+
 /// Backing storage used by littlefs.
 pub struct LittleFsAlloc<D: ManagedBlockDevice> {
     inner: FilesystemAllocation<LfsStorage<D>>,
@@ -60,7 +63,10 @@ impl<'a, D: ManagedBlockDevice> LittleFs<'a, D> {
             return Err(littlefs2::io::Error::NoSpace); // close enough?
         }
 
-        let mut storage = RefCell::new(LfsStorage { inner: blockdev });
+        let mut storage = RefCell::new(LfsStorage {
+            inner: blockdev,
+            reads: None,
+        });
         let fs = RefCell::new(Filesystem::mount(&mut alloc.inner, storage.get_mut())?);
 
         Ok(Self { storage, fs })
@@ -81,7 +87,10 @@ impl<'a, D: ManagedBlockDevice> LittleFs<'a, D> {
 
     /// Formats `blockdev`, creating a fresh littlefs file system (this erases all data!).
     pub fn format(blockdev: D) -> io::Result<()> {
-        Filesystem::format(&mut LfsStorage { inner: blockdev })
+        Filesystem::format(&mut LfsStorage {
+            inner: blockdev,
+            reads: None,
+        })
     }
 
     /// Returns the available space in Bytes (approximated).
@@ -106,6 +115,97 @@ impl<'a, D: ManagedBlockDevice> LittleFs<'a, D> {
             .remove(path.as_ref(), &mut self.storage.borrow_mut())
     }
 
+    /// Removes the file at `path` and physically erases the blocks that held its contents.
+    ///
+    /// `remove` only unlinks the file: littlefs leaves its blocks untouched until it reuses them
+    /// and then merely discards them, so the device may keep the data around. This method instead
+    /// erases all the blocks of the file in a single `ManagedBlockDevice::secure_erase_batch` call
+    /// right after removing it.
+    ///
+    /// NOTE only the blocks the file uses when this is called are erased, not the ones freed by
+    /// earlier overwrites of the file.
+    ///
+    /// littlefs stores small files inline in the metadata of their directory, which is still in
+    /// use after the removal, so their contents can't be erased. For those files `Invalid` is
+    /// returned and the file is not removed; files larger than `BLOCK_SIZE` bytes are never
+    /// stored inline so pad secrets, e.g. keys, to that size if they need to be securely removed.
+    ///
+    /// `NoSpace` is returned, without removing the file, if the file or its metadata is too
+    /// fragmented.
+    pub fn remove_secure(&self, path: impl AsRef<[u8]>) -> io::Result<()> {
+        let path = path.as_ref();
+
+        let res = self.data_blocks(path);
+        self.storage.borrow_mut().reads = None;
+        let mut blocks = res?;
+        if blocks.overflow {
+            return Err(io::Error::NoSpace);
+        }
+
+        self.remove(path)?;
+
+        self.storage
+            .borrow_mut()
+            .inner
+            .secure_erase_batch(blocks.ranges_mut())
+            .map_err(|_| io::Error::Io)
+    }
+
+    /// Returns the blocks that hold the contents, but no metadata, of the file at `path`.
+    fn data_blocks(&self, path: &[u8]) -> io::Result<BlockSet> {
+        // the blocks littlefs reads to open the file hold metadata, possibly shared with other
+        // files, and must be left alone
+        self.storage.borrow_mut().reads = Some(RefCell::new(BlockSet::default()));
+        let mut alloc = FileAlloc::new();
+        let file = File::open(self, &mut alloc, path)?;
+        let metadata = self.storage.borrow_mut().reads.replace(RefCell::default());
+        let len = file.len()?;
+
+        // reading the whole file touches all its data blocks
+        let mut buf = [0; BLOCK_SIZE as usize];
+        let res = loop {
+            match file.read(&mut buf) {
+                Ok(0) => break Ok(()),
+                Ok(_) => {}
+                Err(e) => break Err(e),
+            }
+        };
+        for byte in buf.iter_mut() {
+            // NOTE(volatile) the buffer may hold secrets; don't let the compiler elide this
+            unsafe { ptr::write_volatile(byte, 0) }
+        }
+        file.close()?;
+        res?;
+
+        let reads = self.storage.borrow_mut().reads.take();
+        let (metadata, reads) = match (metadata, reads) {
+            (Some(metadata), Some(reads)) => (metadata.into_inner(), reads.into_inner()),
+            _ => unsafe { assume_unreachable!() },
+        };
+
+        // some metadata blocks may be missing from the set; don't risk erasing them
+        if metadata.overflow {
+            return Err(io::Error::NoSpace);
+        }
+
+        let mut blocks = BlockSet::default();
+        blocks.overflow = reads.overflow;
+        for range in reads.ranges() {
+            for block in range.clone() {
+                if !metadata.contains(block) {
+                    blocks.insert(block);
+                }
+            }
+        }
+
+        // the contents are stored inline, in the (shared) metadata
+        if len != 0 && blocks.ranges().is_empty() && !blocks.overflow {
+            return Err(io::Error::Invalid);
+        }
+
+        Ok(blocks)
+    }
+
     /// Returns an iterator over the contents of the directory at `path`.
     pub fn read_dir<'r>(&'r self, path: impl AsRef<[u8]>) -> io::Result<ReadDir<'r, 'a, D>> {
         self.fs
@@ -356,6 +456,8 @@ impl<D: ManagedBlockDevice> DirEntry<D> {
 #[doc(hidden)]
 pub struct LfsStorage<D: ManagedBlockDevice> {
     inner: D,
+    // when `Some`, records the blocks littlefs reads; see `LittleFs::remove_secure`
+    reads: Option<RefCell<BlockSet>>,
 }
 
 impl<D: ManagedBlockDevice> Storage for LfsStorage<D> {
@@ -377,39 +479,103 @@ impl<D: ManagedBlockDevice> Storage for LfsStorage<D> {
     const FILEBYTES_MAX: usize = 2_147_483_647;
 
     fn read(&self, off: usize, buf: &mut [u8]) -> littlefs2::io::Result<usize> {
-        let mut lba = off / Self::BLOCK_SIZE;
-
-        let mut block = Block::zeroed();
-        for buf_block in buf.chunks_mut(Self::BLOCK_SIZE) {
-            self.inner
-                .read(&mut block, lba as u64)
-                .map_err(|_| littlefs2::io::Error::Io)?;
-            buf_block.copy_from_slice(&block.bytes);
-            lba += 1;
+        let lba = off / Self::BLOCK_SIZE;
+        let len = buf.len();
+
+        self.inner
+            .read_vectored(&mut [buf], lba as u64)
+            .map_err(|_| littlefs2::io::Error::Io)?;
+
+        if let Some(reads) = &self.reads {
+            let mut reads = reads.borrow_mut();
+            for block in lba..lba + len / Self::BLOCK_SIZE {
+                reads.insert(block as u64);
+            }
         }
 
-        Ok(buf.len())
+        Ok(len)
     }
 
     fn write(&mut self, off: usize, data: &[u8]) -> littlefs2::io::Result<usize> {
-        let mut lba = off / Self::BLOCK_SIZE;
-
-        let mut block = Block::zeroed();
-        for buf_block in data.chunks(Self::BLOCK_SIZE) {
-            block.bytes.copy_from_slice(buf_block);
-            self.inner
-                .write(&block, lba as u64)
-                .map_err(|_| littlefs2::io::Error::Io)?;
-            lba += 1;
-        }
+        let lba = off / Self::BLOCK_SIZE;
+
+        self.inner
+            .write_vectored(&[data], lba as u64)
+            .map_err(|_| littlefs2::io::Error::Io)?;
 
         self.inner.flush().map_err(|_| littlefs2::io::Error::Io)?;
 
         Ok(data.len())
     }
 
-    fn erase(&mut self, _off: usize, len: usize) -> littlefs2::io::Result<usize> {
-        // A `ManagedBlockDevice` can just overwrite individual blocks, no need to erase any.
+    fn erase(&mut self, off: usize, len: usize) -> littlefs2::io::Result<usize> {
+        // A `ManagedBlockDevice` can just overwrite individual blocks so erasing is not required;
+        // it's still useful to let the device know the old contents are gone.
+        // NOTE littlefs erases blocks right before reusing them, which is too late (and too
+        // frequent) for a secure erase; see `LittleFs::remove_secure` instead
+        let start = (off / Self::BLOCK_SIZE) as u64;
+        self.inner
+            .discard(start..start + (len / Self::BLOCK_SIZE) as u64)
+            .map_err(|_| littlefs2::io::Error::Io)?;
+
         Ok(len)
     }
 }
+
+/// A set of blocks, stored as up to `MAX_FILE_RANGES` ranges.
+struct BlockSet {
+    ranges: [Range<u64>; MAX_FILE_RANGES],
+    len: usize,
+    // some blocks didn't fit in `ranges`
+    overflow: bool,
+}
+
+impl Default for BlockSet {
+    fn default() -> Self {
+        const EMPTY: Range<u64> = 0..0;
+
+        Self {
+            ranges: [EMPTY; MAX_FILE_RANGES],
+            len: 0,
+            overflow: false,
+        }
+    }
+}
+
+impl BlockSet {
+    fn ranges(&self) -> &[Range<u64>] {
+        &self.ranges[..self.len]
+    }
+
+    fn ranges_mut(&mut self) -> &mut [Range<u64>] {
+        &mut self.ranges[..self.len]
+    }
+
+    fn contains(&self, block: u64) -> bool {
+        self.ranges().iter().any(|range| range.contains(&block))
+    }
+
+    fn insert(&mut self, block: u64) {
+        if self.contains(block) {
+            return;
+        }
+
+        // extend an adjacent range, if any
+        for range in self.ranges_mut() {
+            if range.end == block {
+                range.end += 1;
+                return;
+            } else if range.start == block + 1 {
+                range.start -= 1;
+                return;
+            }
+        }
+
+        if self.len == MAX_FILE_RANGES {
+            self.overflow = true;
+        } else {
+            self.ranges[self.len] = block..block + 1;
+            self.len += 1;
+        }
+    }
+}
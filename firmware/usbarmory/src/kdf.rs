@@ -0,0 +1,147 @@
+//! Key derivation
+//!
+//! - `Kdf` derives keys from a DCP AES-128 key, e.g. the device-bound UNIQUE key
+//! - `Hkdf` derives keys from input key material that lives in RAM, e.g. a shared secret
+
+use core::ptr;
+
+use crate::dcp::{Aes128, Error, HmacSha256, Sha256};
+
+pub use cmac_kdf::MAX_KEY_LEN;
+
+/// Size of the HMAC-SHA256 output, in bytes
+const OUTPUT_SIZE: usize = 32;
+
+/// Key derivation function keyed by a DCP AES-128 key
+///
+/// This is the KDF in counter mode described in NIST SP 800-108, with AES-128 CMAC (NIST SP
+/// 800-38B) as the PRF. When the UNIQUE key is used the key never leaves the DCP so the derived
+/// keys can only be produced on this device
+///
+/// Different `label`s (and `context`s) produce unrelated keys; use a different label for each
+/// purpose, e.g. `b"storage"`, `b"attestation"`, `b"session-wrap"`
+pub struct Kdf {
+    cipher: Cipher,
+}
+
+impl Kdf {
+    /// Gets a handle to the AES-128 channel and configures it to derive keys from the UNIQUE key
+    ///
+    /// This function returns `None` if the channel is currently in use
+    pub fn new_unique() -> Option<Self> {
+        Aes128::new_unique().map(Self::from_cipher)
+    }
+
+    /// Derives keys from the key used by `cipher`
+    pub fn from_cipher(cipher: Aes128) -> Self {
+        Kdf {
+            cipher: Cipher(cipher),
+        }
+    }
+
+    /// Fills `key` with key material derived from the given `label` and `context`
+    ///
+    /// The length of `key` is part of the PRF input so e.g. a 16-byte key is *not* a prefix of a
+    /// 32-byte key derived from the same label and context
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `key` is longer than `MAX_KEY_LEN` bytes
+    pub fn derive(&self, label: &[u8], context: &[u8], key: &mut [u8]) -> Result<(), Error> {
+        cmac_kdf::derive(&self.cipher, label, context, key)
+    }
+}
+
+/// Adapter between the DCP AES-128 cipher and `cmac_kdf`
+struct Cipher(Aes128);
+
+impl cmac_kdf::BlockCipher for Cipher {
+    type Error = Error;
+
+    fn encrypt_block(&self, block: &mut [u8; 16]) -> Result<(), Error> {
+        self.0.encrypt_ecb(block)
+    }
+}
+
+/// HKDF with HMAC-SHA256 (RFC 5869)
+///
+/// The HMAC is computed using the DCP SHA-256 channel; see `HmacSha256`
+pub struct Hkdf {
+    // keyed with the pseudorandom key (PRK)
+    hmac: HmacSha256,
+}
+
+impl Hkdf {
+    /// Maximum length of the output key material, in bytes
+    pub const MAX_OUTPUT_LEN: usize = 255 * OUTPUT_SIZE;
+
+    /// HKDF-Extract: derives a pseudorandom key from the input key material (`ikm`)
+    ///
+    /// When `salt` is `None` a string of 32 zeros is used as the salt
+    pub fn extract(hasher: Sha256, salt: Option<&[u8]>, ikm: &[u8]) -> Result<Self, Error> {
+        let mut hmac = HmacSha256::from_hasher(hasher, salt.unwrap_or(&[0; OUTPUT_SIZE]))?;
+        hmac.input(ikm)?;
+        let mut prk = hmac.finalize_reset()?;
+
+        let res = hmac.set_key(&prk);
+        unsafe { ptr::write_volatile(&mut prk, [0; OUTPUT_SIZE]) }
+        res?;
+
+        Ok(Hkdf { hmac })
+    }
+
+    /// Skips the extract step and uses `prk` as the pseudorandom key
+    ///
+    /// `prk` must be at least 32 bytes long and be uniformly random or pseudorandom
+    pub fn from_prk(hasher: Sha256, prk: &[u8]) -> Result<Self, Error> {
+        Ok(Hkdf {
+            hmac: HmacSha256::from_hasher(hasher, prk)?,
+        })
+    }
+
+    /// HKDF-Expand: fills `okm` with output key material bound to `info`
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `okm` is longer than `MAX_OUTPUT_LEN` bytes
+    pub fn expand(&mut self, info: &[u8], okm: &mut [u8]) -> Result<(), Error> {
+        assert!(
+            okm.len() <= Self::MAX_OUTPUT_LEN,
+            "requested output is too long"
+        );
+
+        let mut t = [0; OUTPUT_SIZE];
+        let mut res = Ok(());
+        for (i, chunk) in okm.chunks_mut(OUTPUT_SIZE).enumerate() {
+            let prev = if i == 0 { &[][..] } else { &t[..] };
+
+            match self.block(prev, info, i as u8 + 1) {
+                Ok(block) => {
+                    t = block;
+                    chunk.copy_from_slice(&t[..chunk.len()]);
+                }
+                Err(e) => {
+                    res = Err(e);
+                    break;
+                }
+            }
+        }
+
+        unsafe { ptr::write_volatile(&mut t, [0; OUTPUT_SIZE]) }
+
+        if res.is_err() {
+            // leave the HMAC ready for the next call
+            let _ = self.hmac.reset();
+        }
+
+        res
+    }
+
+    // T(i) = HMAC-Hash(PRK, T(i - 1) | info | i) -- RFC 5869, section 2.3
+    fn block(&mut self, prev: &[u8], info: &[u8], i: u8) -> Result<[u8; OUTPUT_SIZE], Error> {
+        self.hmac.input(prev)?;
+        self.hmac.input(info)?;
+        self.hmac.input(&[i])?;
+        self.hmac.finalize_reset()
+    }
+}
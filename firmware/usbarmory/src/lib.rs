@@ -29,12 +29,15 @@ pub mod dcp;
 pub mod emmc;
 #[cfg(feature = "fs")]
 pub mod fs;
+pub mod kdf;
 pub mod led;
 pub mod rng;
+pub mod sdcard;
 pub mod serial;
 pub mod storage;
 pub mod time;
 pub mod usbd;
+mod usdhc;
 mod util;
 
 /// Default CPU frequency
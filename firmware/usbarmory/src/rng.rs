@@ -2,10 +2,19 @@
 
 // See chapter 44 of the 6ULLRM
 
-use core::cmp;
+use core::{cmp, num::NonZeroU32, ptr};
 
 use pac::RNG;
-use rand_core::{block::BlockRngCore, CryptoRng};
+use rand_core::{CryptoRng, RngCore};
+use rng_health::{Failure, HealthTests};
+
+use crate::dcp;
+
+pub use drbg::CtrDrbg;
+pub use pool::{Harvester, Pool, POOL_SIZE};
+
+mod drbg;
+mod pool;
 
 /// A Random Number Generator backed by the hardware
 ///
@@ -15,25 +24,63 @@ use rand_core::{block::BlockRngCore, CryptoRng};
 /// requests a new seed from the TRNG. The initial seed takes approximately 2
 /// million clock cycles to produce. Only the output of the PRNG is exposed by
 /// the hardware
+///
+/// The output of the PRNG goes through the continuous health tests of NIST
+/// SP 800-90B (Repetition Count Test and Adaptive Proportion Test) before it's
+/// handed out. The tests also run on the first 1024 bytes of output, which are
+/// discarded, after initialization and after every reset. A health test
+/// failure, a failed self-test or an error reported by the hardware is latched:
+/// no more output is produced until `reset` is called
+///
+/// # Panics
+///
+/// The infallible methods of the `RngCore` implementation (`next_u32`,
+/// `next_u64` and `fill_bytes`) panic while a failure is latched. Use
+/// `try_next_u32`, `fill` or `RngCore::try_fill_bytes`, which return an error
+/// instead, wherever a failure must not reset the device
 pub struct Rng {
     inner: RNG,
+    health: HealthTests,
+    // hardware error or failed self-test
+    latched: Option<HardwareError>,
 }
 
+/// Min-entropy per byte claimed for the purpose of the health tests
+///
+/// Only the output of the PRNG is visible so the tests can only catch gross
+/// failures, like a stuck output. A low value is claimed so that a working
+/// generator practically never trips them
+const MIN_ENTROPY: u8 = 2;
+
 /* Command register */
+/// Self test
+const CMD_ST: u32 = 1;
+/// Clear interrupt
+const CMD_CI: u32 = 1 << 4;
+/// Clear error
+const CMD_CE: u32 = 1 << 5;
 /// Software reset
 const CMD_SR: u32 = 1 << 6;
 
 /* Control Register */
 /// Auto-reseed
 const CR_AR: u32 = 1 << 4;
+/// Mask done interrupt
+const CR_MASKDONE: u32 = 1 << 5;
+/// Mask error interrupt
+const CR_MASKERR: u32 = 1 << 6;
 
 /* Status Register */
 // Statistics test pass failed.
 const SR_STATPF_OFFSET: u8 = 24;
 const SR_STATPF_MASK: u32 = 0xff;
 
+/// Self test done
+const SR_STDN: u32 = 1 << 4;
 /// Seed done
 const SR_SDN: u32 = 1 << 5;
+/// Error
+const SR_ERR: u32 = 1 << 16;
 
 // FIFO level
 const SR_FIFO_LVL_MASK: u32 = 0b1111;
@@ -42,6 +89,18 @@ const SR_FIFO_LVL_OFFSET: u8 = 8;
 /// The maximum number of
 pub const FIFO_SIZE: usize = 5;
 
+/* Error Status Register */
+/// Linear feedback shift register error
+const ESR_LFE: u32 = 1;
+/// Oscillator error
+const ESR_OSCE: u32 = 1 << 1;
+/// Self test error
+const ESR_STE: u32 = 1 << 2;
+/// Statistical test error
+const ESR_SATE: u32 = 1 << 3;
+/// FIFO underflow error
+const ESR_FUFE: u32 = 1 << 4;
+
 impl Rng {
     /// Gets an exclusive handle to the `Rng` singleton
     ///
@@ -53,12 +112,64 @@ impl Rng {
     /// complete.
     pub fn initialize() -> Option<Self> {
         RNG::take().map(|rng| {
-            let rng = Self { inner: rng };
+            let rng = Self {
+                inner: rng,
+                health: HealthTests::new(MIN_ENTROPY),
+                latched: None,
+            };
             rng.auto_seed();
             rng
         })
     }
 
+    /// [blocking] Runs the hardware self-test
+    ///
+    /// The self-test discards the current seed so the PRNG is reseeded
+    /// afterwards; the startup health tests are run again before more output is
+    /// produced. A failure is latched; see `reset`
+    pub fn self_test(&mut self) -> Result<(), HardwareError> {
+        self.inner.CMD.write(CMD_ST);
+
+        while self.inner.SR.read() & (SR_STDN | SR_ERR) == 0 {
+            // busy wait
+            continue;
+        }
+
+        let res = if self.inner.SR.read() & SR_ERR != 0 {
+            Err(HardwareError::from_esr(self.inner.ESR.read()))
+        } else {
+            Ok(())
+        };
+
+        self.inner.CMD.write(CMD_CI | CMD_CE);
+        self.restart();
+
+        if let Err(e) = res {
+            self.latched = Some(e);
+        }
+
+        res
+    }
+
+    /// Returns the failure that's currently latched, if any
+    pub fn failure(&self) -> Option<Error> {
+        if let Some(e) = self.latched {
+            Some(Error::Hardware(e))
+        } else {
+            self.health.failure().map(Error::from)
+        }
+    }
+
+    /// Clears a latched failure and restarts the generation of random data
+    ///
+    /// The hardware is software reset and the startup health tests are run
+    /// again before more output is produced. Like `initialize`, this method
+    /// does not wait for the new seed to be generated
+    pub fn reset(&mut self) {
+        self.restart();
+        self.latched = None;
+    }
+
     /// [blocking] Returns statistics about the seed being currently used by the
     /// PRNG
     ///
@@ -92,10 +203,15 @@ impl Rng {
     /// [blocking] Extracts one 32-bit word of random data from the PRNG
     ///
     /// This method may block if the internal FIFO buffer of random data is
-    /// empty or if the first seed has not yet been generated
-    pub fn next_u32(&self) -> u32 {
-        self.wait_for_fifo_level(1);
-        self.inner.OUT.read()
+    /// empty, if the first seed has not yet been generated or if the startup
+    /// health tests have not yet completed
+    ///
+    /// This method returns an error, and produces no output, while a failure is
+    /// latched
+    pub fn try_next_u32(&mut self) -> Result<u32, Error> {
+        self.startup()?;
+        self.wait_for_fifo_level(1)?;
+        self.read()
     }
 
     /// [blocking] Writes at least one 32-bit word of random data into the
@@ -105,14 +221,52 @@ impl Rng {
     /// returned slice will never exceed the `FIFO_SIZE` constant
     ///
     /// This method may block if the internal FIFO buffer of random data is
-    /// empty or if the first seed has not yet been generated
-    pub fn write<'a>(&self, buf: &'a mut [u32]) -> &'a [u32] {
-        let n = cmp::min(usize::from(self.wait_for_fifo_level(1)), buf.len());
+    /// empty, if the first seed has not yet been generated or if the startup
+    /// health tests have not yet completed
+    ///
+    /// This method returns an error, and produces no output, while a failure is
+    /// latched
+    pub fn write<'a>(&mut self, buf: &'a mut [u32]) -> Result<&'a [u32], Error> {
+        self.startup()?;
+        let n = cmp::min(usize::from(self.wait_for_fifo_level(1)?), buf.len());
 
         for slot in &mut buf[..n] {
-            *slot = self.inner.OUT.read();
+            *slot = self.read()?;
         }
-        &buf[..n]
+        Ok(&buf[..n])
+    }
+
+    /// [blocking] Fills `dest` with random data
+    ///
+    /// This method may block if the internal FIFO buffer of random data is
+    /// empty, if the first seed has not yet been generated or if the startup
+    /// health tests have not yet completed
+    ///
+    /// This method returns an error while a failure is latched; the contents
+    /// of `dest` are unspecified in that case
+    pub fn fill(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        let mut buf = [0; FIFO_SIZE];
+        let mut chunks = dest.chunks_mut(4).peekable();
+
+        let res = loop {
+            if chunks.peek().is_none() {
+                break Ok(());
+            }
+
+            match self.write(&mut buf) {
+                Ok(words) => {
+                    for (word, chunk) in words.iter().zip(&mut chunks) {
+                        chunk.copy_from_slice(&word.to_le_bytes()[..chunk.len()]);
+                    }
+                }
+                Err(e) => break Err(e),
+            }
+        };
+
+        // don't leave random data behind
+        unsafe { ptr::write_volatile(&mut buf, [0; FIFO_SIZE]) }
+
+        res
     }
 
     /// Enables auto-seeding the PRNG
@@ -131,30 +285,69 @@ impl Rng {
         self.inner.CMD.write(CMD_SR);
     }
 
+    /// Software resets the hardware RNG, starts a new seed and restarts the
+    /// health tests
+    fn restart(&mut self) {
+        self.software_reset();
+        self.auto_seed();
+        self.health = HealthTests::new(MIN_ENTROPY);
+    }
+
+    /// Returns the latched failure as an error
+    fn check(&self) -> Result<(), Error> {
+        if let Some(e) = self.failure() {
+            Err(e)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs the startup health tests, if they have not completed yet
+    fn startup(&mut self) -> Result<(), Error> {
+        self.check()?;
+
+        while !self.health.is_ready() {
+            // the output used in the startup tests is discarded
+            self.wait_for_fifo_level(1)?;
+            self.read()?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads one word from the FIFO buffer and runs the health tests on it
+    ///
+    /// The FIFO buffer must not be empty
+    fn read(&mut self) -> Result<u32, Error> {
+        let word = self.inner.OUT.read();
+        self.health.feed_word(word)?;
+        Ok(word)
+    }
+
     /// Waits until the FIFO buffer has at least this many `words`
     ///
     /// Returns the number of 32-bit words that the FIFO buffer currently holds
     ///
     /// This method panics if `words` is greater than `FIFO_SIZE`
-    fn wait_for_fifo_level(&self, words: u8) -> u8 {
+    fn wait_for_fifo_level(&mut self, words: u8) -> Result<u8, Error> {
         assert!(words <= FIFO_SIZE as u8);
 
-        let esr = self.inner.ESR.read();
-
-        if esr != 0 {
-            // error detected: software reset the RNG peripheral
-            self.software_reset();
-            self.auto_seed();
-            self.wait_for_initial_seed();
-        }
-
         // busy wait until there's at least `words` of random data in the FIFO
         // buffer
         loop {
+            let esr = self.inner.ESR.read();
+
+            if esr != 0 {
+                // error detected: stop producing output until `reset` is called
+                let e = HardwareError::from_esr(esr);
+                self.latched = Some(e);
+                break Err(Error::Hardware(e));
+            }
+
             let fifo_level = self.fifo_level();
 
             if fifo_level >= words {
-                break fifo_level;
+                break Ok(fifo_level);
             }
         }
     }
@@ -162,16 +355,115 @@ impl Rng {
 
 impl CryptoRng for Rng {}
 
-impl BlockRngCore for Rng {
-    type Item = u32;
-    type Results = [u32; FIFO_SIZE];
+/// **WARNING** `next_u32`, `next_u64` and `fill_bytes` panic while a failure is
+/// latched; see the "Panics" section of `Rng`
+impl RngCore for Rng {
+    fn next_u32(&mut self) -> u32 {
+        self.try_next_u32().expect("RNG error")
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_u32(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_fill_bytes(dest).expect("RNG error")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        Ok(self.fill(dest)?)
+    }
+}
+
+/// RNG error
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// The Repetition Count Test failed
+    RepetitionCount,
+
+    /// The Adaptive Proportion Test failed
+    AdaptiveProportion,
+
+    /// The hardware reported an error or the self-test failed
+    Hardware(HardwareError),
+
+    /// The DCP reported an error
+    Dcp(dcp::Error),
+
+    /// The entropy pool doesn't hold enough random data; try again later
+    WouldBlock,
+}
+
+impl From<Failure> for Error {
+    fn from(failure: Failure) -> Self {
+        match failure {
+            Failure::RepetitionCount => Error::RepetitionCount,
+            Failure::AdaptiveProportion => Error::AdaptiveProportion,
+        }
+    }
+}
+
+impl From<Error> for rand_core::Error {
+    fn from(e: Error) -> Self {
+        let code = match e {
+            Error::RepetitionCount => 0,
+            Error::AdaptiveProportion => 1,
+            Error::Hardware(..) => 2,
+            Error::Dcp(..) => 3,
+            Error::WouldBlock => 4,
+        };
+
+        // NOTE(unwrap) `CUSTOM_START` is not zero
+        rand_core::Error::from(NonZeroU32::new(rand_core::Error::CUSTOM_START + code).unwrap())
+    }
+}
+
+/// The contents of the error status register
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HardwareError {
+    /// Linear feedback shift register error
+    pub lfsr_error: bool,
 
-    fn generate(&mut self, results: &mut [u32; FIFO_SIZE]) {
-        self.wait_for_fifo_level(FIFO_SIZE as u8);
+    /// Oscillator error
+    pub oscillator_error: bool,
 
-        for slot in results {
-            *slot = self.inner.OUT.read();
+    /// Self test error
+    pub self_test_error: bool,
+
+    /// Statistical test error
+    pub statistical_test_error: bool,
+
+    /// FIFO underflow error
+    pub fifo_underflow_error: bool,
+}
+
+impl HardwareError {
+    fn from_esr(esr: u32) -> Self {
+        Self {
+            lfsr_error: esr & ESR_LFE != 0,
+            oscillator_error: esr & ESR_OSCE != 0,
+            self_test_error: esr & ESR_STE != 0,
+            statistical_test_error: esr & ESR_SATE != 0,
+            fifo_underflow_error: esr & ESR_FUFE != 0,
+        }
+    }
+
+    fn esr(self) -> u32 {
+        let mut esr = 0;
+        for (bit, set) in [
+            (ESR_LFE, self.lfsr_error),
+            (ESR_OSCE, self.oscillator_error),
+            (ESR_STE, self.self_test_error),
+            (ESR_SATE, self.statistical_test_error),
+            (ESR_FUFE, self.fifo_underflow_error),
+        ]
+        .iter()
+        {
+            if *set {
+                esr |= bit;
+            }
         }
+        esr
     }
 }
 
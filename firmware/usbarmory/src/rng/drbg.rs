@@ -0,0 +1,156 @@
+use core::ptr;
+
+use ctr_drbg::{BlockCipher, MAX_REQUEST_SIZE};
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    dcp::{self, Aes128},
+    rng::{Error, Rng},
+};
+
+/// Number of requests served between two reseeds
+///
+/// SP 800-90A allows up to `2^48` requests; the DRBG is reseeded much more often than that
+const RESEED_INTERVAL: u64 = 1 << 12;
+
+/// Size of the entropy input, in bytes
+const ENTROPY_SIZE: usize = 32;
+
+/// Size of the nonce, in bytes
+const NONCE_SIZE: usize = 16;
+
+/// AES-128 CTR_DRBG (NIST SP 800-90A) seeded from the hardware RNG
+///
+/// The block cipher operations are performed by the DCP. The entropy input and the nonce are
+/// taken from `Rng::write`; the DRBG is reseeded with fresh entropy input every 4096 requests, or
+/// when `reseed` is called
+///
+/// Requests larger than 64 KiB are split in chunks of 64 KiB; each chunk counts as one request
+pub struct CtrDrbg {
+    inner: ctr_drbg::CtrDrbg<Cipher>,
+    rng: Rng,
+}
+
+impl CtrDrbg {
+    /// Instantiates a DRBG that uses `cipher` for the block cipher operations and `rng` as its
+    /// entropy source
+    ///
+    /// The `personalization_string` is optional (it can be empty) but it's recommended to use a
+    /// value that's unique to this instance, e.g. a device serial number
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `cipher` uses a hardware key (see `Aes128::new_ram`)
+    pub fn from_cipher(
+        cipher: Aes128,
+        mut rng: Rng,
+        personalization_string: &[u8],
+    ) -> Result<Self, Error> {
+        let mut entropy_input = [0; ENTROPY_SIZE];
+        let mut nonce = [0; NONCE_SIZE];
+
+        let res = rng
+            .fill(&mut entropy_input)
+            .and_then(|_| rng.fill(&mut nonce))
+            .and_then(|_| {
+                ctr_drbg::CtrDrbg::instantiate(
+                    Cipher(cipher),
+                    &entropy_input,
+                    &nonce,
+                    personalization_string,
+                )
+                .map_err(Error::Dcp)
+            });
+
+        // don't leave the seed behind
+        unsafe {
+            ptr::write_volatile(&mut entropy_input, [0; ENTROPY_SIZE]);
+            ptr::write_volatile(&mut nonce, [0; NONCE_SIZE]);
+        }
+
+        Ok(CtrDrbg { inner: res?, rng })
+    }
+
+    /// Reseeds the DRBG with fresh entropy input from the hardware RNG
+    ///
+    /// The `additional_input` is optional (it can be empty)
+    pub fn reseed(&mut self, additional_input: &[u8]) -> Result<(), Error> {
+        let mut entropy_input = [0; ENTROPY_SIZE];
+
+        let res = self.rng.fill(&mut entropy_input).and_then(|_| {
+            self.inner
+                .reseed(&entropy_input, additional_input)
+                .map_err(Error::Dcp)
+        });
+
+        // don't leave the seed behind
+        unsafe { ptr::write_volatile(&mut entropy_input, [0; ENTROPY_SIZE]) }
+
+        res
+    }
+
+    /// Fills `output` with random data
+    ///
+    /// The `additional_input` is optional (it can be empty)
+    ///
+    /// After an error the DRBG must not be used; drop it and instantiate a new one
+    pub fn try_generate(
+        &mut self,
+        output: &mut [u8],
+        additional_input: &[u8],
+    ) -> Result<(), Error> {
+        for chunk in output.chunks_mut(MAX_REQUEST_SIZE) {
+            if self.inner.reseed_counter() > RESEED_INTERVAL {
+                self.reseed(&[])?;
+            }
+
+            self.inner
+                .generate(chunk, additional_input)
+                .map_err(|e| match e {
+                    ctr_drbg::Error::Cipher(e) => Error::Dcp(e),
+                    // we reseed long before this can happen
+                    ctr_drbg::Error::ReseedRequired => unreachable!(),
+                })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl CryptoRng for CtrDrbg {}
+
+/// **WARNING** `next_u32`, `next_u64` and `fill_bytes` panic if the DRBG reports an error, e.g.
+/// because the hardware RNG failed while reseeding; use `try_generate` or `try_fill_bytes` to
+/// handle errors
+impl RngCore for CtrDrbg {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.try_generate(dest, &[]).expect("DRBG error")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        Ok(self.try_generate(dest, &[])?)
+    }
+}
+
+struct Cipher(Aes128);
+
+impl BlockCipher for Cipher {
+    type Error = dcp::Error;
+
+    fn set_key(&mut self, key: &[u8; 16]) -> Result<(), dcp::Error> {
+        self.0.set_ram_key(key);
+        Ok(())
+    }
+
+    fn encrypt_block(&mut self, block: &mut [u8; 16]) -> Result<(), dcp::Error> {
+        self.0.encrypt_ecb(block)
+    }
+}
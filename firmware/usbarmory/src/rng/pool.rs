@@ -0,0 +1,244 @@
+use core::{
+    cell::UnsafeCell,
+    cmp,
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+};
+
+use pac::RNG;
+
+use crate::rng::{Error, HardwareError, Rng, CMD_CE, CMD_CI, CR_AR, CR_MASKDONE, CR_MASKERR};
+
+/// Size of the entropy pool, in 32-bit words
+pub const POOL_SIZE: usize = 256;
+
+/// Generate seed
+const CMD_GS: u32 = 1 << 1;
+
+// NOTE(POOL_SIZE) the indices below wrap around at `usize::MAX`; a power of two keeps the
+// position of the words in the buffer consistent across the wrap around
+struct Words {
+    inner: UnsafeCell<[u32; POOL_SIZE]>,
+}
+
+// NOTE(Sync) `Harvester` only writes to the words that are not in the `HEAD..TAIL` range; `Pool`
+// only reads the words in that range
+unsafe impl Sync for Words {}
+
+static WORDS: Words = Words {
+    inner: UnsafeCell::new([0; POOL_SIZE]),
+};
+
+// index of the next word to take; only `Pool` modifies this
+static HEAD: AtomicUsize = AtomicUsize::new(0);
+
+// index of the next word to add; only `Harvester` modifies this
+static TAIL: AtomicUsize = AtomicUsize::new(0);
+
+// latched failure; see `encode`
+static FAILURE: AtomicU32 = AtomicU32::new(0);
+
+// set while a refill has been requested
+static REFILL: AtomicBool = AtomicBool::new(false);
+
+/// Moves random data from the hardware RNG into the entropy pool
+///
+/// The RNG has no "data available" interrupt; it interrupts the processor when a new seed has been
+/// generated. On each of those interrupts the FIFO buffer is drained into the pool until the pool
+/// is full. `Pool` requests a new seed, and with it a new interrupt, when the pool runs low
+///
+/// The output goes through the health tests of `Rng`; when a failure is detected the RNG
+/// interrupt is masked and `Pool` reports the failure until `reset` is called
+pub struct Harvester {
+    rng: Rng,
+}
+
+impl Harvester {
+    /// Fills the entropy pool
+    ///
+    /// This must be called from the handler of the `RNGB` interrupt. It returns the failure that
+    /// stopped the harvest, if any
+    pub fn on_interrupt(&mut self) -> Result<(), Error> {
+        // acknowledge the interrupt
+        self.rng.inner.CMD.write(CMD_CI);
+
+        let res = self.harvest();
+
+        if let Err(e) = res {
+            FAILURE.store(encode(e), Ordering::Release);
+
+            // stop the interrupts until `reset` is called
+            self.rng.inner.CMD.write(CMD_CI | CMD_CE);
+            self.rng.inner.CR.write(CR_AR | CR_MASKDONE | CR_MASKERR);
+        }
+
+        res
+    }
+
+    /// Clears a latched failure and restarts the generation of random data
+    ///
+    /// See `Rng::reset`
+    pub fn reset(&mut self) {
+        self.rng.reset();
+        FAILURE.store(0, Ordering::Release);
+        request_refill();
+    }
+
+    fn harvest(&mut self) -> Result<(), Error> {
+        // requests made from now on need a new interrupt
+        REFILL.store(false, Ordering::Release);
+
+        self.rng.startup()?;
+
+        let words = WORDS.inner.get() as *mut u32;
+        loop {
+            let head = HEAD.load(Ordering::Acquire);
+            let tail = TAIL.load(Ordering::Relaxed);
+            let free = POOL_SIZE - tail.wrapping_sub(head);
+
+            if free == 0 {
+                break Ok(());
+            }
+
+            let n = cmp::min(usize::from(self.rng.wait_for_fifo_level(1)?), free);
+            for i in 0..n {
+                let word = self.rng.read()?;
+                unsafe {
+                    words
+                        .add(tail.wrapping_add(i) % POOL_SIZE)
+                        .write_volatile(word)
+                }
+            }
+
+            TAIL.store(tail.wrapping_add(n), Ordering::Release);
+        }
+    }
+}
+
+/// Handle to the entropy pool filled by `Harvester`
+///
+/// This handle can be copied and used at any priority. Its methods never block; instead they
+/// return `Error::WouldBlock` when the pool doesn't hold enough random data
+#[derive(Clone, Copy)]
+pub struct Pool {
+    _private: PhantomData<()>,
+}
+
+impl Pool {
+    /// Fills `dest` with random data taken from the pool
+    ///
+    /// Whole words are taken from the pool; the unused bytes of the last word are discarded. If
+    /// the pool doesn't hold enough words `Error::WouldBlock` is returned and nothing is taken.
+    /// While a failure is latched that failure is returned
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `dest` is larger than the pool (`POOL_SIZE * 4` bytes)
+    pub fn try_fill(&self, dest: &mut [u8]) -> Result<(), Error> {
+        let n = (dest.len() + 3) / 4;
+        assert!(n <= POOL_SIZE, "request is larger than the pool");
+
+        let words = WORDS.inner.get() as *const u32;
+        loop {
+            check()?;
+
+            let head = HEAD.load(Ordering::Acquire);
+            let available = TAIL.load(Ordering::Acquire).wrapping_sub(head);
+
+            if available < n {
+                request_refill();
+                break Err(Error::WouldBlock);
+            }
+
+            for (i, chunk) in dest.chunks_mut(4).enumerate() {
+                let word = unsafe { words.add(head.wrapping_add(i) % POOL_SIZE).read_volatile() };
+                chunk.copy_from_slice(&word.to_le_bytes()[..chunk.len()]);
+            }
+
+            // the words are only ours if no other context took them in the meantime; if that
+            // happened the `Harvester` may have overwritten them as well so start over
+            if HEAD
+                .compare_exchange(
+                    head,
+                    head.wrapping_add(n),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                if available - n < POOL_SIZE / 2 {
+                    request_refill();
+                }
+
+                // the failure may have been detected while we were copying the words
+                break check();
+            }
+        }
+    }
+
+    /// Returns the number of bytes the pool currently holds
+    pub fn len(&self) -> usize {
+        let head = HEAD.load(Ordering::Acquire);
+        TAIL.load(Ordering::Acquire).wrapping_sub(head) * 4
+    }
+
+    /// Returns `true` if the pool is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Rng {
+    /// Switches the RNG to interrupt mode
+    ///
+    /// The `Harvester` must be moved into the handler of the `RNGB` interrupt; the `Pool` can be
+    /// copied into tasks of any priority. The first interrupt fires when a new seed has been
+    /// generated, which takes a few milliseconds
+    pub fn into_interrupt_mode(self) -> (Harvester, Pool) {
+        // auto-reseed; done and error interrupts unmasked
+        self.inner.CR.write(CR_AR);
+        request_refill();
+
+        (
+            Harvester { rng: self },
+            Pool {
+                _private: PhantomData,
+            },
+        )
+    }
+}
+
+// Requests a new seed; the `RNGB` interrupt fires when it's ready
+fn request_refill() {
+    if !REFILL.swap(true, Ordering::AcqRel) {
+        // NOTE(borrow_unchecked) single instruction write to a command register
+        RNG::borrow_unchecked(|rng| rng.CMD.write(CMD_GS));
+    }
+}
+
+fn check() -> Result<(), Error> {
+    match decode(FAILURE.load(Ordering::Acquire)) {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+// 0: no failure; 1: RCT; 2: APT; `1 << 8 | ESR`: hardware error
+fn encode(e: Error) -> u32 {
+    match e {
+        Error::RepetitionCount => 1,
+        Error::AdaptiveProportion => 2,
+        Error::Hardware(e) => 1 << 8 | e.esr(),
+        // not reported by `Rng`
+        Error::Dcp(..) | Error::WouldBlock => unreachable!(),
+    }
+}
+
+fn decode(failure: u32) -> Option<Error> {
+    match failure {
+        0 => None,
+        1 => Some(Error::RepetitionCount),
+        2 => Some(Error::AdaptiveProportion),
+        _ => Some(Error::Hardware(HardwareError::from_esr(failure & 0xff))),
+    }
+}
@@ -0,0 +1,346 @@
+//! Low level access to the microSD card
+
+// References
+// - (SDPLS) SD Specifications Part 1 Physical Layer Simplified Specification (Version 6.00)
+
+use core::{convert::TryFrom, time::Duration};
+
+use pac::uSDHC1;
+
+use crate::{
+    memlog,
+    storage::{Block, ManagedBlockDevice, BLOCK_SIZE},
+    time::{self, Instant},
+    usdhc::{cmd::Command, fits_in, Frequency, Rca, Usdhc, Width},
+};
+
+pub use crate::usdhc::Error;
+
+/// [Singleton] Access to the microSD card
+pub struct SdCard {
+    usdhc: Usdhc<pac::usdhc::_1>,
+    blocks: u32,
+}
+
+/// Host Capacity Support / Card Capacity Status
+const OCR_CCS: u32 = 1 << 30;
+
+impl SdCard {
+    /// Gets a handle to the `SdCard` singleton
+    ///
+    /// This method returns the `Some` only once and consumes the `uSDHC1`
+    /// peripheral. An error is returned if the slot is empty or if the card
+    /// could not be initialized; the peripheral is consumed in that case too
+    /// so the initialization can't be retried
+    ///
+    /// NOTE this expects the bootloader to have configured the pins and the
+    /// clock of the uSDHC1 peripheral
+    pub fn take() -> Option<Result<Self, Error>> {
+        uSDHC1::take().map(|usdhc| {
+            let mut sd = SdCard {
+                usdhc: Usdhc::new(usdhc),
+                blocks: 0,
+            };
+
+            sd.initialize()?;
+
+            Ok(sd)
+        })
+    }
+
+    /// Changes the verbosity of the driver (default: false)
+    pub fn verbose(&mut self, verbose: bool) {
+        self.usdhc.verbose = verbose;
+    }
+
+    /// Returns the capacity of the card, in blocks
+    pub fn blocks(&self) -> u32 {
+        self.blocks
+    }
+
+    /// Reads consecutive blocks of memory, starting at block `lba`
+    ///
+    /// Up to 65535 blocks are read using a single command and DMA transfer
+    pub fn read_blocks(&self, lba: u32, blocks: &mut [Block]) -> Result<(), Error> {
+        self.assert_blocks_exist(lba, blocks.len());
+
+        if self.usdhc.verbose {
+            memlog!(
+                "read_blocks(lba={}, n={}) @ {:?}",
+                lba,
+                blocks.len(),
+                time::uptime()
+            );
+        }
+
+        self.usdhc.read_blocks(lba, blocks)
+    }
+
+    /// Transfers consecutive blocks of memory to the card, starting at block
+    /// `lba`, for them to be programmed to flash
+    ///
+    /// Up to 65535 blocks are written using a single command and DMA transfer
+    pub fn write_blocks(&self, lba: u32, blocks: &[Block]) -> Result<(), Error> {
+        self.assert_blocks_exist(lba, blocks.len());
+
+        if self.usdhc.verbose {
+            memlog!(
+                "write_blocks(lba={}, n={}) @ {:?}",
+                lba,
+                blocks.len(),
+                time::uptime()
+            );
+        }
+
+        self.usdhc.write_blocks(lba, blocks)
+    }
+
+    fn assert_blocks_exist(&self, lba: u32, count: usize) {
+        assert!(
+            u64::from(lba) + count as u64 <= u64::from(self.blocks),
+            "block doesn't exist"
+        );
+    }
+
+    // see figure 4-2 "Card Initialization and Identification Flow (SD mode)" of SDPLS
+    fn initialize(&mut self) -> Result<(), Error> {
+        self.usdhc.software_reset();
+
+        // support for `SET_BLOCK_COUNT` is optional; use auto CMD12 instead
+        self.usdhc.set_block_count = false;
+
+        self.usdhc.send_command(Command::GoIdleState)?;
+        memlog!("sent card to idle state @ {:?}", time::uptime());
+
+        let v2 = self.send_if_cond()?;
+        let ocr = self.send_op_cond(v2)?;
+        self.usdhc.high_capacity = ocr & OCR_CCS != 0;
+        memlog!("card ready @ {:?} (OCR={:#010x})", time::uptime(), ocr);
+
+        self.usdhc.send_command(Command::AllSendCid)?;
+        let _cid = self.usdhc.long_response();
+
+        // unlike the eMMC, the card picks its own relative address
+        let rsp = self.usdhc.send_command(Command::SendRelativeAddr)?;
+        let rca = Rca::new((rsp >> 16) as u16).ok_or(Error::Other)?;
+        memlog!("registered a card with RCA={:#06x}", rca);
+
+        self.usdhc.send_command(Command::SendCsd { rca })?;
+        let csd = Csd::from(self.usdhc.long_response());
+        self.blocks = csd.blocks().ok_or(Error::Other)?;
+
+        self.usdhc
+            .send_command(Command::SelectCard { rca: Some(rca) })?;
+        self.usdhc.selected = Some(rca);
+        self.usdhc.change_frequency(Frequency::M25);
+
+        // all SD cards support the 4-bit bus
+        self.usdhc
+            .send_command(Command::AppCmd { rca: Some(rca) })?;
+        self.usdhc
+            .send_command(Command::SetBusWidth { width: 0b10 })?;
+        self.usdhc.set_bus_width(Width::B4);
+
+        // NOTE high capacity cards ignore this; their block length is fixed
+        self.usdhc.send_command(Command::SetBlockLen {
+            len: BLOCK_SIZE.into(),
+        })?;
+
+        if csd.supports_switch() {
+            self.high_speed()?;
+        }
+
+        memlog!("card has {} blocks", self.blocks);
+
+        Ok(())
+    }
+
+    /// Returns `true` if the card implements version 2.00 (or newer) of the
+    /// physical layer specification
+    fn send_if_cond(&self) -> Result<bool, Error> {
+        /// Supply voltage = 2.7-3.6V; check pattern = 0xAA
+        const ARG: u32 = 0x1AA;
+
+        match self.usdhc.send_command(Command::SendIfCond { arg: ARG }) {
+            // the card echoes the argument if it can work with the supplied voltage
+            Ok(rsp) if rsp & 0xfff == ARG => Ok(true),
+            Ok(_) => Err(Error::Other),
+
+            // cards that implement version 1.x of the specification don't
+            // respond to this command
+            Err(Error::Timeout) => {
+                self.usdhc.clear_command_inhibit();
+                Ok(false)
+            }
+
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Waits until the card finishes powering up; returns its OCR register
+    fn send_op_cond(&self, v2: bool) -> Result<u32, Error> {
+        /// 2.7-3.6V
+        const VOLTAGE_WINDOW: u32 = 0x00FF_8000;
+        /// 0 = Card is busy; 1 = Card has finished powering up
+        const OCR_RDY: u32 = 1 << 31;
+
+        // only version 2.00 cards understand the HCS bit
+        let ocr = VOLTAGE_WINDOW | if v2 { OCR_CCS } else { 0 };
+
+        // the card can take up to 1 second to power up
+        let start = Instant::now();
+        loop {
+            self.usdhc.send_command(Command::AppCmd { rca: None })?;
+            let rsp = self.usdhc.send_command(Command::SdSendOpCond { ocr })?;
+
+            if rsp & OCR_RDY != 0 {
+                break Ok(rsp);
+            }
+
+            if start.elapsed() > Duration::from_secs(1) {
+                memlog!("timeout while waiting for card to be ready");
+                break Err(Error::Timeout);
+            }
+
+            // let's not spam the card
+            time::wait(Duration::from_millis(1));
+        }
+    }
+
+    /// Switches the card to high speed mode (50 MHz), if the card supports it
+    fn high_speed(&mut self) -> Result<(), Error> {
+        /// Mode = set; function group 1 (access mode) = high speed; other
+        /// groups = unchanged
+        const ARG: u32 = 0x80FF_FFF1;
+        /// Size of the switch function status, in bytes
+        const STATUS_SIZE: u16 = 64;
+
+        let mut status = Block::zeroed();
+        self.usdhc
+            .read_register(Command::SwitchFunc { arg: ARG }, &mut status, STATUS_SIZE)?;
+
+        // bits 379:376 of the (big endian) status hold the function selected
+        // in group 1; 0xF means the switch failed
+        if status.bytes[16] & 0xf == 1 {
+            // the card starts using the new timing 8 clock cycles after the
+            // end of the status
+            self.usdhc.change_frequency(Frequency::M50);
+            memlog!("put card in high speed mode @ {:?}", time::uptime());
+        }
+
+        Ok(())
+    }
+}
+
+impl ManagedBlockDevice for SdCard {
+    type Error = Error;
+
+    fn total_blocks(&self) -> u64 {
+        u64::from(self.blocks)
+    }
+
+    fn read(&self, block: &mut Block, lba: u64) -> Result<(), Self::Error> {
+        if lba >= self.total_blocks() {
+            return Err(Error::Other);
+        }
+
+        self.usdhc
+            .read_blocks(lba as u32, core::slice::from_mut(block))
+    }
+
+    fn write(&mut self, block: &Block, lba: u64) -> Result<(), Self::Error> {
+        if lba >= self.total_blocks() {
+            return Err(Error::Other);
+        }
+
+        self.usdhc
+            .write_blocks(lba as u32, core::slice::from_ref(block))
+    }
+
+    fn read_vectored(&self, bufs: &mut [&mut [u8]], lba: u64) -> Result<(), Self::Error> {
+        if !fits_in(
+            lba,
+            bufs.iter().map(|buf| buf.len()).sum(),
+            self.total_blocks(),
+        ) {
+            return Err(Error::Other);
+        }
+
+        self.usdhc.read_vectored_any(lba as u32, bufs)
+    }
+
+    fn write_vectored(&mut self, bufs: &[&[u8]], lba: u64) -> Result<(), Self::Error> {
+        if !fits_in(
+            lba,
+            bufs.iter().map(|buf| buf.len()).sum(),
+            self.total_blocks(),
+        ) {
+            return Err(Error::Other);
+        }
+
+        self.usdhc.write_vectored_any(lba as u32, bufs)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        // no-operation; writes complete once the card leaves the programming
+        // state
+        Ok(())
+    }
+}
+
+/// Card Specific Data (SD version)
+// see section 5.3 "CSD Register" of SDPLS
+struct Csd {
+    bits: u128,
+}
+
+impl Csd {
+    /// Extracts the `width`-bit field that starts at bit `offset`
+    fn field(&self, offset: u8, width: u8) -> u32 {
+        // NOTE(as) `width` is at most 22
+        (self.bits >> offset) as u32 & ((1 << width) - 1)
+    }
+
+    /// The capacity of the card, in blocks
+    fn blocks(&self) -> Option<u32> {
+        match self.field(126, 2) {
+            // standard capacity
+            0 => {
+                let c_size = self.field(62, 12);
+                let c_size_mult = self.field(47, 3);
+                let read_bl_len = self.field(80, 4);
+
+                let bytes = u64::from(c_size + 1) << (c_size_mult + 2 + read_bl_len);
+                u32::try_from(bytes / u64::from(BLOCK_SIZE)).ok()
+            }
+
+            // high and extended capacity
+            1 => {
+                let c_size = self.field(48, 22);
+                u32::try_from(u64::from(c_size + 1) * 1024).ok()
+            }
+
+            // SDUC or reserved
+            _ => None,
+        }
+    }
+
+    /// Whether the card supports the `SWITCH_FUNC` command (command class 10)
+    fn supports_switch(&self) -> bool {
+        let ccc = self.field(84, 12);
+        ccc & (1 << 10) != 0
+    }
+}
+
+impl From<[u32; 4]> for Csd {
+    fn from(rsps: [u32; 4]) -> Self {
+        // RSP0[0] is CSD[8]; the CRC is not included in the response
+        let bits = rsps
+            .iter()
+            .rev()
+            .fold(0, |bits, rsp| bits << 32 | u128::from(*rsp))
+            << 8;
+
+        Csd { bits }
+    }
+}
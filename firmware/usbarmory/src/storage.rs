@@ -1,9 +1,17 @@
 //! Partition table and block device access.
 
-use core::{fmt, mem::size_of};
+use core::{fmt, ops::Range};
 
 use memlog::memlog;
-use zerocopy::{AsBytes, FromBytes, LayoutVerified};
+use partition_tables::mbr::{self, EbrChain};
+
+pub use self::gpt::{GptDevice, GptError, GptPartitionRef};
+pub use partition_tables::{
+    gpt::{Entry as GptEntry, Error as GptTableError, Guid},
+    mbr::{part_type, PartError, PartitionEntry, PartitionTable},
+};
+
+mod gpt;
 
 /// Trait for block devices that can read, write, and erase 512-Byte blocks.
 ///
@@ -31,10 +39,102 @@ pub trait ManagedBlockDevice {
     /// buffered data to persistent storage.
     fn write(&mut self, block: &Block, lba: u64) -> Result<(), Self::Error>;
 
+    /// Reads consecutive blocks from the device, starting at `lba`, into the buffers `bufs`.
+    ///
+    /// The buffers are filled in order and the length of each one must be a multiple of
+    /// `BLOCK_SIZE`. Devices that can scatter data into several buffers should override this; the
+    /// default implementation calls `read` once per block and copies each block into place.
+    fn read_vectored(&self, bufs: &mut [&mut [u8]], lba: u64) -> Result<(), Self::Error> {
+        let mut block = Block::zeroed();
+        let mut lba = lba;
+        for buf in bufs.iter_mut() {
+            assert_whole_blocks(buf);
+
+            for chunk in buf.chunks_mut(usize::from(BLOCK_SIZE)) {
+                self.read(&mut block, lba)?;
+                chunk.copy_from_slice(&block.bytes);
+                lba += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the contents of the buffers `bufs` to consecutive blocks of the device, starting at
+    /// `lba`.
+    ///
+    /// See `read_vectored` for the requirements on the buffers. The default implementation calls
+    /// `write` once per block.
+    fn write_vectored(&mut self, bufs: &[&[u8]], lba: u64) -> Result<(), Self::Error> {
+        let mut block = Block::zeroed();
+        let mut lba = lba;
+        for buf in bufs {
+            assert_whole_blocks(buf);
+
+            for chunk in buf.chunks(usize::from(BLOCK_SIZE)) {
+                block.bytes.copy_from_slice(chunk);
+                self.write(&block, lba)?;
+                lba += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tells the device that the data stored in the `blocks` range is no longer needed.
+    ///
+    /// The device may reclaim those blocks, which makes their contents undefined, but it's not
+    /// required to; the data may remain on the device. The default implementation does nothing.
+    fn discard(&mut self, blocks: Range<u64>) -> Result<(), Self::Error> {
+        let _ = blocks;
+        Ok(())
+    }
+
+    /// Physically erases the data stored in the `blocks` range.
+    ///
+    /// Unlike `discard`, this must also erase any copy of the data that the device may have kept
+    /// around, e.g. as a result of wear leveling. The contents of the blocks are undefined
+    /// afterwards.
+    ///
+    /// The default implementation overwrites the blocks with zeros and then flushes the device;
+    /// this doesn't reach the copies a wear-leveling controller may keep so devices that can do
+    /// better should override this.
+    fn secure_erase(&mut self, blocks: Range<u64>) -> Result<(), Self::Error> {
+        let zeros = Block::zeroed();
+        for lba in blocks {
+            self.write(&zeros, lba)?;
+        }
+
+        self.flush()
+    }
+
+    /// Physically erases the data stored in all the `ranges` of blocks.
+    ///
+    /// This has the same effect as calling `secure_erase` on each range but lets the device batch
+    /// the work, e.g. a device that can only erase all its unused blocks at once will do that once
+    /// rather than once per range. `ranges` is used as scratch space; its contents are unspecified
+    /// after this call returns.
+    ///
+    /// The default implementation calls `secure_erase` on each range.
+    fn secure_erase_batch(&mut self, ranges: &mut [Range<u64>]) -> Result<(), Self::Error> {
+        for blocks in ranges.iter() {
+            self.secure_erase(blocks.clone())?;
+        }
+
+        Ok(())
+    }
+
     /// Flushes all buffered writes to persistent storage.
     fn flush(&mut self) -> Result<(), Self::Error>;
 }
 
+fn assert_whole_blocks(buf: &[u8]) {
+    assert!(
+        buf.len() % usize::from(BLOCK_SIZE) == 0,
+        "buffer length must be a multiple of BLOCK_SIZE"
+    );
+}
+
 impl<'a, D: ManagedBlockDevice> ManagedBlockDevice for &'a mut D {
     type Error = D::Error;
 
@@ -50,6 +150,26 @@ impl<'a, D: ManagedBlockDevice> ManagedBlockDevice for &'a mut D {
         (**self).write(block, lba)
     }
 
+    fn read_vectored(&self, bufs: &mut [&mut [u8]], lba: u64) -> Result<(), Self::Error> {
+        (**self).read_vectored(bufs, lba)
+    }
+
+    fn write_vectored(&mut self, bufs: &[&[u8]], lba: u64) -> Result<(), Self::Error> {
+        (**self).write_vectored(bufs, lba)
+    }
+
+    fn discard(&mut self, blocks: Range<u64>) -> Result<(), Self::Error> {
+        (**self).discard(blocks)
+    }
+
+    fn secure_erase(&mut self, blocks: Range<u64>) -> Result<(), Self::Error> {
+        (**self).secure_erase(blocks)
+    }
+
+    fn secure_erase_batch(&mut self, ranges: &mut [Range<u64>]) -> Result<(), Self::Error> {
+        (**self).secure_erase_batch(ranges)
+    }
+
     fn flush(&mut self) -> Result<(), Self::Error> {
         (**self).flush()
     }
@@ -70,7 +190,7 @@ pub struct Block {
 
 impl Block {
     /// Creates a `Block` buffer and initializes it to all zeros.
-    pub fn zeroed() -> Self {
+    pub const fn zeroed() -> Self {
         Self {
             bytes: [0; BLOCK_SIZE as usize],
         }
@@ -83,7 +203,8 @@ impl Default for Block {
     }
 }
 
-/// Wraps an MBR-partitioned `ManagedBlockDevice` and provides access to the primary partitions.
+/// Wraps an MBR-partitioned `ManagedBlockDevice` and provides access to its primary and logical
+/// partitions.
 pub struct MbrDevice<D: ManagedBlockDevice> {
     raw: D,
     part_table: [PartitionEntry; 4],
@@ -91,233 +212,144 @@ pub struct MbrDevice<D: ManagedBlockDevice> {
 
 impl<D: ManagedBlockDevice> MbrDevice<D> {
     /// Creates a new MBR-partitioned block device by writing the given partition `table` into it
+    ///
+    /// The EBRs of the logical partitions, if any, are written as well.
     pub fn create(mut raw: D, part_table: &PartitionTable) -> Result<Self, MbrError<D::Error>> {
         let total_blocks = raw.total_blocks();
         let end = part_table
             .as_slice()
             .last()
-            .map(|entry| {
-                let extent = entry.extent();
-                extent.start + extent.sectors
-            })
+            .map(|entry| entry.blocks().end)
             .unwrap_or(0);
 
-        if u64::from(end) > total_blocks {
+        if end > total_blocks {
             memlog!("PART end = {} > total_blocks = {}", end, total_blocks);
             return Err(MbrError::InvalidPartExtent);
         }
 
-        let mbr = part_table.to_block();
-        raw.write(&mbr, 0).map_err(MbrError::Device)?;
+        // the MBR goes last; until then the old table, if any, remains in place
+        let mut block = Block::zeroed();
+        for (lba, ebr) in part_table.ebrs() {
+            block.bytes = ebr;
+            raw.write(&block, u64::from(lba))
+                .map_err(MbrError::Device)?;
+        }
+        block.bytes = part_table.to_block();
+        raw.write(&block, 0).map_err(MbrError::Device)?;
+
+        let mut entries = [PartitionEntry::UNUSED; 4];
+        let primary = part_table.as_slice();
+        entries[..primary.len()].copy_from_slice(primary);
         Ok(MbrDevice {
             raw,
-            part_table: part_table.entries,
+            part_table: entries,
         })
     }
 
     /// Opens an MBR-partitioned block device `raw` and parses the partition table.
+    ///
+    /// The EBR chain of the extended partition, if any, is validated as well.
     pub fn open(raw: D) -> Result<Self, MbrError<D::Error>> {
         let mut mbr = Block::zeroed();
         raw.read(&mut mbr, 0).map_err(MbrError::Device)?;
+        let part_table = mbr::parse_mbr(&mbr.bytes, raw.total_blocks())?;
 
-        if mbr.bytes[usize::from(BLOCK_SIZE - 2)..] != [0x55, 0xAA] {
-            return Err(MbrError::InvalidMagic);
-        }
-
-        // Copy the partition table to an aligned offset within the block (normally it's only at a
-        // 2-aligned offset).
-        mbr.bytes.copy_within(446..usize::from(BLOCK_SIZE - 2), 0);
-        let parts: &[PartitionEntry] =
-            LayoutVerified::new_slice(&mbr.bytes[..size_of::<PartitionEntry>() * 4])
-                .unwrap()
-                .into_slice();
-        let mut part_table = [PartitionEntry::zeroed(); 4];
-        part_table.copy_from_slice(parts);
-
-        for part in parts {
-            if part.part_type != 0x00 {
-                // Entry allocated, extent must be valid.
-
-                let start = part.extent().start;
-                let end = u64::from(part.extent().start) + u64::from(part.extent().sectors);
-
-                if start == 0 || u64::from(start) >= raw.total_blocks() {
-                    memlog!("PART start = {}", start);
-                    return Err(MbrError::InvalidPartExtent);
-                }
-
-                if end == 0 || end >= raw.total_blocks() {
-                    memlog!("PART end = {}", end);
-                    return Err(MbrError::InvalidPartExtent);
-                }
-
-                if end > u64::from(u32::max_value()) {
-                    memlog!("PART end = {} (> u32 limit)", end);
-                    return Err(MbrError::InvalidPartExtent);
-                }
-            }
-        }
-
-        Ok(Self { raw, part_table })
+        let device = Self { raw, part_table };
+        device.find_logical(|_| false)?;
+        Ok(device)
     }
 
-    /// Obtains access to the partition at index `part` (0 ..= 3).
+    /// Obtains access to the partition at index `part`.
+    ///
+    /// Primary partitions have indices 0 ..= 3; logical partitions follow, in EBR chain order,
+    /// starting at index 4 (Linux numbers them the same way, starting at 1). The extended
+    /// partition itself can't be accessed.
     ///
     /// Returns a `NoPartition` error if `part` does not refer to an allocated partition.
     pub fn partition(&mut self, part: u8) -> Result<MbrPartitionRef<'_, D>, MbrError<D::Error>> {
-        let extent = self.part_extent(part)?;
+        let entry = self.entry(part)?;
+        if entry.is_extended() {
+            return Err(MbrError::NoPartition);
+        }
 
         Ok(MbrPartitionRef {
             raw: &mut self.raw,
-            extent,
+            extent: entry.into(),
         })
     }
 
-    /// Returns a debug view into the partition table
-    pub fn debug<'s>(&'s self) -> impl fmt::Debug + 's {
-        &self.part_table
-    }
-
-    fn part_extent(&self, part: u8) -> Result<PartExtent, MbrError<D::Error>> {
-        if part >= 4 {
-            return Err(MbrError::NoPartition);
-        }
+    /// Returns the entry of the partition at index `part`; see `partition` for the numbering
+    ///
+    /// The start of logical partitions is relative to the start of the device, not to their EBR.
+    pub fn entry(&self, part: u8) -> Result<PartitionEntry, MbrError<D::Error>> {
+        let entry = if part < 4 {
+            Some(self.part_table[usize::from(part)])
+        } else {
+            let mut index = part - 4;
+            self.find_logical(|_| {
+                if index == 0 {
+                    true
+                } else {
+                    index -= 1;
+                    false
+                }
+            })?
+        };
 
-        let entry = &self.part_table[usize::from(part)];
-        if entry.part_type == 0x00 {
+        match entry {
+            Some(entry) if entry.is_used() => Ok(entry),
             // Entry unallocated.
-            return Err(MbrError::NoPartition);
+            _ => Err(MbrError::NoPartition),
         }
-
-        Ok(entry.extent())
     }
-}
-
-/// An entry in the MBR partition table
-#[derive(AsBytes, FromBytes, Debug, Copy, Clone)]
-#[repr(C)]
-pub struct PartitionEntry {
-    status: u8,
-    start_chs: [u8; 3],
-    part_type: u8,
-    end_chs: [u8; 3],
-    start_lba: u32,
-    num_sectors: u32,
-}
 
-impl PartitionEntry {
-    /// Creates a new partition entry that starts at `start_lba` and it's `num_sectors` big
-    pub fn new(start_lba: u32, num_sectors: u32) -> Self {
-        // this is what `fdisk` uses by default; it doesn't really matter in our case
-        const PART_TYPE_LINUX: u8 = 0x83;
-
-        Self {
-            status: 0,
-            // TODO we should enter something sensible here
-            start_chs: [0; 3],
-            // and here
-            end_chs: [0; 3],
-            part_type: PART_TYPE_LINUX,
-            start_lba,
-            num_sectors,
-        }
+    /// Returns a debug view into the partition table
+    pub fn debug<'s>(&'s self) -> impl fmt::Debug + 's {
+        &self.part_table
     }
 
-    /// Returns a zeroed/unallocated partition table entry.
-    fn zeroed() -> Self {
-        Self {
-            status: 0,
-            start_chs: [0; 3],
-            part_type: 0,
-            end_chs: [0; 3],
-            start_lba: 0,
-            num_sectors: 0,
+    /// Walks the EBR chain and returns the first logical partition `f` returns `true` for
+    fn find_logical(
+        &self,
+        mut f: impl FnMut(&PartitionEntry) -> bool,
+    ) -> Result<Option<PartitionEntry>, MbrError<D::Error>> {
+        let extended = match self.part_table.iter().find(|entry| entry.is_extended()) {
+            Some(extended) => extended,
+            None => return Ok(None),
+        };
+
+        let mut chain = EbrChain::new(extended);
+        let mut block = Block::zeroed();
+        while let Some(lba) = chain.next_ebr() {
+            self.raw
+                .read(&mut block, u64::from(lba))
+                .map_err(MbrError::Device)?;
+
+            if let Some(logical) = chain.parse(&block.bytes)? {
+                if f(&logical) {
+                    return Ok(Some(logical));
+                }
+            }
         }
-    }
 
-    fn extent(&self) -> PartExtent {
-        PartExtent {
-            start: self.start_lba,
-            sectors: self.num_sectors,
-        }
+        Ok(None)
     }
 }
 
-/// MBR partition table
-pub struct PartitionTable {
-    entries: [PartitionEntry; 4],
-    index: usize,
-}
-
-impl Default for PartitionTable {
-    fn default() -> Self {
-        Self::new()
-    }
+struct PartExtent {
+    start: u32,
+    sectors: u32,
 }
 
-impl PartitionTable {
-    /// Creates an empty partition table
-    pub fn new() -> Self {
-        Self {
-            entries: [PartitionEntry::zeroed(); 4],
-            index: 0,
-        }
-    }
-
-    fn as_slice(&self) -> &[PartitionEntry] {
-        &self.entries[..self.index]
-    }
-
-    /// Adds a new partition to the table
-    ///
-    /// NOTE partitions must be added in order (increasing `start_lba`)
-    pub fn add(&mut self, entry: PartitionEntry) -> Result<(), PartError> {
-        let end = self
-            .as_slice()
-            .last()
-            .map(|entry| entry.start_lba + entry.num_sectors)
-            .unwrap_or(0);
-
-        if entry.start_lba < end {
-            Err(PartError::PartitionCollision)
-        } else if self.index < self.entries.len() {
-            self.entries[self.index] = entry;
-            Ok(())
-        } else {
-            Err(PartError::TooManyPartitions)
-        }
-    }
-
-    fn to_block(&self) -> Block {
-        let mut block = Block::zeroed();
-        let mut start = 446;
-        for entry in &self.entries {
-            let bytes = entry.as_bytes();
-            let len = bytes.len();
-            block.bytes[start..start + len].copy_from_slice(bytes);
-            start += len;
+impl From<PartitionEntry> for PartExtent {
+    fn from(entry: PartitionEntry) -> Self {
+        PartExtent {
+            start: entry.start_lba(),
+            sectors: entry.num_sectors(),
         }
-        // magic number
-        block.bytes[510] = 0x55;
-        block.bytes[511] = 0xAA;
-        block
     }
 }
 
-/// Partition error
-#[derive(Debug)]
-pub enum PartError {
-    /// New partition collides with an existing collision
-    PartitionCollision,
-    /// The partition table has already 4 primary partitions
-    TooManyPartitions,
-}
-
-struct PartExtent {
-    start: u32,
-    sectors: u32,
-}
-
 /// Errors that can occur while opening or accessing an MBR-formatted block device.
 #[derive(Debug)]
 pub enum MbrError<D> {
@@ -330,6 +362,9 @@ pub enum MbrError<D> {
     /// Encountered partition with invalid location/extent.
     InvalidPartExtent,
 
+    /// An EBR links to a block outside of the extended partition, or back into the chain.
+    InvalidEbr,
+
     /// Attempted to access partition that isn't allocated.
     NoPartition,
 
@@ -343,12 +378,23 @@ impl<D: fmt::Display> fmt::Display for MbrError<D> {
             MbrError::Device(err) => write!(f, "I/O error: {}", err),
             MbrError::InvalidMagic => f.write_str("MBR signature invalid"),
             MbrError::InvalidPartExtent => f.write_str("invalid partition entry (corrupted MBR?)"),
+            MbrError::InvalidEbr => f.write_str("invalid EBR chain (corrupted EBR?)"),
             MbrError::NoPartition => f.write_str("invalid partition index"),
             MbrError::OutOfRangeAccess => f.write_str("block access outside of valid range"),
         }
     }
 }
 
+impl<D> From<mbr::Error> for MbrError<D> {
+    fn from(e: mbr::Error) -> Self {
+        match e {
+            mbr::Error::InvalidMagic => MbrError::InvalidMagic,
+            mbr::Error::InvalidPartExtent => MbrError::InvalidPartExtent,
+            mbr::Error::InvalidEbr => MbrError::InvalidEbr,
+        }
+    }
+}
+
 /// Provides borrowed access to an MBR partition.
 ///
 /// This implements `ManagedBlockDevice` and maps any access to the partition.
@@ -384,7 +430,65 @@ impl<'a, D: ManagedBlockDevice> ManagedBlockDevice for MbrPartitionRef<'a, D> {
             .map_err(MbrError::Device)
     }
 
+    fn read_vectored(&self, bufs: &mut [&mut [u8]], lba: u64) -> Result<(), Self::Error> {
+        let lba = self.map_lba(bufs.iter().map(|buf| buf.len()).sum(), lba)?;
+
+        self.raw.read_vectored(bufs, lba).map_err(MbrError::Device)
+    }
+
+    fn write_vectored(&mut self, bufs: &[&[u8]], lba: u64) -> Result<(), Self::Error> {
+        let lba = self.map_lba(bufs.iter().map(|buf| buf.len()).sum(), lba)?;
+
+        self.raw.write_vectored(bufs, lba).map_err(MbrError::Device)
+    }
+
+    fn discard(&mut self, blocks: Range<u64>) -> Result<(), Self::Error> {
+        let blocks = self.map_range(blocks)?;
+        self.raw.discard(blocks).map_err(MbrError::Device)
+    }
+
+    fn secure_erase(&mut self, blocks: Range<u64>) -> Result<(), Self::Error> {
+        let blocks = self.map_range(blocks)?;
+        self.raw.secure_erase(blocks).map_err(MbrError::Device)
+    }
+
+    fn secure_erase_batch(&mut self, ranges: &mut [Range<u64>]) -> Result<(), Self::Error> {
+        for blocks in ranges.iter_mut() {
+            *blocks = self.map_range(blocks.clone())?;
+        }
+        self.raw
+            .secure_erase_batch(ranges)
+            .map_err(MbrError::Device)
+    }
+
     fn flush(&mut self) -> Result<(), Self::Error> {
         self.raw.flush().map_err(MbrError::Device)
     }
 }
+
+impl<'a, D: ManagedBlockDevice> MbrPartitionRef<'a, D> {
+    /// Checks that the `len` bytes that start at block `lba` are within the partition and maps
+    /// `lba` to the underlying device.
+    fn map_lba(&self, len: usize, lba: u64) -> Result<u64, MbrError<D::Error>> {
+        let blocks = (len / usize::from(BLOCK_SIZE)) as u64;
+        // NOTE `lba` comes from the caller; checked arithmetic keeps it from wrapping around
+        // into some other partition, or the MBR, in release builds
+        match lba.checked_add(blocks) {
+            Some(end) if end <= u64::from(self.extent.sectors) => {}
+            _ => return Err(MbrError::OutOfRangeAccess),
+        }
+
+        lba.checked_add(u64::from(self.extent.start))
+            .ok_or(MbrError::OutOfRangeAccess)
+    }
+
+    /// Maps the `blocks` range of the partition to the underlying device.
+    fn map_range(&self, blocks: Range<u64>) -> Result<Range<u64>, MbrError<D::Error>> {
+        if blocks.start > blocks.end || blocks.end > u64::from(self.extent.sectors) {
+            return Err(MbrError::OutOfRangeAccess);
+        }
+
+        let start = u64::from(self.extent.start);
+        Ok(blocks.start + start..blocks.end + start)
+    }
+}
@@ -0,0 +1,403 @@
+//! GUID partition table (GPT) access.
+
+use core::{fmt, ops::Range};
+
+use memlog::memlog;
+use partition_tables::{
+    gpt::{self, Entry, Guid, Header, ENTRY_SIZE},
+    Crc32,
+};
+
+use super::{Block, ManagedBlockDevice, BLOCK_SIZE};
+
+/// Wraps a GPT-partitioned `ManagedBlockDevice` and provides access to its partitions.
+pub struct GptDevice<D: ManagedBlockDevice> {
+    raw: D,
+    /// Header of the partition table in use; the primary one unless it's corrupted.
+    header: Header,
+    /// Whether one copy of the partition table is corrupted.
+    degraded: bool,
+}
+
+impl<D: ManagedBlockDevice> GptDevice<D> {
+    /// Creates a new GPT-partitioned block device by writing a partition table that holds the
+    /// `partitions` into it.
+    ///
+    /// The protective MBR and both copies of the header and of the partition entry array are
+    /// written. Partitions keep their index in `partitions`; unused entries may be used to leave
+    /// gaps.
+    pub fn create(
+        mut raw: D,
+        disk_guid: Guid,
+        partitions: &[Entry],
+    ) -> Result<Self, GptError<D::Error>> {
+        let mut header = Header::new(disk_guid, raw.total_blocks())?;
+        gpt::check_partitions(&header, partitions)?;
+
+        let mut crc = Crc32::new();
+        for i in 0..header.entry_array_blocks() {
+            crc.update(&gpt::entry_array_block(partitions, i));
+        }
+        header.entries_crc32 = crc.finish();
+        let backup = header.backup()?;
+
+        // the primary header goes last; tools only consider the table valid once it's in place
+        let mut block = Block::zeroed();
+        for i in 0..header.entry_array_blocks() {
+            block.bytes = gpt::entry_array_block(partitions, i);
+            raw.write(&block, backup.entries_lba + i)
+                .map_err(GptError::Device)?;
+            raw.write(&block, header.entries_lba + i)
+                .map_err(GptError::Device)?;
+        }
+
+        for (lba, bytes) in &[
+            (backup.my_lba, backup.to_block()),
+            (0, gpt::protective_mbr(raw.total_blocks())),
+            (header.my_lba, header.to_block()),
+        ] {
+            block.bytes = *bytes;
+            raw.write(&block, *lba).map_err(GptError::Device)?;
+        }
+        raw.flush().map_err(GptError::Device)?;
+
+        Ok(GptDevice {
+            raw,
+            header,
+            degraded: false,
+        })
+    }
+
+    /// Opens a GPT-partitioned block device `raw` and validates its partition table.
+    ///
+    /// Block 0 must hold a protective MBR. Both copies of the partition table are checked: if the
+    /// primary copy is corrupted the backup copy is used instead and the device is reported as
+    /// degraded.
+    pub fn open(raw: D) -> Result<Self, GptError<D::Error>> {
+        let mut mbr = Block::zeroed();
+        raw.read(&mut mbr, 0).map_err(GptError::Device)?;
+        if !gpt::is_protective_mbr(&mbr.bytes) {
+            return Err(GptError::NoProtectiveMbr);
+        }
+
+        let last_lba = raw.total_blocks() - 1;
+        let (header, degraded) = match read_table(&raw, 1) {
+            Ok(header) => {
+                let intact = match read_table(&raw, header.alternate_lba) {
+                    Ok(backup) => {
+                        // the backup entry array may be anywhere
+                        let expected = Header {
+                            my_lba: header.alternate_lba,
+                            alternate_lba: header.my_lba,
+                            entries_lba: backup.entries_lba,
+                            ..header
+                        };
+                        if backup != expected {
+                            memlog!("backup GPT does not match the primary one");
+                        }
+                        backup == expected
+                    }
+                    Err(GptError::Table(e)) => {
+                        memlog!("backup GPT invalid: {}", e);
+                        false
+                    }
+                    Err(e) => return Err(e),
+                };
+
+                (header, !intact)
+            }
+
+            Err(GptError::Table(e)) => {
+                memlog!("primary GPT invalid: {}; using the backup", e);
+                let header = read_table(&raw, last_lba).map_err(|backup| match backup {
+                    // report why the primary table was rejected
+                    GptError::Table(_) => GptError::Table(e),
+                    other => other,
+                })?;
+
+                (header, true)
+            }
+
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            raw,
+            header,
+            degraded,
+        })
+    }
+
+    /// Obtains access to the partition described by entry `index` of the partition entry array.
+    ///
+    /// Returns a `NoPartition` error if the entry is unused or doesn't exist.
+    pub fn partition(&mut self, index: u32) -> Result<GptPartitionRef<'_, D>, GptError<D::Error>> {
+        let entry = self.entry(index)?;
+        if !entry.is_used() {
+            return Err(GptError::NoPartition);
+        }
+
+        Ok(GptPartitionRef {
+            raw: &mut self.raw,
+            extent: entry.blocks(),
+        })
+    }
+
+    /// Obtains access to the partition whose unique GUID is `guid`.
+    pub fn partition_by_guid(
+        &mut self,
+        guid: &Guid,
+    ) -> Result<GptPartitionRef<'_, D>, GptError<D::Error>> {
+        self.find(|entry| entry.unique_guid == *guid)
+    }
+
+    /// Obtains access to the first partition named `name`.
+    pub fn partition_by_name(
+        &mut self,
+        name: &str,
+    ) -> Result<GptPartitionRef<'_, D>, GptError<D::Error>> {
+        self.find(|entry| entry.has_name(name))
+    }
+
+    /// Reads entry `index` of the partition entry array; the entry may be unused.
+    pub fn entry(&self, index: u32) -> Result<Entry, GptError<D::Error>> {
+        if index >= self.header.num_entries {
+            return Err(GptError::NoPartition);
+        }
+
+        let (lba, offset) = self.header.entry_location(index);
+        let mut block = Block::zeroed();
+        self.raw.read(&mut block, lba).map_err(GptError::Device)?;
+        Ok(Entry::from_bytes(&block.bytes[offset..]))
+    }
+
+    /// Returns the number of entries in the partition entry array.
+    pub fn num_entries(&self) -> u32 {
+        self.header.num_entries
+    }
+
+    /// Returns the GUID of the disk.
+    pub fn disk_guid(&self) -> Guid {
+        self.header.disk_guid
+    }
+
+    /// Returns `true` if one copy of the partition table is corrupted or out of date.
+    ///
+    /// The partitions remain accessible; writing the table anew with `create` restores both
+    /// copies.
+    pub fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    fn find(
+        &mut self,
+        f: impl Fn(&Entry) -> bool,
+    ) -> Result<GptPartitionRef<'_, D>, GptError<D::Error>> {
+        let mut block = Block::zeroed();
+        for index in 0..self.header.num_entries {
+            let (lba, offset) = self.header.entry_location(index);
+            if offset == 0 {
+                self.raw.read(&mut block, lba).map_err(GptError::Device)?;
+            }
+
+            let entry = Entry::from_bytes(&block.bytes[offset..]);
+            if entry.is_used() && f(&entry) {
+                return Ok(GptPartitionRef {
+                    raw: &mut self.raw,
+                    extent: entry.blocks(),
+                });
+            }
+        }
+
+        Err(GptError::NoPartition)
+    }
+}
+
+/// Reads the GPT header stored in block `lba` and validates it and its partition entry array.
+///
+/// Tables with more than `gpt::NUM_ENTRIES` used entries are rejected.
+fn read_table<D: ManagedBlockDevice>(raw: &D, lba: u64) -> Result<Header, GptError<D::Error>> {
+    let mut block = Block::zeroed();
+    raw.read(&mut block, lba).map_err(GptError::Device)?;
+    let header = Header::parse(&block.bytes, lba, raw.total_blocks())?;
+
+    // extents of the used entries seen so far; every partition is handed out as a separate
+    // writable device so they must not overlap
+    const EMPTY: Range<u64> = 0..0;
+    let mut extents = [EMPTY; gpt::NUM_ENTRIES as usize];
+    let mut used = 0;
+
+    // the array need not end at a block boundary
+    let mut remaining = u64::from(header.num_entries) * ENTRY_SIZE as u64;
+    let mut crc = Crc32::new();
+    for i in 0..header.entry_array_blocks() {
+        raw.read(&mut block, header.entries_lba + i)
+            .map_err(GptError::Device)?;
+
+        let len = remaining.min(u64::from(BLOCK_SIZE)) as usize;
+        crc.update(&block.bytes[..len]);
+        for entry in block.bytes[..len].chunks_exact(ENTRY_SIZE) {
+            let entry = Entry::from_bytes(entry);
+            entry.check(&header)?;
+
+            if entry.is_used() {
+                let blocks = entry.blocks();
+                gpt::check_overlap(&blocks, extents[..used].iter().cloned())?;
+
+                if used == extents.len() {
+                    return Err(gpt::Error::TooManyPartitions.into());
+                }
+                extents[used] = blocks;
+                used += 1;
+            }
+        }
+        remaining -= len as u64;
+    }
+
+    if crc.finish() != header.entries_crc32 {
+        return Err(gpt::Error::EntriesCrc.into());
+    }
+
+    Ok(header)
+}
+
+/// Errors that can occur while opening, creating or accessing a GPT-formatted block device.
+#[derive(Debug)]
+pub enum GptError<D> {
+    /// Error while accessing the underlying device.
+    Device(D),
+
+    /// Block 0 does not hold a protective MBR.
+    NoProtectiveMbr,
+
+    /// The partition table is invalid, or the requested one doesn't fit the device.
+    Table(gpt::Error),
+
+    /// Attempted to access partition that doesn't exist.
+    NoPartition,
+
+    /// Attempted to access block outside of device/partition.
+    OutOfRangeAccess,
+}
+
+impl<D> From<gpt::Error> for GptError<D> {
+    fn from(e: gpt::Error) -> Self {
+        GptError::Table(e)
+    }
+}
+
+impl<D: fmt::Display> fmt::Display for GptError<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GptError::Device(err) => write!(f, "I/O error: {}", err),
+            GptError::NoProtectiveMbr => f.write_str("protective MBR missing (not a GPT disk?)"),
+            GptError::Table(err) => err.fmt(f),
+            GptError::NoPartition => f.write_str("no such partition"),
+            GptError::OutOfRangeAccess => f.write_str("block access outside of valid range"),
+        }
+    }
+}
+
+/// Provides borrowed access to a GPT partition.
+///
+/// This implements `ManagedBlockDevice` and maps any access to the partition.
+pub struct GptPartitionRef<'a, D: ManagedBlockDevice> {
+    raw: &'a mut D,
+    extent: Range<u64>,
+}
+
+impl<'a, D: ManagedBlockDevice> ManagedBlockDevice for GptPartitionRef<'a, D> {
+    type Error = GptError<D::Error>;
+
+    fn total_blocks(&self) -> u64 {
+        self.extent.end - self.extent.start
+    }
+
+    fn read(&self, block: &mut Block, lba: u64) -> Result<(), Self::Error> {
+        if lba >= self.total_blocks() {
+            return Err(GptError::OutOfRangeAccess);
+        }
+
+        self.raw
+            .read(block, lba + self.extent.start)
+            .map_err(GptError::Device)
+    }
+
+    fn write(&mut self, block: &Block, lba: u64) -> Result<(), Self::Error> {
+        if lba >= self.total_blocks() {
+            return Err(GptError::OutOfRangeAccess);
+        }
+
+        self.raw
+            .write(block, lba + self.extent.start)
+            .map_err(GptError::Device)
+    }
+
+    fn read_vectored(&self, bufs: &mut [&mut [u8]], lba: u64) -> Result<(), Self::Error> {
+        let lba = self.map_lba(bufs.iter().map(|buf| buf.len()).sum(), lba)?;
+
+        self.raw.read_vectored(bufs, lba).map_err(GptError::Device)
+    }
+
+    fn write_vectored(&mut self, bufs: &[&[u8]], lba: u64) -> Result<(), Self::Error> {
+        let lba = self.map_lba(bufs.iter().map(|buf| buf.len()).sum(), lba)?;
+
+        self.raw.write_vectored(bufs, lba).map_err(GptError::Device)
+    }
+
+    fn discard(&mut self, blocks: Range<u64>) -> Result<(), Self::Error> {
+        let blocks = self.map_range(blocks)?;
+        self.raw.discard(blocks).map_err(GptError::Device)
+    }
+
+    fn secure_erase(&mut self, blocks: Range<u64>) -> Result<(), Self::Error> {
+        let blocks = self.map_range(blocks)?;
+        self.raw.secure_erase(blocks).map_err(GptError::Device)
+    }
+
+    fn secure_erase_batch(&mut self, ranges: &mut [Range<u64>]) -> Result<(), Self::Error> {
+        for blocks in ranges.iter_mut() {
+            *blocks = self.map_range(blocks.clone())?;
+        }
+        self.raw
+            .secure_erase_batch(ranges)
+            .map_err(GptError::Device)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.raw.flush().map_err(GptError::Device)
+    }
+}
+
+impl<'a, D: ManagedBlockDevice> GptPartitionRef<'a, D> {
+    /// Checks that the `len` bytes that start at block `lba` are within the partition and maps
+    /// `lba` to the underlying device.
+    fn map_lba(&self, len: usize, lba: u64) -> Result<u64, GptError<D::Error>> {
+        let blocks = (len / usize::from(BLOCK_SIZE)) as u64;
+        // NOTE `lba` comes from the caller; checked arithmetic keeps it from wrapping around
+        // into some other partition, or the partition table, in release builds
+        match lba.checked_add(blocks) {
+            Some(end) if end <= self.total_blocks() => {}
+            _ => return Err(GptError::OutOfRangeAccess),
+        }
+
+        lba.checked_add(self.extent.start)
+            .ok_or(GptError::OutOfRangeAccess)
+    }
+
+    /// Maps the `blocks` range of the partition to the underlying device.
+    fn map_range(&self, blocks: Range<u64>) -> Result<Range<u64>, GptError<D::Error>> {
+        if blocks.start > blocks.end || blocks.end > self.total_blocks() {
+            return Err(GptError::OutOfRangeAccess);
+        }
+
+        let start = self.extent.start;
+        match (
+            blocks.start.checked_add(start),
+            blocks.end.checked_add(start),
+        ) {
+            (Some(first), Some(end)) => Ok(first..end),
+            _ => Err(GptError::OutOfRangeAccess),
+        }
+    }
+}
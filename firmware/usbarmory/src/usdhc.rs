@@ -0,0 +1,1386 @@
+//! Host side of the eMMC and SD card drivers
+//!
+//! Both card types are attached to an instance of the uSDHC (Ultra Secured
+//! Digital Host Controller) peripheral and are accessed using (mostly) the same
+//! commands
+
+use core::{
+    cell::Cell,
+    cmp, fmt,
+    num::NonZeroU16,
+    ptr, slice,
+    sync::atomic::{self, Ordering},
+    time::Duration,
+};
+
+use pac::{usdhc::Registers, Peripheral};
+
+use crate::{
+    memlog, memlog_flush_and_reset,
+    storage::{Block, BLOCK_SIZE},
+    time::{self, Instant},
+    util,
+};
+
+mod adma;
+pub mod card;
+pub mod cmd;
+
+use cmd::Command;
+
+pub fn default_timeout() -> Duration {
+    Duration::from_millis(100)
+}
+
+/// Time allowed to transfer, or program, `count` blocks
+pub fn transfer_timeout(count: u16) -> Duration {
+    default_timeout() + Duration::from_millis(count.into())
+}
+
+/// Maximum number of blocks a single data command can transfer
+const MAX_BLOCK_COUNT: u16 = 0xffff;
+
+pub type Rca = NonZeroU16;
+
+/// Command complete
+const INT_STATUS_CC: u32 = 1; // bit 0
+/// Transfer complete
+const INT_STATUS_TC: u32 = 1 << 1;
+/// DMA Interrupt
+const INT_STATUS_DINT: u32 = 1 << 3;
+/// Buffer Read Ready
+const INT_STATUS_BRR: u32 = 1 << 5;
+/// Command Timeout Error
+const INT_STATUS_CTOE: u32 = 1 << 16;
+/// Command CRC Error
+const INT_STATUS_CCE: u32 = 1 << 17;
+/// Command End Bit Error
+const INT_STATUS_CEBE: u32 = 1 << 18;
+/// Command Index Error
+const INT_STATUS_CIE: u32 = 1 << 19;
+/// Data Timeout Error
+const INT_STATUS_DTOE: u32 = 1 << 20;
+/// Data CRC Error
+const INT_STATUS_DCE: u32 = 1 << 21;
+/// Data End Bit Error
+const INT_STATUS_DEBE: u32 = 1 << 22;
+/// Auto CMD12 Error
+const INT_STATUS_AC12E: u32 = 1 << 24;
+/// DMA error
+const INT_STATUS_DMAE: u32 = 1 << 28;
+
+const INT_STATUS_ANY_ERROR: u32 = INT_STATUS_CTOE
+    | INT_STATUS_CCE
+    | INT_STATUS_CEBE
+    | INT_STATUS_CIE
+    | INT_STATUS_DTOE
+    | INT_STATUS_DCE
+    | INT_STATUS_DEBE
+    | INT_STATUS_AC12E
+    | INT_STATUS_DMAE;
+
+/// Command Inhibit (DATA)
+const PRES_STATE_CDIHB: u32 = 1 << 1;
+/// Command Inhibit
+const PRES_STATE_CIHB: u32 = 1; // bit 0
+/// Data Line Active
+const PRES_STATE_DLA: u32 = 1 << 2;
+
+/// Bits that must always be ones
+const MIX_CTRL_RESERVED: u32 = 1 << 31;
+/// Single block
+const MIX_CTRL_MBSEL_SINGLE: u32 = 0 << 5;
+/// Multiple blocks
+const MIX_CTRL_MBSEL_MULTI: u32 = 1 << 5;
+/// Data Transfer Direction Select = Read (Card to Host)
+const MIX_CTRL_DTDSEL_READ: u32 = 1 << 4;
+/// Data Transfer Direction Select = Write (Host to Card)
+const MIX_CTRL_DTDSEL_WRITE: u32 = 0 << 4;
+/// Auto CMD12 Enable
+const MIX_CTRL_AC12EN: u32 = 1 << 2;
+/// Block Count Enable
+const MIX_CTRL_BCEN: u32 = 1 << 1;
+/// Enable the DMA
+const MIX_CTRL_DMAEN_ENABLE: u32 = 1; // bit 0
+const MIX_CTRL_DDR_EN: u32 = 1 << 3;
+/// Enable auto tuning
+const MIX_CTRL_AUTO_TUNE_EN: u32 = 1 << 24;
+/// Feedback clock source selection (for tuning)
+const MIX_CTRL_FBCLK_SEL: u32 = 1 << 25;
+/// Tuning bits; they must be kept when the transfer bits are written
+const MIX_CTRL_TUNING_MASK: u32 = MIX_CTRL_AUTO_TUNE_EN | MIX_CTRL_FBCLK_SEL;
+
+/// Start the tuning procedure; cleared by the hardware when it completes
+const AUTOCMD12_EXECUTE_TUNING: u32 = 1 << 22;
+/// Sample the data with the tuned clock; set by the hardware if tuning succeeded
+const AUTOCMD12_SMP_CLK_SEL: u32 = 1 << 23;
+
+/// Standard tuning circuit enable
+const TUNING_CTRL_STD_TUNING_EN: u32 = 1 << 24;
+const TUNING_CTRL_START_TAP_MASK: u32 = 0xff;
+const TUNING_CTRL_STEP_OFFSET: u8 = 16;
+const TUNING_CTRL_STEP_MASK: u32 = 0b111 << TUNING_CTRL_STEP_OFFSET;
+
+/// Maximum number of tuning blocks the card is sent before giving up
+const MAX_TUNING_LOOPS: u8 = 40;
+
+const PROT_CTRL_DMASEL_MASK: u32 = 0b11 << 8;
+/// Simple DMA
+const PROT_CTRL_DMASEL_SIMPLE: u32 = 0b00 << 8;
+/// Advanced DMA, version 2
+const PROT_CTRL_DMASEL_ADMA2: u32 = 0b10 << 8;
+
+const VEND_SPEC_CKEN: u32 = 1 << 14;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Width {
+    /// 1-bit
+    B1,
+    /// 4-bit
+    B4,
+    /// 8-bit
+    B8,
+}
+
+impl Width {
+    fn dtw(self) -> u8 {
+        match self {
+            Width::B1 => 0b00,
+            Width::B4 => 0b01,
+            Width::B8 => 0b10,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Frequency {
+    K400,
+    M20,
+    M25,
+    M50,
+    M200,
+}
+
+/// The DMA used to move the data of a transfer
+enum Dma {
+    /// One contiguous buffer that starts at this address
+    Simple(usize),
+    /// Several buffers described by the ADMA2 descriptor table at this address
+    Adma2 { table: usize },
+}
+
+/// A uSDHC peripheral and the card attached to it
+pub struct Usdhc<P>
+where
+    P: Peripheral,
+{
+    regs: Registers<P>,
+    /// Currently selected card
+    pub selected: Option<Rca>,
+    /// `true` if the card is addressed in blocks; `false` if it's addressed in
+    /// bytes (standard capacity SD cards)
+    pub high_capacity: bool,
+    /// `true` if the card supports the `SET_BLOCK_COUNT` command; when `false`
+    /// multiple block transfers are stopped with an (automatic) `CMD12`
+    pub set_block_count: bool,
+    pub verbose: bool,
+    width: Cell<Width>,
+    /// `true` if data is transferred on both edges of the clock
+    ddr: Cell<bool>,
+}
+
+impl<P> Usdhc<P>
+where
+    P: Peripheral,
+{
+    pub fn new(regs: Registers<P>) -> Self {
+        // clear any pending status
+        regs.INT_STATUS.clear(!0);
+        // start with the simple DMA; `prepare_dma` selects the DMA used by each transfer
+        regs.PROT_CTRL.rmw(|r| r & !PROT_CTRL_DMASEL_MASK);
+
+        Usdhc {
+            regs,
+            selected: None,
+            high_capacity: true,
+            set_block_count: true,
+            verbose: false,
+            width: Cell::new(Width::B1),
+            ddr: Cell::new(false),
+        }
+    }
+
+    // high-level API
+    // NOTE(`block_nr`) this is a *block* number; see `address`
+    /// Reads consecutive blocks of memory, starting at block `block_nr`
+    pub fn read_blocks(&self, block_nr: u32, blocks: &mut [Block]) -> Result<(), Error> {
+        let mut block_nr = block_nr;
+        for chunk in blocks.chunks_mut(MAX_BLOCK_COUNT.into()) {
+            let addr = chunk.as_mut_ptr() as *mut u8;
+            // NOTE(as) `chunk.len()` is at most `MAX_BLOCK_COUNT`
+            let count = chunk.len() as u16;
+
+            if count == 1 {
+                let cmd = Command::ReadSingleBlock {
+                    block_nr: self.address(block_nr),
+                };
+                self.read_single_block(cmd, addr, BLOCK_SIZE)?;
+            } else {
+                self.read_multiple_blocks(block_nr, count, addr)?;
+            }
+
+            block_nr += u32::from(count);
+        }
+
+        Ok(())
+    }
+
+    /// Transfers consecutive blocks of memory to the card, starting at block
+    /// `block_nr`, for them to be programmed to flash
+    pub fn write_blocks(&self, block_nr: u32, blocks: &[Block]) -> Result<(), Error> {
+        let mut block_nr = block_nr;
+        for chunk in blocks.chunks(MAX_BLOCK_COUNT.into()) {
+            let addr = chunk.as_ptr() as *const u8;
+            // NOTE(as) `chunk.len()` is at most `MAX_BLOCK_COUNT`
+            let count = chunk.len() as u16;
+
+            if count == 1 {
+                self.write_single_block(block_nr, addr)?;
+            } else {
+                self.write_multiple_blocks(block_nr, count, addr, false)?;
+            }
+
+            block_nr += u32::from(count);
+        }
+
+        Ok(())
+    }
+
+    /// Like `read_blocks` but the blocks are always read with a
+    /// `SET_BLOCK_COUNT` + `READ_MULTIPLE_BLOCK` command pair, even if there's
+    /// a single block to read; the RPMB only accepts this kind of transfer
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the card doesn't support `SET_BLOCK_COUNT` or if
+    /// `blocks` is empty or longer than 65535 blocks
+    pub fn read_blocks_counted(&self, block_nr: u32, blocks: &mut [Block]) -> Result<(), Error> {
+        let count = self.counted(blocks.len());
+        self.read_multiple_blocks(block_nr, count, blocks.as_mut_ptr() as *mut u8)
+    }
+
+    /// Like `write_blocks` but the blocks are always written with a
+    /// `SET_BLOCK_COUNT` + `WRITE_MULTIPLE_BLOCK` command pair, even if there's
+    /// a single block to write; `reliable` requests a reliable write
+    ///
+    /// See `read_blocks_counted` for the panicking conditions
+    pub fn write_blocks_counted(
+        &self,
+        block_nr: u32,
+        blocks: &[Block],
+        reliable: bool,
+    ) -> Result<(), Error> {
+        let count = self.counted(blocks.len());
+        self.write_multiple_blocks(block_nr, count, blocks.as_ptr() as *const u8, reliable)
+    }
+
+    /// Erases blocks `first` to `last` (inclusive) using the erase operation
+    /// selected by `arg`; `timeout` bounds the time the card may take to
+    /// complete the operation
+    pub fn erase(&self, first: u32, last: u32, arg: u32, timeout: Duration) -> Result<(), Error> {
+        let rca = self.ready_for_data()?;
+
+        self.send_command(Command::EraseGroupStart {
+            block_nr: self.address(first),
+        })?;
+        self.send_command(Command::EraseGroupEnd {
+            block_nr: self.address(last),
+        })?;
+        self.send_command(Command::Erase { arg })?;
+
+        self.wait_for_programming(rca, timeout)
+    }
+
+    /// Sets (`protect`) or clears the temporary write protection of the write
+    /// protect group that contains block `block_nr`
+    pub fn write_protect(&self, block_nr: u32, protect: bool) -> Result<(), Error> {
+        let rca = self.ready_for_data()?;
+
+        let block_nr = self.address(block_nr);
+        self.send_command(if protect {
+            Command::SetWriteProt { block_nr }
+        } else {
+            Command::ClrWriteProt { block_nr }
+        })?;
+
+        self.wait_for_programming(rca, default_timeout())
+    }
+
+    /// Returns the write protection type of the 32 write protect groups that
+    /// start with the one that contains block `block_nr`
+    ///
+    /// Each group is described by 2 bits; the group that contains `block_nr`
+    /// is in the least significant bits
+    pub fn write_protect_type(&self, block_nr: u32) -> Result<u64, Error> {
+        let mut block = Block::zeroed();
+        let cmd = Command::SendWriteProtType {
+            block_nr: self.address(block_nr),
+        };
+        self.read_register(cmd, &mut block, 8)?;
+
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&block.bytes[..8]);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    /// Reads a register that the card sends over the data lines, like the
+    /// EXT_CSD register, into the first `len` bytes of `buf`
+    ///
+    /// `len` must be a multiple of 4
+    pub fn read_register(&self, cmd: Command, buf: &mut Block, len: u16) -> Result<(), Error> {
+        debug_assert!(len % 4 == 0 && len <= BLOCK_SIZE);
+
+        self.read_single_block(cmd, buf.bytes.as_mut_ptr(), len)
+    }
+
+    /// Reads consecutive blocks of memory, starting at block `block_nr`, into
+    /// several buffers
+    ///
+    /// The buffers are filled in order. The data is moved by the advanced DMA
+    /// (ADMA2) so the buffers need not be contiguous; a single command is used
+    /// for up to 32 buffers, or 65535 blocks, whichever comes first
+    ///
+    /// # Panics
+    ///
+    /// This method panics if a buffer is not 4-byte aligned or if its length
+    /// is not a multiple of `BLOCK_SIZE`
+    pub fn read_vectored(&self, block_nr: u32, bufs: &mut [&mut [u8]]) -> Result<(), Error> {
+        self.transfer_vectored(
+            MIX_CTRL_DTDSEL_READ,
+            block_nr,
+            bufs.iter_mut()
+                .map(|buf| (buf.as_mut_ptr() as usize, buf.len())),
+        )
+    }
+
+    /// Transfers the contents of several buffers to consecutive blocks of the
+    /// card, starting at block `block_nr`, for them to be programmed to flash
+    ///
+    /// See `read_vectored` for the requirements on the buffers
+    pub fn write_vectored(&self, block_nr: u32, bufs: &[&[u8]]) -> Result<(), Error> {
+        self.transfer_vectored(
+            MIX_CTRL_DTDSEL_WRITE,
+            block_nr,
+            bufs.iter().map(|buf| (buf.as_ptr() as usize, buf.len())),
+        )
+    }
+
+    /// Like `read_vectored` but buffers that the DMA can't access are also
+    /// accepted; those are filled one block at a time, through a `Block`
+    pub fn read_vectored_any(&self, block_nr: u32, bufs: &mut [&mut [u8]]) -> Result<(), Error> {
+        if bufs.iter().all(|buf| is_word_aligned(buf)) {
+            return self.read_vectored(block_nr, bufs);
+        }
+
+        let mut block = Block::zeroed();
+        let mut block_nr = block_nr;
+        for buf in bufs.iter_mut() {
+            for chunk in buf.chunks_mut(BLOCK_SIZE.into()) {
+                self.read_blocks(block_nr, slice::from_mut(&mut block))?;
+                chunk.copy_from_slice(&block.bytes);
+                block_nr += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like `write_vectored` but buffers that the DMA can't access are also
+    /// accepted; those are written one block at a time, through a `Block`
+    pub fn write_vectored_any(&self, block_nr: u32, bufs: &[&[u8]]) -> Result<(), Error> {
+        if bufs.iter().all(|buf| is_word_aligned(buf)) {
+            return self.write_vectored(block_nr, bufs);
+        }
+
+        let mut block = Block::zeroed();
+        let mut block_nr = block_nr;
+        for buf in bufs {
+            for chunk in buf.chunks(BLOCK_SIZE.into()) {
+                block.bytes.copy_from_slice(chunk);
+                self.write_blocks(block_nr, slice::from_ref(&block))?;
+                block_nr += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the 128-bit response (R2) to the last command
+    pub fn long_response(&self) -> [u32; 4] {
+        [
+            self.regs.CMD_RSP0.read(),
+            self.regs.CMD_RSP1.read(),
+            self.regs.CMD_RSP2.read(),
+            self.regs.CMD_RSP3.read(),
+        ]
+    }
+
+    /// Starts moving the `count` blocks at `addr` from (`read`) or to the
+    /// card, starting at block `block_nr`; returns as soon as the card has
+    /// accepted the data command
+    ///
+    /// The end of the transfer is signaled through the interrupt of the
+    /// peripheral; `poll_transfer` returns its outcome. `addr` must point to
+    /// word aligned memory that stays valid until then
+    pub fn start_transfer(
+        &self,
+        read: bool,
+        block_nr: u32,
+        count: u16,
+        addr: usize,
+    ) -> Result<(), Error> {
+        assert!(count != 0, "a transfer must move at least one block");
+
+        self.ready_for_data()?;
+        let direction = if read {
+            MIX_CTRL_DTDSEL_READ
+        } else {
+            MIX_CTRL_DTDSEL_WRITE
+        };
+        self.prepare_dma(direction, count, BLOCK_SIZE, Dma::Simple(addr));
+
+        let block_nr = self.address(block_nr);
+        let cmd = if count == 1 {
+            if read {
+                Command::ReadSingleBlock { block_nr }
+            } else {
+                Command::WriteSingleBlock { block_nr }
+            }
+        } else {
+            self.set_block_count(count, false)?;
+
+            if read {
+                Command::ReadMultipleBlock { block_nr }
+            } else {
+                Command::WriteMultipleBlock { block_nr }
+            }
+        };
+
+        if self.verbose {
+            memlog!("async transfer START @ {:?}", time::uptime());
+        }
+
+        // NOTE the command completion is not signaled; `start_command` polls
+        // for it
+        self.regs
+            .INT_SIGNAL_EN
+            .write(INT_STATUS_TC | INT_STATUS_DINT | INT_STATUS_ANY_ERROR);
+        let res = self.start_command(cmd).and_then(|rsp| {
+            card::Status::from(rsp)?;
+            Ok(())
+        });
+        if res.is_err() {
+            self.regs.INT_SIGNAL_EN.write(0);
+        }
+
+        res
+    }
+
+    /// [Non-blocking] Returns the outcome of the transfer started with
+    /// `start_transfer`, or `None` if it's still in progress
+    ///
+    /// Returning the outcome acknowledges the interrupt
+    pub fn poll_transfer(&self) -> Option<Result<(), Error>> {
+        let res = self.check_data_transfer(INT_STATUS_TC | INT_STATUS_DINT)?;
+
+        self.regs.INT_SIGNAL_EN.write(0);
+        // TODO accessing the buffer requires cache invalidation
+        // buffer handed back to us
+        atomic::fence(Ordering::Acquire);
+
+        if self.verbose {
+            memlog!("async transfer DONE @ {:?}", time::uptime());
+        }
+
+        Some(res)
+    }
+
+    /// [Non-blocking] Gives up on the transfer started with `start_transfer`
+    ///
+    /// Its completion is no longer signaled; use `recover` to stop the DMA and
+    /// get the card ready for the next command
+    pub fn abort_transfer(&self) {
+        self.regs.INT_SIGNAL_EN.write(0);
+
+        if self.verbose {
+            memlog!("async transfer ABORT @ {:?}", time::uptime());
+        }
+    }
+
+    /// Moves data between the card and the buffers yielded by `bufs`, as
+    /// `(address, length)` pairs; as few commands as possible are used
+    fn transfer_vectored(
+        &self,
+        direction: u32,
+        block_nr: u32,
+        bufs: impl Iterator<Item = (usize, usize)>,
+    ) -> Result<(), Error> {
+        let mut table = adma::Table::new();
+        let mut block_nr = block_nr;
+        // blocks described by `table`
+        let mut count: u16 = 0;
+
+        for (mut addr, len) in bufs {
+            assert!(
+                addr % 4 == 0 && len % usize::from(BLOCK_SIZE) == 0,
+                "buffer must be 4-byte aligned and hold whole blocks"
+            );
+
+            let mut left = len / usize::from(BLOCK_SIZE);
+            while left != 0 {
+                // NOTE(as) no truncation; the value is at most `MAX_SEGMENT_BLOCKS`
+                let blocks = cmp::min(
+                    cmp::min(left, usize::from(adma::MAX_SEGMENT_BLOCKS)) as u16,
+                    MAX_BLOCK_COUNT - count,
+                );
+
+                table.push(addr, blocks);
+                addr += usize::from(blocks) * usize::from(BLOCK_SIZE);
+                left -= usize::from(blocks);
+                count += blocks;
+
+                if table.is_full() || count == MAX_BLOCK_COUNT {
+                    self.adma_command(direction, block_nr, count, &mut table)?;
+                    block_nr += u32::from(count);
+                    count = 0;
+                    table.clear();
+                }
+            }
+        }
+
+        if count != 0 {
+            self.adma_command(direction, block_nr, count, &mut table)?;
+        }
+
+        Ok(())
+    }
+
+    /// [Blocking] Transfers the `count` blocks described by `table`
+    fn adma_command(
+        &self,
+        direction: u32,
+        block_nr: u32,
+        count: u16,
+        table: &mut adma::Table,
+    ) -> Result<(), Error> {
+        let rca = self.ready_for_data()?;
+        let table = table.finish();
+        self.prepare_dma(direction, count, BLOCK_SIZE, Dma::Adma2 { table });
+
+        let read = direction == MIX_CTRL_DTDSEL_READ;
+        let block_nr = self.address(block_nr);
+        let cmd = if count == 1 {
+            if read {
+                Command::ReadSingleBlock { block_nr }
+            } else {
+                Command::WriteSingleBlock { block_nr }
+            }
+        } else {
+            self.set_block_count(count, false)?;
+
+            if read {
+                Command::ReadMultipleBlock { block_nr }
+            } else {
+                Command::WriteMultipleBlock { block_nr }
+            }
+        };
+
+        // start the transfer
+        if self.verbose {
+            memlog!("ADMA START @ {:?}", time::uptime());
+        }
+        let rsp = self.send_command(cmd)?;
+        let status = card::Status::from(rsp)?;
+
+        // NOTE `send_command` will block until the data transfer is finished
+
+        // TODO accessing the buffers requires cache invalidation
+        // buffers (and descriptor table) handed back to us
+        atomic::fence(Ordering::Acquire);
+
+        if !read {
+            self.wait_for_programming(rca, transfer_timeout(count))?;
+        }
+
+        if self.verbose {
+            memlog!("ADMA DONE ({:?}) @ {:?}", status, time::uptime());
+        }
+
+        Ok(())
+    }
+
+    // mid-level API
+    fn counted(&self, len: usize) -> u16 {
+        assert!(
+            self.set_block_count,
+            "card doesn't support the `SET_BLOCK_COUNT` command"
+        );
+        assert!(
+            len != 0 && len <= usize::from(MAX_BLOCK_COUNT),
+            "a transfer must move between 1 and 65535 blocks"
+        );
+
+        // NOTE(as) no truncation; see the assertion above
+        len as u16
+    }
+
+    /// Converts a block number into the data address argument of a command
+    fn address(&self, block_nr: u32) -> u32 {
+        if self.high_capacity {
+            block_nr
+        } else {
+            block_nr * u32::from(BLOCK_SIZE)
+        }
+    }
+
+    /// Announces the number of blocks the next multiple block command will
+    /// transfer, if the card supports it
+    fn set_block_count(&self, count: u16, reliable: bool) -> Result<(), Error> {
+        // the card stops transferring data after `count` blocks; no need for a
+        // `STOP_TRANSMISSION` command
+        if self.set_block_count {
+            self.send_command(Command::SetBlockCount {
+                blocks: count,
+                reliable,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    // NOTE `len` is the size of the data block in bytes
+    fn read_single_block(&self, cmd: Command, addr: *mut u8, len: u16) -> Result<(), Error> {
+        self.ready_for_data()?;
+        self.prepare_dma(MIX_CTRL_DTDSEL_READ, 1, len, Dma::Simple(addr as usize));
+
+        // start the transfer
+        if self.verbose {
+            memlog!("read START @ {:?}", time::uptime());
+        }
+        let rsp = self.send_command(cmd)?;
+        let status = card::Status::from(rsp)?;
+
+        // NOTE `send_command` will block until the data transfer is finished
+
+        // TODO accessing `buf` requires cache invalidation
+        // let the DMA finish its memory operations before `block` is read
+        atomic::fence(Ordering::Acquire);
+
+        if self.verbose {
+            memlog!("read DONE ({:?}) @ {:?}", status, time::uptime());
+        }
+        Ok(())
+    }
+
+    // NOTE `count` must be greater than 1 unless `SET_BLOCK_COUNT` is supported
+    fn read_multiple_blocks(&self, block_nr: u32, count: u16, addr: *mut u8) -> Result<(), Error> {
+        self.ready_for_data()?;
+        self.prepare_dma(
+            MIX_CTRL_DTDSEL_READ,
+            count,
+            BLOCK_SIZE,
+            Dma::Simple(addr as usize),
+        );
+
+        self.set_block_count(count, false)?;
+
+        // start the transfer
+        if self.verbose {
+            memlog!("read START @ {:?}", time::uptime());
+        }
+        let rsp = self.send_command(Command::ReadMultipleBlock {
+            block_nr: self.address(block_nr),
+        })?;
+        let status = card::Status::from(rsp)?;
+
+        // NOTE `send_command` will block until the data transfer is finished
+
+        // TODO accessing `buf` requires cache invalidation
+        // let the DMA finish its memory operations before the blocks are read
+        atomic::fence(Ordering::Acquire);
+
+        if self.verbose {
+            memlog!("read DONE ({:?}) @ {:?}", status, time::uptime());
+        }
+        Ok(())
+    }
+
+    fn write_single_block(&self, block_nr: u32, addr: *const u8) -> Result<(), Error> {
+        let rca = self.ready_for_data()?;
+        self.prepare_dma(
+            MIX_CTRL_DTDSEL_WRITE,
+            1,
+            BLOCK_SIZE,
+            Dma::Simple(addr as usize),
+        );
+
+        // start the transfer
+        if self.verbose {
+            memlog!("write START @ {:?}", time::uptime());
+        }
+        let rsp = self.send_command(Command::WriteSingleBlock {
+            block_nr: self.address(block_nr),
+        })?;
+        let status = card::Status::from(rsp)?;
+
+        // NOTE `send_command` will block until the data transfer is finished
+
+        // buffer handled back to us
+        atomic::fence(Ordering::Acquire);
+
+        self.wait_for_programming(rca, default_timeout())?;
+
+        if self.verbose {
+            memlog!("write DONE ({:?}) @ {:?}", status, time::uptime());
+        }
+
+        Ok(())
+    }
+
+    // NOTE `count` must be greater than 1 unless `SET_BLOCK_COUNT` is supported
+    fn write_multiple_blocks(
+        &self,
+        block_nr: u32,
+        count: u16,
+        addr: *const u8,
+        reliable: bool,
+    ) -> Result<(), Error> {
+        let rca = self.ready_for_data()?;
+        self.prepare_dma(
+            MIX_CTRL_DTDSEL_WRITE,
+            count,
+            BLOCK_SIZE,
+            Dma::Simple(addr as usize),
+        );
+
+        self.set_block_count(count, reliable)?;
+
+        // start the transfer
+        if self.verbose {
+            memlog!("write START @ {:?}", time::uptime());
+        }
+        let rsp = self.send_command(Command::WriteMultipleBlock {
+            block_nr: self.address(block_nr),
+        })?;
+        let status = card::Status::from(rsp)?;
+
+        // NOTE `send_command` will block until the data transfer is finished
+
+        // buffer handled back to us
+        atomic::fence(Ordering::Acquire);
+
+        self.wait_for_programming(rca, transfer_timeout(count))?;
+
+        if self.verbose {
+            memlog!("write DONE ({:?}) @ {:?}", status, time::uptime());
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the selected card is ready to transfer data; returns its
+    /// relative address
+    fn ready_for_data(&self) -> Result<Rca, Error> {
+        let rca = if let Some(rca) = self.selected {
+            rca
+        } else {
+            memlog!("a card must be selected first");
+            memlog_flush_and_reset!();
+        };
+
+        let status = self.get_card_status(rca)?;
+        if self.verbose {
+            memlog!("{:?}", status);
+        }
+
+        if !status.ready_for_data || status.state != card::State::Transfer {
+            return Err(Error::NotInTransferState);
+        }
+
+        Ok(rca)
+    }
+
+    /// Hands the buffer(s) described by `dma`, which hold `count` blocks of
+    /// `len` bytes, over to the DMA
+    ///
+    /// `direction` is one of the `MIX_CTRL_DTDSEL_*` values. The transfer
+    /// starts when the data command is sent
+    fn prepare_dma(&self, direction: u32, count: u16, len: u16, dma: Dma) {
+        let blocks = if count == 1 {
+            MIX_CTRL_MBSEL_SINGLE
+        } else if self.set_block_count {
+            MIX_CTRL_MBSEL_MULTI | MIX_CTRL_BCEN
+        } else {
+            MIX_CTRL_MBSEL_MULTI | MIX_CTRL_BCEN | MIX_CTRL_AC12EN
+        };
+        self.regs.MIX_CTRL.rmw(|r| {
+            (r & MIX_CTRL_TUNING_MASK)
+                | MIX_CTRL_RESERVED
+                | if self.ddr.get() { MIX_CTRL_DDR_EN } else { 0 }
+                | blocks
+                | direction
+                | MIX_CTRL_DMAEN_ENABLE
+        });
+
+        // must wait until DLA is cleared
+        while self.regs.PRES_STATE.read() & PRES_STATE_DLA != 0 {
+            crate::memlog_try_flush()
+        }
+
+        // TC must be cleared before writing to DS_ADDR
+        self.regs.INT_STATUS.clear(INT_STATUS_TC);
+
+        // semantically, the next register write transfers ownership of the
+        // buffer to the DMA so ensure all previous memory operations complete
+        // before the ownership transfer (NOTE this could be done a bit later:
+        // in the middle of `send_command(WriteSingleBlock)`)
+        atomic::fence(Ordering::Release);
+        match dma {
+            Dma::Simple(addr) => {
+                debug_assert_eq!(addr % 4, 0);
+
+                self.regs
+                    .PROT_CTRL
+                    .rmw(|r| (r & !PROT_CTRL_DMASEL_MASK) | PROT_CTRL_DMASEL_SIMPLE);
+                self.regs.DS_ADDR.write(addr as u32);
+            }
+
+            Dma::Adma2 { table } => {
+                self.regs
+                    .PROT_CTRL
+                    .rmw(|r| (r & !PROT_CTRL_DMASEL_MASK) | PROT_CTRL_DMASEL_ADMA2);
+                self.regs.ADMA_SYS_ADDR.write(table as u32);
+            }
+        }
+        self.regs
+            .BLK_ATT
+            .write(u32::from(count) << 16 | u32::from(len));
+        self.regs.WTMK_LVL.reset(); // use a reasonable watermark level
+    }
+
+    /// Waits until the card finishes programming the data it received, or
+    /// finishes any other operation that takes it out of the transfer state
+    pub fn wait_for_programming(&self, rca: Rca, timeout: Duration) -> Result<(), Error> {
+        // flush the write
+        if self.get_card_status(rca)?.state != card::State::Transfer {
+            let start = Instant::now();
+            while self.get_card_status(rca)?.state != card::State::Transfer {
+                crate::memlog_try_flush();
+
+                if Instant::now() - start > timeout {
+                    memlog!("card took too long to program to flash the data it received");
+                    return Err(Error::Timeout);
+                }
+            }
+
+            memlog!("write flushed @ {:?}", time::uptime());
+        }
+
+        Ok(())
+    }
+
+    /// Changes the width of the data bus, on the host side
+    ///
+    /// The card must be told about the new width before this is called
+    pub fn set_bus_width(&self, width: Width) {
+        const DTW_OFFSET: u8 = 1;
+        const DTW_MASK: u32 = 0b11 << DTW_OFFSET;
+        self.regs
+            .PROT_CTRL
+            .rmw(|r| (r & !DTW_MASK) | (u32::from(width.dtw()) << DTW_OFFSET));
+        self.width.set(width);
+    }
+
+    /// Switches between single (SDR) and dual (DDR) data rate transfers, on
+    /// the host side
+    ///
+    /// The card must be told about the new data rate before this is called.
+    /// In DDR mode the card clock runs at half the frequency selected with
+    /// `change_frequency` so the frequency must be set again after this call
+    pub fn set_ddr(&self, ddr: bool) {
+        self.regs.MIX_CTRL.rmw(|r| {
+            if ddr {
+                r | MIX_CTRL_DDR_EN
+            } else {
+                r & !MIX_CTRL_DDR_EN
+            }
+        });
+        self.ddr.set(ddr);
+    }
+
+    /// Runs the standard tuning procedure, which finds the sampling point of
+    /// the data lines; required by the HS200 mode
+    ///
+    /// The card must already be in HS200 mode, at its final frequency and bus
+    /// width (4 or 8 bits). `Error::Other` is returned if no working sampling
+    /// point was found
+    pub fn execute_tuning(&self) -> Result<(), Error> {
+        // size of the tuning block, in bytes
+        let len = match self.width.get() {
+            Width::B8 => 128,
+            Width::B4 => 64,
+            Width::B1 => {
+                memlog!("tuning requires a 4-bit or 8-bit bus");
+                return Err(Error::Other);
+            }
+        };
+
+        self.reset_tuning();
+        self.regs.TUNING_CTRL.rmw(|r| {
+            (r & !(TUNING_CTRL_START_TAP_MASK | TUNING_CTRL_STEP_MASK))
+                | TUNING_CTRL_STD_TUNING_EN
+                | 1 // start tap
+                | 1 << TUNING_CTRL_STEP_OFFSET
+        });
+        self.regs
+            .MIX_CTRL
+            .rmw(|r| r | MIX_CTRL_AUTO_TUNE_EN | MIX_CTRL_FBCLK_SEL);
+        self.write_autocmd12(|r| r | AUTOCMD12_EXECUTE_TUNING);
+
+        for _ in 0..MAX_TUNING_LOOPS {
+            // the tuning block is consumed by the tuning circuit; no DMA needed
+            self.regs.MIX_CTRL.rmw(|r| {
+                (r & MIX_CTRL_TUNING_MASK)
+                    | MIX_CTRL_RESERVED
+                    | if self.ddr.get() { MIX_CTRL_DDR_EN } else { 0 }
+                    | MIX_CTRL_MBSEL_SINGLE
+                    | MIX_CTRL_DTDSEL_READ
+            });
+            self.regs.BLK_ATT.write(1 << 16 | len);
+
+            // the card may not answer (correctly) to some of the tuning
+            // commands; that's expected while the sampling point is off
+            if self.send_command(Command::SendTuningBlock).is_err() {
+                self.clear_command_inhibit();
+            }
+
+            if self.regs.AUTOCMD12_ERR_STATUS.read() & AUTOCMD12_EXECUTE_TUNING == 0 {
+                break;
+            }
+        }
+
+        if self.regs.AUTOCMD12_ERR_STATUS.read() & AUTOCMD12_SMP_CLK_SEL != 0 {
+            memlog!("tuning succeeded @ {:?}", time::uptime());
+            Ok(())
+        } else {
+            memlog!("tuning failed");
+            self.reset_tuning();
+            Err(Error::Other)
+        }
+    }
+
+    /// Stops sampling with the tuned clock
+    pub fn reset_tuning(&self) {
+        self.write_autocmd12(|r| r & !(AUTOCMD12_EXECUTE_TUNING | AUTOCMD12_SMP_CLK_SEL));
+        self.regs.MIX_CTRL.rmw(|r| r & !MIX_CTRL_TUNING_MASK);
+        self.regs
+            .TUNING_CTRL
+            .rmw(|r| r & !TUNING_CTRL_STD_TUNING_EN);
+    }
+
+    /// Modifies the tuning control bits of the `AUTOCMD12_ERR_STATUS` register
+    // NOTE the PAC models this register as read-only but these bits are writable
+    fn write_autocmd12(&self, f: impl FnOnce(u32) -> u32) {
+        let r = self.regs.AUTOCMD12_ERR_STATUS.read();
+        unsafe { ptr::write_volatile(pac::usdhc::AUTOCMD12_ERR_STATUS::<P>::address(), f(r)) }
+    }
+
+    pub fn change_frequency(&self, f: Frequency) {
+        memlog!("change_frequency({:?}) @ {:?}", f, time::uptime());
+
+        // wait for the clock to stabilize
+        while self.regs.PRES_STATE.read() & SDSTB == 0 {
+            crate::memlog_try_flush();
+        }
+
+        // gate the SD clock before changing it
+        self.regs
+            .VEND_SPEC
+            .rmw(|vend_spec| vend_spec & !VEND_SPEC_CKEN);
+
+        let (sdclkfs, dvs) = match f {
+            // 200 MHz / 64 / 9 = 347.222 KHz
+            Frequency::K400 => (0x20, 8),
+            // 200 MHz / 2 / 5 = 20 MHz
+            Frequency::M20 => (0x01, 4),
+            // 200 MHz / 2 / 4 = 25 MHz
+            Frequency::M25 => (0x01, 3),
+            // 200 MHz / 2 / 2 = 50 MHz
+            Frequency::M50 => (0x01, 1),
+            // 200 MHz / 1 / 1 = 200 MHz
+            Frequency::M200 => (0x00, 0),
+        };
+
+        // in DDR mode the prescaler divides the clock by twice its value;
+        // compensate so the card still sees the requested frequency
+        let sdclkfs = if self.ddr.get() {
+            sdclkfs >> 1
+        } else {
+            sdclkfs
+        };
+
+        self.regs.SYS_CTRL.rmw(|mut r| {
+            const DVS_OFFSET: u8 = 4;
+            const DVS_MASK: u32 = 0b1111 << DVS_OFFSET;
+            const SDCLKFS_OFFSET: u8 = 8;
+            const SDCLKFS_MASK: u32 = 0xff << SDCLKFS_OFFSET;
+
+            // set divisor
+            r &= !DVS_MASK;
+            r |= dvs << DVS_OFFSET;
+
+            // set prescaler
+            r &= !SDCLKFS_MASK;
+            r |= sdclkfs << SDCLKFS_OFFSET;
+
+            r
+        });
+
+        const SDSTB: u32 = 1 << 3;
+
+        // wait for the clock to stabilize
+        while self.regs.PRES_STATE.read() & SDSTB == 0 {
+            crate::memlog_try_flush();
+        }
+
+        // ungate the SD clock
+        self.regs
+            .VEND_SPEC
+            .rmw(|vend_spec| vend_spec | VEND_SPEC_CKEN);
+    }
+
+    pub fn software_reset(&mut self) {
+        memlog!("software reset START @ {:?}", time::uptime());
+
+        // wait for CMD and DATA lines to become free
+        let busy = PRES_STATE_CDIHB | PRES_STATE_CIHB;
+        while self.regs.PRES_STATE.read() & busy != 0 {
+            crate::memlog_try_flush();
+        }
+
+        // gate off the SD clock
+        self.regs
+            .VEND_SPEC
+            .rmw(|vend_spec| vend_spec & !VEND_SPEC_CKEN);
+
+        // reset the uSDHC peripheral
+        const RSTA: u32 = 1 << 24;
+        self.regs.SYS_CTRL.rmw(|r| r | RSTA);
+
+        // wait for reset
+        while self.regs.SYS_CTRL.read() & RSTA != 0 {
+            crate::memlog_try_flush();
+        }
+
+        self.set_bus_width(Width::B1);
+        self.ddr.set(false);
+        self.change_frequency(Frequency::K400);
+
+        // recommended in the eMMC spec
+        time::wait(Duration::from_millis(1));
+
+        // send 80 clock cycles to the card
+        const INITA: u32 = 1 << 27;
+        self.regs.SYS_CTRL.rmw(|r| r | INITA);
+
+        memlog!("software reset DONE @ {:?}", time::uptime());
+    }
+
+    pub fn get_card_status(&self, rca: Rca) -> Result<card::Status, Error> {
+        let rsp = self.send_command(Command::SendStatus { rca })?;
+        let status = card::Status::from(rsp);
+        Ok(status?)
+    }
+
+    // low-level API
+    /// When a command time outs the command inhibition bit needs to be cleared
+    /// before attempting to send a new command
+    pub fn clear_command_inhibit(&self) {
+        const SYS_CTRL_RSTC: u32 = 1 << 25;
+
+        // clear the Command Inhibit bit
+        self.regs.SYS_CTRL.rmw(|r| r | SYS_CTRL_RSTC);
+        if util::wait_for_or_timeout(
+            || self.regs.SYS_CTRL.read() & SYS_CTRL_RSTC == 0,
+            default_timeout(),
+        )
+        .is_err()
+        {
+            memlog!("RSTC timeout");
+            memlog_flush_and_reset!();
+        }
+    }
+
+    /// Resets the data circuit of the host, which may be left in an
+    /// inconsistent state by a failed data transfer
+    pub fn reset_data_line(&self) {
+        const SYS_CTRL_RSTD: u32 = 1 << 26;
+
+        self.regs.SYS_CTRL.rmw(|r| r | SYS_CTRL_RSTD);
+        if util::wait_for_or_timeout(
+            || self.regs.SYS_CTRL.read() & SYS_CTRL_RSTD == 0,
+            default_timeout(),
+        )
+        .is_err()
+        {
+            memlog!("RSTD timeout");
+            memlog_flush_and_reset!();
+        }
+    }
+
+    /// Brings the host and the card with relative address `rca` back to the
+    /// transfer state after a failed command or data transfer; returns the
+    /// status the card reported, including any error flags
+    ///
+    /// The card is told to stop transmitting if it's still sending or
+    /// receiving data, and selected again if it went back to the stand-by
+    /// state. `Error::NotInTransferState` is returned if the card is in any
+    /// other state; it must then be initialized again
+    pub fn recover(&self, rca: Rca) -> Result<card::Status, Error> {
+        self.reset_data_line();
+        self.clear_command_inhibit();
+
+        // NOTE the error flags are cleared when the status is read
+        let status = match self.get_card_status(rca) {
+            Ok(status) | Err(Error::Card(status)) => status,
+            Err(e) => return Err(e),
+        };
+
+        match status.state {
+            card::State::Transfer | card::State::Programming => {}
+
+            card::State::SendingData | card::State::ReceiveData => {
+                // the response may report the error that caused the failure
+                match self.send_command(Command::StopTransmission) {
+                    Ok(_) | Err(Error::Card(_)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+
+            card::State::Standby => {
+                self.send_command(Command::SelectCard { rca: Some(rca) })?;
+            }
+
+            state => {
+                memlog!("card is in the {:?} state", state);
+                return Err(Error::NotInTransferState);
+            }
+        }
+
+        self.wait_for_programming(rca, default_timeout())?;
+
+        Ok(status)
+    }
+
+    /// [Blocking] send a command to the card
+    pub fn send_command(&self, cmd: Command) -> Result<u32, Error> {
+        if !cmd.data_present() {
+            return self.start_command(cmd);
+        }
+
+        // NOTE(BLK_ATT) read before the transfer starts; the block count
+        // decreases as blocks are transferred
+        let data_timeout = transfer_timeout((self.regs.BLK_ATT.read() >> 16) as u16);
+        // the tuning block is not moved by the DMA; it stays in the buffer
+        let transfer_done = if let Command::SendTuningBlock = cmd {
+            INT_STATUS_BRR
+        } else {
+            INT_STATUS_TC | INT_STATUS_DINT
+        };
+
+        let rsp = self.start_command(cmd)?;
+
+        // let the data transfer complete
+        let mut res = None;
+        let has_transfer_completed = || {
+            res = self.check_data_transfer(transfer_done);
+            res.is_some()
+        };
+        if util::wait_for_or_timeout(has_transfer_completed, data_timeout).is_err() {
+            return Err(Error::Timeout);
+        }
+
+        res.expect("UNREACHABLE").map(|_| rsp)
+    }
+
+    /// Sends a command to the card and waits for its response; if the command
+    /// has a data transfer this doesn't wait for the transfer to complete
+    fn start_command(&self, cmd: Command) -> Result<u32, Error> {
+        if self.verbose {
+            memlog!("send_command(cmd={:?}) @ {:?}", cmd, time::uptime());
+        }
+        debug_assert!(
+            self.regs.INT_STATUS.read() & INT_STATUS_CC == 0,
+            "INT_STATUS.CC bit was not cleared"
+        );
+
+        // wait until the bus is idle
+        let busy = PRES_STATE_CIHB | PRES_STATE_CDIHB | PRES_STATE_DLA;
+        while self.regs.PRES_STATE.read() & busy != 0 {
+            crate::memlog_try_flush();
+        }
+
+        const RSTYP_OFFSET: u8 = 16;
+        const CICEN: u32 = 1 << 20;
+        const CCCEN: u32 = 1 << 19;
+        const DPSEL: u32 = 1 << 21;
+        const CMDTYP_OFFSET: u8 = 22;
+        const CMDINX_OFFSET: u8 = 24;
+
+        let mut w_cmd = u32::from(cmd.index()) << CMDINX_OFFSET;
+        w_cmd |= u32::from(cmd.cmdtyp()) << CMDTYP_OFFSET;
+        if cmd.data_present() {
+            w_cmd |= DPSEL
+        }
+        if cmd.cicen() {
+            w_cmd |= CICEN;
+        }
+        if cmd.cccen() {
+            w_cmd |= CCCEN;
+        }
+        w_cmd |= u32::from(cmd.response_type()) << RSTYP_OFFSET;
+
+        let cmd_arg = cmd.arg();
+
+        // the timing between commands must be at least 8 SD clock cycles according to the spec
+        // @ 20 MHz that's 400 ns; @ 400 KHz that's 20 us
+        time::wait(Duration::from_micros(20));
+
+        // command argument
+        self.regs.CMD_ARG.write(cmd_arg);
+        // issue the command
+        self.regs.CMD_XFR_TYP.write(w_cmd);
+
+        // then wait for a response
+        let any_error = INT_STATUS_ANY_ERROR;
+        let mut int_status = 0;
+        let has_command_completed = || {
+            int_status = self.regs.INT_STATUS.read();
+            int_status & (any_error | INT_STATUS_CC) != 0
+        };
+        if util::wait_for_or_timeout(has_command_completed, default_timeout()).is_err() {
+            return Err(Error::Timeout);
+        }
+
+        if int_status & INT_STATUS_CTOE != 0 {
+            self.regs.INT_STATUS.clear(INT_STATUS_CTOE);
+            Err(Error::Timeout)
+        } else if int_status & any_error != 0 {
+            self.regs.INT_STATUS.clear(int_status & any_error);
+            Err(error(int_status))
+        } else {
+            self.regs.INT_STATUS.clear(INT_STATUS_CC);
+
+            let rsp = self.regs.CMD_RSP0.read();
+            let rspty = cmd.response();
+
+            if rspty == cmd::Response::R1 || rspty == cmd::Response::R1b {
+                let status = card::Status::from(rsp)?;
+                if self.verbose {
+                    memlog!("{:?}", status);
+                }
+            }
+
+            Ok(rsp)
+        }
+    }
+
+    /// Returns the outcome of the data transfer in progress, or `None` if it
+    /// has not completed yet; `done` are the `INT_STATUS` flags that signal
+    /// its completion
+    fn check_data_transfer(&self, done: u32) -> Option<Result<(), Error>> {
+        let int_status = self.regs.INT_STATUS.read();
+        if int_status & INT_STATUS_ANY_ERROR != 0 {
+            self.regs
+                .INT_STATUS
+                .clear(int_status & INT_STATUS_ANY_ERROR);
+            Some(Err(if int_status & INT_STATUS_CTOE != 0 {
+                Error::Timeout
+            } else {
+                error(int_status)
+            }))
+        } else if int_status & done == done {
+            self.regs.INT_STATUS.clear(done);
+            Some(Ok(()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Command error
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error {
+    /// Card did not respond in time.
+    Timeout,
+    /// Card is not in the transfer state (data cannot be accessed)
+    NotInTransferState,
+    /// The CRC of a command response or of a data block didn't match
+    Crc,
+    /// The DMA failed to move the data of a transfer
+    Dma,
+    /// Unclassified error.
+    Other,
+    /// Error condition in the card
+    Card(card::Status),
+}
+
+impl From<card::Status> for Error {
+    fn from(s: card::Status) -> Self {
+        Error::Card(s)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Timeout => f.write_str("timeout"),
+            Error::NotInTransferState => f.write_str("card not in transfer state"),
+            Error::Crc => f.write_str("CRC error"),
+            Error::Dma => f.write_str("DMA error"),
+            Error::Other => f.write_str("unclassified error"),
+            Error::Card(s) => write!(f, "card error (code: {:#010x})", s.bits),
+        }
+    }
+}
+
+/// Classifies the error flags of the `INT_STATUS` register (other than the
+/// command timeout)
+fn error(int_status: u32) -> Error {
+    if int_status & (INT_STATUS_CCE | INT_STATUS_DCE) != 0 {
+        Error::Crc
+    } else if int_status & INT_STATUS_DTOE != 0 {
+        Error::Timeout
+    } else if int_status & INT_STATUS_DMAE != 0 {
+        Error::Dma
+    } else {
+        Error::Other
+    }
+}
+
+/// Number of whole blocks in `len` bytes
+pub fn blocks_in(len: usize) -> u64 {
+    (len / usize::from(BLOCK_SIZE)) as u64
+}
+
+/// Returns `true` if the `len` bytes that start at block `lba` fit in a device of `total` blocks
+pub fn fits_in(lba: u64, len: usize, total: u64) -> bool {
+    // NOTE `lba` comes from the caller; avoid wrapping around in release builds
+    lba.checked_add(blocks_in(len))
+        .map_or(false, |end| end <= total)
+}
+
+// the DMA can only access buffers that are 4-byte aligned
+fn is_word_aligned(buf: &[u8]) -> bool {
+    buf.as_ptr() as usize % 4 == 0
+}
@@ -0,0 +1,95 @@
+//! ADMA2 (Advanced DMA) descriptor tables
+
+use crate::storage::BLOCK_SIZE;
+
+/// Maximum number of descriptors in a table
+pub const MAX_DESCRIPTORS: usize = 32;
+
+/// Maximum number of blocks a single descriptor can move
+// NOTE the length field is a `u16` and it must be a multiple of 4
+pub const MAX_SEGMENT_BLOCKS: u16 = 0xfffc / BLOCK_SIZE;
+
+/// Valid descriptor
+const ATTR_VALID: u32 = 1; // bit 0
+/// Last descriptor in the table
+const ATTR_END: u32 = 1 << 1;
+/// Act = Transfer data
+const ATTR_ACT_TRAN: u32 = 0b10 << 4;
+
+const LENGTH_OFFSET: u8 = 16;
+
+// See "ADMA2 descriptor" in the uSDHC chapter of ULRM
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Descriptor {
+    attr_length: u32,
+    address: u32,
+}
+
+impl Descriptor {
+    const EMPTY: Self = Descriptor {
+        attr_length: 0,
+        address: 0,
+    };
+}
+
+/// Table of transfer descriptors
+///
+/// The DMA walks the table in order; the table must not be moved or modified
+/// while a transfer is in progress
+#[repr(C, align(8))]
+pub struct Table {
+    descriptors: [Descriptor; MAX_DESCRIPTORS],
+    len: usize,
+}
+
+impl Table {
+    /// Creates an empty table
+    pub fn new() -> Self {
+        Table {
+            descriptors: [Descriptor::EMPTY; MAX_DESCRIPTORS],
+            len: 0,
+        }
+    }
+
+    /// Appends a descriptor that moves `blocks` blocks from / to the buffer at
+    /// `addr`
+    ///
+    /// `addr` must be 4-byte aligned
+    pub fn push(&mut self, addr: usize, blocks: u16) {
+        assert!(!self.is_full(), "ADMA descriptor table is full");
+        debug_assert!(blocks != 0 && blocks <= MAX_SEGMENT_BLOCKS);
+        debug_assert_eq!(addr % 4, 0);
+
+        self.descriptors[self.len] = Descriptor {
+            attr_length: u32::from(blocks * BLOCK_SIZE) << LENGTH_OFFSET
+                | ATTR_ACT_TRAN
+                | ATTR_VALID,
+            address: addr as u32,
+        };
+        self.len += 1;
+    }
+
+    /// Whether the table has no room for more descriptors
+    pub fn is_full(&self) -> bool {
+        self.len == MAX_DESCRIPTORS
+    }
+
+    /// Removes all the descriptors
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Marks the last descriptor as the end of the table and returns the
+    /// address of the table
+    pub fn finish(&mut self) -> usize {
+        assert!(self.len != 0, "ADMA descriptor table is empty");
+
+        for descriptor in &mut self.descriptors[..self.len - 1] {
+            descriptor.attr_length &= !ATTR_END;
+        }
+        self.descriptors[self.len - 1].attr_length |= ATTR_END;
+
+        self.descriptors.as_ptr() as usize
+    }
+}
@@ -3,79 +3,16 @@
 
 use core::fmt;
 
-/// Card Specific Data
-#[derive(Debug)]
-pub struct Csd {
-    read_block_size_log2: u8,
-    size: u16,
-    size_mult: u8,
-    spec_vers: u8,
-    write_block_size_log2: u8,
-}
-
-#[cfg(TODO = "eMMC_and_uSD_support")]
-impl Csd {
-    /// Returns the version of the eMMC specification supported by the card
-    pub fn version(&self) -> u8 {
-        self.spec_vers
-    }
-
-    /// Maximum supported block length for read operations
-    pub fn max_read_block_size(&self) -> u16 {
-        1 << self.write_block_size_log2
-    }
-
-    /// Maximum supported block length for write operations
-    pub fn max_write_block_size(&self) -> u16 {
-        1 << self.write_block_size_log2
-    }
-
-    /// The number of memory blocks this card has
-    ///
-    /// NOTE for card with capacities greater than 2GB this value may not match
-    /// the actual capacity of the card and the EXT_CSD register should be
-    /// consulted.
-    #[allow(dead_code)]
-    pub fn number_of_blocks(&self) -> u32 {
-        (1 << (self.size_mult + 2)) * u32::from(self.size + 1)
-    }
-}
-
-impl From<[u32; 4]> for Csd {
-    fn from(rsps: [u32; 4]) -> Self {
-        let extract = |offset, mask| {
-            // RSP0[0] is CSD[8]
-            const OFFSET: usize = 8;
-            let offset = offset - OFFSET;
-
-            (rsps[offset / 32] >> (offset % 32)) & mask
-        };
-
-        let spec_vers = extract(122, 0b1111) as u8;
-        let write_block_size_log2 = extract(22, 0b1111) as u8;
-        let read_block_size_log2 = extract(80, 0b1111) as u8;
-        let size = extract(62, (1 << 12) - 1) as u16;
-        let size_mult = extract(47, 0b111) as u8;
-
-        Csd {
-            read_block_size_log2,
-            size,
-            size_mult,
-            spec_vers,
-            write_block_size_log2,
-        }
-    }
-}
-
 macro_rules! status {
-	  ($($error:ident : $epos:expr),+;$($status:ident : $spos:expr),+) => {
+	  ($($(#[$edoc:meta])* $error:ident : $epos:expr),+;$($(#[$sdoc:meta])* $status:ident : $spos:expr),+) => {
         /// Card status
         #[derive(Clone, Copy)]
         pub struct Status {
             pub(crate) bits: u32,
+            /// State of the card when it received the command
             pub state: State,
-            $(pub $error: bool,)+
-            $(pub $status: bool,)+
+            $($(#[$edoc])* pub $error: bool,)+
+            $($(#[$sdoc])* pub $status: bool,)+
         }
 
         impl fmt::Debug for Status {
@@ -93,7 +30,7 @@ macro_rules! status {
         }
 
         impl Status {
-            pub fn from(bits: u32) -> Result<Self, Self> {
+            pub(crate) fn from(bits: u32) -> Result<Self, Self> {
                 let state = match (bits >> 9) & 0b1111 {
                     0 => State::Idle,
                     1 => State::Ready,
@@ -135,41 +72,72 @@ impl PartialEq for Status {
 }
 
 status!(
+    /// The address argument was out of range
     address_out_of_range: 31,
+    /// The address argument didn't match the block length
     address_misalign: 30,
+    /// The block length or count was not allowed
     block_len_error: 29,
+    /// The erase commands were sent in the wrong order
     erase_seq_error: 28,
+    /// The erase group selection was not valid
     erase_param: 27,
+    /// A write protected block was to be programmed
     wp_violation: 26,
+    /// The lock / unlock command failed
     lock_unlock_failed: 24,
+    /// The CRC of the previous command didn't match
     com_crc_error: 23,
+    /// The command was not legal in the card state
     illegal_command: 22,
+    /// The internal ECC failed to correct the data
     card_ecc_failed: 21,
+    /// Internal card controller error
     cc_error: 20,
+    /// Unclassified error
     error: 19,
+    /// The card could not sustain a read transfer
     underrun: 18,
+    /// The card could not sustain a write transfer
     overrun: 17,
+    /// The CID or CSD could not be overwritten
     cid_csd_overwrite: 16,
+    /// Only part of the erase range was erased due to write protection
     wp_erase_skip: 15,
+    /// An erase sequence was aborted by another command
     erase_reset: 13,
+    /// The `SWITCH` command could not be executed
     switch_error: 7;
 
+    /// The card is locked by the host
     card_is_locked: 25,
+    /// The card is ready to receive data
     ready_for_data: 8,
+    /// The card needs background operations urgently
     urgent_bkops: 6,
+    /// The card expects an application specific command
     app_cmd: 5
 );
 
 /// Card state
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum State {
+    /// `idle`
     Idle,
+    /// `ready`
     Ready,
+    /// `ident`
     Identification,
+    /// `stby`
     Standby,
+    /// `tran`; data can be accessed
     Transfer,
+    /// `data`
     SendingData,
+    /// `rcv`
     ReceiveData,
+    /// `prg`; the card is busy programming data to flash
     Programming,
+    /// Any other state
     Other,
 }
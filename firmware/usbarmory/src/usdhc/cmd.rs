@@ -1,6 +1,6 @@
-//! eMMC commands
+//! eMMC and SD card commands
 
-use crate::emmc::Rca;
+use crate::usdhc::Rca;
 
 // Table 56-3 in ULRM
 #[allow(dead_code)]
@@ -24,12 +24,22 @@ pub enum Command {
         rca: Rca,
     },
 
+    // 3
+    // NOTE SD specific version; the card picks its own relative address
+    SendRelativeAddr,
+
     // 6
     // NOTE MMC specific version; `SWITCH_FUNC` is the SD variant of the command
     Switch {
         data: u32,
     },
 
+    // 6
+    // NOTE SD specific version; `SWITCH` is the MMC variant of the command
+    SwitchFunc {
+        arg: u32,
+    },
+
     // 7
     SelectCard {
         rca: Option<Rca>,
@@ -38,11 +48,21 @@ pub enum Command {
     // 8
     SendExtCsd,
 
+    // 8
+    // NOTE SD specific
+    SendIfCond {
+        /// Supply voltage (bits 8..=11) and check pattern (bits 0..=7)
+        arg: u32,
+    },
+
     // 9
     SendCsd {
         rca: Rca,
     },
 
+    // 12
+    StopTransmission,
+
     // 13
     SendStatus {
         rca: Rca,
@@ -59,12 +79,93 @@ pub enum Command {
         block_nr: u32,
     },
 
+    // 18
+    ReadMultipleBlock {
+        /// *Block* number of the first block (multiply by 512 to get the actual address)
+        // NOTE for low capacity (<2GB) cards this is the actual 32-bit address
+        block_nr: u32,
+    },
+
+    // 21
+    // NOTE MMC specific; HS200 mode only
+    SendTuningBlock,
+
+    // 23
+    SetBlockCount {
+        /// Number of blocks the next read / write multiple block command will transfer
+        blocks: u16,
+        /// Request a reliable write (the next command must be `WRITE_MULTIPLE_BLOCK`)
+        reliable: bool,
+    },
+
     // 24
     WriteSingleBlock {
         /// *Block* address (multiply by 512 to get the actual address)
         // NOTE for low capacity (<2GB) cards this is the actual 32-bit address
         block_nr: u32,
     },
+
+    // 25
+    WriteMultipleBlock {
+        /// *Block* number of the first block (multiply by 512 to get the actual address)
+        // NOTE for low capacity (<2GB) cards this is the actual 32-bit address
+        block_nr: u32,
+    },
+
+    // 28
+    SetWriteProt {
+        /// *Block* number of a block in the write protect group
+        block_nr: u32,
+    },
+
+    // 29
+    ClrWriteProt {
+        /// *Block* number of a block in the write protect group
+        block_nr: u32,
+    },
+
+    // 31
+    // NOTE MMC specific
+    SendWriteProtType {
+        /// *Block* number of a block in the first write protect group
+        block_nr: u32,
+    },
+
+    // 35
+    EraseGroupStart {
+        /// *Block* number of the first block to erase
+        block_nr: u32,
+    },
+
+    // 36
+    EraseGroupEnd {
+        /// *Block* number of the last block to erase
+        block_nr: u32,
+    },
+
+    // 38
+    Erase {
+        /// Kind of erase operation (erase, trim, discard, secure trim, etc.)
+        arg: u32,
+    },
+
+    // 55
+    // NOTE SD specific; the next command is an application specific command (ACMD)
+    AppCmd {
+        rca: Option<Rca>,
+    },
+
+    // ACMD6
+    SetBusWidth {
+        /// `0b00` = 1-bit; `0b10` = 4-bit
+        width: u8,
+    },
+
+    // ACMD41
+    SdSendOpCond {
+        /// Host Capacity Support (bit 30) and voltage window (bits 15..=23)
+        ocr: u32,
+    },
 }
 
 #[allow(dead_code)]
@@ -93,6 +194,7 @@ pub enum Response {
     // doesn't exist in the standard
     // R5b,
     R6,
+    R7,
 }
 
 impl Command {
@@ -118,8 +220,12 @@ impl Command {
                 (3, Type::ac, u32::from(rca.get()) << 16, Response::R1)
             }
 
+            Command::SendRelativeAddr => (3, Type::bcr, 0, Response::R6),
+
             Command::Switch { data } => (6, Type::ac, *data, Response::R1b),
 
+            Command::SwitchFunc { arg } => (6, Type::adtc, *arg, Response::R1),
+
             Command::SelectCard { rca } => (
                 7,
                 Type::ac,
@@ -131,8 +237,12 @@ impl Command {
 
             Command::SendExtCsd => (8, Type::adtc, 0, Response::R1),
 
+            Command::SendIfCond { arg } => (8, Type::bcr, *arg, Response::R7),
+
             Command::SendCsd { rca } => (9, Type::ac, u32::from(rca.get()) << 16, Response::R2),
 
+            Command::StopTransmission => (12, Type::ac, 0, Response::R1b),
+
             Command::SendStatus { rca } => (13, Type::ac, u32::from(rca.get()) << 16, Response::R1),
 
             // table 23
@@ -140,8 +250,47 @@ impl Command {
 
             Command::ReadSingleBlock { block_nr } => (17, Type::adtc, *block_nr, Response::R1),
 
+            Command::ReadMultipleBlock { block_nr } => (18, Type::adtc, *block_nr, Response::R1),
+
+            Command::SendTuningBlock => (21, Type::adtc, 0, Response::R1),
+
+            Command::SetBlockCount { blocks, reliable } => (
+                23,
+                Type::ac,
+                u32::from(*reliable) << 31 | u32::from(*blocks),
+                Response::R1,
+            ),
+
             // table 25
             Command::WriteSingleBlock { block_nr } => (24, Type::adtc, *block_nr, Response::R1),
+
+            Command::WriteMultipleBlock { block_nr } => (25, Type::adtc, *block_nr, Response::R1),
+
+            // write protection commands (class 6)
+            Command::SetWriteProt { block_nr } => (28, Type::ac, *block_nr, Response::R1b),
+
+            Command::ClrWriteProt { block_nr } => (29, Type::ac, *block_nr, Response::R1b),
+
+            Command::SendWriteProtType { block_nr } => (31, Type::adtc, *block_nr, Response::R1),
+
+            // erase commands (class 5)
+            Command::EraseGroupStart { block_nr } => (35, Type::ac, *block_nr, Response::R1),
+
+            Command::EraseGroupEnd { block_nr } => (36, Type::ac, *block_nr, Response::R1),
+
+            Command::Erase { arg } => (38, Type::ac, *arg, Response::R1b),
+
+            // SD physical layer specification
+            Command::AppCmd { rca } => (
+                55,
+                Type::ac,
+                u32::from(rca.map(|nz| nz.get()).unwrap_or(0)) << 16,
+                Response::R1,
+            ),
+
+            Command::SetBusWidth { width } => (6, Type::ac, u32::from(*width), Response::R1),
+
+            Command::SdSendOpCond { ocr } => (41, Type::bcr, *ocr, Response::R3),
         }
     }
 
@@ -198,7 +347,7 @@ impl Command {
             Response::None => (0b00, 0, 0),
             Response::R2 => (0b01, 0, 1),
             Response::R3 | Response::R4 => (0b10, 0, 0),
-            Response::R1 | Response::R5 | Response::R6 => (0b10, 1, 1),
+            Response::R1 | Response::R5 | Response::R6 | Response::R7 => (0b10, 1, 1),
             Response::R1b => (0b11, 1, 1),
         }
     }
@@ -62,7 +62,15 @@ fn main() -> Result<(), anyhow::Error> {
                 }
 
                 // add SET and CLR registers
-                const SET_CLR_LIST: &[&str] = &["HW_DCP_CHANNELCTRL"];
+                const SET_CLR_LIST: &[&str] = &[
+                    "HW_DCP_CTRL",
+                    "HW_DCP_STAT",
+                    "HW_DCP_CHANNELCTRL",
+                    "HW_DCP_CH0STAT",
+                    "HW_DCP_CH1STAT",
+                    "HW_DCP_CH2STAT",
+                    "HW_DCP_CH3STAT",
+                ];
 
                 let mut new_registers = vec![];
                 for register in &registers {